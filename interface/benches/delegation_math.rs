@@ -0,0 +1,245 @@
+//! Host microbenchmark of `Delegation::stake_activating_and_deactivating`,
+//! walking warmup and cooldown across stake histories of increasing depth.
+//!
+//! Each walk is timed against `reference`, a copy of the loop as it was
+//! before the integer conversions and the rate lookup were hoisted out of
+//! it, and must return the same result. Criterion reports both under
+//! `<walk>/before/<depth>` and `<walk>/after/<depth>`, and the numbers last
+//! recorded are in `delegation_math.md`:
+//!
+//! ```text
+//! cargo bench -p pinocchio-stake-interface --bench delegation_math
+//! ```
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pinocchio_stake_interface::state::{
+    bytes_to_u64, warmup_cooldown_rate, Delegation, Epoch, StakeActivationStatus, StakeHistory,
+    StakeHistoryEntry, StakeHistoryGetEntry,
+};
+
+const DEPTHS: [u64; 5] = [1, 4, 16, 64, 511];
+
+// far more cluster stake warming up or cooling down than becomes effective,
+// so neither walk finishes before it runs out of depth
+const CLUSTER_EFFECTIVE: u64 = 1_000_000_000_000;
+const CLUSTER_IN_FLIGHT: u64 = 1_000_000_000_000_000_000;
+const DELEGATED: u64 = 1_000_000_000_000_000;
+
+fn history() -> StakeHistory {
+    let mut history = StakeHistory::default();
+    for epoch in 0..512u64 {
+        history.add(
+            epoch,
+            StakeHistoryEntry {
+                effective: CLUSTER_EFFECTIVE.to_le_bytes(),
+                activating: CLUSTER_IN_FLIGHT.to_le_bytes(),
+                deactivating: CLUSTER_IN_FLIGHT.to_le_bytes(),
+            },
+        );
+    }
+    history
+}
+
+struct Walk {
+    name: &'static str,
+    delegation: Delegation,
+}
+
+fn walks() -> [Walk; 2] {
+    // bootstrap stake is fully effective, so its cooldown starts from all of it
+    let mut cooling = Delegation::new(&[1; 32], DELEGATED, u64::MAX.to_le_bytes());
+    cooling.set_deactivation_epoch(0);
+    [
+        Walk {
+            name: "warmup",
+            delegation: Delegation::new(&[1; 32], DELEGATED, 0u64.to_le_bytes()),
+        },
+        Walk {
+            name: "cooldown",
+            delegation: cooling,
+        },
+    ]
+}
+
+fn bench_walks(c: &mut Criterion) {
+    let history = history();
+    for walk in walks() {
+        let mut group = c.benchmark_group(walk.name);
+        for depth in DEPTHS {
+            let target = depth.to_le_bytes();
+            // the rate drops halfway through, as it did on mainnet
+            let new_rate_epoch = Some((depth / 2).to_le_bytes());
+
+            let after = || {
+                walk.delegation.stake_activating_and_deactivating(
+                    black_box(target),
+                    black_box(&history),
+                    black_box(new_rate_epoch),
+                )
+            };
+            let before = || {
+                reference(
+                    &walk.delegation,
+                    black_box(target),
+                    black_box(&history),
+                    black_box(new_rate_epoch),
+                )
+            };
+            assert_eq!(after(), before(), "{} at depth {depth}", walk.name);
+
+            // one element per history entry walked
+            group.throughput(Throughput::Elements(depth));
+            group.bench_function(BenchmarkId::new("before", depth), |b| b.iter(before));
+            group.bench_function(BenchmarkId::new("after", depth), |b| b.iter(after));
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_walks);
+criterion_main!(benches);
+
+/// `stake_activating_and_deactivating` before the hoisting, verbatim but for
+/// being a free function.
+fn reference<T: StakeHistoryGetEntry>(
+    delegation: &Delegation,
+    target_epoch: Epoch,
+    history: &T,
+    new_rate_activation_epoch: Option<Epoch>,
+) -> StakeActivationStatus {
+    let (effective_stake, activating_stake) = reference_stake_and_activating(
+        delegation,
+        target_epoch,
+        history,
+        new_rate_activation_epoch,
+    );
+
+    let target_epoch_u64 = bytes_to_u64(target_epoch);
+    let deactivation_epoch = bytes_to_u64(delegation.deactivation_epoch);
+
+    if target_epoch_u64 < deactivation_epoch {
+        if activating_stake == 0 {
+            StakeActivationStatus::with_effective(effective_stake.to_le_bytes())
+        } else {
+            StakeActivationStatus::with_effective_and_activating(
+                effective_stake.to_le_bytes(),
+                activating_stake.to_le_bytes(),
+            )
+        }
+    } else if target_epoch_u64 == deactivation_epoch {
+        StakeActivationStatus::with_deactivating(effective_stake)
+    } else if let Some(mut prev_cluster_stake) =
+        history.get_entry(bytes_to_u64(delegation.deactivation_epoch))
+    {
+        let mut prev_epoch = delegation.deactivation_epoch;
+        let mut current_epoch;
+        let mut current_effective_stake = effective_stake;
+
+        loop {
+            current_epoch = bytes_to_u64(prev_epoch) + 1;
+            let prev_cluster_stake_deactivating = bytes_to_u64(prev_cluster_stake.deactivating);
+            let prev_cluster_stake_effective = bytes_to_u64(prev_cluster_stake.effective);
+            if prev_cluster_stake_deactivating == 0 {
+                break;
+            }
+
+            let weight = current_effective_stake as f64 / prev_cluster_stake_deactivating as f64;
+            let warmup_cooldown_rate =
+                warmup_cooldown_rate(current_epoch.to_le_bytes(), new_rate_activation_epoch);
+
+            let newly_not_effective_cluster_stake =
+                prev_cluster_stake_effective as f64 * warmup_cooldown_rate;
+            let newly_not_effective_stake =
+                ((weight * newly_not_effective_cluster_stake) as u64).max(1);
+
+            current_effective_stake =
+                current_effective_stake.saturating_sub(newly_not_effective_stake);
+            if current_effective_stake == 0 {
+                break;
+            }
+
+            if current_epoch >= bytes_to_u64(target_epoch) {
+                break;
+            }
+            if let Some(current_cluster_stake) = history.get_entry(current_epoch) {
+                prev_epoch = current_epoch.to_le_bytes();
+                prev_cluster_stake = current_cluster_stake;
+            } else {
+                break;
+            }
+        }
+
+        StakeActivationStatus::with_deactivating(current_effective_stake)
+    } else {
+        StakeActivationStatus::default()
+    }
+}
+
+fn reference_stake_and_activating<T: StakeHistoryGetEntry>(
+    delegation: &Delegation,
+    target_epoch: Epoch,
+    history: &T,
+    new_rate_activation_epoch: Option<Epoch>,
+) -> (u64, u64) {
+    let delegated_stake = delegation.stake;
+
+    if delegation.is_bootstrap() {
+        (bytes_to_u64(delegated_stake), 0)
+    } else if delegation.activation_epoch == delegation.deactivation_epoch {
+        (0, 0)
+    } else if target_epoch == delegation.activation_epoch {
+        (0, bytes_to_u64(delegated_stake))
+    } else if bytes_to_u64(target_epoch) < bytes_to_u64(delegation.activation_epoch) {
+        (0, 0)
+    } else if let Some(mut prev_cluster_stake) =
+        history.get_entry(bytes_to_u64(delegation.activation_epoch))
+    {
+        let mut prev_epoch = delegation.activation_epoch;
+        let mut current_epoch;
+        let mut current_effective_stake = 0;
+        loop {
+            current_epoch = bytes_to_u64(prev_epoch) + 1;
+            if bytes_to_u64(prev_cluster_stake.activating) == 0 {
+                break;
+            }
+
+            let remaining_activating_stake =
+                u64::from_le_bytes(delegated_stake) - current_effective_stake;
+            let weight = remaining_activating_stake as f64
+                / bytes_to_u64(prev_cluster_stake.activating) as f64;
+            let warmup_cooldown_rate =
+                warmup_cooldown_rate(current_epoch.to_le_bytes(), new_rate_activation_epoch);
+
+            let newly_effective_cluster_stake =
+                bytes_to_u64(prev_cluster_stake.effective) as f64 * warmup_cooldown_rate;
+            let newly_effective_stake = ((weight * newly_effective_cluster_stake) as u64).max(1);
+
+            current_effective_stake += newly_effective_stake;
+            if current_effective_stake >= bytes_to_u64(delegated_stake) {
+                current_effective_stake = bytes_to_u64(delegated_stake);
+                break;
+            }
+
+            if current_epoch >= bytes_to_u64(target_epoch)
+                || current_epoch >= bytes_to_u64(delegation.deactivation_epoch)
+            {
+                break;
+            }
+            if let Some(current_cluster_stake) = history.get_entry(current_epoch) {
+                prev_epoch = current_epoch.to_le_bytes();
+                prev_cluster_stake = current_cluster_stake;
+            } else {
+                break;
+            }
+        }
+
+        (
+            current_effective_stake,
+            bytes_to_u64(delegated_stake) - current_effective_stake,
+        )
+    } else {
+        (bytes_to_u64(delegated_stake), 0)
+    }
+}
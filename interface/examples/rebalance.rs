@@ -0,0 +1,341 @@
+//! Epoch rebalance for a liquid staking pool.
+//!
+//! Reads the pool's stake accounts, works out where each one stands in its
+//! activation, and plans the instructions that move the pool towards its
+//! target weight per validator:
+//!
+//! * stake above a validator's target is split off, if needed, and deactivated
+//! * inactive stake is delegated to the validator furthest below target
+//! * fully active accounts on the same validator are merged
+//!
+//! A real bot fetches the accounts and the stake history over RPC and signs
+//! the plan; here both come from fixtures so the example runs offline:
+//!
+//! ```text
+//! cargo run -p pinocchio-stake-interface --example rebalance --features client
+//! ```
+
+use std::collections::BTreeMap;
+
+use pinocchio::pubkey::Pubkey;
+use pinocchio_stake_interface::{
+    client::{
+        activation_state, authority_filters, deactivate_stake, delegate_stake,
+        estimate_withdrawable_at, merge, split, stake_state_from_data, stake_state_to_data,
+        Instruction, StakeActivationState, StakeHistoryData,
+    },
+    consts::{CLOCK_ID, STAKE_CONFIG_ID},
+    state::{
+        stake_history, Delegation, Meta, Stake, StakeAuthorize, StakeFlags, StakeHistory,
+        StakeHistoryEntry, StakeHistoryGetEntry, StakeStateV2,
+    },
+};
+
+const POOL_AUTHORITY: Pubkey = [0xaa; 32];
+const VALIDATOR_A: Pubkey = [0xa1; 32];
+const VALIDATOR_B: Pubkey = [0xb1; 32];
+const VALIDATOR_C: Pubkey = [0xc1; 32];
+
+const RENT_EXEMPT_RESERVE: u64 = 2_282_880;
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+const CURRENT_EPOCH: u64 = 500;
+
+/// Target share of the pool per validator, in basis points.
+const TARGETS: [(Pubkey, u64); 3] = [
+    (VALIDATOR_A, 5_000),
+    (VALIDATOR_B, 3_000),
+    (VALIDATOR_C, 2_000),
+];
+
+struct PoolAccount {
+    address: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+}
+
+/// A decoded pool account that can take part in the rebalance.
+struct Position {
+    address: Pubkey,
+    stake: u64,
+    delegation: Option<Delegation>,
+    state: StakeActivationState,
+}
+
+fn main() {
+    let history_data = cluster_history_data();
+    let history = StakeHistoryData::new(&history_data).expect("stake history account data");
+    // what `getProgramAccounts` with these filters returns for the pool
+    let filters = authority_filters(&POOL_AUTHORITY, StakeAuthorize::Withdrawer);
+    let accounts: Vec<PoolAccount> = pool_accounts()
+        .into_iter()
+        .filter(|account| filters.iter().all(|filter| filter.matches(&account.data)))
+        .collect();
+    for instruction in plan_rebalance(&accounts, &history, CURRENT_EPOCH) {
+        print_instruction(&instruction);
+    }
+}
+
+fn plan_rebalance<T: StakeHistoryGetEntry>(
+    accounts: &[PoolAccount],
+    history: &T,
+    epoch: u64,
+) -> Vec<Instruction> {
+    let positions = positions(accounts, history, epoch);
+
+    // stake that counts towards a validator: active, or on its way there
+    let mut committed: BTreeMap<Pubkey, u64> = TARGETS.iter().map(|(vote, _)| (*vote, 0)).collect();
+    let mut free = 0;
+    for position in &positions {
+        match (position.state, position.delegation) {
+            (StakeActivationState::Active | StakeActivationState::Activating, Some(delegation)) => {
+                *committed.entry(delegation.voter_pubkey).or_default() += position.stake;
+            }
+            (StakeActivationState::Inactive, _) => free += position.stake,
+            _ => {}
+        }
+    }
+    let total: u64 = committed.values().sum::<u64>() + free;
+    let target = |vote: &Pubkey| {
+        let bps = TARGETS
+            .iter()
+            .find(|(target, _)| target == vote)
+            .map_or(0, |(_, bps)| *bps);
+        total / 10_000 * bps
+    };
+
+    let mut plan = Vec::new();
+    let mut deactivated = Vec::new();
+    let mut next_split = 0u8;
+
+    // trim validators above target, largest accounts first
+    for (vote, committed) in &committed {
+        let mut excess = committed.saturating_sub(target(vote));
+        let mut active: Vec<&Position> = positions
+            .iter()
+            .filter(|position| {
+                position.state == StakeActivationState::Active
+                    && position
+                        .delegation
+                        .map(|delegation| delegation.voter_pubkey)
+                        == Some(*vote)
+            })
+            .collect();
+        active.sort_by_key(|position| std::cmp::Reverse(position.stake));
+        for position in active {
+            if excess == 0 {
+                break;
+            }
+            if position.stake <= excess {
+                plan.push(deactivate_stake(&position.address, &POOL_AUTHORITY));
+                deactivated.push(position.address);
+                excess -= position.stake;
+            } else {
+                // created and funded with its rent exempt reserve beforehand
+                let mut split_stake = [0xee; 32];
+                split_stake[1] = next_split;
+                next_split += 1;
+                plan.push(split(
+                    &position.address,
+                    &POOL_AUTHORITY,
+                    excess,
+                    &split_stake,
+                ));
+                plan.push(deactivate_stake(&split_stake, &POOL_AUTHORITY));
+                excess = 0;
+            }
+        }
+    }
+
+    // hand inactive stake to whichever validator is furthest below target
+    let mut deficits: BTreeMap<Pubkey, u64> = committed
+        .iter()
+        .map(|(vote, committed)| (*vote, target(vote).saturating_sub(*committed)))
+        .collect();
+    for position in positions
+        .iter()
+        .filter(|position| position.state == StakeActivationState::Inactive)
+    {
+        let Some((vote, deficit)) = deficits.iter_mut().max_by_key(|(_, deficit)| **deficit) else {
+            break;
+        };
+        if *deficit == 0 {
+            break;
+        }
+        plan.push(delegate_stake(&position.address, &POOL_AUTHORITY, vote));
+        *deficit = deficit.saturating_sub(position.stake);
+    }
+
+    // fold the fully active accounts left on each validator into one
+    for (vote, _) in TARGETS {
+        let mut untouched = positions.iter().filter(|position| {
+            position.state == StakeActivationState::Active
+                && position
+                    .delegation
+                    .map(|delegation| delegation.voter_pubkey)
+                    == Some(vote)
+                && !deactivated.contains(&position.address)
+        });
+        if let Some(destination) = untouched.next() {
+            for source in untouched {
+                plan.push(merge(
+                    &destination.address,
+                    &source.address,
+                    &POOL_AUTHORITY,
+                ));
+            }
+        }
+    }
+
+    plan
+}
+
+fn positions<T: StakeHistoryGetEntry>(
+    accounts: &[PoolAccount],
+    history: &T,
+    epoch: u64,
+) -> Vec<Position> {
+    let mut positions = Vec::new();
+    for account in accounts {
+        let meta_and_delegation = match stake_state_from_data(&account.data) {
+            Ok(StakeStateV2::Initialized(meta)) => (meta, None),
+            Ok(StakeStateV2::Stake(meta, stake, _)) => (meta, Some(stake.delegation)),
+            Ok(_) | Err(_) => {
+                eprintln!(
+                    "skipping {}: not a usable stake account",
+                    short(&account.address)
+                );
+                continue;
+            }
+        };
+        let (meta, delegation) = meta_and_delegation;
+        let state = delegation.map_or(StakeActivationState::Inactive, |delegation| {
+            activation_state(&delegation, history, epoch)
+        });
+        if state == StakeActivationState::Deactivating {
+            let delegation = delegation.unwrap();
+            match estimate_withdrawable_at(&delegation, history, epoch) {
+                Some(at) => eprintln!(
+                    "{} is cooling down, free in ~{} epochs",
+                    short(&account.address),
+                    at.saturating_sub(epoch)
+                ),
+                None => eprintln!("{} is cooling down", short(&account.address)),
+            }
+        }
+        positions.push(Position {
+            address: account.address,
+            stake: account.lamports.saturating_sub(meta.rent_exempt_reserve()),
+            delegation,
+            state,
+        });
+    }
+    positions
+}
+
+fn print_instruction(instruction: &Instruction) {
+    let accounts: Vec<String> = instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            let flags = match (meta.is_writable, meta.is_signer) {
+                (true, true) => "ws",
+                (true, false) => "w",
+                (false, true) => "s",
+                (false, false) => "r",
+            };
+            format!("{}:{flags}", short(&meta.pubkey))
+        })
+        .collect();
+    println!(
+        "{:?} [{}] data={:02x?}",
+        instruction.stake_instruction().unwrap(),
+        accounts.join(" "),
+        &instruction.data[1..]
+    );
+}
+
+fn short(pubkey: &Pubkey) -> String {
+    // sysvar keys share their leading bytes, so name them instead
+    match *pubkey {
+        CLOCK_ID => "clock".to_string(),
+        stake_history::ID => "stake_history".to_string(),
+        STAKE_CONFIG_ID => "stake_config".to_string(),
+        _ => pubkey[..4]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect(),
+    }
+}
+
+/// Stake history account data for a steady cluster: 400M SOL effective, a
+/// little stake moving either way.
+fn cluster_history_data() -> Vec<u8> {
+    let mut history = StakeHistory::default();
+    for epoch in CURRENT_EPOCH - 10..CURRENT_EPOCH {
+        history.add(
+            epoch,
+            StakeHistoryEntry {
+                effective: (400_000_000 * LAMPORTS_PER_SOL).to_le_bytes(),
+                activating: (1_000_000 * LAMPORTS_PER_SOL).to_le_bytes(),
+                deactivating: (1_000_000 * LAMPORTS_PER_SOL).to_le_bytes(),
+            },
+        );
+    }
+
+    // as the sysvar account holds it: a length, then entries newest first
+    let mut data = (history.len() as u64).to_le_bytes().to_vec();
+    for (epoch, entry) in history.iter() {
+        data.extend_from_slice(&epoch.to_le_bytes());
+        data.extend_from_slice(&entry.effective);
+        data.extend_from_slice(&entry.activating);
+        data.extend_from_slice(&entry.deactivating);
+    }
+    data
+}
+
+fn pool_accounts() -> Vec<PoolAccount> {
+    let mut meta = Meta::default();
+    meta.set_rent_exempt_reserve(RENT_EXEMPT_RESERVE);
+    meta.authorized.staker = POOL_AUTHORITY;
+    meta.authorized.withdrawer = POOL_AUTHORITY;
+
+    let delegated = |address: u8, vote: Pubkey, sol: u64, activation: u64, deactivation: u64| {
+        let mut delegation =
+            Delegation::new(&vote, sol * LAMPORTS_PER_SOL, activation.to_le_bytes());
+        delegation.set_deactivation_epoch(deactivation);
+        let stake = Stake {
+            delegation,
+            ..Stake::default()
+        };
+        PoolAccount {
+            address: [address; 32],
+            lamports: RENT_EXEMPT_RESERVE + sol * LAMPORTS_PER_SOL,
+            data: stake_state_to_data(&StakeStateV2::Stake(meta, stake, StakeFlags::empty()))
+                .to_vec(),
+        }
+    };
+
+    vec![
+        // validator A holds far more than its half
+        delegated(1, VALIDATOR_A, 4_000, 100, u64::MAX),
+        delegated(2, VALIDATOR_A, 2_500, 200, u64::MAX),
+        delegated(3, VALIDATOR_A, 500, 300, u64::MAX),
+        // validator B is close to target
+        delegated(4, VALIDATOR_B, 2_800, 100, u64::MAX),
+        // validator C is still warming up, and some earlier stake cools down
+        delegated(5, VALIDATOR_C, 300, CURRENT_EPOCH, u64::MAX),
+        delegated(6, VALIDATOR_C, 200, 100, CURRENT_EPOCH),
+        // deposits waiting to be delegated
+        PoolAccount {
+            address: [7; 32],
+            lamports: RENT_EXEMPT_RESERVE + 1_200 * LAMPORTS_PER_SOL,
+            data: stake_state_to_data(&StakeStateV2::Initialized(meta)).to_vec(),
+        },
+        // not a stake account at all
+        PoolAccount {
+            address: [8; 32],
+            lamports: LAMPORTS_PER_SOL,
+            data: vec![0; 16],
+        },
+    ]
+}
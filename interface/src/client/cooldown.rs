@@ -0,0 +1,175 @@
+use crate::{
+    consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    state::{bytes_to_u64, warmup_cooldown_rate, Delegation, StakeHistoryGetEntry, MAX_ENTRIES},
+};
+
+/// Estimates the first epoch at which a deactivated `delegation` has fully
+/// cooled down, so that all of it can be withdrawn.
+///
+/// Recorded epochs follow the same math as the program. Epochs from
+/// `current_epoch` on have no history yet; for those, the latest recorded
+/// cluster stake is carried forward, with the cluster's deactivating stake
+/// draining at the cooldown rate. Returns `None` when the stake is not
+/// deactivated, or would take more than `MAX_ENTRIES` epochs to cool down
+/// under that projection.
+pub fn estimate_withdrawable_at<T: StakeHistoryGetEntry>(
+    delegation: &Delegation,
+    stake_history: &T,
+    current_epoch: u64,
+) -> Option<u64> {
+    let deactivation_epoch = delegation.deactivation_epoch();
+    if deactivation_epoch == u64::MAX {
+        return None;
+    }
+
+    let mut remaining = bytes_to_u64(
+        delegation
+            .stake_activating_and_deactivating(
+                delegation.deactivation_epoch,
+                stake_history,
+                PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+            )
+            .effective,
+    );
+
+    // (effective, deactivating) cluster stake assumed for unrecorded epochs
+    let mut projected = current_epoch
+        .checked_sub(1)
+        .and_then(|epoch| stake_history.get_entry(epoch))
+        .map(|entry| {
+            (
+                bytes_to_u64(entry.effective),
+                bytes_to_u64(entry.deactivating),
+            )
+        })
+        .unwrap_or_default();
+
+    let mut epoch = deactivation_epoch;
+    while remaining > 0 {
+        let (cluster_effective, cluster_deactivating) = if epoch < current_epoch {
+            match stake_history.get_entry(epoch) {
+                Some(entry) => (
+                    bytes_to_u64(entry.effective),
+                    bytes_to_u64(entry.deactivating),
+                ),
+                // like the program, presume stake that fell out of history
+                // has fully deactivated
+                None => return Some(epoch + 1),
+            }
+        } else if epoch >= current_epoch.saturating_add(MAX_ENTRIES as u64) {
+            return None;
+        } else {
+            projected
+        };
+        // our own stake is part of whatever the cluster is deactivating
+        let cluster_deactivating = cluster_deactivating.max(remaining);
+
+        epoch += 1;
+        let rate = warmup_cooldown_rate(
+            epoch.to_le_bytes(),
+            PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+        );
+        let cluster_cooldown = cluster_effective as f64 * rate;
+        let weight = remaining as f64 / cluster_deactivating as f64;
+        remaining = remaining.saturating_sub(((weight * cluster_cooldown) as u64).max(1));
+
+        if epoch > current_epoch {
+            projected = (
+                cluster_effective,
+                cluster_deactivating.saturating_sub(cluster_cooldown as u64),
+            );
+        }
+    }
+    Some(epoch)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{StakeHistory, StakeHistoryEntry};
+
+    const STAKE: u64 = 200_000;
+
+    fn deactivated_delegation(deactivation_epoch: u64) -> Delegation {
+        // bootstrap stake, fully effective until deactivated
+        let mut delegation = Delegation::new(&[1; 32], STAKE, u64::MAX.to_le_bytes());
+        delegation.set_deactivation_epoch(deactivation_epoch);
+        delegation
+    }
+
+    fn history(epochs: core::ops::Range<u64>, effective: u64, deactivating: u64) -> StakeHistory {
+        let mut history = StakeHistory::default();
+        for epoch in epochs {
+            history.add(
+                epoch,
+                StakeHistoryEntry {
+                    effective: effective.to_le_bytes(),
+                    deactivating: deactivating.to_le_bytes(),
+                    ..StakeHistoryEntry::default()
+                },
+            );
+        }
+        history
+    }
+
+    #[test]
+    fn test_not_deactivated() {
+        let delegation = Delegation::new(&[1; 32], STAKE, u64::MAX.to_le_bytes());
+        assert_eq!(
+            estimate_withdrawable_at(&delegation, &StakeHistory::default(), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn test_matches_program_over_recorded_epochs() {
+        let delegation = deactivated_delegation(10);
+        // the cluster cools down 180_000 an epoch, shared with 100_000 more
+        // stake, so this stake loses 60% of what is left each epoch
+        let history = history(0..40, 2_000_000, STAKE + 100_000);
+
+        let first_inactive = (10..40)
+            .find(|&epoch: &u64| {
+                delegation.stake(
+                    epoch.to_le_bytes(),
+                    &history,
+                    PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+                ) == 0
+            })
+            .unwrap();
+        assert_eq!(
+            estimate_withdrawable_at(&delegation, &history, 40),
+            Some(first_inactive)
+        );
+    }
+
+    #[test]
+    fn test_projects_past_current_epoch() {
+        // deactivated this epoch, the cluster cools down 90_000 an epoch:
+        // 200_000 -> 110_000 -> 20_000 -> 0
+        let delegation = deactivated_delegation(10);
+        let history = history(0..10, 1_000_000, 0);
+        assert_eq!(
+            estimate_withdrawable_at(&delegation, &history, 10),
+            Some(13)
+        );
+    }
+
+    #[test]
+    fn test_fell_out_of_history() {
+        let delegation = deactivated_delegation(10);
+        assert_eq!(
+            estimate_withdrawable_at(&delegation, &StakeHistory::default(), 600),
+            Some(11)
+        );
+    }
+
+    #[test]
+    fn test_no_cluster_stake_to_project() {
+        let delegation = deactivated_delegation(10);
+        assert_eq!(
+            estimate_withdrawable_at(&delegation, &StakeHistory::default(), 10),
+            None
+        );
+    }
+}
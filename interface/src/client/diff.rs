@@ -0,0 +1,261 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use pinocchio::pubkey::Pubkey;
+
+use crate::state::{Delegation, Meta, Stake, StakeFlags, StakeStateV2};
+
+/// A field two stake states disagree on, named by its path through the state,
+/// such as `meta.lockup.epoch`, with both values as a person would read them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, self.old, self.new)
+    }
+}
+
+/// Every field that differs between `old` and `new`, empty when they are
+/// equal. States of different variants differ in `variant`, and are compared
+/// on whatever else they both have: the meta of `Initialized` and `Stake`.
+pub fn diff_stake_states(old: &StakeStateV2, new: &StakeStateV2) -> Vec<FieldDiff> {
+    let mut diffs = Vec::new();
+    if variant_name(old) != variant_name(new) {
+        push(&mut diffs, "variant", variant_name(old), variant_name(new));
+    }
+    if let (Some(old), Some(new)) = (meta(old), meta(new)) {
+        diff_meta(&mut diffs, old, new);
+    }
+    if let (StakeStateV2::Stake(_, old, old_flags), StakeStateV2::Stake(_, new, new_flags)) =
+        (old, new)
+    {
+        diff_stake(&mut diffs, old, new);
+        diff_flags(&mut diffs, *old_flags, *new_flags);
+    }
+    diffs
+}
+
+fn variant_name(state: &StakeStateV2) -> &'static str {
+    match state {
+        StakeStateV2::Uninitialized => "Uninitialized",
+        StakeStateV2::Initialized(_) => "Initialized",
+        StakeStateV2::Stake(..) => "Stake",
+        StakeStateV2::RewardsPool => "RewardsPool",
+    }
+}
+
+fn meta(state: &StakeStateV2) -> Option<&Meta> {
+    match state {
+        StakeStateV2::Initialized(meta) | StakeStateV2::Stake(meta, ..) => Some(meta),
+        _ => None,
+    }
+}
+
+fn push<T: PartialEq + fmt::Display>(
+    diffs: &mut Vec<FieldDiff>,
+    field: &'static str,
+    old: T,
+    new: T,
+) {
+    if old != new {
+        diffs.push(FieldDiff {
+            field,
+            old: format!("{old}"),
+            new: format!("{new}"),
+        });
+    }
+}
+
+/// Pubkeys in hex, as the interface has no base58 encoder. Test keys are
+/// usually a repeated byte, which stays recognizable this way.
+fn push_pubkey(diffs: &mut Vec<FieldDiff>, field: &'static str, old: &Pubkey, new: &Pubkey) {
+    let hex = |key: &Pubkey| {
+        key.iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    };
+    push(diffs, field, hex(old), hex(new));
+}
+
+fn diff_meta(diffs: &mut Vec<FieldDiff>, old: &Meta, new: &Meta) {
+    push(
+        diffs,
+        "meta.rent_exempt_reserve",
+        u64::from_le_bytes(old.rent_exempt_reserve),
+        u64::from_le_bytes(new.rent_exempt_reserve),
+    );
+    push_pubkey(
+        diffs,
+        "meta.authorized.staker",
+        &old.authorized.staker,
+        &new.authorized.staker,
+    );
+    push_pubkey(
+        diffs,
+        "meta.authorized.withdrawer",
+        &old.authorized.withdrawer,
+        &new.authorized.withdrawer,
+    );
+    push(
+        diffs,
+        "meta.lockup.unix_timestamp",
+        i64::from_le_bytes(old.lockup.unix_timestamp),
+        i64::from_le_bytes(new.lockup.unix_timestamp),
+    );
+    push(
+        diffs,
+        "meta.lockup.epoch",
+        u64::from_le_bytes(old.lockup.epoch),
+        u64::from_le_bytes(new.lockup.epoch),
+    );
+    push_pubkey(
+        diffs,
+        "meta.lockup.custodian",
+        &old.lockup.custodian,
+        &new.lockup.custodian,
+    );
+}
+
+fn diff_stake(diffs: &mut Vec<FieldDiff>, old: &Stake, new: &Stake) {
+    diff_delegation(diffs, &old.delegation, &new.delegation);
+    push(
+        diffs,
+        "stake.credits_observed",
+        u64::from_le_bytes(old.credits_observed),
+        u64::from_le_bytes(new.credits_observed),
+    );
+}
+
+fn diff_delegation(diffs: &mut Vec<FieldDiff>, old: &Delegation, new: &Delegation) {
+    push_pubkey(
+        diffs,
+        "stake.delegation.voter_pubkey",
+        &old.voter_pubkey,
+        &new.voter_pubkey,
+    );
+    push(
+        diffs,
+        "stake.delegation.stake",
+        u64::from_le_bytes(old.stake),
+        u64::from_le_bytes(new.stake),
+    );
+    push(
+        diffs,
+        "stake.delegation.activation_epoch",
+        u64::from_le_bytes(old.activation_epoch),
+        u64::from_le_bytes(new.activation_epoch),
+    );
+    push(
+        diffs,
+        "stake.delegation.deactivation_epoch",
+        u64::from_le_bytes(old.deactivation_epoch),
+        u64::from_le_bytes(new.deactivation_epoch),
+    );
+    // still stored, so still compared, as bits so a NaN equals itself
+    #[allow(deprecated)]
+    if old.warmup_cooldown_rate != new.warmup_cooldown_rate {
+        diffs.push(FieldDiff {
+            field: "stake.delegation.warmup_cooldown_rate",
+            old: format!("{}", f64::from_le_bytes(old.warmup_cooldown_rate)),
+            new: format!("{}", f64::from_le_bytes(new.warmup_cooldown_rate)),
+        });
+    }
+}
+
+fn diff_flags(diffs: &mut Vec<FieldDiff>, old: StakeFlags, new: StakeFlags) {
+    push(
+        diffs,
+        "stake_flags",
+        format!("{:#010b}", old.bits()),
+        format!("{:#010b}", new.bits()),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::{string::ToString, vec};
+
+    fn meta() -> Meta {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(2_282_880);
+        meta.authorized.staker = [1; 32];
+        meta.authorized.withdrawer = [2; 32];
+        meta
+    }
+
+    fn stake() -> Stake {
+        Stake {
+            delegation: Delegation::new(&[3; 32], 1_000, 5u64.to_le_bytes()),
+            credits_observed: 7u64.to_le_bytes(),
+        }
+    }
+
+    #[test]
+    fn test_equal_states() {
+        for state in [
+            StakeStateV2::Uninitialized,
+            StakeStateV2::Initialized(meta()),
+            StakeStateV2::Stake(meta(), stake(), StakeFlags::empty()),
+            StakeStateV2::RewardsPool,
+        ] {
+            assert_eq!(diff_stake_states(&state, &state), vec![]);
+        }
+    }
+
+    #[test]
+    fn test_changed_fields() {
+        let old = StakeStateV2::Stake(meta(), stake(), StakeFlags::empty());
+        let mut new_meta = meta();
+        new_meta.lockup.epoch = 400u64.to_le_bytes();
+        let mut new_stake = stake();
+        new_stake.delegation.deactivation_epoch = 9u64.to_le_bytes();
+        let new = StakeStateV2::Stake(new_meta, new_stake, StakeFlags::from_bits(1));
+
+        let diffs = diff_stake_states(&old, &new);
+        assert_eq!(
+            diffs.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "meta.lockup.epoch: 0 -> 400".to_string(),
+                format!("stake.delegation.deactivation_epoch: {} -> 9", u64::MAX),
+                "stake_flags: 0b00000000 -> 0b00000001".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_variant() {
+        let mut delegated_meta = meta();
+        delegated_meta.authorized.staker = [0xab; 32];
+        let diffs = diff_stake_states(
+            &StakeStateV2::Initialized(meta()),
+            &StakeStateV2::Stake(delegated_meta, stake(), StakeFlags::empty()),
+        );
+        // the meta both have is still compared field by field
+        assert_eq!(
+            diffs,
+            vec![
+                FieldDiff {
+                    field: "variant",
+                    old: "Initialized".to_string(),
+                    new: "Stake".to_string(),
+                },
+                FieldDiff {
+                    field: "meta.authorized.staker",
+                    old: "01".repeat(32),
+                    new: "ab".repeat(32),
+                },
+            ]
+        );
+
+        assert_eq!(
+            diff_stake_states(&StakeStateV2::Uninitialized, &StakeStateV2::RewardsPool).len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use alloc::{vec, vec::Vec};
+
+use pinocchio::pubkey::Pubkey;
+
+use crate::state::{
+    layout::{STAKER_OFFSET, STAKE_STATE_LEN, WITHDRAWER_OFFSET},
+    StakeAuthorize,
+};
+
+/// A `getProgramAccounts` filter, ready to be converted into the RPC type of
+/// whichever client library is in use. `Memcmp` bytes are raw; encoding them
+/// for the request is left to that library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccountFilter {
+    DataSize(u64),
+    Memcmp { offset: usize, bytes: Vec<u8> },
+}
+
+impl AccountFilter {
+    /// Whether the validator would return an account holding `data`.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            Self::DataSize(len) => data.len() as u64 == *len,
+            Self::Memcmp { offset, bytes } => data
+                .get(*offset..)
+                .and_then(|data| data.get(..bytes.len()))
+                .is_some_and(|data| data == bytes.as_slice()),
+        }
+    }
+}
+
+/// Filters that find every stake account whose `stake_authorize` authority
+/// is `authority`, when passed to `getProgramAccounts` on the stake program.
+///
+/// Both `Initialized` and `Stake` accounts match, since the authorities are
+/// part of the meta they share. The tag isn't filtered on, so decode each
+/// result rather than trusting the match.
+pub fn authority_filters(
+    authority: &Pubkey,
+    stake_authorize: StakeAuthorize,
+) -> Vec<AccountFilter> {
+    let offset = match stake_authorize {
+        StakeAuthorize::Staker => STAKER_OFFSET,
+        StakeAuthorize::Withdrawer => WITHDRAWER_OFFSET,
+    };
+    vec![
+        AccountFilter::DataSize(STAKE_STATE_LEN as u64),
+        AccountFilter::Memcmp {
+            offset,
+            bytes: authority.to_vec(),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        client::stake_state_to_data,
+        state::{Delegation, Meta, Stake, StakeFlags, StakeStateV2},
+    };
+
+    const STAKER: Pubkey = [1; 32];
+    const WITHDRAWER: Pubkey = [2; 32];
+
+    fn meta() -> Meta {
+        let mut meta = Meta::default();
+        meta.authorized.staker = STAKER;
+        meta.authorized.withdrawer = WITHDRAWER;
+        meta
+    }
+
+    fn found(filters: &[AccountFilter], data: &[u8]) -> bool {
+        filters.iter().all(|filter| filter.matches(data))
+    }
+
+    #[test]
+    fn test_authority_filters() {
+        let initialized = stake_state_to_data(&StakeStateV2::Initialized(meta()));
+        let delegated = stake_state_to_data(&StakeStateV2::Stake(
+            meta(),
+            Stake {
+                delegation: Delegation::new(&STAKER, 1_000, 5u64.to_le_bytes()),
+                ..Stake::default()
+            },
+            StakeFlags::empty(),
+        ));
+
+        for data in [initialized, delegated] {
+            assert!(found(
+                &authority_filters(&STAKER, StakeAuthorize::Staker),
+                &data
+            ));
+            assert!(found(
+                &authority_filters(&WITHDRAWER, StakeAuthorize::Withdrawer),
+                &data
+            ));
+            // each authority only at its own offset
+            assert!(!found(
+                &authority_filters(&STAKER, StakeAuthorize::Withdrawer),
+                &data
+            ));
+            assert!(!found(
+                &authority_filters(&WITHDRAWER, StakeAuthorize::Staker),
+                &data
+            ));
+        }
+    }
+
+    #[test]
+    fn test_data_size() {
+        let data = stake_state_to_data(&StakeStateV2::Initialized(meta()));
+        let filters = authority_filters(&STAKER, StakeAuthorize::Staker);
+
+        // a larger account with the same prefix isn't a stake account
+        let mut longer = data.to_vec();
+        longer.push(0);
+        assert!(!found(&filters, &longer));
+
+        // nor is a truncated one, even if the authority is all there
+        assert!(!found(&filters, &data[..WITHDRAWER_OFFSET]));
+        assert!(!AccountFilter::Memcmp {
+            offset: STAKE_STATE_LEN - 1,
+            bytes: vec![0, 0],
+        }
+        .matches(&data));
+    }
+}
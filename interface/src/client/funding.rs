@@ -0,0 +1,83 @@
+use pinocchio::{program_error::ProgramError, sysvars::rent::Rent};
+
+use crate::{consts::MINIMUM_DELEGATION, error::StakeError, state::StakeStateV2};
+
+/// Lamports a new stake account must be funded with to delegate
+/// `delegation_amount`: its rent exempt reserve plus the delegation. Fails
+/// with `InsufficientDelegation` below the cluster's minimum delegation,
+/// which the program would reject anyway.
+pub fn required_lamports_for_delegation(
+    delegation_amount: u64,
+    rent: &Rent,
+) -> Result<u64, ProgramError> {
+    required_lamports(delegation_amount, rent, MINIMUM_DELEGATION)
+}
+
+fn required_lamports(
+    delegation_amount: u64,
+    rent: &Rent,
+    minimum_delegation: u64,
+) -> Result<u64, ProgramError> {
+    if delegation_amount < minimum_delegation {
+        return Err(StakeError::InsufficientDelegation.into());
+    }
+    rent.minimum_balance(StakeStateV2::size_of())
+        .checked_add(delegation_amount)
+        .ok_or(ProgramError::ArithmeticOverflow)
+}
+
+#[cfg(test)]
+mod test {
+    use pinocchio::sysvars::rent::{
+        DEFAULT_BURN_PERCENT, DEFAULT_EXEMPTION_THRESHOLD, DEFAULT_LAMPORTS_PER_BYTE_YEAR,
+    };
+
+    use super::*;
+    use crate::consts::LAMPORTS_PER_SOL;
+
+    const RESERVE: u64 = 2_282_880;
+
+    fn rent() -> Rent {
+        Rent {
+            lamports_per_byte_year: DEFAULT_LAMPORTS_PER_BYTE_YEAR,
+            exemption_threshold: DEFAULT_EXEMPTION_THRESHOLD,
+            burn_percent: DEFAULT_BURN_PERCENT,
+        }
+    }
+
+    #[test]
+    fn test_required_lamports() {
+        assert_eq!(
+            required_lamports_for_delegation(MINIMUM_DELEGATION, &rent()),
+            Ok(RESERVE + MINIMUM_DELEGATION)
+        );
+        assert_eq!(
+            required_lamports_for_delegation(10 * LAMPORTS_PER_SOL, &rent()),
+            Ok(RESERVE + 10 * LAMPORTS_PER_SOL)
+        );
+        assert_eq!(
+            required_lamports_for_delegation(u64::MAX - RESERVE + 1, &rent()),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn test_raised_minimum_delegation_boundary() {
+        for minimum_delegation in [1, LAMPORTS_PER_SOL] {
+            assert_eq!(
+                required_lamports(minimum_delegation, &rent(), minimum_delegation),
+                Ok(RESERVE + minimum_delegation)
+            );
+            assert_eq!(
+                required_lamports(minimum_delegation - 1, &rent(), minimum_delegation),
+                Err(StakeError::InsufficientDelegation.into())
+            );
+        }
+
+        // the reserve doesn't count towards the delegation
+        assert_eq!(
+            required_lamports(LAMPORTS_PER_SOL - RESERVE, &rent(), LAMPORTS_PER_SOL),
+            Err(StakeError::InsufficientDelegation.into())
+        );
+    }
+}
@@ -0,0 +1,620 @@
+use alloc::{vec, vec::Vec};
+
+use pinocchio::{pubkey::Pubkey, sysvars::rent::RENT_ID};
+
+use crate::{
+    consts::{CLOCK_ID, STAKE_CONFIG_ID},
+    instruction::{ExtensionInstruction, LockupArgs, StakeInstruction},
+    state::{stake_history, Authorized, Lockup, StakeAuthorize},
+};
+
+/// Owned account reference of a client-built instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl AccountMeta {
+    pub fn writable(pubkey: Pubkey) -> Self {
+        Self {
+            pubkey,
+            is_signer: false,
+            is_writable: true,
+        }
+    }
+
+    pub fn readonly(pubkey: Pubkey) -> Self {
+        Self {
+            pubkey,
+            is_signer: false,
+            is_writable: false,
+        }
+    }
+
+    pub fn readonly_signer(pubkey: Pubkey) -> Self {
+        Self {
+            pubkey,
+            is_signer: true,
+            is_writable: false,
+        }
+    }
+}
+
+/// A stake program instruction, ready to be converted into the transaction
+/// type of whichever client library is in use.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub program_id: Pubkey,
+    pub accounts: Vec<AccountMeta>,
+    pub data: Vec<u8>,
+}
+
+impl Instruction {
+    fn new(instruction: StakeInstruction, args: &[u8], accounts: Vec<AccountMeta>) -> Self {
+        let mut data = Vec::with_capacity(1 + args.len());
+        data.push(instruction as u8);
+        data.extend_from_slice(args);
+        Self {
+            program_id: crate::ID,
+            accounts,
+            data,
+        }
+    }
+
+    /// The instruction this one encodes.
+    pub fn stake_instruction(&self) -> Option<StakeInstruction> {
+        self.data
+            .first()
+            .and_then(|tag| StakeInstruction::try_from(tag).ok())
+    }
+}
+
+/// Splits `lamports` off `stake` into the uninitialized `split_stake`.
+pub fn split(
+    stake: &Pubkey,
+    authorized: &Pubkey,
+    lamports: u64,
+    split_stake: &Pubkey,
+) -> Instruction {
+    Instruction::new(
+        StakeInstruction::Split,
+        &lamports.to_le_bytes(),
+        vec![
+            AccountMeta::writable(*stake),
+            AccountMeta::writable(*split_stake),
+            AccountMeta::readonly_signer(*authorized),
+        ],
+    )
+}
+
+/// Merges `source_stake` into `destination_stake`, closing the source.
+pub fn merge(
+    destination_stake: &Pubkey,
+    source_stake: &Pubkey,
+    authorized: &Pubkey,
+) -> Instruction {
+    Instruction::new(
+        StakeInstruction::Merge,
+        &[],
+        vec![
+            AccountMeta::writable(*destination_stake),
+            AccountMeta::writable(*source_stake),
+            AccountMeta::readonly(CLOCK_ID),
+            AccountMeta::readonly(stake_history::ID),
+            AccountMeta::readonly_signer(*authorized),
+        ],
+    )
+}
+
+pub fn delegate_stake(stake: &Pubkey, authorized: &Pubkey, vote: &Pubkey) -> Instruction {
+    Instruction::new(
+        StakeInstruction::DelegateStake,
+        &[],
+        vec![
+            AccountMeta::writable(*stake),
+            AccountMeta::readonly(*vote),
+            AccountMeta::readonly(CLOCK_ID),
+            AccountMeta::readonly(stake_history::ID),
+            AccountMeta::readonly(STAKE_CONFIG_ID),
+            AccountMeta::readonly_signer(*authorized),
+        ],
+    )
+}
+
+/// Delegates `stake` to `vote` and hands its staker authority from
+/// `authorized` to `new_staker` in the same instruction, for onboarding a
+/// stake into a pool in one transaction. Only programs built with the
+/// `extensions` feature take it.
+pub fn set_staker_and_delegate(
+    stake: &Pubkey,
+    authorized: &Pubkey,
+    vote: &Pubkey,
+    new_staker: &Pubkey,
+) -> Instruction {
+    let mut data = Vec::with_capacity(1 + 32);
+    data.push(ExtensionInstruction::SetStakerAndDelegate as u8);
+    data.extend_from_slice(new_staker);
+    Instruction {
+        program_id: crate::ID,
+        accounts: delegate_stake(stake, authorized, vote).accounts,
+        data,
+    }
+}
+
+pub fn deactivate_stake(stake: &Pubkey, authorized: &Pubkey) -> Instruction {
+    Instruction::new(
+        StakeInstruction::Deactivate,
+        &[],
+        vec![
+            AccountMeta::writable(*stake),
+            AccountMeta::readonly(CLOCK_ID),
+            AccountMeta::readonly_signer(*authorized),
+        ],
+    )
+}
+
+/// Hands `stake_authorize` from `authorized` over to `new_authorized`, which
+/// doesn't sign, see `authorize_checked`. Changing the withdrawer while the
+/// lockup is in force takes the `custodian` too.
+pub fn authorize(
+    stake: &Pubkey,
+    authorized: &Pubkey,
+    new_authorized: &Pubkey,
+    stake_authorize: StakeAuthorize,
+    custodian: Option<&Pubkey>,
+) -> Instruction {
+    let mut args = Vec::with_capacity(32 + 4);
+    args.extend_from_slice(new_authorized);
+    args.extend_from_slice(&(stake_authorize as u32).to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::writable(*stake),
+        AccountMeta::readonly(CLOCK_ID),
+        AccountMeta::readonly_signer(*authorized),
+    ];
+    if let Some(custodian) = custodian {
+        accounts.push(AccountMeta::readonly_signer(*custodian));
+    }
+    Instruction::new(StakeInstruction::Authorize, &args, accounts)
+}
+
+/// Hands `stake_authorize` over to `new_authorized`, signed for by `base`
+/// of the current authority `create_with_seed(base, seed, owner)`. `base` may
+/// be a PDA, in which case the owning program issues this through
+/// `invoke_signed`.
+pub fn authorize_with_seed(
+    stake: &Pubkey,
+    base: &Pubkey,
+    seed: &str,
+    owner: &Pubkey,
+    new_authorized: &Pubkey,
+    stake_authorize: StakeAuthorize,
+    custodian: Option<&Pubkey>,
+) -> Instruction {
+    let mut args = Vec::with_capacity(32 + 4 + 8 + seed.len() + 32);
+    args.extend_from_slice(new_authorized);
+    args.extend_from_slice(&(stake_authorize as u32).to_le_bytes());
+    args.extend_from_slice(&(seed.len() as u64).to_le_bytes());
+    args.extend_from_slice(seed.as_bytes());
+    args.extend_from_slice(owner);
+
+    let mut accounts = vec![
+        AccountMeta::writable(*stake),
+        AccountMeta::readonly_signer(*base),
+        AccountMeta::readonly(CLOCK_ID),
+    ];
+    if let Some(custodian) = custodian {
+        accounts.push(AccountMeta::readonly_signer(*custodian));
+    }
+    Instruction::new(StakeInstruction::AuthorizeWithSeed, &args, accounts)
+}
+
+/// Initializes the uninitialized, rent exempt `stake` with `authorized` and
+/// `lockup`. Neither authority signs, see `initialize_checked`.
+pub fn initialize(stake: &Pubkey, authorized: &Authorized, lockup: &Lockup) -> Instruction {
+    let mut args = Vec::with_capacity(32 + 32 + 8 + 8 + 32);
+    args.extend_from_slice(&authorized.staker);
+    args.extend_from_slice(&authorized.withdrawer);
+    args.extend_from_slice(&lockup.unix_timestamp);
+    args.extend_from_slice(&lockup.epoch);
+    args.extend_from_slice(&lockup.custodian);
+    Instruction::new(
+        StakeInstruction::Initialize,
+        &args,
+        vec![
+            AccountMeta::writable(*stake),
+            AccountMeta::readonly(RENT_ID),
+        ],
+    )
+}
+
+/// Initializes `stake` with `staker` and `withdrawer`, as `Initialize` with
+/// no lockup, but with the withdrawer signing to show it is a key someone
+/// holds.
+pub fn initialize_checked(stake: &Pubkey, staker: &Pubkey, withdrawer: &Pubkey) -> Instruction {
+    Instruction::new(
+        StakeInstruction::InitializeChecked,
+        &[],
+        vec![
+            AccountMeta::writable(*stake),
+            AccountMeta::readonly(RENT_ID),
+            AccountMeta::readonly(*staker),
+            AccountMeta::readonly_signer(*withdrawer),
+        ],
+    )
+}
+
+/// Hands `stake_authorize` from `authorized` over to `new_authorized`, which
+/// signs too, after the current authority.
+pub fn authorize_checked(
+    stake: &Pubkey,
+    authorized: &Pubkey,
+    new_authorized: &Pubkey,
+    stake_authorize: StakeAuthorize,
+    custodian: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::writable(*stake),
+        AccountMeta::readonly(CLOCK_ID),
+        AccountMeta::readonly_signer(*authorized),
+        AccountMeta::readonly_signer(*new_authorized),
+    ];
+    if let Some(custodian) = custodian {
+        accounts.push(AccountMeta::readonly_signer(*custodian));
+    }
+    Instruction::new(
+        StakeInstruction::AuthorizeChecked,
+        &(stake_authorize as u32).to_le_bytes(),
+        accounts,
+    )
+}
+
+/// `authorize_with_seed`, with `new_authorized` signing after the clock
+/// rather than being named in the arguments.
+pub fn authorize_checked_with_seed(
+    stake: &Pubkey,
+    base: &Pubkey,
+    seed: &str,
+    owner: &Pubkey,
+    new_authorized: &Pubkey,
+    stake_authorize: StakeAuthorize,
+    custodian: Option<&Pubkey>,
+) -> Instruction {
+    let mut args = Vec::with_capacity(4 + 8 + seed.len() + 32);
+    args.extend_from_slice(&(stake_authorize as u32).to_le_bytes());
+    args.extend_from_slice(&(seed.len() as u64).to_le_bytes());
+    args.extend_from_slice(seed.as_bytes());
+    args.extend_from_slice(owner);
+
+    let mut accounts = vec![
+        AccountMeta::writable(*stake),
+        AccountMeta::readonly_signer(*base),
+        AccountMeta::readonly(CLOCK_ID),
+        AccountMeta::readonly_signer(*new_authorized),
+    ];
+    if let Some(custodian) = custodian {
+        accounts.push(AccountMeta::readonly_signer(*custodian));
+    }
+    Instruction::new(StakeInstruction::AuthorizeCheckedWithSeed, &args, accounts)
+}
+
+/// Sets the parts of `stake`'s lockup given in `lockup`, signed for by
+/// `authority`: the custodian while the lockup is in force, the withdrawer
+/// otherwise. A new custodian isn't encoded in the arguments but signs last.
+pub fn set_lockup_checked(stake: &Pubkey, lockup: &LockupArgs, authority: &Pubkey) -> Instruction {
+    let mut args = Vec::with_capacity(2 * (1 + 8));
+    for value in [lockup.unix_timestamp, lockup.epoch] {
+        match value {
+            Some(value) => {
+                args.push(1);
+                args.extend_from_slice(&value);
+            }
+            None => args.push(0),
+        }
+    }
+
+    let mut accounts = vec![
+        AccountMeta::writable(*stake),
+        AccountMeta::readonly_signer(*authority),
+    ];
+    if let Some(custodian) = lockup.custodian {
+        accounts.push(AccountMeta::readonly_signer(custodian));
+    }
+    Instruction::new(StakeInstruction::SetLockupChecked, &args, accounts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruction::InitializeArgs;
+
+    #[test]
+    fn test_split_layout() {
+        let instruction = split(&[1; 32], &[2; 32], 500, &[3; 32]);
+        assert_eq!(instruction.program_id, crate::ID);
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::Split)
+        );
+        assert_eq!(instruction.data[1..], 500u64.to_le_bytes());
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::writable([3; 32]),
+                AccountMeta::readonly_signer([2; 32]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_authority_signs_last() {
+        for instruction in [
+            merge(&[1; 32], &[3; 32], &[2; 32]),
+            delegate_stake(&[1; 32], &[2; 32], &[4; 32]),
+            deactivate_stake(&[1; 32], &[2; 32]),
+        ] {
+            assert_eq!(instruction.data.len(), 1);
+            assert_eq!(instruction.accounts[0], AccountMeta::writable([1; 32]));
+            assert_eq!(
+                instruction.accounts.last(),
+                Some(&AccountMeta::readonly_signer([2; 32]))
+            );
+            assert_eq!(
+                instruction
+                    .accounts
+                    .iter()
+                    .filter(|meta| meta.is_signer)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_staker_and_delegate_layout() {
+        let instruction = set_staker_and_delegate(&[1; 32], &[2; 32], &[4; 32], &[5; 32]);
+        // not a native instruction
+        assert_eq!(instruction.stake_instruction(), None);
+        assert_eq!(
+            ExtensionInstruction::try_from(&instruction.data[0]),
+            Ok(ExtensionInstruction::SetStakerAndDelegate)
+        );
+        assert_eq!(
+            crate::instruction::new_staker_from_data(&instruction.data[1..]),
+            Ok([5; 32])
+        );
+        assert_eq!(
+            instruction.accounts,
+            delegate_stake(&[1; 32], &[2; 32], &[4; 32]).accounts
+        );
+    }
+
+    #[test]
+    fn test_authorize_args() {
+        let instruction = authorize(&[1; 32], &[2; 32], &[4; 32], StakeAuthorize::Staker, None);
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::Authorize)
+        );
+        assert_eq!(
+            crate::instruction::AuthorizeArgs::from_data(&instruction.data[1..]),
+            Ok(crate::instruction::AuthorizeArgs {
+                new_authorized_pubkey: [4; 32],
+                stake_authorize: StakeAuthorize::Staker,
+            })
+        );
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly(CLOCK_ID),
+                AccountMeta::readonly_signer([2; 32]),
+            ]
+        );
+
+        // the custodian comes last
+        let instruction = authorize(
+            &[1; 32],
+            &[2; 32],
+            &[4; 32],
+            StakeAuthorize::Withdrawer,
+            Some(&[5; 32]),
+        );
+        assert_eq!(instruction.data[33..], 1u32.to_le_bytes());
+        assert_eq!(
+            instruction.accounts.last(),
+            Some(&AccountMeta::readonly_signer([5; 32]))
+        );
+    }
+
+    #[test]
+    fn test_authorize_with_seed_args() {
+        let instruction = authorize_with_seed(
+            &[1; 32],
+            &[2; 32],
+            "pool-7",
+            &[3; 32],
+            &[4; 32],
+            StakeAuthorize::Withdrawer,
+            Some(&[5; 32]),
+        );
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::AuthorizeWithSeed)
+        );
+        assert_eq!(
+            crate::instruction::AuthorizeWithSeedArgs::from_data(&instruction.data[1..]),
+            Ok(crate::instruction::AuthorizeWithSeedArgs {
+                new_authorized_pubkey: [4; 32],
+                stake_authorize: StakeAuthorize::Withdrawer,
+                authority_seed: "pool-7",
+                authority_owner: [3; 32],
+            })
+        );
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly_signer([2; 32]),
+                AccountMeta::readonly(CLOCK_ID),
+                AccountMeta::readonly_signer([5; 32]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_initialize_args() {
+        let authorized = Authorized {
+            staker: [2; 32],
+            withdrawer: [3; 32],
+        };
+        let lockup = Lockup {
+            unix_timestamp: 10i64.to_le_bytes(),
+            epoch: 20u64.to_le_bytes(),
+            custodian: [4; 32],
+        };
+        let instruction = initialize(&[1; 32], &authorized, &lockup);
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::Initialize)
+        );
+        assert_eq!(
+            InitializeArgs::from_data(&instruction.data[1..]),
+            Ok(InitializeArgs { authorized, lockup })
+        );
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly(RENT_ID),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_initialize_checked_layout() {
+        let instruction = initialize_checked(&[1; 32], &[2; 32], &[3; 32]);
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::InitializeChecked)
+        );
+        assert_eq!(instruction.data.len(), 1);
+        // only the withdrawer signs
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly(RENT_ID),
+                AccountMeta::readonly([2; 32]),
+                AccountMeta::readonly_signer([3; 32]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_authorize_checked_signers() {
+        let instruction = authorize_checked(
+            &[1; 32],
+            &[2; 32],
+            &[4; 32],
+            StakeAuthorize::Withdrawer,
+            Some(&[5; 32]),
+        );
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::AuthorizeChecked)
+        );
+        assert_eq!(instruction.data[1..], 1u32.to_le_bytes());
+        // current authority, then new authority, then custodian
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly(CLOCK_ID),
+                AccountMeta::readonly_signer([2; 32]),
+                AccountMeta::readonly_signer([4; 32]),
+                AccountMeta::readonly_signer([5; 32]),
+            ]
+        );
+
+        let instruction =
+            authorize_checked(&[1; 32], &[2; 32], &[4; 32], StakeAuthorize::Staker, None);
+        assert_eq!(instruction.data[1..], 0u32.to_le_bytes());
+        assert_eq!(instruction.accounts.len(), 4);
+    }
+
+    #[test]
+    fn test_authorize_checked_with_seed_args() {
+        let instruction = authorize_checked_with_seed(
+            &[1; 32],
+            &[2; 32],
+            "pool-7",
+            &[3; 32],
+            &[4; 32],
+            StakeAuthorize::Staker,
+            None,
+        );
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::AuthorizeCheckedWithSeed)
+        );
+        assert_eq!(
+            crate::instruction::AuthorizeCheckedWithSeedArgs::from_data(&instruction.data[1..]),
+            Ok(crate::instruction::AuthorizeCheckedWithSeedArgs {
+                stake_authorize: StakeAuthorize::Staker,
+                authority_seed: "pool-7",
+                authority_owner: [3; 32],
+            })
+        );
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly_signer([2; 32]),
+                AccountMeta::readonly(CLOCK_ID),
+                AccountMeta::readonly_signer([4; 32]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_lockup_checked_args() {
+        let lockup = LockupArgs {
+            unix_timestamp: None,
+            epoch: Some(400u64.to_le_bytes()),
+            custodian: Some([6; 32]),
+        };
+        let instruction = set_lockup_checked(&[1; 32], &lockup, &[2; 32]);
+        assert_eq!(
+            instruction.stake_instruction(),
+            Some(StakeInstruction::SetLockupChecked)
+        );
+        let mut args = vec![0, 1];
+        args.extend_from_slice(&400u64.to_le_bytes());
+        assert_eq!(instruction.data[1..], args);
+        // the new custodian signs instead of being in the arguments
+        assert_eq!(
+            instruction.accounts,
+            vec![
+                AccountMeta::writable([1; 32]),
+                AccountMeta::readonly_signer([2; 32]),
+                AccountMeta::readonly_signer([6; 32]),
+            ]
+        );
+
+        let lockup = LockupArgs {
+            unix_timestamp: Some((-1i64).to_le_bytes()),
+            epoch: None,
+            custodian: None,
+        };
+        let instruction = set_lockup_checked(&[1; 32], &lockup, &[2; 32]);
+        let mut args = vec![1];
+        args.extend_from_slice(&(-1i64).to_le_bytes());
+        args.push(0);
+        assert_eq!(instruction.data[1..], args);
+        assert_eq!(instruction.accounts.len(), 2);
+    }
+}
@@ -0,0 +1,20 @@
+//! Off-chain helpers for wallets and other clients, built with the `client`
+//! feature.
+
+pub mod cooldown;
+pub mod diff;
+pub mod filter;
+pub mod funding;
+pub mod instruction;
+pub mod seed;
+pub mod stake_history;
+pub mod state;
+
+pub use cooldown::*;
+pub use diff::*;
+pub use filter::*;
+pub use funding::*;
+pub use instruction::*;
+pub use seed::*;
+pub use stake_history::*;
+pub use state::*;
@@ -0,0 +1,168 @@
+use alloc::{format, string::String, vec::Vec};
+
+use pinocchio::{
+    program_error::ProgramError,
+    pubkey::{Pubkey, MAX_SEED_LEN},
+};
+use sha2::{Digest, Sha256};
+
+/// Most addresses `getMultipleAccounts` takes in one request.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// A stake account address derived from a wallet's base address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeedStakeAddress {
+    pub seed: String,
+    pub address: Pubkey,
+}
+
+/// The address `Pubkey::create_with_seed(base, seed, &stake::ID)` gives: a
+/// stake account `base` can create with `CreateAccountWithSeed`.
+pub fn stake_address_with_seed(base: &Pubkey, seed: &str) -> Result<Pubkey, ProgramError> {
+    if seed.len() > MAX_SEED_LEN {
+        return Err(ProgramError::MaxSeedLengthExceeded);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(base);
+    hasher.update(seed);
+    hasher.update(crate::ID);
+    Ok(hasher.finalize().into())
+}
+
+/// The stake addresses `base` derives with the seeds `{prefix}0` through
+/// `{prefix}{count - 1}`, the way wallets number the stake accounts they
+/// create, e.g. `stake:0`, `stake:1`.
+pub fn seed_stake_addresses(
+    base: &Pubkey,
+    prefix: &str,
+    count: usize,
+) -> Result<Vec<SeedStakeAddress>, ProgramError> {
+    (0..count)
+        .map(|index| {
+            let seed = format!("{prefix}{index}");
+            let address = stake_address_with_seed(base, &seed)?;
+            Ok(SeedStakeAddress { seed, address })
+        })
+        .collect()
+}
+
+/// The seed-derived stake accounts of `base` that exist, out of the first
+/// `count` candidates of `seed_stake_addresses`.
+///
+/// `exists` is handed up to `MAX_MULTIPLE_ACCOUNTS` addresses at a time and
+/// returns, in the same order, whether each is there. Backed by a
+/// `getMultipleAccounts` request, listing a wallet costs one round trip per
+/// hundred candidates. Accounts that were closed, or never created, are
+/// skipped over, so gaps in the numbering don't hide later accounts.
+pub fn find_seed_stake_accounts<E: From<ProgramError>>(
+    base: &Pubkey,
+    prefix: &str,
+    count: usize,
+    mut exists: impl FnMut(&[Pubkey]) -> Result<Vec<bool>, E>,
+) -> Result<Vec<SeedStakeAddress>, E> {
+    let candidates = seed_stake_addresses(base, prefix, count)?;
+    let mut found = Vec::new();
+    for batch in candidates.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let addresses: Vec<Pubkey> = batch.iter().map(|candidate| candidate.address).collect();
+        let existing = exists(&addresses)?;
+        if existing.len() != addresses.len() {
+            return Err(ProgramError::InvalidArgument.into());
+        }
+        found.extend(
+            batch
+                .iter()
+                .zip(existing)
+                .filter(|(_, exists)| *exists)
+                .map(|(candidate, _)| candidate.clone()),
+        );
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BASE: Pubkey = [7; 32];
+
+    #[test]
+    fn test_stake_address_with_seed() {
+        // computed with `Pubkey::create_with_seed`
+        assert_eq!(
+            stake_address_with_seed(&BASE, "stake:0"),
+            Ok([
+                110, 74, 143, 68, 80, 221, 167, 221, 120, 31, 235, 125, 101, 48, 98, 176, 73, 220,
+                36, 131, 231, 141, 255, 29, 144, 220, 202, 116, 131, 39, 24, 190
+            ])
+        );
+        assert_eq!(
+            stake_address_with_seed(&BASE, "stake:1"),
+            Ok([
+                247, 52, 231, 39, 134, 30, 37, 123, 11, 5, 188, 226, 253, 123, 207, 10, 109, 16,
+                202, 222, 114, 192, 59, 116, 64, 196, 118, 87, 201, 96, 19, 77
+            ])
+        );
+
+        let longest = "s".repeat(MAX_SEED_LEN);
+        assert!(stake_address_with_seed(&BASE, &longest).is_ok());
+        assert_eq!(
+            stake_address_with_seed(&BASE, &format!("{longest}s")),
+            Err(ProgramError::MaxSeedLengthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_seed_stake_addresses() {
+        let candidates = seed_stake_addresses(&BASE, "stake:", 12).unwrap();
+        assert_eq!(candidates.len(), 12);
+        assert_eq!(candidates[0].seed, "stake:0");
+        assert_eq!(candidates[11].seed, "stake:11");
+        for candidate in &candidates {
+            assert_eq!(
+                Ok(candidate.address),
+                stake_address_with_seed(&BASE, &candidate.seed)
+            );
+        }
+
+        // a prefix leaving no room for the index fails rather than truncates
+        let prefix = "p".repeat(MAX_SEED_LEN);
+        assert_eq!(
+            seed_stake_addresses(&BASE, &prefix, 1),
+            Err(ProgramError::MaxSeedLengthExceeded)
+        );
+    }
+
+    #[test]
+    fn test_find_seed_stake_accounts() {
+        let candidates = seed_stake_addresses(&BASE, "stake:", 250).unwrap();
+        // a wallet that created accounts 0 through 2 and closed 1, then
+        // created 150 and 249
+        let existing: Vec<Pubkey> = [0, 2, 150, 249]
+            .iter()
+            .map(|index| candidates[*index].address)
+            .collect();
+
+        let mut requests = Vec::new();
+        let found = find_seed_stake_accounts::<ProgramError>(&BASE, "stake:", 250, |batch| {
+            requests.push(batch.len());
+            Ok(batch
+                .iter()
+                .map(|address| existing.contains(address))
+                .collect())
+        })
+        .unwrap();
+        let seeds: Vec<&str> = found.iter().map(|found| found.seed.as_str()).collect();
+        assert_eq!(seeds, ["stake:0", "stake:2", "stake:150", "stake:249"]);
+        assert_eq!(requests, [100, 100, 50]);
+
+        // lookup failures and short answers are passed back
+        assert_eq!(
+            find_seed_stake_accounts(&BASE, "stake:", 3, |_| Err(ProgramError::Custom(1))),
+            Err(ProgramError::Custom(1))
+        );
+        assert_eq!(
+            find_seed_stake_accounts::<ProgramError>(&BASE, "stake:", 3, |_| Ok(vec![true])),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
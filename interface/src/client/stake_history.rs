@@ -0,0 +1,4 @@
+//! `StakeHistoryData` lives with the rest of the state, so processors can read
+//! stake history from account bytes too; kept here for existing imports.
+
+pub use crate::state::StakeHistoryData;
@@ -0,0 +1,115 @@
+use pinocchio::program_error::ProgramError;
+
+use crate::{
+    consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    state::{bytes_to_u64, Delegation, StakeHistoryGetEntry, StakeStateV2},
+};
+
+/// Decodes stake account data as fetched by a client. Unlike
+/// `StakeStateV2::try_from_bytes`, `data` need not be aligned.
+pub fn stake_state_from_data(data: &[u8]) -> Result<StakeStateV2, ProgramError> {
+    StakeStateV2::read_from(data)
+}
+
+/// Serializes `state` as it is stored in a stake account.
+pub fn stake_state_to_data(state: &StakeStateV2) -> [u8; StakeStateV2::size_of()] {
+    let mut data = [0u8; StakeStateV2::size_of()];
+    // SAFETY: `StakeStateV2` is `repr(C)` and `size_of()` bytes long
+    let bytes = unsafe {
+        core::slice::from_raw_parts(state as *const StakeStateV2 as *const u8, data.len())
+    };
+    data.copy_from_slice(bytes);
+    data
+}
+
+/// Where a delegation stands at a given epoch, as wallets display it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakeActivationState {
+    Activating,
+    Active,
+    Deactivating,
+    Inactive,
+}
+
+pub fn activation_state<T: StakeHistoryGetEntry>(
+    delegation: &Delegation,
+    stake_history: &T,
+    epoch: u64,
+) -> StakeActivationState {
+    let status = delegation.stake_activating_and_deactivating(
+        epoch.to_le_bytes(),
+        stake_history,
+        PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    );
+    if bytes_to_u64(status.deactivating) > 0 {
+        StakeActivationState::Deactivating
+    } else if bytes_to_u64(status.activating) > 0 {
+        StakeActivationState::Activating
+    } else if bytes_to_u64(status.effective) > 0 {
+        StakeActivationState::Active
+    } else {
+        StakeActivationState::Inactive
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{Meta, Stake, StakeFlags, StakeHistory};
+
+    #[test]
+    fn test_data_round_trip() {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(2_282_880);
+        let stake = Stake {
+            delegation: Delegation::new(&[7; 32], 1_000, 5u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        let state = StakeStateV2::Stake(meta, stake, StakeFlags::empty());
+
+        let data = stake_state_to_data(&state);
+        // decoding copies, so any offset into a buffer works
+        let mut unaligned = vec![0u8; data.len() + 1];
+        unaligned[1..].copy_from_slice(&data);
+        assert_eq!(stake_state_from_data(&unaligned[1..]), Ok(state));
+
+        unaligned[1] = 4;
+        assert_eq!(
+            stake_state_from_data(&unaligned[1..]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            stake_state_from_data(&data[1..]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_activation_state() {
+        let history = StakeHistory::default();
+        let mut delegation = Delegation::new(&[7; 32], 1_000, 10u64.to_le_bytes());
+        assert_eq!(
+            activation_state(&delegation, &history, 9),
+            StakeActivationState::Inactive
+        );
+        assert_eq!(
+            activation_state(&delegation, &history, 10),
+            StakeActivationState::Activating
+        );
+        // no history for the activation epoch, so presumed fully active
+        assert_eq!(
+            activation_state(&delegation, &history, 11),
+            StakeActivationState::Active
+        );
+
+        delegation.set_deactivation_epoch(20);
+        assert_eq!(
+            activation_state(&delegation, &history, 20),
+            StakeActivationState::Deactivating
+        );
+        assert_eq!(
+            activation_state(&delegation, &history, 21),
+            StakeActivationState::Inactive
+        );
+    }
+}
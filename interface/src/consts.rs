@@ -0,0 +1,27 @@
+use pinocchio::pubkey::Pubkey;
+use pinocchio_pubkey::pubkey;
+
+pub const DEFAULT_WARMUP_COOLDOWN_RATE: f64 = 0.25;
+pub const NEW_WARMUP_COOLDOWN_RATE: f64 = 0.09;
+/// Epoch from which the new warmup/cooldown rate applies; it has been active
+/// since genesis on every cluster this program targets.
+pub const PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH: Option<[u8; 8]> = Some((0u64).to_le_bytes());
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+pub const FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL: bool = false;
+/// Smallest stake a delegation may hold, 1 SOL once the cluster raises it.
+pub const MINIMUM_DELEGATION: u64 =
+    minimum_delegation(FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL);
+
+/// The minimum delegation on either side of the raise.
+pub const fn minimum_delegation(raise_minimum_delegation_to_1_sol: bool) -> u64 {
+    if raise_minimum_delegation_to_1_sol {
+        LAMPORTS_PER_SOL
+    } else {
+        1
+    }
+}
+
+pub const CLOCK_ID: Pubkey = pubkey!("SysvarC1ock11111111111111111111111111111111");
+/// The retired stake config account, still expected by `DelegateStake`.
+pub const STAKE_CONFIG_ID: Pubkey = pubkey!("StakeConfig11111111111111111111111111111111");
@@ -0,0 +1,131 @@
+//! Helpers for programs that sign for a PDA when calling into the stake
+//! program, or any other, through `invoke_signed`.
+
+use pinocchio::{
+    instruction::{Seed, Signer},
+    program_error::ProgramError,
+    pubkey::MAX_SEED_LEN,
+};
+
+/// Seed parts a `SignerSeeds` holds, not counting the bump.
+pub const MAX_SIGNER_SEED_PARTS: usize = 4;
+
+/// Seeds of a PDA signer, built on the stack: up to
+/// `MAX_SIGNER_SEED_PARTS` parts, followed by the bump.
+///
+/// ```
+/// # use pinocchio::{instruction::Signer, ProgramResult};
+/// # use pinocchio_stake_interface::cpi::SignerSeeds;
+/// # fn withdraw(signer: Signer) -> ProgramResult { Ok(()) }
+/// # let (owner, bump) = ([7u8; 32], 254);
+/// let seeds = SignerSeeds::new(bump).seed(b"withdrawer")?.seed(&owner)?;
+/// // e.g. `Withdraw { .. }.invoke_signed(&[signer])`
+/// seeds.with_signer(|signer| withdraw(signer))?;
+/// # Ok::<(), pinocchio::program_error::ProgramError>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignerSeeds<'a> {
+    parts: [&'a [u8]; MAX_SIGNER_SEED_PARTS],
+    len: usize,
+    bump: [u8; 1],
+}
+
+impl<'a> SignerSeeds<'a> {
+    pub const fn new(bump: u8) -> Self {
+        Self {
+            parts: [&[]; MAX_SIGNER_SEED_PARTS],
+            len: 0,
+            bump: [bump],
+        }
+    }
+
+    /// Appends `part`, failing as address derivation would for a part longer
+    /// than `MAX_SEED_LEN`, and with `InvalidSeeds` past
+    /// `MAX_SIGNER_SEED_PARTS` parts.
+    pub fn seed(mut self, part: &'a [u8]) -> Result<Self, ProgramError> {
+        if part.len() > MAX_SEED_LEN {
+            return Err(ProgramError::MaxSeedLengthExceeded);
+        }
+        let slot = self
+            .parts
+            .get_mut(self.len)
+            .ok_or(ProgramError::InvalidSeeds)?;
+        *slot = part;
+        self.len += 1;
+        Ok(self)
+    }
+
+    pub fn bump(&self) -> u8 {
+        self.bump[0]
+    }
+
+    /// The seed parts and then the bump, in the order the address was
+    /// derived with.
+    pub fn parts(&self) -> impl Iterator<Item = &[u8]> {
+        self.parts[..self.len]
+            .iter()
+            .copied()
+            .chain(core::iter::once(&self.bump[..]))
+    }
+
+    /// Calls `f` with the `Signer` for these seeds, which only lives as long
+    /// as the seeds array behind it.
+    pub fn with_signer<R>(&self, f: impl FnOnce(Signer) -> R) -> R {
+        let mut seeds: [Seed; MAX_SIGNER_SEED_PARTS + 1] =
+            core::array::from_fn(|_| Seed::from(&self.bump[..0]));
+        let len = self.len + 1;
+        for (seed, part) in seeds.iter_mut().zip(self.parts()) {
+            *seed = Seed::from(part);
+        }
+        f(Signer::from(&seeds[..len]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_parts_end_with_bump() {
+        let owner = [7u8; 32];
+        let seeds = SignerSeeds::new(254)
+            .seed(b"withdrawer")
+            .and_then(|seeds| seeds.seed(&owner))
+            .unwrap();
+        assert_eq!(seeds.bump(), 254);
+        assert_eq!(
+            seeds.parts().collect::<Vec<_>>(),
+            [&b"withdrawer"[..], &owner, &[254]]
+        );
+
+        // the bump alone
+        let seeds = SignerSeeds::new(1);
+        assert_eq!(seeds.parts().collect::<Vec<_>>(), [&[1u8][..]]);
+    }
+
+    #[test]
+    fn test_seed_limits() {
+        let too_long = [0u8; MAX_SEED_LEN + 1];
+        assert_eq!(
+            SignerSeeds::new(0).seed(&too_long),
+            Err(ProgramError::MaxSeedLengthExceeded)
+        );
+        assert!(SignerSeeds::new(0).seed(&too_long[..MAX_SEED_LEN]).is_ok());
+
+        let mut seeds = SignerSeeds::new(0);
+        for _ in 0..MAX_SIGNER_SEED_PARTS {
+            seeds = seeds.seed(b"part").unwrap();
+        }
+        assert_eq!(seeds.seed(b"part"), Err(ProgramError::InvalidSeeds));
+    }
+
+    #[test]
+    fn test_with_signer() {
+        let seeds = SignerSeeds::new(3).seed(b"a").unwrap();
+        assert_eq!(seeds.with_signer(|_| 42), 42);
+    }
+}
@@ -164,6 +164,52 @@ impl ToPrimitive for StakeError {
     }
 }
 
+/// The native program's messages, so tools print the diagnostics users know.
+#[cfg(any(feature = "client", test))]
+impl core::fmt::Display for StakeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::NoCreditsToRedeem => "not enough credits to redeem",
+            Self::LockupInForce => "lockup has not yet expired",
+            Self::AlreadyDeactivated => "stake already deactivated",
+            Self::TooSoonToRedelegate => "one re-delegation permitted per epoch",
+            Self::InsufficientStake => "split amount is more than is staked",
+            Self::MergeTransientStake => "stake account with transient stake cannot be merged",
+            Self::MergeMismatch => {
+                "stake account merge failed due to different authority, lockups or state"
+            }
+            Self::CustodianMissing => "custodian address not present",
+            Self::CustodianSignatureMissing => "custodian signature not present",
+            Self::InsufficientReferenceVotes => {
+                "insufficient voting activity in the reference vote account"
+            }
+            Self::VoteAddressMismatch => {
+                "stake account is not delegated to the provided vote account"
+            }
+            Self::MinimumDelinquentEpochsForDeactivationNotMet => {
+                "stake account has not been delinquent for the minimum epochs required for \
+                 deactivation"
+            }
+            Self::InsufficientDelegation => "delegation amount is less than the minimum",
+            Self::RedelegateTransientOrInactiveStake => {
+                "stake account with transient or inactive stake cannot be redelegated"
+            }
+            Self::RedelegateToSameVoteAccount => {
+                "stake redelegation to the same vote account is not permitted"
+            }
+            Self::RedelegatedStakeMustFullyActivateBeforeDeactivationIsPermitted => {
+                "redelegated stake must be fully activated before deactivation"
+            }
+            Self::EpochRewardsActive => {
+                "stake action is not permitted while the epoch rewards period is active"
+            }
+        })
+    }
+}
+
+#[cfg(any(feature = "client", test))]
+impl core::error::Error for StakeError {}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum InstructionError {
     /// Deprecated! Use CustomError instead!
@@ -388,6 +434,6 @@ impl TryFrom<InstructionError> for ProgramError {
     }
 }
 
-pub(crate) fn to_program_error(e: InstructionError) -> ProgramError {
+pub fn to_program_error(e: InstructionError) -> ProgramError {
     ProgramError::try_from(e).unwrap_or(ProgramError::InvalidAccountData)
 }
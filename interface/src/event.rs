@@ -0,0 +1,125 @@
+//! Binary schema of the events the program logs with `sol_log_data`: a
+//! one-byte event tag, then the event's fields at fixed offsets, integers
+//! little-endian. A field is never moved or resized; a layout change gets a
+//! new tag, so indexers can keep decoding records of older program versions.
+
+use crate::instruction::StakeInstruction;
+
+/// Offset of the tag every event starts with.
+pub const EVENT_TAG_OFFSET: usize = 0;
+
+/// `InstructionMetricsEvent`, logged after each successful instruction when
+/// the program is built with metrics.
+pub const INSTRUCTION_METRICS_EVENT: u8 = 1;
+
+/// `tag: u8 | instruction: u8 | lamports: u64 | compute units: u64`
+pub const INSTRUCTION_METRICS_EVENT_LEN: usize = 1 + 1 + 8 + 8;
+pub const INSTRUCTION_METRICS_INSTRUCTION_OFFSET: usize = 1;
+pub const INSTRUCTION_METRICS_LAMPORTS_OFFSET: usize = 2;
+pub const INSTRUCTION_METRICS_COMPUTE_UNITS_OFFSET: usize = 10;
+
+/// Counters for one processed instruction: the lamports its amount argument
+/// moved, zero for instructions without one, and the compute units it used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionMetricsEvent {
+    pub instruction: StakeInstruction,
+    pub lamports: u64,
+    pub compute_units: u64,
+}
+
+impl InstructionMetricsEvent {
+    pub fn encode(&self) -> [u8; INSTRUCTION_METRICS_EVENT_LEN] {
+        let mut record = [0u8; INSTRUCTION_METRICS_EVENT_LEN];
+        record[EVENT_TAG_OFFSET] = INSTRUCTION_METRICS_EVENT;
+        record[INSTRUCTION_METRICS_INSTRUCTION_OFFSET] = self.instruction as u8;
+        record[INSTRUCTION_METRICS_LAMPORTS_OFFSET..INSTRUCTION_METRICS_COMPUTE_UNITS_OFFSET]
+            .copy_from_slice(&self.lamports.to_le_bytes());
+        record[INSTRUCTION_METRICS_COMPUTE_UNITS_OFFSET..]
+            .copy_from_slice(&self.compute_units.to_le_bytes());
+        record
+    }
+
+    /// None unless `data` is exactly one such event with a known instruction.
+    #[cfg(any(feature = "client", test))]
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let record: &[u8; INSTRUCTION_METRICS_EVENT_LEN] = data.try_into().ok()?;
+        if record[EVENT_TAG_OFFSET] != INSTRUCTION_METRICS_EVENT {
+            return None;
+        }
+        let field =
+            |offset: usize| u64::from_le_bytes(record[offset..offset + 8].try_into().unwrap());
+        Some(Self {
+            instruction: StakeInstruction::try_from(
+                &record[INSTRUCTION_METRICS_INSTRUCTION_OFFSET],
+            )
+            .ok()?,
+            lamports: field(INSTRUCTION_METRICS_LAMPORTS_OFFSET),
+            compute_units: field(INSTRUCTION_METRICS_COMPUTE_UNITS_OFFSET),
+        })
+    }
+}
+
+/// Any event the program logs.
+#[cfg(any(feature = "client", test))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StakeEvent {
+    InstructionMetrics(InstructionMetricsEvent),
+}
+
+/// Decodes one `sol_log_data` field, as an indexer finds it base64 encoded
+/// after `Program data:` in the logs. None for anything that isn't an event
+/// of this schema, including events of tags added since.
+#[cfg(any(feature = "client", test))]
+pub fn decode_event(data: &[u8]) -> Option<StakeEvent> {
+    match *data.get(EVENT_TAG_OFFSET)? {
+        INSTRUCTION_METRICS_EVENT => {
+            InstructionMetricsEvent::decode(data).map(StakeEvent::InstructionMetrics)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event() -> InstructionMetricsEvent {
+        InstructionMetricsEvent {
+            instruction: StakeInstruction::Withdraw,
+            lamports: 0x0102_0304_0506_0708,
+            compute_units: 1_500,
+        }
+    }
+
+    #[test]
+    fn test_instruction_metrics_layout() {
+        // the schema indexers rely on, byte for byte
+        let mut expected = vec![INSTRUCTION_METRICS_EVENT, 4];
+        expected.extend_from_slice(&[8, 7, 6, 5, 4, 3, 2, 1]);
+        expected.extend_from_slice(&1_500u64.to_le_bytes());
+        assert_eq!(event().encode().as_slice(), expected);
+    }
+
+    #[test]
+    fn test_decode_event() {
+        let record = event().encode();
+        assert_eq!(
+            decode_event(&record),
+            Some(StakeEvent::InstructionMetrics(event()))
+        );
+
+        // truncated, padded, of an unknown tag, or of an unknown instruction
+        assert_eq!(decode_event(&[]), None);
+        assert_eq!(decode_event(&record[..record.len() - 1]), None);
+        let mut padded = record.to_vec();
+        padded.push(0);
+        assert_eq!(decode_event(&padded), None);
+        let mut unknown = record;
+        unknown[EVENT_TAG_OFFSET] = 0;
+        assert_eq!(decode_event(&unknown), None);
+        let mut unknown = record;
+        unknown[INSTRUCTION_METRICS_INSTRUCTION_OFFSET] = u8::MAX;
+        assert_eq!(decode_event(&unknown), None);
+    }
+}
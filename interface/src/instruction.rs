@@ -0,0 +1,875 @@
+use pinocchio::{
+    program_error::ProgramError,
+    pubkey::{Pubkey, MAX_SEED_LEN},
+};
+
+use crate::state::{Authorized, Epoch, Lockup, StakeAuthorize, UnixTimestamp};
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StakeInstruction {
+    Initialize,
+    Authorize,
+    DelegateStake,
+    Split,
+    Withdraw,
+    Deactivate,
+    SetLockup,
+    Merge,
+    AuthorizeWithSeed,
+    InitializeChecked,
+    AuthorizeChecked,
+    AuthorizeCheckedWithSeed,
+    SetLockupChecked,
+    GetMinimumDelegation,
+    DeactivateDelinquent,
+    #[deprecated(since = "2.1.0", note = "Redelegate will not be enabled")]
+    Redelegate,
+    MoveStake,
+    MoveLamports,
+}
+
+impl TryFrom<&u8> for StakeInstruction {
+    type Error = ProgramError;
+
+    fn try_from(value: &u8) -> Result<Self, Self::Error> {
+        match *value {
+            0 => Ok(StakeInstruction::Initialize),
+            1 => Ok(StakeInstruction::Authorize),
+            2 => Ok(StakeInstruction::DelegateStake),
+            3 => Ok(StakeInstruction::Split),
+            4 => Ok(StakeInstruction::Withdraw),
+            5 => Ok(StakeInstruction::Deactivate),
+            6 => Ok(StakeInstruction::SetLockup),
+            7 => Ok(StakeInstruction::Merge),
+            8 => Ok(StakeInstruction::AuthorizeWithSeed),
+            9 => Ok(StakeInstruction::InitializeChecked),
+            10 => Ok(StakeInstruction::AuthorizeChecked),
+            11 => Ok(StakeInstruction::AuthorizeCheckedWithSeed),
+            12 => Ok(StakeInstruction::SetLockupChecked),
+            13 => Ok(StakeInstruction::GetMinimumDelegation),
+            14 => Ok(StakeInstruction::DeactivateDelinquent),
+            #[allow(deprecated)]
+            15 => Ok(StakeInstruction::Redelegate),
+            16 => Ok(StakeInstruction::MoveStake),
+            17 => Ok(StakeInstruction::MoveLamports),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+/// Instructions only this program's `extensions` build takes. Their
+/// discriminants start well past native's, so the two never collide, and a
+/// build without the feature rejects them as any unknown instruction.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtensionInstruction {
+    /// `DelegateStake`, then the staker authority handed to the pubkey in the
+    /// instruction data, such as a pool's PDA, all in one instruction. Takes
+    /// `DelegateStake`'s accounts, signed by the current staker.
+    SetStakerAndDelegate = 128,
+}
+
+impl TryFrom<&u8> for ExtensionInstruction {
+    type Error = ProgramError;
+
+    fn try_from(value: &u8) -> Result<Self, Self::Error> {
+        match *value {
+            128 => Ok(ExtensionInstruction::SetStakerAndDelegate),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+// the accounts of `DelegateStake`, with the staker that signs for both
+pub const SET_STAKER_AND_DELEGATE_ACCOUNTS: usize = 6;
+
+impl ExtensionInstruction {
+    /// The fewest accounts the instruction can be processed with.
+    pub const fn min_accounts(self) -> usize {
+        match self {
+            Self::SetStakerAndDelegate => SET_STAKER_AND_DELEGATE_ACCOUNTS,
+        }
+    }
+
+    /// Fails with `NotEnoughAccountKeys` unless `accounts` holds at least
+    /// `min_accounts`.
+    #[inline(always)]
+    pub fn check_account_count<T>(self, accounts: &[T]) -> Result<(), ProgramError> {
+        if accounts.len() < self.min_accounts() {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        Ok(())
+    }
+}
+
+/// Reads the new staker of `SetStakerAndDelegate`.
+pub fn new_staker_from_data(data: &[u8]) -> Result<Pubkey, ProgramError> {
+    let (new_staker, rest) = pubkey_from_data(data)?;
+    check_trailing_bytes(rest)?;
+    Ok(new_staker)
+}
+
+// Fewest accounts each instruction takes, as native checks them: the
+// accounts it indexes unconditionally, sysvars included. Optional trailing
+// accounts, such as a lockup custodian, don't count.
+pub const INITIALIZE_ACCOUNTS: usize = 2;
+pub const AUTHORIZE_ACCOUNTS: usize = 3;
+pub const DELEGATE_STAKE_ACCOUNTS: usize = 5;
+pub const SPLIT_ACCOUNTS: usize = 2;
+pub const WITHDRAW_ACCOUNTS: usize = 5;
+pub const DEACTIVATE_ACCOUNTS: usize = 2;
+pub const SET_LOCKUP_ACCOUNTS: usize = 1;
+pub const MERGE_ACCOUNTS: usize = 4;
+pub const AUTHORIZE_WITH_SEED_ACCOUNTS: usize = 3;
+pub const INITIALIZE_CHECKED_ACCOUNTS: usize = 4;
+pub const AUTHORIZE_CHECKED_ACCOUNTS: usize = 4;
+pub const AUTHORIZE_CHECKED_WITH_SEED_ACCOUNTS: usize = 4;
+pub const SET_LOCKUP_CHECKED_ACCOUNTS: usize = 1;
+pub const GET_MINIMUM_DELEGATION_ACCOUNTS: usize = 0;
+pub const DEACTIVATE_DELINQUENT_ACCOUNTS: usize = 3;
+pub const MOVE_STAKE_ACCOUNTS: usize = 3;
+pub const MOVE_LAMPORTS_ACCOUNTS: usize = 3;
+
+impl StakeInstruction {
+    /// The fewest accounts the instruction can be processed with.
+    /// `Redelegate` is rejected whatever it is passed, so it needs none.
+    pub const fn min_accounts(self) -> usize {
+        match self {
+            Self::Initialize => INITIALIZE_ACCOUNTS,
+            Self::Authorize => AUTHORIZE_ACCOUNTS,
+            Self::DelegateStake => DELEGATE_STAKE_ACCOUNTS,
+            Self::Split => SPLIT_ACCOUNTS,
+            Self::Withdraw => WITHDRAW_ACCOUNTS,
+            Self::Deactivate => DEACTIVATE_ACCOUNTS,
+            Self::SetLockup => SET_LOCKUP_ACCOUNTS,
+            Self::Merge => MERGE_ACCOUNTS,
+            Self::AuthorizeWithSeed => AUTHORIZE_WITH_SEED_ACCOUNTS,
+            Self::InitializeChecked => INITIALIZE_CHECKED_ACCOUNTS,
+            Self::AuthorizeChecked => AUTHORIZE_CHECKED_ACCOUNTS,
+            Self::AuthorizeCheckedWithSeed => AUTHORIZE_CHECKED_WITH_SEED_ACCOUNTS,
+            Self::SetLockupChecked => SET_LOCKUP_CHECKED_ACCOUNTS,
+            Self::GetMinimumDelegation => GET_MINIMUM_DELEGATION_ACCOUNTS,
+            Self::DeactivateDelinquent => DEACTIVATE_DELINQUENT_ACCOUNTS,
+            #[allow(deprecated)]
+            Self::Redelegate => 0,
+            Self::MoveStake => MOVE_STAKE_ACCOUNTS,
+            Self::MoveLamports => MOVE_LAMPORTS_ACCOUNTS,
+        }
+    }
+
+    /// Fails with `NotEnoughAccountKeys` unless `accounts` holds at least
+    /// `min_accounts`, so handlers can index that many without a panic.
+    #[inline(always)]
+    pub fn check_account_count<T>(self, accounts: &[T]) -> Result<(), ProgramError> {
+        if accounts.len() < self.min_accounts() {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InitializeArgs {
+    pub authorized: Authorized,
+    pub lockup: Lockup,
+}
+
+// Bincode
+// authorized (staker 32 bytes, withdrawer 32 bytes)
+// lockup (unix_timestamp 8 bytes, epoch 8 bytes, custodian 32 bytes)
+
+impl InitializeArgs {
+    pub fn from_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let (staker, data) = pubkey_from_data(data)?;
+        let (withdrawer, data) = pubkey_from_data(data)?;
+        let (unix_timestamp, data) = data
+            .split_first_chunk::<8>()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let (epoch, data) = data
+            .split_first_chunk::<8>()
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let (custodian, rest) = pubkey_from_data(data)?;
+        check_trailing_bytes(rest)?;
+        Ok(Self {
+            authorized: Authorized { staker, withdrawer },
+            lockup: Lockup {
+                unix_timestamp: *unix_timestamp,
+                epoch: *epoch,
+                custodian,
+            },
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct LockupArgs {
+    pub unix_timestamp: Option<UnixTimestamp>,
+    pub epoch: Option<Epoch>,
+    pub custodian: Option<Pubkey>,
+}
+
+// Bincode
+// unix_timestamp (Option tag, 1 byte + 8 bytes if Some)
+// epoch (Option tag, 1 byte + 8 bytes if Some)
+// custodian (Option tag, 1 byte + 32 bytes if Some)
+
+impl LockupArgs {
+    pub fn from_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let (unix_timestamp, data) = option_from_data::<8>(data)?;
+        let (epoch, data) = option_from_data::<8>(data)?;
+        let (custodian, rest) = option_from_data::<32>(data)?;
+        check_trailing_bytes(rest)?;
+        Ok(Self {
+            unix_timestamp,
+            epoch,
+            custodian,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuthorizeArgs {
+    pub new_authorized_pubkey: Pubkey,
+    pub stake_authorize: StakeAuthorize,
+}
+
+// Bincode
+// new_authorized_pubkey (32 bytes)
+// stake_authorize (u32 tag, 4 bytes)
+
+impl AuthorizeArgs {
+    pub fn from_data(data: &[u8]) -> Result<Self, ProgramError> {
+        let (new_authorized_pubkey, data) = pubkey_from_data(data)?;
+        let (stake_authorize, rest) = stake_authorize_from_data(data)?;
+        check_trailing_bytes(rest)?;
+        Ok(Self {
+            new_authorized_pubkey,
+            stake_authorize,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AuthorizeWithSeedArgs<'a> {
+    pub new_authorized_pubkey: Pubkey,
+    pub stake_authorize: StakeAuthorize,
+    pub authority_seed: &'a str,
+    pub authority_owner: Pubkey,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AuthorizeCheckedWithSeedArgs<'a> {
+    pub stake_authorize: StakeAuthorize,
+    pub authority_seed: &'a str,
+    pub authority_owner: Pubkey,
+}
+
+// Bincode
+// new_authorized_pubkey (32 bytes, AuthorizeWithSeed only)
+// stake_authorize (u32 tag, 4 bytes)
+// authority_seed (u64 length, 8 bytes + seed bytes)
+// authority_owner (32 bytes)
+
+impl<'a> AuthorizeWithSeedArgs<'a> {
+    pub fn from_data(data: &'a [u8]) -> Result<Self, ProgramError> {
+        let (new_authorized_pubkey, data) = pubkey_from_data(data)?;
+        let (stake_authorize, data) = stake_authorize_from_data(data)?;
+        let (authority_seed, data) = seed_from_data(data)?;
+        let (authority_owner, rest) = pubkey_from_data(data)?;
+        check_trailing_bytes(rest)?;
+        Ok(Self {
+            new_authorized_pubkey,
+            stake_authorize,
+            authority_seed,
+            authority_owner,
+        })
+    }
+}
+
+impl<'a> AuthorizeCheckedWithSeedArgs<'a> {
+    pub fn from_data(data: &'a [u8]) -> Result<Self, ProgramError> {
+        let (stake_authorize, data) = stake_authorize_from_data(data)?;
+        let (authority_seed, data) = seed_from_data(data)?;
+        let (authority_owner, rest) = pubkey_from_data(data)?;
+        check_trailing_bytes(rest)?;
+        Ok(Self {
+            stake_authorize,
+            authority_seed,
+            authority_owner,
+        })
+    }
+}
+
+/// Reads the lamports argument of `Split`, `Withdraw`, `MoveStake` and
+/// `MoveLamports`.
+pub fn lamports_from_data(data: &[u8]) -> Result<u64, ProgramError> {
+    let (lamports, rest) = data
+        .split_first_chunk::<8>()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    check_trailing_bytes(rest)?;
+    Ok(u64::from_le_bytes(*lamports))
+}
+
+/// Reads the authority type argument of `AuthorizeChecked`.
+pub fn stake_authorize_arg_from_data(data: &[u8]) -> Result<StakeAuthorize, ProgramError> {
+    let (stake_authorize, rest) = stake_authorize_from_data(data)?;
+    check_trailing_bytes(rest)?;
+    Ok(stake_authorize)
+}
+
+/// Checks what is left after an instruction's arguments. Native deserializes
+/// them with bincode, which ignores anything past the payload, so by default
+/// this does too. The `strict-instruction-data` feature rejects them instead,
+/// to surface clients that encode arguments wrong.
+#[inline(always)]
+pub fn check_trailing_bytes(rest: &[u8]) -> Result<(), ProgramError> {
+    if cfg!(feature = "strict-instruction-data") && !rest.is_empty() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
+
+fn pubkey_from_data(data: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+    let (pubkey, rest) = data
+        .split_first_chunk::<32>()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    Ok((*pubkey, rest))
+}
+
+/// Reads a bincode `Option` of an `N` byte value: a `0` tag for `None`, or a
+/// `1` tag followed by the value. Any other tag is rejected.
+fn option_from_data<const N: usize>(data: &[u8]) -> Result<(Option<[u8; N]>, &[u8]), ProgramError> {
+    match data.split_first() {
+        Some((0, rest)) => Ok((None, rest)),
+        Some((1, rest)) => {
+            let (value, rest) = rest
+                .split_first_chunk::<N>()
+                .ok_or(ProgramError::InvalidInstructionData)?;
+            Ok((Some(*value), rest))
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn stake_authorize_from_data(data: &[u8]) -> Result<(StakeAuthorize, &[u8]), ProgramError> {
+    let (tag, rest) = data
+        .split_first_chunk::<4>()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let stake_authorize = match u32::from_le_bytes(*tag) {
+        0 => StakeAuthorize::Staker,
+        1 => StakeAuthorize::Withdrawer,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+    Ok((stake_authorize, rest))
+}
+
+/// Reads a bincode `String` seed without copying it.
+///
+/// Malformed data and invalid UTF-8 fail as deserialization would, with
+/// `InvalidInstructionData`. A well-formed seed longer than `MAX_SEED_LEN`
+/// bytes fails with `MaxSeedLengthExceeded`, as `Pubkey::create_with_seed`
+/// does.
+pub fn seed_from_data(data: &[u8]) -> Result<(&str, &[u8]), ProgramError> {
+    let (len, rest) = data
+        .split_first_chunk::<8>()
+        .ok_or(ProgramError::InvalidInstructionData)?;
+    let len = usize::try_from(u64::from_le_bytes(*len))
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    if len > rest.len() {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (seed, rest) = rest.split_at(len);
+    let seed = core::str::from_utf8(seed).map_err(|_| ProgramError::InvalidInstructionData)?;
+    if seed.len() > MAX_SEED_LEN {
+        return Err(ProgramError::MaxSeedLengthExceeded);
+    }
+    Ok((seed, rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        lamports_from_data, seed_from_data, stake_authorize_arg_from_data, AuthorizeArgs,
+        AuthorizeCheckedWithSeedArgs, AuthorizeWithSeedArgs, InitializeArgs, LockupArgs,
+        MAX_SEED_LEN,
+    };
+    use crate::state::{Authorized, Lockup, StakeAuthorize};
+    use bincode::serialize;
+    use pinocchio::program_error::ProgramError;
+
+    #[test]
+    fn test_instruction_data() {
+        let args_arr = [
+            LockupArgs {
+                unix_timestamp: None,
+                epoch: None,
+                custodian: None,
+            },
+            LockupArgs {
+                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
+                epoch: None,
+                custodian: None,
+            },
+            LockupArgs {
+                unix_timestamp: None,
+                epoch: Some(9464321479845648u64.to_le_bytes()),
+                custodian: None,
+            },
+            LockupArgs {
+                unix_timestamp: None,
+                epoch: None,
+                custodian: Some([
+                    13, 54, 98, 123, 59, 67, 165, 78, 3, 12, 23, 45, 67, 89, 1, 2, 3, 4, 5, 6, 7,
+                    8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+                ]),
+            },
+            LockupArgs {
+                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
+                epoch: Some(9464321479845648u64.to_le_bytes()),
+                custodian: None,
+            },
+            LockupArgs {
+                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
+                epoch: None,
+                custodian: Some([
+                    13, 54, 98, 123, 59, 67, 165, 78, 3, 12, 23, 45, 67, 89, 1, 2, 3, 4, 5, 6, 7,
+                    8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+                ]),
+            },
+            LockupArgs {
+                unix_timestamp: None,
+                epoch: Some(9464321479845648u64.to_le_bytes()),
+                custodian: Some([
+                    13, 54, 98, 123, 59, 67, 165, 78, 3, 12, 23, 45, 67, 89, 1, 2, 3, 4, 5, 6, 7,
+                    8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+                ]),
+            },
+            LockupArgs {
+                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
+                epoch: Some(9464321479845648u64.to_le_bytes()),
+                custodian: Some([
+                    13, 54, 98, 123, 59, 67, 165, 78, 3, 12, 23, 45, 67, 89, 1, 2, 3, 4, 5, 6, 7,
+                    8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+                ]),
+            },
+        ];
+
+        for args in args_arr {
+            let data = serialize(&args).unwrap();
+
+            let args_new = LockupArgs::from_data(data.as_ref()).unwrap();
+            assert_eq!(args, args_new);
+        }
+    }
+
+    #[test]
+    fn test_lockup_args_custodian_encoding() {
+        let custodian = [9; 32];
+        let args = LockupArgs {
+            unix_timestamp: None,
+            epoch: Some(300u64.to_le_bytes()),
+            custodian: Some(custodian),
+        };
+
+        // each Option is a single tag byte, followed by the value only if Some
+        let mut expected = vec![0, 1];
+        expected.extend_from_slice(&300u64.to_le_bytes());
+        expected.push(1);
+        expected.extend_from_slice(&custodian);
+        let data = serialize(&args).unwrap();
+        assert_eq!(data, expected);
+        assert_eq!(LockupArgs::from_data(&data), Ok(args));
+
+        // trailing bytes are ignored, as native deserialization does, unless
+        // strict
+        let mut trailing = data.clone();
+        trailing.extend_from_slice(&[0xff; 4]);
+        assert_eq!(
+            LockupArgs::from_data(&trailing),
+            trailing_bytes_result(args)
+        );
+
+        // every truncation is rejected, including one inside the custodian
+        for len in 0..data.len() {
+            assert_eq!(
+                LockupArgs::from_data(&data[..len]),
+                Err(ProgramError::InvalidInstructionData),
+                "{len}"
+            );
+        }
+
+        // tags other than 0 and 1 are rejected in every position
+        for (position, tag) in [(0, 2), (1, 2), (10, 2), (10, u8::MAX)] {
+            let mut bad_tag = data.clone();
+            bad_tag[position] = tag;
+            assert_eq!(
+                LockupArgs::from_data(&bad_tag),
+                Err(ProgramError::InvalidInstructionData),
+                "{position}"
+            );
+        }
+    }
+
+    // mirrors of the native argument types, for their bincode encoding
+    #[derive(serde::Serialize)]
+    enum NativeStakeAuthorize {
+        Staker,
+        Withdrawer,
+    }
+
+    #[derive(serde::Serialize)]
+    struct NativeAuthorizeArgs {
+        new_authorized_pubkey: [u8; 32],
+        stake_authorize: NativeStakeAuthorize,
+    }
+
+    #[derive(serde::Serialize)]
+    struct NativeAuthorizeWithSeedArgs {
+        new_authorized_pubkey: [u8; 32],
+        stake_authorize: NativeStakeAuthorize,
+        authority_seed: String,
+        authority_owner: [u8; 32],
+    }
+
+    #[derive(serde::Serialize)]
+    struct NativeAuthorizeCheckedWithSeedArgs {
+        stake_authorize: NativeStakeAuthorize,
+        authority_seed: String,
+        authority_owner: [u8; 32],
+    }
+
+    #[derive(serde::Serialize)]
+    struct NativeInitializeArgs {
+        staker: [u8; 32],
+        withdrawer: [u8; 32],
+        unix_timestamp: i64,
+        epoch: u64,
+        custodian: [u8; 32],
+    }
+
+    fn encoded_seed(seed: &[u8]) -> Vec<u8> {
+        let mut data = (seed.len() as u64).to_le_bytes().to_vec();
+        data.extend_from_slice(seed);
+        data
+    }
+
+    #[test]
+    fn test_authorize_instruction_data() {
+        for (native, stake_authorize) in [
+            (NativeStakeAuthorize::Staker, StakeAuthorize::Staker),
+            (NativeStakeAuthorize::Withdrawer, StakeAuthorize::Withdrawer),
+        ] {
+            let data = serialize(&NativeAuthorizeArgs {
+                new_authorized_pubkey: [1; 32],
+                stake_authorize: native,
+            })
+            .unwrap();
+            let args = AuthorizeArgs {
+                new_authorized_pubkey: [1; 32],
+                stake_authorize,
+            };
+            assert_eq!(AuthorizeArgs::from_data(&data), Ok(args));
+
+            let mut trailing = data.clone();
+            trailing.push(0xff);
+            assert_eq!(
+                AuthorizeArgs::from_data(&trailing),
+                trailing_bytes_result(args)
+            );
+
+            // every truncation is rejected
+            for len in 0..data.len() {
+                assert_eq!(
+                    AuthorizeArgs::from_data(&data[..len]),
+                    Err(ProgramError::InvalidInstructionData),
+                    "{len}"
+                );
+            }
+        }
+
+        // unknown authority type
+        let mut data = [1; 36];
+        data[32..].copy_from_slice(&2u32.to_le_bytes());
+        assert_eq!(
+            AuthorizeArgs::from_data(&data),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_authorize_checked_instruction_data() {
+        for (native, stake_authorize) in [
+            (NativeStakeAuthorize::Staker, StakeAuthorize::Staker),
+            (NativeStakeAuthorize::Withdrawer, StakeAuthorize::Withdrawer),
+        ] {
+            let data = serialize(&native).unwrap();
+            assert_eq!(stake_authorize_arg_from_data(&data), Ok(stake_authorize));
+
+            let mut trailing = data.clone();
+            trailing.push(0xff);
+            assert_eq!(
+                stake_authorize_arg_from_data(&trailing),
+                trailing_bytes_result(stake_authorize)
+            );
+            assert_eq!(
+                stake_authorize_arg_from_data(&data[..3]),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+
+        assert_eq!(
+            stake_authorize_arg_from_data(&2u32.to_le_bytes()),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_with_seed_instruction_data() {
+        let data = serialize(&NativeAuthorizeWithSeedArgs {
+            new_authorized_pubkey: [1; 32],
+            stake_authorize: NativeStakeAuthorize::Withdrawer,
+            authority_seed: "seed €".to_string(),
+            authority_owner: [2; 32],
+        })
+        .unwrap();
+        assert_eq!(
+            AuthorizeWithSeedArgs::from_data(&data),
+            Ok(AuthorizeWithSeedArgs {
+                new_authorized_pubkey: [1; 32],
+                stake_authorize: StakeAuthorize::Withdrawer,
+                authority_seed: "seed €",
+                authority_owner: [2; 32],
+            })
+        );
+        // the seed is borrowed from the instruction data, not copied out
+        let seed = AuthorizeWithSeedArgs::from_data(&data)
+            .unwrap()
+            .authority_seed;
+        assert!(data.as_ptr_range().contains(&seed.as_ptr()));
+
+        let data = serialize(&NativeAuthorizeCheckedWithSeedArgs {
+            stake_authorize: NativeStakeAuthorize::Staker,
+            authority_seed: String::new(),
+            authority_owner: [3; 32],
+        })
+        .unwrap();
+        assert_eq!(
+            AuthorizeCheckedWithSeedArgs::from_data(&data),
+            Ok(AuthorizeCheckedWithSeedArgs {
+                stake_authorize: StakeAuthorize::Staker,
+                authority_seed: "",
+                authority_owner: [3; 32],
+            })
+        );
+
+        // every truncation is rejected
+        for len in 0..data.len() {
+            assert_eq!(
+                AuthorizeCheckedWithSeedArgs::from_data(&data[..len]),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+
+        // unknown authority type
+        let mut bad_tag = data.clone();
+        bad_tag[0] = 2;
+        assert_eq!(
+            AuthorizeCheckedWithSeedArgs::from_data(&bad_tag),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_initialize_instruction_data() {
+        let data = serialize(&NativeInitializeArgs {
+            staker: [1; 32],
+            withdrawer: [2; 32],
+            unix_timestamp: -5,
+            epoch: 300,
+            custodian: [3; 32],
+        })
+        .unwrap();
+        let args = InitializeArgs {
+            authorized: Authorized {
+                staker: [1; 32],
+                withdrawer: [2; 32],
+            },
+            lockup: Lockup {
+                unix_timestamp: (-5i64).to_le_bytes(),
+                epoch: 300u64.to_le_bytes(),
+                custodian: [3; 32],
+            },
+        };
+        assert_eq!(InitializeArgs::from_data(&data), Ok(args));
+
+        let mut trailing = data.clone();
+        trailing.push(0xff);
+        assert_eq!(
+            InitializeArgs::from_data(&trailing),
+            trailing_bytes_result(args)
+        );
+
+        // every truncation is rejected
+        for len in 0..data.len() {
+            assert_eq!(
+                InitializeArgs::from_data(&data[..len]),
+                Err(ProgramError::InvalidInstructionData),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_seed_length() {
+        let seed = "a".repeat(MAX_SEED_LEN);
+        let data = encoded_seed(seed.as_bytes());
+        assert_eq!(seed_from_data(&data), Ok((seed.as_str(), &[][..])));
+
+        let seed = "a".repeat(MAX_SEED_LEN + 1);
+        let data = encoded_seed(seed.as_bytes());
+        assert_eq!(
+            seed_from_data(&data),
+            Err(ProgramError::MaxSeedLengthExceeded)
+        );
+
+        // the limit is on bytes, not chars: 31 bytes plus a 2-byte char is over
+        let seed = format!("{}é", "a".repeat(MAX_SEED_LEN - 1));
+        let data = encoded_seed(seed.as_bytes());
+        assert_eq!(
+            seed_from_data(&data),
+            Err(ProgramError::MaxSeedLengthExceeded)
+        );
+
+        // lengths that don't fit the data
+        let mut data = encoded_seed(b"abc");
+        data[0] = 4;
+        assert_eq!(
+            seed_from_data(&data),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        let mut data = encoded_seed(b"abc");
+        data[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            seed_from_data(&data),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_seed_utf8_boundaries() {
+        // chars of every UTF-8 width, cut at every byte so multi-byte chars
+        // straddle the end of the seed in every possible way
+        let text = "a\u{e9}\u{20ac}\u{1f600}b\u{1f600}\u{20ac}\u{e9}".repeat(4);
+        let bytes = text.as_bytes();
+        for start in 0..8 {
+            for end in start..=bytes.len() {
+                let seed = &bytes[start..end];
+                let mut data = encoded_seed(seed);
+                data.extend_from_slice(&[7; 32]);
+
+                let expected = match core::str::from_utf8(seed) {
+                    Err(_) => Err(ProgramError::InvalidInstructionData),
+                    Ok(seed) if seed.len() > MAX_SEED_LEN => {
+                        Err(ProgramError::MaxSeedLengthExceeded)
+                    }
+                    Ok(seed) => Ok((seed, &[7u8; 32][..])),
+                };
+                assert_eq!(seed_from_data(&data), expected, "{start}..{end}");
+            }
+        }
+
+        // pseudo-random bytes, some of which happen to be valid
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for _ in 0..10_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let len = (state % 40) as usize;
+            let seed: Vec<u8> = (0..len)
+                .map(|i| (state >> (i % 8 * 8)) as u8 | if i % 3 == 0 { 0x80 } else { 0 })
+                .collect();
+            let data = encoded_seed(&seed);
+
+            let expected = match core::str::from_utf8(&seed) {
+                Err(_) => Err(ProgramError::InvalidInstructionData),
+                Ok(seed) if seed.len() > MAX_SEED_LEN => Err(ProgramError::MaxSeedLengthExceeded),
+                Ok(seed) => Ok((seed, &[][..])),
+            };
+            assert_eq!(seed_from_data(&data), expected);
+        }
+    }
+
+    /// What parsing `value`'s encoding with bytes appended to it yields.
+    fn trailing_bytes_result<T>(value: T) -> Result<T, ProgramError> {
+        if cfg!(feature = "strict-instruction-data") {
+            Err(ProgramError::InvalidInstructionData)
+        } else {
+            Ok(value)
+        }
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let lockup_args = LockupArgs {
+            unix_timestamp: None,
+            epoch: Some(300u64.to_le_bytes()),
+            custodian: None,
+        };
+        let with_seed = NativeAuthorizeWithSeedArgs {
+            new_authorized_pubkey: [1; 32],
+            stake_authorize: NativeStakeAuthorize::Staker,
+            authority_seed: "seed".to_string(),
+            authority_owner: [2; 32],
+        };
+        let checked_with_seed = NativeAuthorizeCheckedWithSeedArgs {
+            stake_authorize: NativeStakeAuthorize::Withdrawer,
+            authority_seed: "seed".to_string(),
+            authority_owner: [3; 32],
+        };
+
+        for trailing in [1, 100] {
+            let with_trailing = |mut data: Vec<u8>| {
+                data.resize(data.len() + trailing, 0xff);
+                data
+            };
+
+            let data = with_trailing(42u64.to_le_bytes().to_vec());
+            assert_eq!(lamports_from_data(&data), trailing_bytes_result(42));
+
+            let data = with_trailing(serialize(&lockup_args).unwrap());
+            assert_eq!(
+                LockupArgs::from_data(&data),
+                trailing_bytes_result(lockup_args)
+            );
+
+            let data = with_trailing(serialize(&with_seed).unwrap());
+            assert_eq!(
+                AuthorizeWithSeedArgs::from_data(&data),
+                trailing_bytes_result(AuthorizeWithSeedArgs {
+                    new_authorized_pubkey: [1; 32],
+                    stake_authorize: StakeAuthorize::Staker,
+                    authority_seed: "seed",
+                    authority_owner: [2; 32],
+                })
+            );
+
+            let data = with_trailing(serialize(&checked_with_seed).unwrap());
+            assert_eq!(
+                AuthorizeCheckedWithSeedArgs::from_data(&data),
+                trailing_bytes_result(AuthorizeCheckedWithSeedArgs {
+                    stake_authorize: StakeAuthorize::Withdrawer,
+                    authority_seed: "seed",
+                    authority_owner: [3; 32],
+                })
+            );
+        }
+
+        // short is malformed either way
+        assert_eq!(
+            lamports_from_data(&[0; 7]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+}
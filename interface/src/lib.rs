@@ -0,0 +1,19 @@
+//! Types shared by the stake program and its off-chain users: account state,
+//! instruction data and error codes. Nothing in here depends on program-only
+//! code, so clients can depend on this crate alone.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+#[cfg(any(feature = "client", test))]
+pub mod client;
+pub mod consts;
+pub mod cpi;
+pub mod error;
+pub mod event;
+pub mod instruction;
+pub mod state;
+#[cfg(any(feature = "wasm", test))]
+pub mod wasm;
+
+pinocchio_pubkey::declare_id!("Stake11111111111111111111111111111111111111");
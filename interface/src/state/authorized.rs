@@ -0,0 +1,167 @@
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock};
+
+use crate::error::StakeError;
+
+use super::{Lockup, LockupEvaluator, StakeAuthorize};
+
+#[repr(C)]
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Authorized {
+    pub staker: Pubkey,
+    pub withdrawer: Pubkey,
+}
+
+impl Authorized {
+    pub fn auto(authorized: &Pubkey) -> Self {
+        Self {
+            staker: *authorized,
+            withdrawer: *authorized,
+        }
+    }
+
+    pub fn check(
+        &self,
+        signers: &[Pubkey],
+        stake_authorize: StakeAuthorize,
+    ) -> Result<(), ProgramError> {
+        let authorized_signer = match stake_authorize {
+            StakeAuthorize::Staker => &self.staker,
+            StakeAuthorize::Withdrawer => &self.withdrawer,
+        };
+        if signers.iter().any(|p| p == authorized_signer) {
+            Ok(())
+        } else {
+            Err(ProgramError::MissingRequiredSignature)
+        }
+    }
+
+    /// Checks that `signers` may replace the `stake_authorize` authority.
+    /// Either authority may replace the staker, while the withdrawer can only
+    /// be replaced by itself.
+    pub fn check_authorize(
+        &self,
+        signers: &[Pubkey],
+        stake_authorize: StakeAuthorize,
+    ) -> Result<(), ProgramError> {
+        match stake_authorize {
+            StakeAuthorize::Staker if signers.contains(&self.withdrawer) => Ok(()),
+            _ => self.check(signers, stake_authorize),
+        }
+    }
+
+    /// Replaces the `stake_authorize` authority. A new withdrawer is subject
+    /// to the account's lockup, as `L` evaluates it.
+    pub fn authorize<L: LockupEvaluator + From<Lockup>>(
+        &mut self,
+        signers: &[Pubkey],
+        new_authorized: &Pubkey,
+        stake_authorize: StakeAuthorize,
+        lockup_custodian_args: Option<(&Lockup, &Clock, Option<&Pubkey>)>,
+    ) -> Result<(), ProgramError> {
+        match stake_authorize {
+            StakeAuthorize::Staker => {
+                self.check_authorize(signers, stake_authorize)?;
+                self.staker = *new_authorized
+            }
+            StakeAuthorize::Withdrawer => {
+                if let Some((lockup, clock, custodian)) = lockup_custodian_args {
+                    let lockup = L::from(*lockup);
+                    if lockup.is_in_force(clock, None) {
+                        match custodian {
+                            None => {
+                                return Err(StakeError::CustodianMissing.into());
+                            }
+                            Some(custodian) => {
+                                if !signers.contains(custodian) {
+                                    return Err(StakeError::CustodianSignatureMissing.into());
+                                }
+
+                                if lockup.is_in_force(clock, Some(custodian)) {
+                                    return Err(StakeError::LockupInForce.into());
+                                }
+                            }
+                        }
+                    }
+                }
+                self.check_authorize(signers, stake_authorize)?;
+                self.withdrawer = *new_authorized
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock};
+
+    use super::Authorized;
+    use crate::state::{Lockup, StakeAuthorize};
+
+    const STAKER: Pubkey = [1; 32];
+    const WITHDRAWER: Pubkey = [2; 32];
+    const NEW_AUTHORITY: Pubkey = [3; 32];
+
+    fn authorize(
+        signer: Pubkey,
+        stake_authorize: StakeAuthorize,
+    ) -> Result<Authorized, ProgramError> {
+        let mut authorized = Authorized {
+            staker: STAKER,
+            withdrawer: WITHDRAWER,
+        };
+        let lockup = Lockup::default();
+        let clock = Clock::default();
+        authorized.authorize::<Lockup>(
+            &[signer],
+            &NEW_AUTHORITY,
+            stake_authorize,
+            Some((&lockup, &clock, None)),
+        )?;
+        Ok(authorized)
+    }
+
+    #[test]
+    fn test_authorize_signers() {
+        // the staker rotates itself
+        assert_eq!(
+            authorize(STAKER, StakeAuthorize::Staker),
+            Ok(Authorized {
+                staker: NEW_AUTHORITY,
+                withdrawer: WITHDRAWER,
+            })
+        );
+
+        // the withdrawer may rotate the staker too
+        assert_eq!(
+            authorize(WITHDRAWER, StakeAuthorize::Staker),
+            Ok(Authorized {
+                staker: NEW_AUTHORITY,
+                withdrawer: WITHDRAWER,
+            })
+        );
+
+        // but the staker may not rotate the withdrawer
+        assert_eq!(
+            authorize(STAKER, StakeAuthorize::Withdrawer),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        // the withdrawer rotates itself
+        assert_eq!(
+            authorize(WITHDRAWER, StakeAuthorize::Withdrawer),
+            Ok(Authorized {
+                staker: STAKER,
+                withdrawer: NEW_AUTHORITY,
+            })
+        );
+
+        // and neither authority can be rotated by anyone else
+        for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+            assert_eq!(
+                authorize(NEW_AUTHORITY, stake_authorize),
+                Err(ProgramError::MissingRequiredSignature)
+            );
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use pinocchio::pubkey::Pubkey;
 
-use super::{bytes_to_u64, warmup_cooldown_rate, Epoch, StakeHistoryEntry, StakeHistoryGetEntry};
+use crate::consts::{DEFAULT_WARMUP_COOLDOWN_RATE, NEW_WARMUP_COOLDOWN_RATE};
+
+use super::{bytes_to_u64, Epoch, StakeHistoryEntry, StakeHistoryGetEntry};
 
 pub type StakeActivationStatus = StakeHistoryEntry;
 
@@ -60,7 +62,7 @@ impl Delegation {
         let result = self
             .stake_activating_and_deactivating(epoch, history, new_rate_activation_epoch)
             .effective;
-        u64::from_be_bytes(result)
+        u64::from_le_bytes(result)
     }
 
     #[allow(clippy::comparison_chain)]
@@ -74,8 +76,11 @@ impl Delegation {
         let (effective_stake, activating_stake) =
             self.stake_and_activating(target_epoch, history, new_rate_activation_epoch);
 
+        let target_epoch_u64 = bytes_to_u64(target_epoch);
+        let deactivation_epoch = bytes_to_u64(self.deactivation_epoch);
+
         // then de-activate some portion if necessary
-        if target_epoch < self.deactivation_epoch {
+        if target_epoch_u64 < deactivation_epoch {
             // not deactivated
             if activating_stake == 0 {
                 StakeActivationStatus::with_effective(effective_stake.to_le_bytes())
@@ -85,11 +90,11 @@ impl Delegation {
                     activating_stake.to_le_bytes(),
                 )
             }
-        } else if target_epoch == self.deactivation_epoch {
+        } else if target_epoch_u64 == deactivation_epoch {
             // can only deactivate what's activated
             StakeActivationStatus::with_deactivating(effective_stake)
-        } else if let Some((history, mut prev_epoch, mut prev_cluster_stake)) = history
-            .get_entry(bytes_to_u64(self.deactivation_epoch))
+        } else if let Some((history, prev_epoch, mut prev_cluster_stake)) = history
+            .get_entry(deactivation_epoch)
             .map(|cluster_stake_at_deactivation_epoch| {
                 (
                     history,
@@ -102,16 +107,17 @@ impl Delegation {
 
             // loop from my deactivation epoch until the target epoch
             // current effective stake is updated using its previous epoch's cluster stake
-            let mut current_epoch;
+            let new_rate_epoch = new_rate_epoch(new_rate_activation_epoch);
+            let mut current_epoch = bytes_to_u64(prev_epoch);
             let mut current_effective_stake = effective_stake;
-            let prev_cluster_stake_deactivating = bytes_to_u64(prev_cluster_stake.deactivating);
-            let prev_cluster_stake_effective = bytes_to_u64(prev_cluster_stake.effective);
 
             loop {
-                current_epoch = bytes_to_u64(prev_epoch) + 1;
+                current_epoch += 1;
+                let prev_cluster_stake_deactivating = bytes_to_u64(prev_cluster_stake.deactivating);
+                let prev_cluster_stake_effective = bytes_to_u64(prev_cluster_stake.effective);
                 // if there is no deactivating stake at prev epoch, we should have been
                 // fully undelegated at this moment
-                if bytes_to_u64(prev_cluster_stake.deactivating) == 0 {
+                if prev_cluster_stake_deactivating == 0 {
                     break;
                 }
 
@@ -119,8 +125,7 @@ impl Delegation {
                 //   this account is entitled to take
                 let weight =
                     current_effective_stake as f64 / prev_cluster_stake_deactivating as f64;
-                let warmup_cooldown_rate =
-                    warmup_cooldown_rate(current_epoch.to_be_bytes(), new_rate_activation_epoch);
+                let warmup_cooldown_rate = rate_at(current_epoch, new_rate_epoch);
 
                 // portion of newly not-effective cluster stake I'm entitled to at current epoch
                 let newly_not_effective_cluster_stake =
@@ -134,11 +139,10 @@ impl Delegation {
                     break;
                 }
 
-                if current_epoch >= bytes_to_u64(target_epoch) {
+                if current_epoch >= target_epoch_u64 {
                     break;
                 }
                 if let Some(current_cluster_stake) = history.get_entry(current_epoch) {
-                    prev_epoch = current_epoch.to_le_bytes();
                     prev_cluster_stake = current_cluster_stake;
                 } else {
                     break;
@@ -160,22 +164,22 @@ impl Delegation {
         history: &T,
         new_rate_activation_epoch: Option<Epoch>,
     ) -> (u64, u64) {
-        let delegated_stake = self.stake;
+        let delegated_stake = bytes_to_u64(self.stake);
 
         if self.is_bootstrap() {
             // fully effective immediately
-            (bytes_to_u64(delegated_stake), 0)
+            (delegated_stake, 0)
         } else if self.activation_epoch == self.deactivation_epoch {
             // activated but instantly deactivated; no stake at all regardless of target_epoch
             // this must be after the bootstrap check and before all-is-activating check
             (0, 0)
         } else if target_epoch == self.activation_epoch {
             // all is activating
-            (0, bytes_to_u64(delegated_stake))
-        } else if target_epoch < self.activation_epoch {
+            (0, delegated_stake)
+        } else if bytes_to_u64(target_epoch) < bytes_to_u64(self.activation_epoch) {
             // not yet enabled
             (0, 0)
-        } else if let Some((history, mut prev_epoch, mut prev_cluster_stake)) = history
+        } else if let Some((history, prev_epoch, mut prev_cluster_stake)) = history
             .get_entry(bytes_to_u64(self.activation_epoch))
             .map(|cluster_stake_at_activation_epoch| {
                 (
@@ -187,26 +191,31 @@ impl Delegation {
         {
             // target_epoch > self.activation_epoch
 
+            // the loop only ever reads these as integers, so convert them once
+            let target_epoch = bytes_to_u64(target_epoch);
+            let deactivation_epoch = bytes_to_u64(self.deactivation_epoch);
+            let new_rate_epoch = new_rate_epoch(new_rate_activation_epoch);
+
             // loop from my activation epoch until the target epoch summing up my entitlement
             // current effective stake is updated using its previous epoch's cluster stake
-            let mut current_epoch;
+            let mut current_epoch = bytes_to_u64(prev_epoch);
             let mut current_effective_stake = 0;
             loop {
-                current_epoch = bytes_to_u64(prev_epoch) + 1;
+                current_epoch += 1;
+                let prev_cluster_stake_activating = bytes_to_u64(prev_cluster_stake.activating);
                 // if there is no activating stake at prev epoch, we should have been
                 // fully effective at this moment
-                if bytes_to_u64(prev_cluster_stake.activating) == 0 {
+                if prev_cluster_stake_activating == 0 {
                     break;
                 }
 
                 // how much of the growth in stake this account is
-                //  entitled to take
-                let remaining_activating_stake =
-                    u64::from_le_bytes(delegated_stake) - current_effective_stake;
-                let weight = remaining_activating_stake as f64
-                    / bytes_to_u64(prev_cluster_stake.activating) as f64;
-                let warmup_cooldown_rate =
-                    warmup_cooldown_rate(current_epoch.to_le_bytes(), new_rate_activation_epoch);
+                //  entitled to take; the division stays per epoch, folding it
+                //  into the product below would round differently from native
+                let remaining_activating_stake = delegated_stake - current_effective_stake;
+                let weight =
+                    remaining_activating_stake as f64 / prev_cluster_stake_activating as f64;
+                let warmup_cooldown_rate = rate_at(current_epoch, new_rate_epoch);
 
                 // portion of newly effective cluster stake I'm entitled to at current epoch
                 let newly_effective_cluster_stake =
@@ -215,18 +224,15 @@ impl Delegation {
                     ((weight * newly_effective_cluster_stake) as u64).max(1);
 
                 current_effective_stake += newly_effective_stake;
-                if current_effective_stake >= bytes_to_u64(delegated_stake) {
-                    current_effective_stake = bytes_to_u64(delegated_stake);
+                if current_effective_stake >= delegated_stake {
+                    current_effective_stake = delegated_stake;
                     break;
                 }
 
-                if current_epoch >= bytes_to_u64(target_epoch)
-                    || current_epoch >= bytes_to_u64(self.deactivation_epoch)
-                {
+                if current_epoch >= target_epoch || current_epoch >= deactivation_epoch {
                     break;
                 }
                 if let Some(current_cluster_stake) = history.get_entry(current_epoch) {
-                    prev_epoch = current_epoch.to_le_bytes();
                     prev_cluster_stake = current_cluster_stake;
                 } else {
                     break;
@@ -235,11 +241,11 @@ impl Delegation {
 
             (
                 current_effective_stake,
-                bytes_to_u64(delegated_stake) - current_effective_stake,
+                delegated_stake - current_effective_stake,
             )
         } else {
             // no history or I've dropped out of history, so assume fully effective
-            (bytes_to_u64(delegated_stake), 0)
+            (delegated_stake, 0)
         }
     }
 
@@ -264,7 +270,24 @@ impl Delegation {
     }
 }
 
-pub const DEFAULT_WARMUP_COOLDOWN_RATE: f64 = 0.25;
+/// The epoch `NEW_WARMUP_COOLDOWN_RATE` takes effect in, converted once so the
+/// history walks compare integers rather than re-decoding it every epoch.
+#[inline(always)]
+fn new_rate_epoch(new_rate_activation_epoch: Option<Epoch>) -> u64 {
+    new_rate_activation_epoch
+        .map(bytes_to_u64)
+        .unwrap_or(u64::MAX)
+}
+
+/// `warmup_cooldown_rate` for an epoch against a pre-converted switch epoch.
+#[inline(always)]
+fn rate_at(epoch: u64, new_rate_epoch: u64) -> f64 {
+    if epoch < new_rate_epoch {
+        DEFAULT_WARMUP_COOLDOWN_RATE
+    } else {
+        NEW_WARMUP_COOLDOWN_RATE
+    }
+}
 
 impl Default for Delegation {
     fn default() -> Self {
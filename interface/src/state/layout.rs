@@ -0,0 +1,222 @@
+//! Byte offsets of the fields of a serialized `StakeStateV2`, and writers
+//! whose offset is checked against the 200-byte layout at compile time, so a
+//! write can never slice past the end of the account data.
+
+use super::StakeStateV2;
+
+pub const STAKE_STATE_LEN: usize = StakeStateV2::size_of();
+
+pub const TAG_OFFSET: usize = 0;
+
+// Meta, in both `Initialized` and `Stake`
+pub const RENT_EXEMPT_RESERVE_OFFSET: usize = 4;
+pub const STAKER_OFFSET: usize = 12;
+pub const WITHDRAWER_OFFSET: usize = 44;
+pub const LOCKUP_UNIX_TIMESTAMP_OFFSET: usize = 76;
+pub const LOCKUP_EPOCH_OFFSET: usize = 84;
+pub const LOCKUP_CUSTODIAN_OFFSET: usize = 92;
+pub const META_END: usize = 124;
+
+// Stake, only in `Stake`
+pub const VOTER_PUBKEY_OFFSET: usize = 124;
+pub const DELEGATION_STAKE_OFFSET: usize = 156;
+pub const ACTIVATION_EPOCH_OFFSET: usize = 164;
+pub const DEACTIVATION_EPOCH_OFFSET: usize = 172;
+pub const WARMUP_COOLDOWN_RATE_OFFSET: usize = 180;
+pub const CREDITS_OBSERVED_OFFSET: usize = 188;
+pub const STAKE_FLAGS_OFFSET: usize = 196;
+pub const STAKE_END: usize = 197;
+
+/// Copies `value` to `data[OFFSET..OFFSET + N]`.
+#[inline(always)]
+pub fn write_bytes_at<const OFFSET: usize, const N: usize>(
+    data: &mut [u8; STAKE_STATE_LEN],
+    value: &[u8; N],
+) {
+    const {
+        assert!(
+            OFFSET + N <= STAKE_STATE_LEN,
+            "write past the stake account layout"
+        )
+    };
+    // in bounds by the assertion above, so the check compiles away
+    data[OFFSET..OFFSET + N].copy_from_slice(value);
+}
+
+#[inline(always)]
+pub fn write_u32_le_at<const OFFSET: usize>(data: &mut [u8; STAKE_STATE_LEN], value: u32) {
+    write_bytes_at::<OFFSET, 4>(data, &value.to_le_bytes());
+}
+
+/// An offset that doesn't leave room for the value fails to build:
+///
+/// ```compile_fail
+/// # use pinocchio_stake_interface::state::layout::*;
+/// let mut data = [0u8; STAKE_STATE_LEN];
+/// write_u64_le_at::<STAKE_FLAGS_OFFSET>(&mut data, 1);
+/// ```
+#[inline(always)]
+pub fn write_u64_le_at<const OFFSET: usize>(data: &mut [u8; STAKE_STATE_LEN], value: u64) {
+    write_bytes_at::<OFFSET, 8>(data, &value.to_le_bytes());
+}
+
+#[inline(always)]
+pub fn write_i64_le_at<const OFFSET: usize>(data: &mut [u8; STAKE_STATE_LEN], value: i64) {
+    write_bytes_at::<OFFSET, 8>(data, &value.to_le_bytes());
+}
+
+#[inline(always)]
+pub fn read_u64_le_at<const OFFSET: usize>(data: &[u8; STAKE_STATE_LEN]) -> u64 {
+    const {
+        assert!(
+            OFFSET + 8 <= STAKE_STATE_LEN,
+            "read past the stake account layout"
+        )
+    };
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&data[OFFSET..OFFSET + 8]);
+    u64::from_le_bytes(value)
+}
+
+impl StakeStateV2 {
+    /// Serializes `self` into `data` as bincode would: the tag and the
+    /// fields of the variant, leaving the bytes past them untouched.
+    pub fn write_to(&self, data: &mut [u8; STAKE_STATE_LEN]) {
+        let (tag, meta, stake) = match self {
+            Self::Uninitialized => (0, None, None),
+            Self::Initialized(meta) => (1, Some(meta), None),
+            Self::Stake(meta, stake, stake_flags) => (2, Some(meta), Some((stake, stake_flags))),
+            Self::RewardsPool => (3, None, None),
+        };
+        write_u32_le_at::<TAG_OFFSET>(data, tag);
+
+        if let Some(meta) = meta {
+            write_bytes_at::<RENT_EXEMPT_RESERVE_OFFSET, 8>(data, &meta.rent_exempt_reserve);
+            write_bytes_at::<STAKER_OFFSET, 32>(data, &meta.authorized.staker);
+            write_bytes_at::<WITHDRAWER_OFFSET, 32>(data, &meta.authorized.withdrawer);
+            write_bytes_at::<LOCKUP_UNIX_TIMESTAMP_OFFSET, 8>(data, &meta.lockup.unix_timestamp);
+            write_bytes_at::<LOCKUP_EPOCH_OFFSET, 8>(data, &meta.lockup.epoch);
+            write_bytes_at::<LOCKUP_CUSTODIAN_OFFSET, 32>(data, &meta.lockup.custodian);
+        }
+
+        if let Some((stake, stake_flags)) = stake {
+            let delegation = &stake.delegation;
+            write_bytes_at::<VOTER_PUBKEY_OFFSET, 32>(data, &delegation.voter_pubkey);
+            write_bytes_at::<DELEGATION_STAKE_OFFSET, 8>(data, &delegation.stake);
+            write_bytes_at::<ACTIVATION_EPOCH_OFFSET, 8>(data, &delegation.activation_epoch);
+            write_bytes_at::<DEACTIVATION_EPOCH_OFFSET, 8>(data, &delegation.deactivation_epoch);
+            // still part of the layout, whatever it is worth
+            #[allow(deprecated)]
+            write_bytes_at::<WARMUP_COOLDOWN_RATE_OFFSET, 8>(
+                data,
+                &delegation.warmup_cooldown_rate,
+            );
+            write_bytes_at::<CREDITS_OBSERVED_OFFSET, 8>(data, &stake.credits_observed);
+            write_bytes_at::<STAKE_FLAGS_OFFSET, 1>(data, &[stake_flags.bits()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{Delegation, Meta, Stake, StakeFlags};
+
+    fn stake_state() -> StakeStateV2 {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(2_282_880);
+        meta.authorized.staker = [1; 32];
+        meta.authorized.withdrawer = [2; 32];
+        meta.lockup.set_unix_timestamp(-5);
+        meta.lockup.set_epoch(400);
+        meta.lockup.custodian = [3; 32];
+
+        let mut delegation = Delegation::new(&[4; 32], 10_000, 300u64.to_le_bytes());
+        delegation.set_deactivation_epoch(350);
+        let mut stake = Stake {
+            delegation,
+            ..Stake::default()
+        };
+        stake.set_credits_observed(77);
+        StakeStateV2::Stake(meta, stake, StakeFlags::from_bits(0b10))
+    }
+
+    #[test]
+    fn test_offsets_match_repr() {
+        let state = stake_state();
+        let mut data = [0u8; STAKE_STATE_LEN];
+        state.write_to(&mut data);
+
+        // SAFETY: `StakeStateV2` is `repr(C)` and `STAKE_STATE_LEN` bytes long
+        let repr = unsafe {
+            core::slice::from_raw_parts(&state as *const StakeStateV2 as *const u8, STAKE_STATE_LEN)
+        };
+        assert_eq!(data[..STAKE_END], repr[..STAKE_END]);
+
+        assert_eq!(
+            read_u64_le_at::<RENT_EXEMPT_RESERVE_OFFSET>(&data),
+            2_282_880
+        );
+        assert_eq!(
+            read_u64_le_at::<LOCKUP_UNIX_TIMESTAMP_OFFSET>(&data),
+            -5i64 as u64
+        );
+        assert_eq!(read_u64_le_at::<LOCKUP_EPOCH_OFFSET>(&data), 400);
+        assert_eq!(read_u64_le_at::<DELEGATION_STAKE_OFFSET>(&data), 10_000);
+        assert_eq!(read_u64_le_at::<ACTIVATION_EPOCH_OFFSET>(&data), 300);
+        assert_eq!(read_u64_le_at::<DEACTIVATION_EPOCH_OFFSET>(&data), 350);
+        assert_eq!(read_u64_le_at::<CREDITS_OBSERVED_OFFSET>(&data), 77);
+        assert_eq!(data[STAKE_FLAGS_OFFSET], 0b10);
+    }
+
+    #[test]
+    fn test_write_leaves_trailing_bytes() {
+        let mut data = [0xff; STAKE_STATE_LEN];
+        StakeStateV2::Uninitialized.write_to(&mut data);
+        assert_eq!(data[..4], [0; 4]);
+        assert!(data[4..].iter().all(|byte| *byte == 0xff));
+
+        let mut data = [0xff; STAKE_STATE_LEN];
+        let meta = stake_state().meta().unwrap();
+        StakeStateV2::Initialized(meta).write_to(&mut data);
+        assert_eq!(
+            StakeStateV2::try_from_bytes(&aligned(&data)),
+            Ok(&StakeStateV2::Initialized(meta))
+        );
+        assert!(data[META_END..].iter().all(|byte| *byte == 0xff));
+    }
+
+    #[test]
+    fn test_write_fields() {
+        let mut data = [0u8; STAKE_STATE_LEN];
+        stake_state().write_to(&mut data);
+        write_u64_le_at::<DEACTIVATION_EPOCH_OFFSET>(&mut data, u64::MAX);
+        write_i64_le_at::<LOCKUP_UNIX_TIMESTAMP_OFFSET>(&mut data, i64::MIN);
+
+        let state = *StakeStateV2::try_from_bytes(&aligned(&data)).unwrap();
+        assert_eq!(
+            state.delegation().unwrap().deactivation_epoch,
+            u64::MAX.to_le_bytes()
+        );
+        assert_eq!(
+            state.lockup().unwrap().unix_timestamp,
+            i64::MIN.to_le_bytes()
+        );
+    }
+
+    fn aligned(data: &[u8; STAKE_STATE_LEN]) -> AlignedData {
+        let mut aligned = AlignedData([0; STAKE_STATE_LEN]);
+        aligned.0.copy_from_slice(data);
+        aligned
+    }
+
+    #[repr(align(4))]
+    struct AlignedData([u8; STAKE_STATE_LEN]);
+
+    impl core::ops::Deref for AlignedData {
+        type Target = [u8];
+        fn deref(&self) -> &[u8] {
+            &self.0
+        }
+    }
+}
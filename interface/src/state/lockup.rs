@@ -0,0 +1,186 @@
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock};
+
+use super::{Epoch, UnixTimestamp};
+use crate::error::StakeError;
+
+/// Decides what a lockup holds back from withdrawal. `Lockup` implements the
+/// mainnet rules; a private cluster can evaluate the same stored fields as
+/// another vesting schedule, such as a linear unlock, by implementing this for
+/// a type built from the `Lockup`.
+pub trait LockupEvaluator {
+    /// Whether the lockup is still in force for a transaction signed by
+    /// `custodian`.
+    fn is_in_force(&self, clock: &Clock, custodian: Option<&Pubkey>) -> bool;
+
+    /// Checks `withdraw_lamports` of the account's `lamports` may be withdrawn.
+    /// `vesting_lamports` is the fixed amount a schedule vests from, the rent
+    /// exempt reserve and delegated stake of a delegated account, which no
+    /// withdrawal lowers, or `None` for an account with no delegation. By
+    /// default nothing may be withdrawn while the lockup is in force.
+    fn check_withdraw(
+        &self,
+        clock: &Clock,
+        custodian: Option<&Pubkey>,
+        _withdraw_lamports: u64,
+        _lamports: u64,
+        _vesting_lamports: Option<u64>,
+    ) -> Result<(), ProgramError> {
+        if self.is_in_force(clock, custodian) {
+            return Err(StakeError::LockupInForce.into());
+        }
+        Ok(())
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Lockup {
+    /// UnixTimestamp at which this stake will allow withdrawal, unless the
+    ///   transaction is signed by the custodian
+    pub unix_timestamp: UnixTimestamp, //i64
+    /// epoch height at which this stake will allow withdrawal, unless the
+    ///   transaction is signed by the custodian
+    pub epoch: Epoch,
+    /// custodian signature on a transaction exempts the operation from
+    ///  lockup constraints
+    pub custodian: Pubkey,
+}
+
+impl Lockup {
+    #[inline(always)]
+    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        self.unix_timestamp = unix_timestamp.to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn unix_timestamp(&self) -> i64 {
+        i64::from_le_bytes(self.unix_timestamp)
+    }
+
+    #[inline(always)]
+    pub fn set_epoch(&mut self, epoch: u64) {
+        self.epoch = epoch.to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn epoch(&self) -> u64 {
+        u64::from_le_bytes(self.epoch)
+    }
+
+    /// Whether a custodian is set. The default pubkey is how SetLockup removes
+    /// one: no one holds its key, so it never counts as signing.
+    #[inline(always)]
+    pub fn has_custodian(&self) -> bool {
+        self.custodian != Pubkey::default()
+    }
+
+    pub fn is_in_force(&self, clock: &Clock, custodian: Option<&Pubkey>) -> bool {
+        self.is_in_force_at(clock.epoch, clock.unix_timestamp, custodian)
+    }
+
+    /// `is_in_force` at `epoch` and `unix_timestamp`, for callers that have
+    /// the two without a whole `Clock`.
+    pub fn is_in_force_at(
+        &self,
+        epoch: u64,
+        unix_timestamp: i64,
+        custodian: Option<&Pubkey>,
+    ) -> bool {
+        if self.has_custodian() && custodian == Some(&self.custodian) {
+            return false;
+        }
+
+        self.unix_timestamp() > unix_timestamp || self.epoch() > epoch
+    }
+}
+
+impl LockupEvaluator for Lockup {
+    #[inline(always)]
+    fn is_in_force(&self, clock: &Clock, custodian: Option<&Pubkey>) -> bool {
+        Lockup::is_in_force(self, clock, custodian)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CUSTODIAN: Pubkey = [1; 32];
+
+    fn lockup(epoch: u64) -> Lockup {
+        let mut lockup = Lockup {
+            custodian: CUSTODIAN,
+            ..Lockup::default()
+        };
+        lockup.set_epoch(epoch);
+        lockup
+    }
+
+    fn clock(epoch: u64) -> Clock {
+        Clock {
+            epoch,
+            ..Clock::default()
+        }
+    }
+
+    #[test]
+    fn test_default_check_withdraw() {
+        let lockup = lockup(10);
+        assert_eq!(
+            lockup.check_withdraw(&clock(9), None, 0, 1_000, None),
+            Err(StakeError::LockupInForce.into())
+        );
+        // mainnet's lockup is a cliff, whatever the account vests from
+        assert_eq!(
+            lockup.check_withdraw(&clock(9), None, 1, 1_000, Some(1_000)),
+            Err(StakeError::LockupInForce.into())
+        );
+        assert_eq!(
+            lockup.check_withdraw(&clock(9), Some(&CUSTODIAN), 1_000, 1_000, None),
+            Ok(())
+        );
+        assert_eq!(
+            lockup.check_withdraw(&clock(10), None, 1_000, 1_000, None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_removed_custodian() {
+        let mut lockup = lockup(10);
+        lockup.custodian = Pubkey::default();
+        assert!(!lockup.has_custodian());
+        assert!(lockup.is_in_force(&clock(9), Some(&Pubkey::default())));
+        assert_eq!(
+            lockup.check_withdraw(&clock(9), Some(&Pubkey::default()), 1_000, 1_000, None),
+            Err(StakeError::LockupInForce.into())
+        );
+        assert!(!lockup.is_in_force(&clock(10), None));
+    }
+
+    #[test]
+    fn test_in_force_at() {
+        let mut lockup = lockup(10);
+        lockup.set_unix_timestamp(1_000);
+
+        // until both the epoch and the timestamp have been reached
+        assert!(lockup.is_in_force_at(9, 1_000, None));
+        assert!(lockup.is_in_force_at(10, 999, None));
+        assert!(!lockup.is_in_force_at(10, 1_000, None));
+        assert!(!lockup.is_in_force_at(u64::MAX, i64::MAX, None));
+
+        // unless the custodian signs
+        assert!(!lockup.is_in_force_at(0, i64::MIN, Some(&CUSTODIAN)));
+        assert!(lockup.is_in_force_at(0, i64::MIN, Some(&[2; 32])));
+
+        let clock = Clock {
+            epoch: 10,
+            unix_timestamp: 999,
+            ..Clock::default()
+        };
+        assert_eq!(
+            lockup.is_in_force(&clock, None),
+            lockup.is_in_force_at(10, 999, None)
+        );
+    }
+}
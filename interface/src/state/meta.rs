@@ -2,7 +2,7 @@ use pinocchio::sysvars::clock::Clock;
 
 use crate::{error::InstructionError, instruction::LockupArgs};
 
-use super::{Authorized, Lockup};
+use super::{Authorized, Lockup, LockupEvaluator};
 
 #[repr(C)]
 #[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
@@ -28,7 +28,9 @@ impl Meta {
         u64::from_le_bytes(self.rent_exempt_reserve)
     }
 
-    pub fn set_lockup(
+    /// Applies `lockup`, signed as `signer_args` says. While the current
+    /// lockup is in force, as `L` evaluates it, only the custodian may.
+    pub fn set_lockup<L: LockupEvaluator + From<Lockup>>(
         &mut self,
         lockup: &LockupArgs,
         signer_args: SetLockupSignerArgs,
@@ -37,7 +39,7 @@ impl Meta {
         // post-stake_program_v4 behavior:
         // * custodian can update the lockup while in force
         // * withdraw authority can set a new lockup
-        if self.lockup.is_in_force(clock, None) {
+        if L::from(self.lockup).is_in_force(clock, None) {
             if !signer_args.has_custodian_signer {
                 return Err(InstructionError::MissingRequiredSignature);
             }
@@ -0,0 +1,72 @@
+pub mod authorized;
+pub mod delegation;
+pub mod layout;
+pub mod lockup;
+pub mod meta;
+pub mod stake;
+pub mod stake_authorize;
+pub mod stake_flags;
+pub mod stake_history;
+pub mod stake_history_data;
+pub mod stake_state_v2;
+
+pub use authorized::*;
+pub use delegation::*;
+pub use lockup::*;
+pub use meta::*;
+pub use stake::*;
+pub use stake_authorize::*;
+pub use stake_flags::*;
+pub use stake_history::*;
+pub use stake_history_data::*;
+pub use stake_state_v2::*;
+
+use crate::consts::{DEFAULT_WARMUP_COOLDOWN_RATE, NEW_WARMUP_COOLDOWN_RATE};
+
+pub type Epoch = [u8; 8]; //u64
+pub type UnixTimestamp = [u8; 8]; //i64;
+
+#[macro_export]
+macro_rules! impl_sysvar_id {
+    ($type:ty) => {
+        impl $crate::state::stake_history::SysvarId for $type {
+            fn id() -> Pubkey {
+                id()
+            }
+
+            fn check_id(pubkey: &Pubkey) -> bool {
+                check_id(pubkey)
+            }
+        }
+    };
+}
+
+/// Declares an ID that implements [`SysvarId`].
+#[macro_export]
+macro_rules! declare_sysvar_id {
+    ($name:expr, $type:ty) => (
+        pinocchio_pubkey::declare_id!($name);
+        $crate::impl_sysvar_id!($type);
+    );
+}
+
+// Means that no more than RATE of current effective stake may be added or subtracted per
+// epoch.
+
+pub fn warmup_cooldown_rate(
+    current_epoch: [u8; 8],
+    new_rate_activation_epoch: Option<[u8; 8]>
+) -> f64 {
+    let current = bytes_to_u64(current_epoch);
+    let activation = new_rate_activation_epoch.map(bytes_to_u64).unwrap_or(u64::MAX);
+
+    if current < activation {
+        DEFAULT_WARMUP_COOLDOWN_RATE
+    } else {
+        NEW_WARMUP_COOLDOWN_RATE
+    }
+}
+
+pub fn bytes_to_u64(bytes: [u8; 8]) -> u64 {
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,88 @@
+#[repr(C)]
+#[derive(Copy, PartialEq, Eq, Clone, PartialOrd, Ord, Hash, Debug)]
+pub struct StakeFlags {
+    bits: u8,
+}
+
+impl StakeFlags {
+    /// Stake must be fully activated before deactivation is allowed (bit 1).
+    #[deprecated(
+        since = "2.1.0",
+        note = "This flag will be removed because it was only used for `redelegate`, which will not be enabled."
+    )]
+    pub const MUST_FULLY_ACTIVATE_BEFORE_DEACTIVATION_IS_PERMITTED: Self = Self { bits: 0b0000_0001 };
+
+    /// Every flag this program assigns a meaning to. Any other bit belongs to a
+    /// future runtime and is carried through untouched.
+    pub const KNOWN: Self = Self { bits: 0b0000_0001 };
+
+    pub const fn empty() -> Self {
+        Self { bits: 0 }
+    }
+
+    pub const fn from_bits(bits: u8) -> Self {
+        Self { bits }
+    }
+
+    pub const fn bits(&self) -> u8 {
+        self.bits
+    }
+
+    /// The flags with every known bit cleared.
+    pub const fn unknown(self) -> Self {
+        Self {
+            bits: self.bits & !Self::KNOWN.bits,
+        }
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        (self.bits & other.bits) == other.bits
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.bits &= !other.bits;
+    }
+
+    pub fn set(&mut self, other: Self) {
+        self.bits |= other.bits;
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self {
+            bits: self.bits | other.bits,
+        }
+    }
+}
+
+impl Default for StakeFlags {
+    fn default() -> Self {
+        StakeFlags::empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::StakeStateV2;
+
+    // tag + Meta + Stake
+    const FLAGS_OFFSET: usize = 4 + 120 + 72;
+
+    #[test]
+    fn test_unknown_bits_round_trip() {
+        let mut data = [0u8; StakeStateV2::size_of()];
+        data[0] = 2;
+        data[FLAGS_OFFSET] = 0b1010_0001;
+
+        let state = unsafe { *StakeStateV2::from_bytes(&data) };
+        let StakeStateV2::Stake(_, _, stake_flags) = state else {
+            panic!("not a delegated stake account");
+        };
+        assert_eq!(stake_flags.bits(), 0b1010_0001);
+        assert_eq!(stake_flags.unknown().bits(), 0b1010_0000);
+
+        let mut rewritten = [0u8; StakeStateV2::size_of()];
+        unsafe { *StakeStateV2::from_bytes_mut(&mut rewritten) = state };
+        assert_eq!(rewritten[FLAGS_OFFSET], 0b1010_0001);
+        assert_eq!(rewritten[..FLAGS_OFFSET], data[..FLAGS_OFFSET]);
+    }
+}
@@ -16,8 +16,6 @@ pub trait SysvarId {
     fn check_id(pubkey: &Pubkey) -> bool;
 }
 
-/// Declares an ID that implements [`SysvarId`].
-
 pub const MAX_ENTRIES: usize = 512; // it should never take as many as 512 epochs to warm up or cool down
 
 #[repr(C)]
@@ -101,7 +99,7 @@ impl StakeHistory {
 )]
 impl StakeHistory {
     // override
-    fn size_of() -> usize {
+    pub fn size_of() -> usize {
         // hard-coded so that we don't have to construct an empty
         16392 // golden, update if MAX_ENTRIES changes
     }
@@ -118,6 +116,18 @@ pub trait StakeHistoryGetEntry {
     fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry>;
 }
 
+impl<T: StakeHistoryGetEntry + ?Sized> StakeHistoryGetEntry for &T {
+    fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
+        (**self).get_entry(epoch)
+    }
+}
+
+impl<T: StakeHistoryGetEntry + ?Sized> StakeHistoryGetEntry for &mut T {
+    fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
+        (**self).get_entry(epoch)
+    }
+}
+
 impl StakeHistoryGetEntry for StakeHistory {
     fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
         self.binary_search_by(|probe| epoch.cmp(&probe.0))
@@ -0,0 +1,165 @@
+use pinocchio::{program_error::ProgramError, sysvars::clock::Epoch};
+
+use crate::state::{StakeHistoryEntry, StakeHistoryGetEntry};
+
+// epoch followed by the three entry fields
+const RECORD_LEN: usize = 32;
+
+/// Stake history sysvar account data, as fetched over RPC or held in an
+/// account, read in place so the delegation math runs over it without
+/// deserializing all of it or making a syscall.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StakeHistoryData<'a> {
+    records: &'a [u8],
+}
+
+impl<'a> StakeHistoryData<'a> {
+    /// Checks that `data` holds as many records as its length prefix claims.
+    pub fn new(data: &'a [u8]) -> Result<Self, ProgramError> {
+        let (len, records) = data
+            .split_first_chunk::<8>()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let len = usize::try_from(u64::from_le_bytes(*len))
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let records = len
+            .checked_mul(RECORD_LEN)
+            .and_then(|records_len| records.get(..records_len))
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Ok(Self { records })
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len() / RECORD_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    fn record(&self, index: usize) -> (Epoch, StakeHistoryEntry) {
+        let record = &self.records[index * RECORD_LEN..(index + 1) * RECORD_LEN];
+        let field = |offset: usize| -> [u8; 8] { record[offset..offset + 8].try_into().unwrap() };
+        (
+            u64::from_le_bytes(field(0)),
+            StakeHistoryEntry {
+                effective: field(8),
+                activating: field(16),
+                deactivating: field(24),
+            },
+        )
+    }
+}
+
+impl StakeHistoryGetEntry for StakeHistoryData<'_> {
+    fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
+        // records are sorted newest epoch first
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (probe, entry) = self.record(mid);
+            match epoch.cmp(&probe) {
+                core::cmp::Ordering::Equal => return Some(entry),
+                core::cmp::Ordering::Greater => high = mid,
+                core::cmp::Ordering::Less => low = mid + 1,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{
+        consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+        state::{Delegation, StakeHistory},
+    };
+
+    fn history() -> StakeHistory {
+        let mut history = StakeHistory::default();
+        // leave a gap so lookups can't rely on epochs being contiguous
+        for epoch in (0..300u64).filter(|epoch| epoch % 7 != 3) {
+            history.add(
+                epoch,
+                StakeHistoryEntry {
+                    effective: (1_000_000 + epoch * 1_000).to_le_bytes(),
+                    activating: (50_000 + epoch).to_le_bytes(),
+                    deactivating: (20_000 + epoch).to_le_bytes(),
+                },
+            );
+        }
+        history
+    }
+
+    fn to_data(history: &StakeHistory) -> Vec<u8> {
+        let mut data = (history.len() as u64).to_le_bytes().to_vec();
+        for (epoch, entry) in history.iter() {
+            data.extend_from_slice(&epoch.to_le_bytes());
+            data.extend_from_slice(&entry.effective);
+            data.extend_from_slice(&entry.activating);
+            data.extend_from_slice(&entry.deactivating);
+        }
+        data
+    }
+
+    #[test]
+    fn test_matches_in_memory_history() {
+        let history = history();
+        let data = to_data(&history);
+        let history_data = StakeHistoryData::new(&data).unwrap();
+        assert_eq!(history_data.len(), history.len());
+
+        for epoch in 0..310 {
+            assert_eq!(
+                history_data.get_entry(epoch),
+                history.get_entry(epoch),
+                "epoch {epoch}"
+            );
+        }
+
+        let mut delegation = Delegation::new(&[1; 32], 200_000, 10u64.to_le_bytes());
+        delegation.set_deactivation_epoch(150);
+        for epoch in 0..310u64 {
+            assert_eq!(
+                delegation.stake_activating_and_deactivating(
+                    epoch.to_le_bytes(),
+                    &history_data,
+                    PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH
+                ),
+                delegation.stake_activating_and_deactivating(
+                    epoch.to_le_bytes(),
+                    &history,
+                    PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH
+                ),
+                "epoch {epoch}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        let data = to_data(&history());
+        assert_eq!(
+            StakeHistoryData::new(&data[..7]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            StakeHistoryData::new(&data[..data.len() - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        // the sysvar account is allocated at full size, trailing bytes are
+        // unused
+        let mut padded = data.clone();
+        padded.resize(data.len() + RECORD_LEN, 0);
+        assert_eq!(StakeHistoryData::new(&padded).unwrap().len(), history().len());
+
+        let no_entries = 0u64.to_le_bytes();
+        let empty = StakeHistoryData::new(&no_entries).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.get_entry(0), None);
+    }
+}
@@ -14,7 +14,7 @@ pub enum StakeStateV2 {
     RewardsPool,
 }
 
-impl<'a> StakeStateV2 {
+impl StakeStateV2 {
     /// The fixed number of bytes used to serialize each stake account
     pub const fn size_of() -> usize {
         200
@@ -23,13 +23,13 @@ impl<'a> StakeStateV2 {
     #[inline]
     pub fn from_account_info(
         account_info: &AccountInfo,
-    ) -> Result<Ref<StakeStateV2>, ProgramError> {
+    ) -> Result<Ref<'_, StakeStateV2>, ProgramError> {
         if account_info.data_len() != Self::size_of() {
             return Err(ProgramError::InvalidAccountData);
         }
 
         let data = account_info.try_borrow_data()?;
-        if !Self::is_aligned_to_4(&*data) || data[0] > 3 {
+        if !Self::is_aligned_to_4(&data) || !Self::has_valid_tag(&data) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -48,7 +48,7 @@ impl<'a> StakeStateV2 {
             return Err(ProgramError::InvalidAccountData);
         }
         let data = account_info.borrow_data_unchecked();
-        if !Self::is_aligned_to_4(data) || data[0] > 3 {
+        if !Self::is_aligned_to_4(data) || !Self::has_valid_tag(data) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -58,13 +58,13 @@ impl<'a> StakeStateV2 {
     #[inline]
     pub fn try_from_account_info_mut(
         account_info: &AccountInfo,
-    ) -> Result<RefMut<StakeStateV2>, ProgramError> {
+    ) -> Result<RefMut<'_, StakeStateV2>, ProgramError> {
         if account_info.data_len() != Self::size_of() {
             return Err(ProgramError::InvalidAccountData);
         }
 
         let data = account_info.try_borrow_mut_data()?;
-        if !Self::is_aligned_to_4(&*data) || data[0] > 3 {
+        if !Self::is_aligned_to_4(&data) || !Self::has_valid_tag(&data) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -78,6 +78,7 @@ impl<'a> StakeStateV2 {
     /// The caller must ensure that it is safe to borrow the account data – e.g., there are
     /// no mutable borrows of the account data.
     #[inline]
+    #[allow(clippy::mut_from_ref)]
     pub unsafe fn from_account_info_mut_unchecked(
         account_info: &AccountInfo,
     ) -> Result<&mut StakeStateV2, ProgramError> {
@@ -85,12 +86,41 @@ impl<'a> StakeStateV2 {
             return Err(ProgramError::InvalidAccountData);
         }
         let data = account_info.borrow_mut_data_unchecked();
-        if !Self::is_aligned_to_4(data) || data[0] > 3 {
+        if !Self::is_aligned_to_4(data) || !Self::has_valid_tag(data) {
             return Err(ProgramError::InvalidAccountData);
         }
 
         Ok(Self::from_bytes_mut(data))
     }
+    /// Checked counterpart of `from_bytes`, for state that isn't held in an
+    /// `AccountInfo`, e.g. account data fetched by a client.
+    #[inline]
+    pub fn try_from_bytes(bytes: &[u8]) -> Result<&Self, ProgramError> {
+        if bytes.len() != Self::size_of()
+            || !Self::is_aligned_to_4(bytes)
+            || !Self::has_valid_tag(bytes)
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // SAFETY: the tag is checked and every other byte pattern is valid
+        Ok(unsafe { Self::from_bytes(bytes) })
+    }
+
+    /// Copies the state out of `bytes`, which unlike for `try_from_bytes`
+    /// needn't be aligned: account data isn't guaranteed to be on every
+    /// runtime.
+    #[inline]
+    pub fn read_from(bytes: &[u8]) -> Result<Self, ProgramError> {
+        if bytes.len() != Self::size_of() || !Self::has_valid_tag(bytes) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // SAFETY: the length and tag are checked, every other byte pattern is
+        // valid, and the read makes no assumption about alignment
+        Ok(unsafe { core::ptr::read_unaligned(bytes.as_ptr() as *const Self) })
+    }
+
     /// # Safety
     ///
     /// The caller must ensure that `bytes` contains a valid representation of `StakeStateV2`.
@@ -109,7 +139,13 @@ impl<'a> StakeStateV2 {
 
     fn is_aligned_to_4(data: &[u8]) -> bool {
         let ptr = data.as_ptr() as usize;
-        ptr % 4 == 0
+        ptr & 0b11 == 0
+    }
+
+    /// The variant tag is a `u32`, so all four bytes must be checked: any
+    /// value past `RewardsPool` is not a valid `StakeStateV2`.
+    fn has_valid_tag(data: &[u8]) -> bool {
+        matches!(data.first_chunk::<4>(), Some(tag) if u32::from_le_bytes(*tag) <= 3)
     }
 
     pub fn stake(&self) -> Option<Stake> {
@@ -162,6 +198,8 @@ impl<'a> StakeStateV2 {
 }
 #[cfg(test)]
 mod test {
+    use pinocchio::program_error::ProgramError;
+
     use super::StakeStateV2;
 
     #[test]
@@ -203,4 +241,50 @@ mod test {
 
         println!("{:?}", val);
     }
+
+    #[test]
+    fn test_tag_validation() {
+        for tag in 0u32..=3 {
+            assert!(StakeStateV2::has_valid_tag(&tag.to_le_bytes()), "{tag}");
+        }
+
+        // a valid low byte doesn't make a valid tag
+        for tag in [4u32, 0x0100_0003, 0x0000_0100, u32::MAX] {
+            assert!(!StakeStateV2::has_valid_tag(&tag.to_le_bytes()), "{tag:#x}");
+        }
+        assert!(!StakeStateV2::has_valid_tag(&[0; 3]));
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        // u32 backing storage keeps the data aligned like account data
+        let mut data = [0u32; StakeStateV2::size_of() / 4];
+        let bytes = |data: &[u32]| unsafe {
+            core::slice::from_raw_parts(data.as_ptr() as *const u8, StakeStateV2::size_of())
+        };
+
+        for tag in 0..=3 {
+            data[0] = tag;
+            assert!(StakeStateV2::try_from_bytes(bytes(&data)).is_ok(), "{tag}");
+        }
+        assert_eq!(
+            StakeStateV2::try_from_bytes(bytes(&data)),
+            Ok(&StakeStateV2::RewardsPool)
+        );
+
+        for tag in (4..=255).chain([0x0100_0002, u32::MAX]) {
+            data[0] = tag;
+            assert_eq!(
+                StakeStateV2::try_from_bytes(bytes(&data)),
+                Err(ProgramError::InvalidAccountData),
+                "{tag:#x}"
+            );
+        }
+
+        data[0] = 1;
+        assert_eq!(
+            StakeStateV2::try_from_bytes(&bytes(&data)[..199]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
 }
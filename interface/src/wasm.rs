@@ -0,0 +1,289 @@
+//! Bindings for web dashboards, built with the `wasm` feature for
+//! wasm32-unknown-unknown. They decode stake accounts and compute their
+//! activation with the same code the program runs, reading the stake history
+//! from sysvar account data rather than through a syscall.
+
+use alloc::{format, string::String, vec::Vec};
+
+use pinocchio::program_error::ProgramError;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    client::{activation_state, stake_state_from_data, StakeActivationState},
+    consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    state::{bytes_to_u64, Meta, Stake, StakeHistoryData, StakeStateV2},
+};
+
+/// A decoded stake account. Fields a state doesn't have are `undefined`.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StakeAccount {
+    state: StakeStateV2,
+}
+
+#[wasm_bindgen]
+impl StakeAccount {
+    /// `Uninitialized`, `Initialized`, `Stake` or `RewardsPool`.
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> String {
+        String::from(match self.state {
+            StakeStateV2::Uninitialized => "Uninitialized",
+            StakeStateV2::Initialized(_) => "Initialized",
+            StakeStateV2::Stake(..) => "Stake",
+            StakeStateV2::RewardsPool => "RewardsPool",
+        })
+    }
+
+    #[wasm_bindgen(getter, js_name = rentExemptReserve)]
+    pub fn rent_exempt_reserve(&self) -> Option<u64> {
+        self.meta().map(Meta::rent_exempt_reserve)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn staker(&self) -> Option<Vec<u8>> {
+        self.meta().map(|meta| meta.authorized.staker.to_vec())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn withdrawer(&self) -> Option<Vec<u8>> {
+        self.meta().map(|meta| meta.authorized.withdrawer.to_vec())
+    }
+
+    #[wasm_bindgen(getter, js_name = lockupUnixTimestamp)]
+    pub fn lockup_unix_timestamp(&self) -> Option<i64> {
+        self.meta().map(|meta| meta.lockup.unix_timestamp())
+    }
+
+    #[wasm_bindgen(getter, js_name = lockupEpoch)]
+    pub fn lockup_epoch(&self) -> Option<u64> {
+        self.meta().map(|meta| meta.lockup.epoch())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn custodian(&self) -> Option<Vec<u8>> {
+        self.meta().map(|meta| meta.lockup.custodian.to_vec())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn voter(&self) -> Option<Vec<u8>> {
+        self.stake()
+            .map(|stake| stake.delegation.voter_pubkey.to_vec())
+    }
+
+    #[wasm_bindgen(getter, js_name = delegatedStake)]
+    pub fn delegated_stake(&self) -> Option<u64> {
+        self.stake()
+            .map(|stake| bytes_to_u64(stake.delegation.stake))
+    }
+
+    #[wasm_bindgen(getter, js_name = activationEpoch)]
+    pub fn activation_epoch(&self) -> Option<u64> {
+        self.stake()
+            .map(|stake| bytes_to_u64(stake.delegation.activation_epoch))
+    }
+
+    /// `u64::MAX` until the stake is deactivated.
+    #[wasm_bindgen(getter, js_name = deactivationEpoch)]
+    pub fn deactivation_epoch(&self) -> Option<u64> {
+        self.stake()
+            .map(|stake| bytes_to_u64(stake.delegation.deactivation_epoch))
+    }
+
+    #[wasm_bindgen(getter, js_name = creditsObserved)]
+    pub fn credits_observed(&self) -> Option<u64> {
+        self.stake()
+            .map(|stake| bytes_to_u64(stake.credits_observed))
+    }
+}
+
+impl StakeAccount {
+    fn meta(&self) -> Option<&Meta> {
+        match &self.state {
+            StakeStateV2::Initialized(meta) | StakeStateV2::Stake(meta, ..) => Some(meta),
+            _ => None,
+        }
+    }
+
+    fn stake(&self) -> Option<&Stake> {
+        match &self.state {
+            StakeStateV2::Stake(_, stake, _) => Some(stake),
+            _ => None,
+        }
+    }
+}
+
+/// How much of a delegation is effective, activating and deactivating at an
+/// epoch, and the status a wallet shows for it.
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StakeActivation {
+    pub effective: u64,
+    pub activating: u64,
+    pub deactivating: u64,
+    status: StakeActivationState,
+}
+
+#[wasm_bindgen]
+impl StakeActivation {
+    /// `Activating`, `Active`, `Deactivating` or `Inactive`.
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        format!("{:?}", self.status)
+    }
+}
+
+/// Decodes the data of a stake account.
+#[wasm_bindgen(js_name = decodeStakeAccount)]
+pub fn decode_stake_account(data: &[u8]) -> Result<StakeAccount, JsError> {
+    read_stake_account(data).map_err(to_js_error)
+}
+
+/// The activation of the stake account holding `data` at `epoch`, with the
+/// stake history sysvar's account data as `stake_history`. Accounts without
+/// a delegation are inactive.
+#[wasm_bindgen(js_name = stakeActivation)]
+pub fn stake_activation(
+    data: &[u8],
+    stake_history: &[u8],
+    epoch: u64,
+) -> Result<StakeActivation, JsError> {
+    read_stake_activation(data, stake_history, epoch).map_err(to_js_error)
+}
+
+fn read_stake_account(data: &[u8]) -> Result<StakeAccount, ProgramError> {
+    Ok(StakeAccount {
+        state: stake_state_from_data(data)?,
+    })
+}
+
+fn read_stake_activation(
+    data: &[u8],
+    stake_history: &[u8],
+    epoch: u64,
+) -> Result<StakeActivation, ProgramError> {
+    let account = read_stake_account(data)?;
+    let stake_history = StakeHistoryData::new(stake_history)?;
+    let Some(stake) = account.stake() else {
+        return Ok(StakeActivation {
+            effective: 0,
+            activating: 0,
+            deactivating: 0,
+            status: StakeActivationState::Inactive,
+        });
+    };
+    let status = stake.delegation.stake_activating_and_deactivating(
+        epoch.to_le_bytes(),
+        &stake_history,
+        PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    );
+    Ok(StakeActivation {
+        effective: bytes_to_u64(status.effective),
+        activating: bytes_to_u64(status.activating),
+        deactivating: bytes_to_u64(status.deactivating),
+        status: activation_state(&stake.delegation, &stake_history, epoch),
+    })
+}
+
+fn to_js_error(error: ProgramError) -> JsError {
+    JsError::new(&format!("{error:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        client::stake_state_to_data,
+        state::{Delegation, StakeFlags, StakeHistoryEntry},
+    };
+
+    fn stake_data(deactivation_epoch: u64) -> Vec<u8> {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(2_282_880);
+        meta.authorized.staker = [1; 32];
+        meta.authorized.withdrawer = [2; 32];
+        let mut delegation = Delegation::new(&[3; 32], 1_000, 5u64.to_le_bytes());
+        delegation.set_deactivation_epoch(deactivation_epoch);
+        let stake = Stake {
+            delegation,
+            credits_observed: 7u64.to_le_bytes(),
+        };
+        stake_state_to_data(&StakeStateV2::Stake(meta, stake, StakeFlags::empty())).to_vec()
+    }
+
+    /// Stake history account data holding `entries`, newest first.
+    fn stake_history_data(entries: &[(u64, StakeHistoryEntry)]) -> Vec<u8> {
+        let mut data = (entries.len() as u64).to_le_bytes().to_vec();
+        for (epoch, entry) in entries {
+            data.extend_from_slice(&epoch.to_le_bytes());
+            data.extend_from_slice(&entry.effective);
+            data.extend_from_slice(&entry.activating);
+            data.extend_from_slice(&entry.deactivating);
+        }
+        data
+    }
+
+    #[test]
+    fn test_decode_stake_account() {
+        let account = read_stake_account(&stake_data(u64::MAX)).unwrap();
+        assert_eq!(account.state(), "Stake");
+        assert_eq!(account.rent_exempt_reserve(), Some(2_282_880));
+        assert_eq!(account.staker(), Some(vec![1; 32]));
+        assert_eq!(account.withdrawer(), Some(vec![2; 32]));
+        assert_eq!(account.lockup_epoch(), Some(0));
+        assert_eq!(account.voter(), Some(vec![3; 32]));
+        assert_eq!(account.delegated_stake(), Some(1_000));
+        assert_eq!(account.activation_epoch(), Some(5));
+        assert_eq!(account.deactivation_epoch(), Some(u64::MAX));
+        assert_eq!(account.credits_observed(), Some(7));
+
+        let uninitialized = read_stake_account(&[0; StakeStateV2::size_of()]).unwrap();
+        assert_eq!(uninitialized.state(), "Uninitialized");
+        assert_eq!(uninitialized.staker(), None);
+        assert_eq!(uninitialized.delegated_stake(), None);
+
+        assert_eq!(
+            read_stake_account(&[4; StakeStateV2::size_of()]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_stake_activation() {
+        let empty_history = stake_history_data(&[]);
+        // activating in its activation epoch, then active with no history to
+        // hold it back
+        let activation = read_stake_activation(&stake_data(u64::MAX), &empty_history, 5).unwrap();
+        assert_eq!((activation.activating, activation.effective), (1_000, 0));
+        assert_eq!(activation.status(), "Activating");
+        let activation = read_stake_activation(&stake_data(u64::MAX), &empty_history, 6).unwrap();
+        assert_eq!(activation.status(), "Active");
+
+        // a cluster warming up slowly only lets part of it in
+        let history = stake_history_data(&[(
+            5,
+            StakeHistoryEntry {
+                effective: 1_000u64.to_le_bytes(),
+                activating: 1_000_000u64.to_le_bytes(),
+                deactivating: 0u64.to_le_bytes(),
+            },
+        )]);
+        let activation = read_stake_activation(&stake_data(u64::MAX), &history, 6).unwrap();
+        assert_eq!(activation.status(), "Activating");
+        assert!(activation.effective > 0 && activation.activating > 0);
+        assert_eq!(activation.effective + activation.activating, 1_000);
+
+        let activation = read_stake_activation(&stake_data(8), &empty_history, 9).unwrap();
+        assert_eq!(activation.status(), "Inactive");
+
+        // accounts with nothing delegated
+        let data = [0; StakeStateV2::size_of()];
+        let activation = read_stake_activation(&data, &empty_history, 9).unwrap();
+        assert_eq!(activation.effective + activation.activating, 0);
+        assert_eq!(activation.status(), "Inactive");
+
+        assert_eq!(
+            read_stake_activation(&stake_data(u64::MAX), &[1, 0], 6),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -1,113 +1,358 @@
-// use mollusk_svm::{program, Mollusk};
-// use mollusk_svm_bencher::MolluskComputeUnitBencher;
-// use solana_pinocchio_starter::{
-//     instruction::{InitializeMyStateIxData, UpdateMyStateIxData},
-//     state::{to_bytes, DataLen, MyState, State},
-//     ID,
-// };
-// use solana_sdk::pubkey;
-// use solana_sdk::{
-//     account::Account,
-//     instruction::{AccountMeta, Instruction},
-//     native_token::LAMPORTS_PER_SOL,
-//     pubkey::Pubkey,
-// };
-
-// pub const PROGRAM: Pubkey = Pubkey::new_from_array(ID);
-
-// pub const RENT: Pubkey = pubkey!("SysvarRent111111111111111111111111111111111");
-
-// pub const PAYER: Pubkey = pubkey!("41LzznNicELmc5iCR9Jxke62a3v1VhzpBYodQF5AQwHX");
-
-// fn main() {
-//     let mollusk = Mollusk::new(&PROGRAM, "target/deploy/solana_pinocchio_starter");
-
-//     let (system_program, system_account) = program::keyed_account_for_system_program();
-
-//     // Create the PDA
-//     let (mystate_pda, bump) =
-//         Pubkey::find_program_address(&[MyState::SEED.as_bytes(), &PAYER.to_bytes()], &PROGRAM);
-
-//     //Initialize the accounts
-//     let payer_account = Account::new(1 * LAMPORTS_PER_SOL, 0, &system_program);
-//     let mystate_account = Account::new(0, 0, &system_program);
-//     let min_balance = mollusk.sysvars.rent.minimum_balance(0);
-//     let rent_account = Account::new(min_balance, 0, &RENT);
-
-//     //Push the accounts in to the instruction_accounts vec!
-//     let ix_accounts = vec![
-//         AccountMeta::new(PAYER, true),
-//         AccountMeta::new(mystate_pda, false),
-//         AccountMeta::new_readonly(RENT, false),
-//         AccountMeta::new_readonly(system_program, false),
-//     ];
-
-//     // Create the instruction data
-//     let ix_data = InitializeMyStateIxData {
-//         owner: *PAYER.as_array(),
-//         data: [1; 32],
-//         bump,
-//     };
-
-//     // Ix discriminator = 0
-//     let mut ser_ix_data = vec![0];
-
-//     // Serialize the instruction data
-//     ser_ix_data.extend_from_slice(unsafe { to_bytes(&ix_data) });
-
-//     // Create instruction
-//     let instruction0 = Instruction::new_with_bytes(PROGRAM, &ser_ix_data, ix_accounts);
-
-//     // Create tx_accounts vec
-//     let tx_accounts0 = &vec![
-//         (PAYER, payer_account.clone()),
-//         (mystate_pda, mystate_account.clone()),
-//         (RENT, rent_account.clone()),
-//         (system_program, system_account.clone()),
-//     ];
-
-//     let rent = mollusk.sysvars.rent.minimum_balance(MyState::LEN);
-//     let mut mystate_account = Account::new(rent, MyState::LEN, &ID.into());
-
-//     let my_state = MyState {
-//         is_initialized: true,
-//         owner: *PAYER.as_array(),
-//         state: State::Initialized,
-//         data: [1; 32],
-//         update_count: 0,
-//     };
-
-//     mystate_account.data = unsafe { to_bytes(&my_state).to_vec() };
-
-//     //Push the accounts in to the instruction_accounts vec!
-//     let ix_accounts = vec![
-//         AccountMeta::new(PAYER, true),
-//         AccountMeta::new(mystate_pda, false),
-//     ];
-
-//     // Create the instruction data
-//     let ix_data = UpdateMyStateIxData { data: [1; 32] };
-
-//     // Ix discriminator = 1
-//     let mut ser_ix_data = vec![1];
-
-//     // Serialize the instruction data
-//     ser_ix_data.extend_from_slice(unsafe { to_bytes(&ix_data) });
-
-//     // Create instruction
-//     let instruction1 = Instruction::new_with_bytes(PROGRAM, &ser_ix_data, ix_accounts);
-//     // Create tx_accounts vec
-//     let tx_accounts1 = &vec![
-//         (PAYER, payer_account.clone()),
-//         (mystate_pda, mystate_account.clone()),
-//     ];
-
-//     MolluskComputeUnitBencher::new(mollusk)
-//         .bench(("InitializeMyState", &instruction0, tx_accounts0))
-//         .bench(("UpdateMyState", &instruction1, tx_accounts1))
-//         .must_pass(true)
-//         .out_dir("benches/")
-//         .execute();
-// }
-
-fn main() {}
+//! Runs the same scenarios the native builtin is charged for against this
+//! program's BPF build, and prepends the compute units, next to the builtin's
+//! documented cost, to the run history in `benches/compute_units.md`.
+//!
+//! Build the program first with `cargo build-sbf`, then `cargo bench`. The
+//! ELF is looked up in `SBF_OUT_DIR`, or `target/deploy` of the workspace.
+
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use mollusk_svm::{program::loader_keys::LOADER_V3, Mollusk};
+use mollusk_svm_bencher::get_solana_version;
+use pinocchio_stake_interface::client::{self, stake_state_to_data};
+use solana_pinocchio_starter::{
+    helpers::create_with_seed,
+    instruction::StakeInstruction,
+    state::{Authorized, Lockup, Meta, StakeAuthorize, StakeStateV2},
+};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    system_program,
+};
+
+const PROGRAM: Pubkey = Pubkey::new_from_array(solana_pinocchio_starter::ID);
+
+/// What the native stake program charges for any of its instructions:
+/// `DEFAULT_COMPUTE_UNITS` in `solana_stake_program::stake_instruction`
+/// (1.18), which `declare_process_instruction!` consumes up front whatever
+/// the instruction goes on to do. It is one constant for the program, not a
+/// table per instruction, so Split and SetLockup are charged the same; the
+/// cost model's builtin costs (`solana-builtins-default-costs` in agave 2.x)
+/// take the stake program's entry from the same constant.
+const BUILTIN_COMPUTE_UNITS: u64 = 750;
+
+const STAKE_RENT_EXEMPT_RESERVE: u64 = 2_282_880;
+const STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + 10 * LAMPORTS_PER_SOL;
+
+const SOURCE: [u8; 32] = [1; 32];
+const DESTINATION: [u8; 32] = [2; 32];
+const STAKER: [u8; 32] = [3; 32];
+const WITHDRAWER: [u8; 32] = [4; 32];
+const BASE: [u8; 32] = [5; 32];
+const SEED_OWNER: [u8; 32] = [6; 32];
+const SEED: &str = "bench";
+
+struct Scenario {
+    name: &'static str,
+    instruction: Instruction,
+    accounts: Vec<(Pubkey, Account)>,
+}
+
+fn initialized_meta(staker: [u8; 32], withdrawer: [u8; 32]) -> Meta {
+    let mut meta = Meta {
+        rent_exempt_reserve: STAKE_RENT_EXEMPT_RESERVE.to_le_bytes(),
+        ..Meta::default()
+    };
+    meta.authorized.staker = staker;
+    meta.authorized.withdrawer = withdrawer;
+    meta
+}
+
+fn stake_account(lamports: u64, state: StakeStateV2) -> Account {
+    Account {
+        lamports,
+        data: stake_state_to_data(&state).to_vec(),
+        owner: PROGRAM,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn initialized_account(staker: [u8; 32], withdrawer: [u8; 32]) -> Account {
+    stake_account(
+        STAKE_LAMPORTS,
+        StakeStateV2::Initialized(initialized_meta(staker, withdrawer)),
+    )
+}
+
+fn wallet() -> Account {
+    Account::new(LAMPORTS_PER_SOL, 0, &system_program::ID)
+}
+
+fn amount_data(instruction: StakeInstruction, lamports: u64) -> Vec<u8> {
+    let mut data = vec![instruction as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    data
+}
+
+fn from_client(instruction: client::Instruction) -> Instruction {
+    Instruction {
+        program_id: Pubkey::new_from_array(instruction.program_id),
+        accounts: instruction
+            .accounts
+            .into_iter()
+            .map(|meta| AccountMeta {
+                pubkey: Pubkey::new_from_array(meta.pubkey),
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: instruction.data,
+    }
+}
+
+fn scenarios(mollusk: &Mollusk) -> Vec<Scenario> {
+    let [source, destination, staker, withdrawer, base] =
+        [SOURCE, DESTINATION, STAKER, WITHDRAWER, BASE].map(Pubkey::new_from_array);
+    let seed_authority = create_with_seed(&BASE, SEED.as_bytes(), &SEED_OWNER).unwrap();
+    let extra_signers: Vec<(Pubkey, Account)> = (0..15u8)
+        .map(|i| (Pubkey::new_from_array([100 + i; 32]), wallet()))
+        .collect();
+
+    vec![
+        Scenario {
+            name: "Initialize",
+            instruction: from_client(client::initialize(
+                &SOURCE,
+                &Authorized {
+                    staker: STAKER,
+                    withdrawer: WITHDRAWER,
+                },
+                &Lockup::default(),
+            )),
+            accounts: vec![
+                (
+                    source,
+                    stake_account(STAKE_LAMPORTS, StakeStateV2::Uninitialized),
+                ),
+                mollusk.sysvars.keyed_account_for_rent_sysvar(),
+            ],
+        },
+        Scenario {
+            name: "Split",
+            instruction: from_client(client::split(
+                &SOURCE,
+                &STAKER,
+                LAMPORTS_PER_SOL,
+                &DESTINATION,
+            )),
+            accounts: vec![
+                (source, initialized_account(STAKER, WITHDRAWER)),
+                (
+                    destination,
+                    stake_account(STAKE_RENT_EXEMPT_RESERVE, StakeStateV2::Uninitialized),
+                ),
+                (staker, wallet()),
+            ],
+        },
+        Scenario {
+            name: "Withdraw",
+            instruction: Instruction::new_with_bytes(
+                PROGRAM,
+                &amount_data(StakeInstruction::Withdraw, LAMPORTS_PER_SOL),
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+                    AccountMeta::new_readonly(solana_sdk::sysvar::stake_history::ID, false),
+                    AccountMeta::new_readonly(withdrawer, true),
+                ],
+            ),
+            accounts: vec![
+                (source, initialized_account(STAKER, WITHDRAWER)),
+                (destination, wallet()),
+                mollusk.sysvars.keyed_account_for_clock_sysvar(),
+                mollusk.sysvars.keyed_account_for_stake_history_sysvar(),
+                (withdrawer, wallet()),
+            ],
+        },
+        Scenario {
+            // closes the account, so its state is written as well as read
+            name: "Withdraw all",
+            instruction: Instruction::new_with_bytes(
+                PROGRAM,
+                &amount_data(StakeInstruction::Withdraw, STAKE_LAMPORTS),
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+                    AccountMeta::new_readonly(solana_sdk::sysvar::stake_history::ID, false),
+                    AccountMeta::new_readonly(withdrawer, true),
+                ],
+            ),
+            accounts: vec![
+                (source, initialized_account(STAKER, WITHDRAWER)),
+                (destination, wallet()),
+                mollusk.sysvars.keyed_account_for_clock_sysvar(),
+                mollusk.sysvars.keyed_account_for_stake_history_sysvar(),
+                (withdrawer, wallet()),
+            ],
+        },
+        Scenario {
+            name: "SetLockup",
+            instruction: Instruction::new_with_bytes(
+                PROGRAM,
+                &[StakeInstruction::SetLockup as u8, 0, 0, 0],
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new_readonly(withdrawer, true),
+                ],
+            ),
+            accounts: vec![
+                (source, initialized_account(STAKER, WITHDRAWER)),
+                (withdrawer, wallet()),
+            ],
+        },
+        Scenario {
+            // every signer's key is compared to the withdrawer's, so this is
+            // the row `MEMCMP_SYSCALL_THRESHOLD` moves most
+            name: "SetLockup, 16 signers",
+            instruction: Instruction::new_with_bytes(
+                PROGRAM,
+                &[StakeInstruction::SetLockup as u8, 0, 0, 0],
+                [AccountMeta::new(source, false)]
+                    .into_iter()
+                    .chain(
+                        extra_signers
+                            .iter()
+                            .map(|(key, _)| AccountMeta::new_readonly(*key, true)),
+                    )
+                    .chain([AccountMeta::new_readonly(withdrawer, true)])
+                    .collect(),
+            ),
+            accounts: [(source, initialized_account(STAKER, WITHDRAWER))]
+                .into_iter()
+                .chain(extra_signers.iter().cloned())
+                .chain([(withdrawer, wallet())])
+                .collect(),
+        },
+        Scenario {
+            name: "MoveLamports",
+            instruction: Instruction::new_with_bytes(
+                PROGRAM,
+                &amount_data(StakeInstruction::MoveLamports, LAMPORTS_PER_SOL),
+                vec![
+                    AccountMeta::new(source, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new_readonly(staker, true),
+                ],
+            ),
+            accounts: vec![
+                (source, initialized_account(STAKER, WITHDRAWER)),
+                (destination, initialized_account(STAKER, WITHDRAWER)),
+                (staker, wallet()),
+            ],
+        },
+        Scenario {
+            name: "AuthorizeWithSeed",
+            instruction: from_client(client::authorize_with_seed(
+                &SOURCE,
+                &BASE,
+                SEED,
+                &SEED_OWNER,
+                &[9; 32],
+                StakeAuthorize::Staker,
+                None,
+            )),
+            accounts: vec![
+                (source, initialized_account(seed_authority, WITHDRAWER)),
+                (base, wallet()),
+                mollusk.sysvars.keyed_account_for_clock_sysvar(),
+            ],
+        },
+    ]
+}
+
+fn program_elf() -> Option<Vec<u8>> {
+    let out_dir = std::env::var_os("SBF_OUT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../target/deploy"));
+    std::fs::read(out_dir.join("solana_pinocchio_starter.so")).ok()
+}
+
+/// The CUs of each scenario in the newest table of `history`, to report how
+/// a run moved them.
+fn last_run(history: &str) -> Vec<(&str, u64)> {
+    history
+        .lines()
+        .skip_while(|line| !line.starts_with("| Name "))
+        .skip(2)
+        .take_while(|line| line.starts_with('|'))
+        .filter_map(|line| {
+            let mut cells = line.split('|').skip(1).map(str::trim);
+            Some((cells.next()?, cells.next()?.parse().ok()?))
+        })
+        .collect()
+}
+
+fn report(results: &[(&str, u64)], last_run: &[(&str, u64)]) -> String {
+    let generated = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    let mut report = String::new();
+    writeln!(report, "#### Generated at unix time {generated}\n").unwrap();
+    writeln!(report, "Solana CLI Version: {}\n", get_solana_version()).unwrap();
+    writeln!(
+        report,
+        "Builtin costs are the flat {BUILTIN_COMPUTE_UNITS} CUs the native stake program is charged per instruction, its `DEFAULT_COMPUTE_UNITS`.\n"
+    )
+    .unwrap();
+    writeln!(report, "| Name | CUs | Delta | Builtin CUs | Ratio |").unwrap();
+    writeln!(report, "| ---- | --- | ----- | ----------- | ----- |").unwrap();
+    for (name, compute_units) in results {
+        let delta = match last_run.iter().find(|(last_name, _)| last_name == name) {
+            Some((_, last)) if last == compute_units => "--".to_string(),
+            Some((_, last)) => format!("{:+}", *compute_units as i64 - *last as i64),
+            None => "- new -".to_string(),
+        };
+        writeln!(
+            report,
+            "| {name} | {compute_units} | {delta} | {BUILTIN_COMPUTE_UNITS} | {:.2} |",
+            *compute_units as f64 / BUILTIN_COMPUTE_UNITS as f64
+        )
+        .unwrap();
+    }
+    writeln!(report).unwrap();
+    report
+}
+
+fn main() {
+    let Some(elf) = program_elf() else {
+        eprintln!("solana_pinocchio_starter.so not found, run `cargo build-sbf` first");
+        return;
+    };
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_program_with_elf_and_loader(&PROGRAM, &elf, &LOADER_V3);
+
+    let results: Vec<(&str, u64)> = scenarios(&mollusk)
+        .iter()
+        .map(|scenario| {
+            let result = mollusk.process_instruction(&scenario.instruction, &scenario.accounts);
+            assert!(
+                !result.program_result.is_err(),
+                "{} failed: {:?}",
+                scenario.name,
+                result.program_result
+            );
+            (scenario.name, result.compute_units_consumed)
+        })
+        .collect();
+
+    // Newest run first, as `mollusk-svm-bencher` keeps its history, with the
+    // delta from the run before it, so a change's cost is the top two tables.
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("benches/compute_units.md");
+    let history = std::fs::read_to_string(&path).unwrap_or_default();
+    let report = report(&results, &last_run(&history));
+    std::fs::write(&path, format!("{report}{history}")).unwrap();
+    println!("{report}");
+}
@@ -0,0 +1,103 @@
+"""Executes the probes of `src/lib.rs`, from the BPF assembly rustc emits for
+them, on equal inputs and on inputs differing in their first byte, and
+prints the compute units each takes as tables for `../mem_syscalls.md`.
+
+Instructions cost one CU each, as the SBF meter charges them, and the mem
+syscalls what agave 2.2's `mem_op_consume` does: the larger of
+`mem_op_base_cost` (10) and the length over `cpi_bytes_per_unit` (250). The
+trailing `exit` is left out, the probes being inlined in the program.
+
+    python3 count.py target/bpfel-unknown-none/release/deps/mem_syscalls_probe-*.s
+"""
+import re, sys
+
+src = open(sys.argv[1]).read()
+funcs = {}
+for m in re.finditer(r'^(\w+):\n(.*?)^\.Lfunc_end', src, re.S | re.M):
+    lines = [l.strip() for l in m.group(2).splitlines()]
+    funcs[m.group(1)] = [l for l in lines if l and not l.startswith('.') and not l.startswith('#')]
+
+SIZE = {'8': 1, '16': 2, '32': 4, '64': 8}
+
+def run(body, n, differ, tag=None):
+    labels = {}
+    code = []
+    for l in body:
+        if l.endswith(':'):
+            labels[l[:-1]] = len(code)
+        else:
+            code.append(l)
+    mem = {}
+    a, b, stack = 0x10000, 0x20000, 0x90000
+    for i in range(n):
+        mem[a + i] = i & 0xff
+        mem[b + i] = i & 0xff
+    if differ:
+        mem[a] ^= 1
+    if tag is not None:
+        for i in range(200):
+            mem[a + i] = 0
+        for i, byte in enumerate(tag.to_bytes(4, 'little')):
+            mem[a + i] = byte
+    reg = {f'r{i}': 0 for i in range(11)}
+    reg['r1'], reg['r2'], reg['r10'] = a, b, stack
+    def load(addr, size):
+        return int.from_bytes(bytes(mem.get(addr + i, 0) for i in range(size)), 'little')
+    def store(addr, size, v):
+        for i, byte in enumerate((v & ((1 << 8 * size) - 1)).to_bytes(size, 'little')):
+            mem[addr + i] = byte
+    def val(x):
+        return reg[x] if x in reg else int(x, 0)
+    pc, cu = 0, 0
+    while True:
+        ins = code[pc]
+        pc += 1
+        if ins == 'exit':
+            return cu
+        cu += 1
+        if m := re.fullmatch(r'(r\d+) = \*\(u(\d+) \*\)\((r\d+) ([+-]) (\d+)\)', ins):
+            off = int(m[5]) * (1 if m[4] == '+' else -1)
+            reg[m[1]] = load(reg[m[3]] + off, int(m[2]) // 8)
+        elif m := re.fullmatch(r'\*\(u(\d+) \*\)\((r\d+) ([+-]) (\d+)\) = (\w+)', ins):
+            off = int(m[4]) * (1 if m[3] == '+' else -1)
+            store(reg[m[2]] + off, int(m[1]) // 8, val(m[5]))
+        elif m := re.fullmatch(r'(r\d+) = (-?\w+)', ins):
+            reg[m[1]] = val(m[2]) & (2**64 - 1)
+        elif m := re.fullmatch(r'(r\d+) (\+|<<|>>|\||&|\^)= (-?\w+)', ins):
+            x, y = reg[m[1]], val(m[3])
+            ops = {'+': lambda: x + y, '<<': lambda: x << y, '>>': lambda: x >> y,
+                   '|': lambda: x | y, '&': lambda: x & y, '^': lambda: x ^ y}
+            reg[m[1]] = ops[m[2]]() & (2**64 - 1)
+        elif m := re.fullmatch(r'if (r\d+) (==|!=|s?[<>]=?) (-?\w+) goto (\w+)', ins):
+            x, y = reg[m[1]], val(m[3]) & (2**64 - 1)
+            if m[2].startswith('s'):
+                x, y = [v - 2**64 if v >= 2**63 else v for v in (x, y)]
+            if {'==': x == y, '!=': x != y, '>': x > y, '>=': x >= y, '<': x < y,
+                '<=': x <= y}[m[2].lstrip('s')]:
+                pc = labels[m[4]]
+        elif m := re.fullmatch(r'goto (\w+)', ins):
+            pc = labels[m[1]]
+        elif m := re.fullmatch(r'call (\w+)', ins):
+            length = reg['r3']
+            cu += max(10, length // 250)
+            if m[1] == 'sol_memcmp_':
+                differs = any(load(reg['r1'] + i, 1) != load(reg['r2'] + i, 1) for i in range(length))
+                store(reg['r4'], 4, 1 if differs else 0)
+            else:
+                for i in range(length):
+                    store(reg['r1'] + i, 1, load(reg['r2'] + i, 1))
+        else:
+            raise SystemExit(f'unhandled: {ins}')
+
+print('| Bytes | Compare, compiled (equal / first byte differs) | Compare, sol_memcmp | Copy, compiled | Copy, sol_memcpy |')
+print('| ----- | --- | --- | --- | --- |')
+for n in [8, 16, 24, 32, 40, 48, 56, 64, 96, 128, 200]:
+    cmp = funcs[f'cmp_{n}']
+    print(f'| {n} | {run(cmp, n, False)} / {run(cmp, n, True)} | {run(funcs[f"sys_cmp_{n}"], n, False)} '
+          f'| {run(funcs[f"copy_{n}"], n, False)} | {run(funcs[f"sys_copy_{n}"], n, False)} |')
+
+print()
+print('| State written | write_to | write_stake_state |')
+print('| ------------- | -------- | ----------------- |')
+for tag, name in [(0, 'Uninitialized'), (1, 'Initialized'), (2, 'Stake')]:
+    print(f'| {name} | {run(funcs["write_to"], 200, False, tag)} | {run(funcs["write_stake_state"], 200, False, tag)} |')
@@ -0,0 +1,97 @@
+//! The compares and copies `helpers::memory` chooses between, each as plain
+//! ops and through the mem syscalls, and the two ways of writing a stake
+//! state, compiled to BPF for `count.py` to execute.
+#![no_std]
+
+use pinocchio_stake_interface::state::StakeStateV2;
+
+extern "C" {
+    fn sol_memcmp_(s1: *const u8, s2: *const u8, n: u64, result: *mut i32);
+    fn sol_memcpy_(dst: *mut u8, src: *const u8, n: u64);
+}
+
+macro_rules! probes {
+    ($($n:literal => $cmp:ident, $copy:ident, $sys_cmp:ident, $sys_copy:ident;)*) => {$(
+        #[no_mangle]
+        pub fn $cmp(a: &[u8; $n], b: &[u8; $n]) -> bool {
+            a == b
+        }
+
+        #[no_mangle]
+        pub fn $copy(dst: &mut [u8; $n], src: &[u8; $n]) {
+            *dst = *src;
+        }
+
+        #[no_mangle]
+        pub fn $sys_cmp(a: &[u8; $n], b: &[u8; $n]) -> bool {
+            let mut result = 0;
+            unsafe { sol_memcmp_(a.as_ptr(), b.as_ptr(), $n, &mut result) };
+            result == 0
+        }
+
+        #[no_mangle]
+        pub fn $sys_copy(dst: &mut [u8; $n], src: &[u8; $n]) {
+            unsafe { sol_memcpy_(dst.as_mut_ptr(), src.as_ptr(), $n) }
+        }
+    )*};
+}
+
+probes! {
+    8 => cmp_8, copy_8, sys_cmp_8, sys_copy_8;
+    16 => cmp_16, copy_16, sys_cmp_16, sys_copy_16;
+    24 => cmp_24, copy_24, sys_cmp_24, sys_copy_24;
+    32 => cmp_32, copy_32, sys_cmp_32, sys_copy_32;
+    40 => cmp_40, copy_40, sys_cmp_40, sys_copy_40;
+    48 => cmp_48, copy_48, sys_cmp_48, sys_copy_48;
+    56 => cmp_56, copy_56, sys_cmp_56, sys_copy_56;
+    64 => cmp_64, copy_64, sys_cmp_64, sys_copy_64;
+    96 => cmp_96, copy_96, sys_cmp_96, sys_copy_96;
+    128 => cmp_128, copy_128, sys_cmp_128, sys_copy_128;
+    200 => cmp_200, copy_200, sys_cmp_200, sys_copy_200;
+}
+
+/// Field by field, as the program wrote states before.
+#[no_mangle]
+pub fn write_to(state: &StakeStateV2, data: &mut [u8; 200]) {
+    state.write_to(data)
+}
+
+/// The program's `write_stake_state`, with `copy_bytes` on its on-chain
+/// path.
+#[no_mangle]
+pub fn write_stake_state(state: &StakeStateV2, data: &mut [u8; 200]) {
+    #[inline(always)]
+    fn prefix<const N: usize>(data: &mut [u8; 200], state: &StakeStateV2) {
+        let (data, _) = data.split_first_chunk_mut::<N>().unwrap();
+        let repr = unsafe { &*(state as *const StakeStateV2 as *const [u8; N]) };
+        if N >= 56 {
+            unsafe { sol_memcpy_(data.as_mut_ptr(), repr.as_ptr(), N as u64) }
+        } else {
+            *data = *repr;
+        }
+    }
+    match state {
+        StakeStateV2::Initialized(_) => prefix::<124>(data, state),
+        StakeStateV2::Stake(..) => prefix::<197>(data, state),
+        StakeStateV2::Uninitialized | StakeStateV2::RewardsPool => prefix::<4>(data, state),
+    }
+}
+
+// the interface links `alloc`, though nothing here allocates
+struct NoAlloc;
+
+unsafe impl core::alloc::GlobalAlloc for NoAlloc {
+    unsafe fn alloc(&self, _layout: core::alloc::Layout) -> *mut u8 {
+        core::ptr::null_mut()
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: core::alloc::Layout) {}
+}
+
+#[global_allocator]
+static ALLOCATOR: NoAlloc = NoAlloc;
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,74 @@
+//! Times the two ways a handler can validate and then rewrite a stake
+//! account's state: copying it out with `get_stake_state` and back with
+//! `set_stake_state` ("before", a borrow each), or through the one mutable
+//! borrow of `try_get_stake_state_mut` ("after"), as the handlers do since
+//! they were restructured. Results are recorded in
+//! `benches/stake_state_access.md`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::*;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pinocchio::pubkey::Pubkey;
+use solana_pinocchio_starter::state::{
+    get_stake_state, set_stake_state, try_get_stake_state_mut, Delegation, Stake, StakeFlags,
+    StakeStateV2,
+};
+
+const STAKE: Pubkey = [1; 32];
+const VOTE: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+
+fn states() -> [(&'static str, StakeStateV2, StakeStateV2); 2] {
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+    let stake = Stake {
+        delegation: Delegation::new(&VOTE, 1_000_000_000, 300u64.to_le_bytes()),
+        credits_observed: 7u64.to_le_bytes(),
+    };
+    [
+        // as DelegateStake leaves an Initialized account
+        (
+            "delegate",
+            StakeStateV2::Initialized(meta),
+            StakeStateV2::Stake(meta, stake, StakeFlags::empty()),
+        ),
+        // as a full Withdraw leaves a delegated one
+        (
+            "close",
+            StakeStateV2::Stake(meta, stake, StakeFlags::empty()),
+            StakeStateV2::Uninitialized,
+        ),
+    ]
+}
+
+fn bench_access(c: &mut Criterion) {
+    for (name, state, new_state) in states() {
+        let mut group = c.benchmark_group(name);
+        let inputs = [stake_account(STAKE, 2 * STAKE_RENT_EXEMPT_RESERVE, state).into()];
+        with_accounts(&inputs, |accounts| {
+            let stake_account_info = &accounts[0];
+
+            group.bench_function("before", |b| {
+                b.iter(|| {
+                    let stake_state = get_stake_state(black_box(stake_account_info)).unwrap();
+                    black_box(&stake_state);
+                    set_stake_state(stake_account_info, &new_state).unwrap();
+                })
+            });
+            group.bench_function("after", |b| {
+                b.iter(|| {
+                    let mut stake_state =
+                        try_get_stake_state_mut(black_box(stake_account_info)).unwrap();
+                    black_box(&*stake_state);
+                    *stake_state = new_state;
+                })
+            });
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_access);
+criterion_main!(benches);
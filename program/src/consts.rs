@@ -1,14 +1,12 @@
 use pinocchio::pubkey::Pubkey;
 use pinocchio_pubkey::pubkey;
 
+pub use pinocchio_stake_interface::consts::*;
+
 pub const MAX_SIGNERS: usize = 32;
-pub const FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL: bool = false;
-pub const PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH: Option<[u8; 8]> = Some((0u64).to_le_bytes());
-pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
 pub const SYSVAR: Pubkey = pubkey!("Sysvar1111111111111111111111111111111111111");
-pub const DEFAULT_WARMUP_COOLDOWN_RATE: f64 = 0.25;
-pub const NEW_WARMUP_COOLDOWN_RATE: f64 = 0.09;
-pub const CLOCK_ID: Pubkey = pubkey!("SysvarC1ock11111111111111111111111111111111");
+pub const EPOCH_REWARDS_ID: Pubkey = pubkey!("SysvarEpochRewards1111111111111111111111111");
+pub const EPOCH_SCHEDULE_ID: Pubkey = pubkey!("SysvarEpochSchedu1e111111111111111111111111");
 pub const VOTE_PROGRAM_ID: Pubkey = pubkey!("Vote111111111111111111111111111111111111111");
 
 // Maximum number of votes to keep around, tightly coupled with epoch_schedule::MINIMUM_SLOTS_PER_EPOCH
@@ -19,7 +17,7 @@ pub const INITIAL_LOCKOUT: usize = 2;
 pub const MAX_EPOCH_CREDITS_HISTORY: usize = 64;
 
 // Offset of VoteState::prior_voters, for determining initialization status without deserialization
-const DEFAULT_PRIOR_VOTERS_OFFSET: usize = 114;
+pub const DEFAULT_PRIOR_VOTERS_OFFSET: usize = 114;
 
 // Number of slots of grace period for which maximum vote credits are awarded - votes landing within this number of slots of the slot that is being voted on are awarded full credits.
 pub const VOTE_CREDITS_GRACE_SLOTS: u8 = 2;
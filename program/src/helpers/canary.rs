@@ -0,0 +1,34 @@
+//! Invariant checks for canary deployments, compiled in only with the
+//! `canary` feature. A violated invariant logs and aborts the transaction,
+//! so a staging cluster surfaces it instead of committing the state.
+
+use pinocchio::account_info::AccountInfo;
+
+/// Aborts if `$invariant` doesn't hold; a no-op unless the `canary` feature
+/// is on, in which case `$invariant` isn't evaluated at all.
+macro_rules! canary_assert {
+    ($invariant:expr, $message:literal) => {
+        #[cfg(feature = "canary")]
+        if !$invariant {
+            pinocchio::msg!(concat!("canary: ", $message));
+            panic!($message);
+        }
+    };
+}
+
+pub(crate) use canary_assert;
+
+/// Lamports held by `accounts`, each counted once however many times it is
+/// passed, which no stake instruction may change.
+pub fn total_lamports(accounts: &[AccountInfo]) -> u128 {
+    accounts
+        .iter()
+        .enumerate()
+        .filter(|(index, account)| {
+            !accounts[..*index]
+                .iter()
+                .any(|earlier| earlier.key() == account.key())
+        })
+        .map(|(_, account)| account.lamports() as u128)
+        .sum()
+}
@@ -0,0 +1,85 @@
+use pinocchio::{
+    memory::{sol_memcmp, sol_memcpy},
+    pubkey::Pubkey,
+};
+
+// Where the mem syscalls start to beat compiled code, from the BPF each
+// compiles to, executed in `benches/mem_syscalls.md`. A syscall costs 10 CU
+// plus setting up its arguments, 19 for a compare and 12 for a copy, while a
+// compiled compare takes 3 CU per 8 bytes and a copy 2. A compare that
+// matches passes 19 CU at 48 bytes, and one that differs early never does,
+// so pubkeys are compared by plain ops; a copy passes 12 at 56. Off-chain
+// the syscalls are no-ops, so plain ops are always used.
+pub const MEMCMP_SYSCALL_THRESHOLD: usize = 48;
+pub const MEMCPY_SYSCALL_THRESHOLD: usize = 56;
+
+/// Copies `src` into `dst`. Both lengths are fixed at compile time, so the
+/// threshold check folds away and nothing can panic.
+#[inline(always)]
+pub fn copy_bytes<const N: usize>(dst: &mut [u8; N], src: &[u8; N]) {
+    if cfg!(target_os = "solana") && N >= MEMCPY_SYSCALL_THRESHOLD {
+        // SAFETY: both arrays are `N` long and `dst` is borrowed mutably, so
+        // they can't overlap
+        unsafe { sol_memcpy(dst, src, N) }
+    } else {
+        *dst = *src;
+    }
+}
+
+#[inline(always)]
+pub fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    if cfg!(target_os = "solana") && a.len() >= MEMCMP_SYSCALL_THRESHOLD {
+        // SAFETY: both slices are `a.len()` long
+        unsafe { sol_memcmp(a, b, a.len()) == 0 }
+    } else {
+        a == b
+    }
+}
+
+#[inline(always)]
+pub fn pubkey_eq(a: &Pubkey, b: &Pubkey) -> bool {
+    bytes_eq(a, b)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches_plain_ops_around_thresholds() {
+        let threshold = MEMCMP_SYSCALL_THRESHOLD;
+        for len in [0, 1, threshold - 1, threshold, threshold + 1, 200] {
+            let src: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut dst = src.clone();
+
+            assert!(bytes_eq(&src, &dst));
+            if len > 0 {
+                assert!(!bytes_eq(&src, &src[..len - 1]));
+                dst[len - 1] ^= 1;
+                assert!(!bytes_eq(&src, &dst));
+                dst[len - 1] ^= 1;
+                dst[0] ^= 1;
+                assert!(!bytes_eq(&src, &dst));
+            }
+        }
+
+        assert!(pubkey_eq(&[7; 32], &[7; 32]));
+        assert!(!pubkey_eq(&[7; 32], &[8; 32]));
+
+        fn copies<const N: usize>() {
+            let src: [u8; N] = core::array::from_fn(|i| i as u8);
+            let mut dst = [0xff; N];
+            copy_bytes(&mut dst, &src);
+            assert_eq!(dst, src);
+        }
+        // either side of `MEMCPY_SYSCALL_THRESHOLD`
+        copies::<1>();
+        copies::<55>();
+        copies::<56>();
+        copies::<57>();
+        copies::<200>();
+    }
+}
@@ -0,0 +1,96 @@
+use crate::instruction::StakeInstruction;
+
+use pinocchio_stake_interface::event::{
+    InstructionMetricsEvent, INSTRUCTION_METRICS_EVENT, INSTRUCTION_METRICS_EVENT_LEN,
+};
+
+/// The event tag of the record, which changes with its layout, so log
+/// consumers can tell records of different program versions apart.
+pub const METRICS_VERSION: u8 = INSTRUCTION_METRICS_EVENT;
+
+/// `version: u8 | instruction tag: u8 | lamports moved: u64 | compute units: u64`,
+/// integers little-endian, as `InstructionMetricsEvent` lays it out.
+pub const METRICS_RECORD_LEN: usize = INSTRUCTION_METRICS_EVENT_LEN;
+
+/// Counters for one processed instruction, emitted through `sol_log_data`
+/// when the `metrics` feature is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstructionMetrics {
+    pub instruction: StakeInstruction,
+    pub lamports: u64,
+    pub compute_units: u64,
+}
+
+impl InstructionMetrics {
+    pub fn to_bytes(&self) -> [u8; METRICS_RECORD_LEN] {
+        InstructionMetricsEvent {
+            instruction: self.instruction,
+            lamports: self.lamports,
+            compute_units: self.compute_units,
+        }
+        .encode()
+    }
+
+    pub fn log(&self) {
+        pinocchio::log::sol_log_data(&[&self.to_bytes()]);
+    }
+}
+
+/// Lamports an instruction moves, as given by its amount argument; zero for
+/// instructions that don't take one.
+pub fn lamports_moved(instruction: StakeInstruction, instruction_data: &[u8]) -> u64 {
+    match instruction {
+        StakeInstruction::Split
+        | StakeInstruction::Withdraw
+        | StakeInstruction::MoveStake
+        | StakeInstruction::MoveLamports => instruction_data
+            .get(..8)
+            .and_then(|amount| amount.try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Compute units left in the transaction; always zero off-chain.
+#[inline(always)]
+pub fn remaining_compute_units() -> u64 {
+    #[cfg(target_os = "solana")]
+    unsafe {
+        pinocchio::syscalls::sol_remaining_compute_units()
+    }
+
+    #[cfg(not(target_os = "solana"))]
+    0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_record_layout() {
+        let metrics = InstructionMetrics {
+            instruction: StakeInstruction::Withdraw,
+            lamports: 0x0102_0304_0506_0708,
+            compute_units: 1_500,
+        };
+        let record = metrics.to_bytes();
+        assert_eq!(record[0], METRICS_VERSION);
+        assert_eq!(record[1], 4);
+        assert_eq!(record[2..10], [8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(
+            u64::from_le_bytes(record[10..18].try_into().unwrap()),
+            1_500
+        );
+    }
+
+    #[test]
+    fn test_lamports_moved() {
+        let amount = 42u64.to_le_bytes();
+        assert_eq!(lamports_moved(StakeInstruction::Split, &amount), 42);
+        assert_eq!(lamports_moved(StakeInstruction::MoveLamports, &amount), 42);
+        assert_eq!(lamports_moved(StakeInstruction::Withdraw, &amount[..4]), 0);
+        assert_eq!(lamports_moved(StakeInstruction::SetLockup, &amount), 0);
+    }
+}
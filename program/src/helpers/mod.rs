@@ -1,7 +1,21 @@
-pub(crate) mod merge;
-pub(crate) use merge::*;
 use pinocchio::program_error::ProgramError;
 
-pub(crate) fn checked_add(a: u64, b: u64) -> Result<u64, ProgramError> {
+pub mod canary;
+pub mod memory;
+pub mod metrics;
+pub mod return_data;
+pub mod seed;
+pub mod summary;
+pub mod sysvar;
+
+pub use canary::*;
+pub use memory::*;
+pub use metrics::*;
+pub use return_data::*;
+pub use seed::*;
+pub use summary::*;
+pub use sysvar::*;
+
+pub fn checked_add(a: u64, b: u64) -> Result<u64, ProgramError> {
     a.checked_add(b).ok_or(ProgramError::InsufficientFunds)
 }
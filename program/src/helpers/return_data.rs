@@ -0,0 +1,50 @@
+/// Most accounts an instruction reports on: the two sides of a Split.
+pub const MAX_REPORTED_STAKES: usize = 2;
+
+/// Writes `stakes` into `data` as consecutive little-endian `u64`s, returning
+/// the number of bytes written. Stakes that don't fit are dropped.
+pub fn encode_stake_amounts(stakes: &[u64], data: &mut [u8]) -> usize {
+    let mut len = 0;
+    for (stake, chunk) in stakes.iter().zip(data.chunks_exact_mut(8)) {
+        chunk.copy_from_slice(&stake.to_le_bytes());
+        len += 8;
+    }
+    len
+}
+
+/// Sets the resulting delegated stake of the affected accounts, in
+/// instruction account order, as return data, so a calling program needn't
+/// reload them; a no-op unless the `extensions` feature is on.
+#[inline(always)]
+pub fn set_stake_return_data(_stakes: &[u64]) {
+    #[cfg(feature = "extensions")]
+    {
+        let mut data = [0u8; MAX_REPORTED_STAKES * 8];
+        let len = encode_stake_amounts(_stakes, &mut data);
+
+        #[cfg(target_os = "solana")]
+        pinocchio::cpi::set_return_data(&data[..len]);
+
+        //if not on chain use the mock
+        #[cfg(not(target_os = "solana"))]
+        crate::state::sol_set_return_data(data.as_ptr(), len as u64);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_stake_amounts() {
+        let mut data = [0xffu8; MAX_REPORTED_STAKES * 8];
+        assert_eq!(encode_stake_amounts(&[1, u64::MAX], &mut data), 16);
+        assert_eq!(data[..8], 1u64.to_le_bytes());
+        assert_eq!(data[8..], u64::MAX.to_le_bytes());
+
+        assert_eq!(encode_stake_amounts(&[7], &mut data), 8);
+        assert_eq!(data[..8], 7u64.to_le_bytes());
+
+        assert_eq!(encode_stake_amounts(&[1, 2, 3], &mut data), 16);
+    }
+}
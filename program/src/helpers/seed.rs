@@ -0,0 +1,91 @@
+use pinocchio::{
+    program_error::ProgramError,
+    pubkey::{Pubkey, MAX_SEED_LEN},
+};
+
+/// Suffix reserved for program derived addresses, which seed-derived
+/// addresses must not be able to collide with.
+pub const PDA_MARKER: &[u8; 21] = b"ProgramDerivedAddress";
+
+/// Address derived from `base`, as `Pubkey::create_with_seed` computes it:
+/// `sha256(base || seed || owner)`. Since only the holder of `base` can sign
+/// for it, it works as an authority even when `base` is itself a PDA signed
+/// for through `invoke_signed`.
+pub fn create_with_seed(
+    base: &Pubkey,
+    seed: &[u8],
+    owner: &Pubkey,
+) -> Result<Pubkey, ProgramError> {
+    if seed.len() > MAX_SEED_LEN {
+        return Err(ProgramError::MaxSeedLengthExceeded);
+    }
+    if owner.ends_with(PDA_MARKER) {
+        return Err(ProgramError::IllegalOwner);
+    }
+    Ok(sha256(&[base, seed, owner]))
+}
+
+fn sha256(vals: &[&[u8]]) -> Pubkey {
+    #[cfg(target_os = "solana")]
+    {
+        let mut hash = core::mem::MaybeUninit::<Pubkey>::uninit();
+        // SAFETY: the syscall reads `vals.len()` slices and always writes 32
+        // bytes to `hash`
+        unsafe {
+            pinocchio::syscalls::sol_sha256(
+                vals as *const _ as *const u8,
+                vals.len() as u64,
+                hash.as_mut_ptr() as *mut u8,
+            );
+            hash.assume_init()
+        }
+    }
+
+    #[cfg(not(target_os = "solana"))]
+    {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        for val in vals {
+            hasher.update(val);
+        }
+        hasher.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_create_with_seed() {
+        let base = solana_sdk::pubkey::Pubkey::new_unique();
+        let owner = solana_sdk::pubkey::Pubkey::new_unique();
+        for seed in ["", "stake-0", &"s".repeat(MAX_SEED_LEN)] {
+            assert_eq!(
+                create_with_seed(&base.to_bytes(), seed.as_bytes(), &owner.to_bytes()),
+                Ok(
+                    solana_sdk::pubkey::Pubkey::create_with_seed(&base, seed, &owner)
+                        .unwrap()
+                        .to_bytes()
+                )
+            );
+        }
+
+        assert_eq!(
+            create_with_seed(
+                &base.to_bytes(),
+                &[b's'; MAX_SEED_LEN + 1],
+                &owner.to_bytes()
+            ),
+            Err(ProgramError::MaxSeedLengthExceeded)
+        );
+
+        let mut pda_owner = [7u8; 32];
+        pda_owner[32 - PDA_MARKER.len()..].copy_from_slice(PDA_MARKER);
+        assert_eq!(
+            create_with_seed(&base.to_bytes(), b"stake-0", &pda_owner),
+            Err(ProgramError::IllegalOwner)
+        );
+    }
+}
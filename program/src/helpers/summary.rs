@@ -0,0 +1,156 @@
+use core::fmt;
+
+use pinocchio::pubkey::Pubkey;
+
+use crate::state::StakeStateV2;
+
+/// Number of leading key bytes shown, enough to tell accounts apart in logs.
+const KEY_PREFIX_LEN: usize = 4;
+
+/// Longest line `log_stake_state` emits.
+#[cfg(any(feature = "debug", test))]
+const MAX_LINE_LEN: usize = 160;
+
+/// Compact single-line rendering of a stake account, e.g.
+/// `Stake staker=0a0b0c0d withdrawer=01020304 stake=1000 activation=300 deactivation=max`.
+pub struct StakeStateSummary<'a>(pub &'a StakeStateV2);
+
+struct KeyPrefix<'a>(&'a Pubkey);
+
+impl fmt::Display for KeyPrefix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0[..KEY_PREFIX_LEN] {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+struct EpochField(u64);
+
+impl fmt::Display for EpochField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == u64::MAX {
+            f.write_str("max")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+impl fmt::Display for StakeStateSummary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            StakeStateV2::Uninitialized => f.write_str("Uninitialized"),
+            StakeStateV2::RewardsPool => f.write_str("RewardsPool"),
+            StakeStateV2::Initialized(meta) => write!(
+                f,
+                "Initialized staker={} withdrawer={}",
+                KeyPrefix(&meta.authorized.staker),
+                KeyPrefix(&meta.authorized.withdrawer),
+            ),
+            StakeStateV2::Stake(meta, stake, _stake_flags) => write!(
+                f,
+                "Stake staker={} withdrawer={} stake={} activation={} deactivation={}",
+                KeyPrefix(&meta.authorized.staker),
+                KeyPrefix(&meta.authorized.withdrawer),
+                u64::from_le_bytes(stake.delegation.stake),
+                EpochField(stake.delegation.activation_epoch()),
+                EpochField(stake.delegation.deactivation_epoch()),
+            ),
+        }
+    }
+}
+
+/// Fixed-size buffer so the summary can be formatted without an allocator.
+#[cfg(feature = "debug")]
+struct LineBuffer {
+    buf: [u8; MAX_LINE_LEN],
+    len: usize,
+}
+
+#[cfg(feature = "debug")]
+impl fmt::Write for LineBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len.checked_add(s.len()).ok_or(fmt::Error)?;
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(fmt::Error)?
+            .copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Logs the summary of `stake_state`; a no-op unless the `debug` feature is on.
+#[inline(always)]
+pub fn log_stake_state(_stake_state: &StakeStateV2) {
+    #[cfg(feature = "debug")]
+    {
+        use core::fmt::Write;
+
+        let mut line = LineBuffer {
+            buf: [0; MAX_LINE_LEN],
+            len: 0,
+        };
+        // the summary always fits, a truncated line is still worth logging
+        let _ = write!(line, "{}", StakeStateSummary(_stake_state));
+        // only whole `&str`s are ever written, so this is valid utf8
+        pinocchio::log::sol_log(unsafe { core::str::from_utf8_unchecked(&line.buf[..line.len]) });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::format;
+
+    use super::{StakeStateSummary, MAX_LINE_LEN};
+    use crate::state::{Delegation, Meta, Stake, StakeFlags, StakeStateV2};
+
+    fn meta() -> Meta {
+        let mut meta = Meta::default();
+        meta.authorized.staker = [0xab; 32];
+        meta.authorized.withdrawer = [1; 32];
+        meta
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(
+            format!("{}", StakeStateSummary(&StakeStateV2::Uninitialized)),
+            "Uninitialized"
+        );
+        assert_eq!(
+            format!("{}", StakeStateSummary(&StakeStateV2::Initialized(meta()))),
+            "Initialized staker=abababab withdrawer=01010101"
+        );
+
+        let mut stake = Stake {
+            delegation: Delegation::new(&[7; 32], 1_000, 300u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        let state = StakeStateV2::Stake(meta(), stake, StakeFlags::empty());
+        assert_eq!(
+            format!("{}", StakeStateSummary(&state)),
+            "Stake staker=abababab withdrawer=01010101 stake=1000 activation=300 deactivation=max"
+        );
+
+        stake.delegation.set_deactivation_epoch(310);
+        let state = StakeStateV2::Stake(meta(), stake, StakeFlags::empty());
+        assert_eq!(
+            format!("{}", StakeStateSummary(&state)),
+            "Stake staker=abababab withdrawer=01010101 stake=1000 activation=300 deactivation=310"
+        );
+    }
+
+    #[test]
+    fn test_longest_summary_fits_log_buffer() {
+        let mut stake = Stake {
+            delegation: Delegation::new(&[7; 32], u64::MAX, (u64::MAX - 1).to_le_bytes()),
+            ..Stake::default()
+        };
+        stake.delegation.set_deactivation_epoch(u64::MAX - 1);
+        let state = StakeStateV2::Stake(meta(), stake, StakeFlags::empty());
+        assert!(format!("{}", StakeStateSummary(&state)).len() <= MAX_LINE_LEN);
+    }
+}
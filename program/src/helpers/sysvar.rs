@@ -0,0 +1,206 @@
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, SUCCESS};
+
+use crate::state::UNSUPPORTED_SYSVAR;
+
+/// What `sol_get_sysvar` returns when the bytes asked for run past the end
+/// of the sysvar.
+pub const OFFSET_LENGTH_EXCEEDS_SYSVAR: u64 = 1;
+
+/// What `sol_get_sysvar` returns for an id that isn't a sysvar it serves.
+pub const SYSVAR_NOT_FOUND: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysvarError {
+    /// The bytes asked for don't all lie within the sysvar.
+    OffsetOutOfRange,
+    /// The runtime doesn't serve this sysvar through `sol_get_sysvar`.
+    UnsupportedSysvar,
+    /// Any other value the syscall returned.
+    Syscall(u64),
+}
+
+impl From<SysvarError> for ProgramError {
+    fn from(e: SysvarError) -> Self {
+        match e {
+            SysvarError::OffsetOutOfRange => ProgramError::InvalidArgument,
+            SysvarError::UnsupportedSysvar => ProgramError::UnsupportedSysvar,
+            SysvarError::Syscall(result) => result.into(),
+        }
+    }
+}
+
+impl core::fmt::Display for SysvarError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::OffsetOutOfRange => f.write_str("offset and length exceed the sysvar"),
+            Self::UnsupportedSysvar => f.write_str("sysvar not supported"),
+            Self::Syscall(result) => write!(f, "sol_get_sysvar returned {result}"),
+        }
+    }
+}
+
+/// Fills `dst` with the bytes of sysvar `sysvar_id` starting at `offset`,
+/// with a single `sol_get_sysvar` call.
+pub fn get_sysvar_slice(
+    dst: &mut [u8],
+    sysvar_id: &Pubkey,
+    offset: u64,
+) -> Result<(), SysvarError> {
+    let length = dst.len() as u64;
+    // the runtime rejects these too, but only after charging for the call
+    if offset.checked_add(length).is_none() {
+        return Err(SysvarError::OffsetOutOfRange);
+    }
+
+    let sysvar_id = sysvar_id as *const _ as *const u8;
+    let var_addr = dst as *mut _ as *mut u8;
+
+    //if on Solana call the actual syscall
+    #[cfg(target_os = "solana")]
+    let result =
+        unsafe { pinocchio::syscalls::sol_get_sysvar(sysvar_id, var_addr, offset, length) };
+
+    //if not on chain use the mock
+    #[cfg(not(target_os = "solana"))]
+    let result = crate::state::sol_get_sysvar(sysvar_id, var_addr, offset, length);
+
+    match result {
+        SUCCESS => Ok(()),
+        OFFSET_LENGTH_EXCEEDS_SYSVAR => Err(SysvarError::OffsetOutOfRange),
+        SYSVAR_NOT_FOUND | UNSUPPORTED_SYSVAR => Err(SysvarError::UnsupportedSysvar),
+        result => Err(SysvarError::Syscall(result)),
+    }
+}
+
+/// Reads `length` bytes of sysvar `sysvar_id` from `offset`, `CHUNK` bytes
+/// at a time through one stack buffer, handing each chunk and its offset in
+/// the sysvar to `f`. The last chunk may be shorter.
+///
+/// Stops at the first error, from the syscall or from `f`; chunks before it
+/// have already been handed over.
+pub fn get_sysvar_chunks<const CHUNK: usize, E: From<SysvarError>>(
+    sysvar_id: &Pubkey,
+    offset: u64,
+    length: u64,
+    mut f: impl FnMut(u64, &[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    const { assert!(CHUNK > 0) };
+    let end = offset
+        .checked_add(length)
+        .ok_or(SysvarError::OffsetOutOfRange)?;
+
+    let mut buffer = [0u8; CHUNK];
+    let mut chunk_offset = offset;
+    while chunk_offset < end {
+        let len = (end - chunk_offset).min(CHUNK as u64) as usize;
+        let chunk = &mut buffer[..len];
+        get_sysvar_slice(chunk, sysvar_id, chunk_offset)?;
+        f(chunk_offset, chunk)?;
+        chunk_offset += len as u64;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{mock_get_sysvar_syscall, take_mock_get_sysvar_calls};
+
+    const SYSVAR: Pubkey = [1; 32];
+
+    #[test]
+    fn test_get_sysvar_slice() {
+        mock_get_sysvar_syscall(&[1, 2, 3, 4, 5]);
+        take_mock_get_sysvar_calls();
+
+        let mut dst = [0; 3];
+        assert_eq!(get_sysvar_slice(&mut dst, &SYSVAR, 2), Ok(()));
+        assert_eq!(dst, [3, 4, 5]);
+        assert_eq!(get_sysvar_slice(&mut [], &SYSVAR, 5), Ok(()));
+
+        assert_eq!(
+            get_sysvar_slice(&mut dst, &SYSVAR, 3),
+            Err(SysvarError::OffsetOutOfRange)
+        );
+        assert_eq!(take_mock_get_sysvar_calls(), 3);
+
+        // past the end of the address space, without a syscall
+        assert_eq!(
+            get_sysvar_slice(&mut dst, &SYSVAR, u64::MAX - 1),
+            Err(SysvarError::OffsetOutOfRange)
+        );
+        assert_eq!(take_mock_get_sysvar_calls(), 0);
+    }
+
+    #[test]
+    fn test_sysvar_error_to_program_error() {
+        assert_eq!(
+            ProgramError::from(SysvarError::OffsetOutOfRange),
+            ProgramError::InvalidArgument
+        );
+        assert_eq!(
+            ProgramError::from(SysvarError::UnsupportedSysvar),
+            ProgramError::UnsupportedSysvar
+        );
+        assert_eq!(
+            ProgramError::from(SysvarError::Syscall(7)),
+            ProgramError::Custom(7)
+        );
+    }
+
+    #[test]
+    fn test_get_sysvar_chunks() {
+        let data: Vec<u8> = (0..100).collect();
+        mock_get_sysvar_syscall(&data);
+        take_mock_get_sysvar_calls();
+
+        let mut read = Vec::new();
+        let mut offsets = Vec::new();
+        let result = get_sysvar_chunks::<16, SysvarError>(&SYSVAR, 3, 50, |offset, chunk| {
+            offsets.push(offset);
+            read.extend_from_slice(chunk);
+            Ok(())
+        });
+        assert_eq!(result, Ok(()));
+        assert_eq!(read, data[3..53]);
+        assert_eq!(offsets, [3, 19, 35, 51]);
+        assert_eq!(take_mock_get_sysvar_calls(), 4);
+
+        // nothing to read, nothing to call
+        let result =
+            get_sysvar_chunks::<16, SysvarError>(&SYSVAR, 100, 0, |_, _| panic!("no chunks"));
+        assert_eq!(result, Ok(()));
+        assert_eq!(take_mock_get_sysvar_calls(), 0);
+    }
+
+    #[test]
+    fn test_get_sysvar_chunks_errors() {
+        let data: Vec<u8> = (0..100).collect();
+        mock_get_sysvar_syscall(&data);
+
+        // the chunks that fit are handed over before the one that doesn't
+        let mut chunks = 0;
+        let result = get_sysvar_chunks::<32, ProgramError>(&SYSVAR, 0, 101, |_, _| {
+            chunks += 1;
+            Ok(())
+        });
+        assert_eq!(result, Err(ProgramError::InvalidArgument));
+        assert_eq!(chunks, 3);
+
+        // the callback can stop the read
+        take_mock_get_sysvar_calls();
+        let result = get_sysvar_chunks::<32, ProgramError>(&SYSVAR, 0, 100, |offset, _| {
+            if offset > 0 {
+                return Err(ProgramError::Custom(0));
+            }
+            Ok(())
+        });
+        assert_eq!(result, Err(ProgramError::Custom(0)));
+        assert_eq!(take_mock_get_sysvar_calls(), 2);
+
+        assert_eq!(
+            get_sysvar_chunks::<32, SysvarError>(&SYSVAR, u64::MAX, 1, |_, _| Ok(())),
+            Err(SysvarError::OffsetOutOfRange)
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use pinocchio::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
+};
+
+use crate::{
+    consts::MAX_SIGNERS,
+    instruction::AuthorizeArgs,
+    state::{clock_from_account_info, collect_signers, do_authorize},
+};
+
+pub fn process_authorize(accounts: &[AccountInfo], args: AuthorizeArgs) -> ProgramResult {
+    let mut signers = [Pubkey::default(); MAX_SIGNERS];
+    let signers_len = collect_signers(accounts, &mut signers)?;
+
+    // native asserts: 3 accounts (1 sysvar)
+    let [stake_account_info, clock_info, _stake_or_withdraw_authority_info, rest @ ..] = accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // other accounts
+    let option_lockup_authority_info = rest.first();
+
+    let clock = clock_from_account_info(clock_info)?;
+
+    // native takes the custodian whether it signed or not: a withdrawer
+    // change under lockup fails with `CustodianMissing` without one, and with
+    // `CustodianSignatureMissing` when it is passed unsigned
+    let custodian = option_lockup_authority_info.map(|a| a.key());
+
+    // `get_stake_state()` is called unconditionally, which checks owner
+    do_authorize(
+        stake_account_info,
+        &signers[..signers_len],
+        &args.new_authorized_pubkey,
+        args.stake_authorize,
+        custodian,
+        &clock,
+    )
+}
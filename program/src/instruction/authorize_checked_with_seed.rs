@@ -0,0 +1,49 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+
+use crate::{
+    helpers::create_with_seed,
+    instruction::AuthorizeCheckedWithSeedArgs,
+    state::{add_signer, clock_from_account_info, collect_signers_checked, do_authorize},
+};
+
+pub fn process_authorize_checked_with_seed(
+    accounts: &[AccountInfo],
+    authorize_args: AuthorizeCheckedWithSeedArgs,
+) -> ProgramResult {
+    // native asserts: 4 accounts (1 sysvar)
+    let [stake_account_info, stake_or_withdraw_authority_base_info, clock_info, new_stake_or_withdraw_authority_info, remaining @ ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    let clock = clock_from_account_info(clock_info)?;
+
+    // other accounts
+    let option_lockup_authority_info = remaining.first();
+
+    // the new authority must sign, as must a custodian passed along
+    let (mut signers, custodian, mut signers_count) = collect_signers_checked(
+        Some(new_stake_or_withdraw_authority_info),
+        option_lockup_authority_info,
+    )?;
+
+    // as `process_authorize_with_seed`, the base may be a PDA
+    if stake_or_withdraw_authority_base_info.is_signer() {
+        let derived_key = create_with_seed(
+            stake_or_withdraw_authority_base_info.key(),
+            authorize_args.authority_seed.as_bytes(),
+            &authorize_args.authority_owner,
+        )?;
+        add_signer(&mut signers, &mut signers_count, &derived_key)?;
+    }
+
+    do_authorize(
+        stake_account_info,
+        &signers[..signers_count],
+        new_stake_or_withdraw_authority_info.key(),
+        authorize_args.stake_authorize,
+        custodian,
+        &clock,
+    )
+}
@@ -1,43 +1,16 @@
-use pinocchio::{
-    account_info::AccountInfo,
-    program_error::ProgramError,
-    pubkey::{self, Pubkey},
-    ProgramResult,
-};
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
 
-use crate::state::{
-    add_signer, clock_from_account_info, collect_signers_checked, do_authorize, StakeAuthorize,
+use crate::{
+    helpers::create_with_seed,
+    instruction::AuthorizeWithSeedArgs,
+    state::{add_signer, clock_from_account_info, collect_signers_checked, do_authorize},
 };
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct AuthorizeWithSeedArgs<'a> {
-    pub new_authorized_pubkey: Pubkey,
-    pub stake_authorize: StakeAuthorize,
-    pub authority_seed: &'a str,
-    pub authority_owner: Pubkey,
-}
-
-#[repr(C)]
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct AuthorizeCheckedWithSeedArgs<'a> {
-    pub authority_owner: Pubkey,
-    pub authority_seed_len: u32,
-    // 4 bytes padding
-    pub authority_seed: &'a str,
-    pub stake_authorize: StakeAuthorize,
-    // 7 bytes
-}
-
-// Borsh
-// 10 (4bytes)
-// abcdefghij (10 bytes)
-// 111..32 (32 bytes)
-// 1 (byte)
-
 pub fn process_authorize_with_seed(
     accounts: &[AccountInfo],
     authorize_args: AuthorizeWithSeedArgs,
 ) -> ProgramResult {
+    // native asserts: 3 accounts (1 sysvar)
     let [stake_account_info, stake_or_withdraw_authority_base_info, clock_info, remaining @ ..] =
         accounts
     else {
@@ -52,20 +25,20 @@ pub fn process_authorize_with_seed(
     let (mut signers, custodian, mut signers_count) =
         collect_signers_checked(None, option_lockup_authority_info)?;
 
-    let seeds = &[
-        stake_or_withdraw_authority_base_info.key().as_ref(),
-        authorize_args.authority_seed.as_bytes(),
-        authorize_args.authority_owner.as_ref(),
-    ];
-    let derived_key = pubkey::checked_create_program_address(seeds, &crate::id())?;
-
+    // the base may be a PDA the calling program signed for with
+    // `invoke_signed`, which is all the runtime needs to mark it a signer
     if stake_or_withdraw_authority_base_info.is_signer() {
+        let derived_key = create_with_seed(
+            stake_or_withdraw_authority_base_info.key(),
+            authorize_args.authority_seed.as_bytes(),
+            &authorize_args.authority_owner,
+        )?;
         add_signer(&mut signers, &mut signers_count, &derived_key)?;
     }
 
     do_authorize(
         stake_account_info,
-        &signers,
+        &signers[..signers_count],
         &authorize_args.new_authorized_pubkey,
         authorize_args.stake_authorize,
         custodian,
@@ -2,15 +2,19 @@ use pinocchio::{
     account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
 };
 
-use crate::state::{clock_from_account_info, collect_signers, do_authorize, StakeAuthorize};
+use crate::{
+    consts::MAX_SIGNERS,
+    state::{clock_from_account_info, collect_signers, do_authorize, StakeAuthorize},
+};
 
 pub fn process_authorize_checked(
     accounts: &[AccountInfo],
     authority_type: StakeAuthorize,
 ) -> ProgramResult {
-    let mut signers = [Pubkey::default(); 32];
-    let _signers_len = collect_signers(accounts, &mut signers)?;
+    let mut signers = [Pubkey::default(); MAX_SIGNERS];
+    let signers_len = collect_signers(accounts, &mut signers)?;
 
+    // native asserts: 4 accounts (1 sysvar)
     let [stake_account_info, clock_info, _old_stake_or_withdraw_authority_info, new_stake_or_withdraw_authority_info, rest @ ..] =
         accounts
     else {
@@ -30,14 +34,13 @@ pub fn process_authorize_checked(
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    let custodian = option_lockup_authority_info
-        .filter(|a| a.is_signer())
-        .map(|a| a.key());
+    // signed or not, as `process_authorize`
+    let custodian = option_lockup_authority_info.map(|a| a.key());
 
     // `get_stake_state()` is called unconditionally, which checks owner
     do_authorize(
         stake_account_info,
-        &signers,
+        &signers[..signers_len],
         new_stake_or_withdraw_authority_info.key(),
         authority_type,
         custodian,
@@ -1,90 +1,93 @@
 use pinocchio::{
-    account_info::AccountInfo,
-    program_error::ProgramError,
-    pubkey::Pubkey,
-    ProgramResult,
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
 };
-use crate::state::{
-    bytes_to_u64,
-    clock_from_account_info,
-    collect_signers,
-    get_stake_state,
-    get_vote_state,
-    new_stake,
-    next_account_info,
-    redelegate_stake,
-    set_stake_state,
-    to_program_error,
-    validate_delegated_amount,
-    StakeFlags,
-    StakeHistorySysvar,
-    StakeStateV2,
-    ValidatedDelegatedInfo,
+
+use crate::{
+    consts::MAX_SIGNERS,
+    state::{
+        check_stake_account_owner, check_sysvar_account, clock_from_account_info, collect_signers,
+        get_vote_credits, new_stake, redelegate_stake, stake_history, to_program_error,
+        try_get_stake_state_mut, validate_delegated_amount, StakeAuthorize, StakeFlags,
+        StakeHistoryAccountOrSyscall, StakeStateV2, SysvarReader, ValidatedDelegatedInfo,
+    },
 };
 
-pub fn process_delegate(accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
-    let mut signers = [Pubkey::default(); 32];
-    let _signers_len = collect_signers(accounts, &mut signers)?;
+pub fn process_delegate(accounts: &[AccountInfo]) -> ProgramResult {
+    let sysvars = StakeHistoryAccountOrSyscall::from_accounts(accounts)?;
+    process_delegate_with(accounts, &sysvars)
+}
 
-    // native accounts -- asserted
-    let accounts_info_iter = &mut accounts.iter();
-    let stake_account_info = next_account_info(accounts_info_iter)?;
-    let vote_account_info = next_account_info(accounts_info_iter)?;
-    let clock_info = next_account_info(accounts_info_iter)?;
-    let _stake_history_info = next_account_info(accounts_info_iter)?;
-    let _stake_config_info = next_account_info(accounts_info_iter)?;
+/// `process_delegate` with the stake history and cluster features read from
+/// `sysvars`.
+pub fn process_delegate_with<S: SysvarReader>(
+    accounts: &[AccountInfo],
+    sysvars: &S,
+) -> ProgramResult {
+    let mut signers = [Pubkey::default(); MAX_SIGNERS];
+    let signers_len = collect_signers(accounts, &mut signers)?;
+    let signers = &signers[..signers_len];
 
-    // for future refactors, after the bpf switchover we may assert them as well.
-    // other account info
-    // let _stake_authority_info = next_account_info(accounts_info_iter)?;
+    // native asserts: 5 accounts (2 sysvars + stake config)
+    let [stake_account_info, vote_account_info, clock_info, stake_history_info, _stake_config_info, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
 
+    // the stake account is checked before the vote account, as native does
+    check_stake_account_owner(stake_account_info)?;
     let clock = clock_from_account_info(clock_info)?;
-    let stake_history = &StakeHistorySysvar(bytes_to_u64(clock.epoch.to_le_bytes()));
-    let vote_state = get_vote_state(vote_account_info)?;
+    // the account's bytes are read by `process_delegate`, when it has any
+    check_sysvar_account(stake_history_info, &stake_history::ID)?;
+    let stake_history = &sysvars.stake_history(&clock);
+    let feature_set = sysvars.feature_set();
+    let credits = get_vote_credits(vote_account_info)?;
 
-    match *get_stake_state(stake_account_info)? {
-        crate::state::StakeStateV2::Initialized(meta) => {
+    // validated through the same borrow the new state is written through
+    let mut stake_state = try_get_stake_state_mut(stake_account_info)?;
+    match *stake_state {
+        StakeStateV2::Initialized(meta) => {
             meta.authorized
-                .check(&signers, crate::state::StakeAuthorize::Staker)
+                .check(signers, StakeAuthorize::Staker)
                 .map_err(to_program_error)?;
             let ValidatedDelegatedInfo { stake_amount } = validate_delegated_amount(
                 stake_account_info,
-                &meta
+                &meta,
+                feature_set.minimum_delegation(),
             )?;
             let stake = new_stake(
                 stake_amount,
                 vote_account_info.key(),
-                &vote_state,
-                clock.epoch.to_le_bytes()
+                credits,
+                clock.epoch.to_le_bytes(),
             );
-            set_stake_state(
-                stake_account_info,
-                &StakeStateV2::Stake(meta, stake, StakeFlags::empty())
-            )?;
+            *stake_state = StakeStateV2::Stake(meta, stake, StakeFlags::empty());
+            Ok(())
         }
-        crate::state::StakeStateV2::Stake(meta, mut stake, flags) => {
+        StakeStateV2::Stake(meta, mut stake, stake_flags) => {
             meta.authorized
-                .check(&signers, crate::state::StakeAuthorize::Staker)
+                .check(signers, StakeAuthorize::Staker)
                 .map_err(to_program_error)?;
             let ValidatedDelegatedInfo { stake_amount } = validate_delegated_amount(
                 stake_account_info,
-                &meta
+                &meta,
+                feature_set.minimum_delegation(),
             )?;
 
+            // still effective stake can only come back to the same voter in
+            // the epoch it was deactivated, see `revive_deactivating_stake`
             redelegate_stake(
                 &mut stake,
                 stake_amount,
                 vote_account_info.key(),
-                &vote_state,
+                credits,
                 clock.epoch.to_le_bytes(),
-                stake_history
+                stake_history,
+                feature_set.new_rate_activation_epoch(),
             )?;
-            set_stake_state(stake_account_info, &StakeStateV2::Stake(meta, stake, flags))?;
-        }
-        _ => {
-            return Err(ProgramError::InvalidAccountData);
+            *stake_state = StakeStateV2::Stake(meta, stake, stake_flags);
+            Ok(())
         }
+        _ => Err(ProgramError::InvalidAccountData),
     }
-
-    Ok(())
 }
@@ -0,0 +1,83 @@
+use pinocchio::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    sysvars::rent::{Rent, RENT_ID},
+    ProgramResult,
+};
+
+use crate::{
+    instruction::InitializeArgs,
+    state::{
+        check_stake_account_owner, check_sysvar_account, get_rent, try_get_stake_state_mut,
+        Authorized, Lockup, Meta, StakeStateV2,
+    },
+};
+
+pub fn process_initialize(accounts: &[AccountInfo], args: InitializeArgs) -> ProgramResult {
+    // native asserts: 2 accounts (1 sysvar)
+    let [stake_account_info, rent_info, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    check_stake_account_owner(stake_account_info)?;
+    // read through the syscall, the account is only there for old clients
+    check_sysvar_account(rent_info, &RENT_ID)?;
+    let rent = get_rent()?;
+
+    do_initialize(stake_account_info, args.authorized, args.lockup, &rent)
+}
+
+pub fn process_initialize_checked(accounts: &[AccountInfo]) -> ProgramResult {
+    // native asserts: 4 accounts (1 sysvar)
+    let [stake_account_info, rent_info, stake_authority_info, withdraw_authority_info, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    check_stake_account_owner(stake_account_info)?;
+    // native checks the signature before it looks at the rent account
+    if !withdraw_authority_info.is_signer() {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    check_sysvar_account(rent_info, &RENT_ID)?;
+    let rent = get_rent()?;
+
+    let authorized = Authorized {
+        staker: *stake_authority_info.key(),
+        withdrawer: *withdraw_authority_info.key(),
+    };
+    do_initialize(stake_account_info, authorized, Lockup::default(), &rent)
+}
+
+/// Initializes an uninitialized stake account of the right size, holding at
+/// least the rent exempt reserve, which is recorded in its meta so later
+/// instructions keep it there.
+pub fn do_initialize(
+    stake_account_info: &AccountInfo,
+    authorized: Authorized,
+    lockup: Lockup,
+    rent: &Rent,
+) -> ProgramResult {
+    if stake_account_info.data_len() != StakeStateV2::size_of() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let mut stake_state = try_get_stake_state_mut(stake_account_info)?;
+    let StakeStateV2::Uninitialized = *stake_state else {
+        return Err(ProgramError::InvalidAccountData);
+    };
+
+    let rent_exempt_reserve = rent.minimum_balance(stake_account_info.data_len());
+    if stake_account_info.lamports() < rent_exempt_reserve {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    *stake_state = StakeStateV2::Initialized(Meta {
+        rent_exempt_reserve: rent_exempt_reserve.to_le_bytes(),
+        authorized,
+        lockup,
+    });
+    Ok(())
+}
@@ -1,17 +1,26 @@
+#[cfg(feature = "extensions")]
+use crate::{helpers::set_stake_return_data, state::delegated_stake};
+use crate::helpers::pubkey_eq;
 use crate::state::{
-    clock_from_account_info, get_stake_state, relocate_lamports, set_stake_state, MergeKind,
-    StakeAuthorize, StakeHistorySysvar, StakeStateV2,
+    check_stake_account_owner, clock_from_account_info, collect_signers, relocate_lamports, try_get_stake_state_mut,
+    MergeKind, StakeAuthorize, StakeStateV2, SyscallSysvars, SysvarReader,
 };
 use pinocchio::{
     account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
 };
-use pinocchio_log::log;
 
 // const MAX_SIGNERS: usize = 32;
 use crate::consts::MAX_SIGNERS;
 
 pub fn process_merge(accounts: &[AccountInfo]) -> ProgramResult {
-    let signers_arr = [Pubkey::default(); MAX_SIGNERS];
+    process_merge_with(accounts, &SyscallSysvars)
+}
+
+/// `process_merge` with the stake history read from `sysvars`.
+pub fn process_merge_with<S: SysvarReader>(accounts: &[AccountInfo], sysvars: &S) -> ProgramResult {
+    let mut signers_arr = [Pubkey::default(); MAX_SIGNERS];
+    let signers_len = collect_signers(accounts, &mut signers_arr)?;
+    let signers = &signers_arr[..signers_len];
 
     // native asserts: 4 accounts (2 sysvars)
     // let destination_stake_account_info = next_account_info(account_info_iter)?;
@@ -19,54 +28,67 @@ pub fn process_merge(accounts: &[AccountInfo]) -> ProgramResult {
     // let clock_info = next_account_info(account_info_iter)?;
     // let _stake_history_info = next_account_info(account_info_iter)?;
 
-    let [destination_stake_account_info, source_stake_account_info, clock_info, _stake_history_info] =
+    let [destination_stake_account_info, source_stake_account_info, clock_info, _stake_history_info, ..] =
         accounts
     else {
         return Err(ProgramError::NotEnoughAccountKeys);
     };
 
-    // other accounts
+    // other accounts, the staker among them, are only read as signers
     // let _stake_authority_info = next_account_info(account_info_iter)?;
 
+    check_stake_account_owner(destination_stake_account_info)?;
     let clock = clock_from_account_info(clock_info)?;
-    let stake_history = &StakeHistorySysvar(clock.epoch);
+
+    // a spoofed source is refused before anything is read from either
+    // account, with the error native gives it
+    if !source_stake_account_info.is_owned_by(&crate::ID) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
 
     // check source stake account and destination stake account are not having same key
-    if source_stake_account_info.key() == destination_stake_account_info.key() {
+    if pubkey_eq(source_stake_account_info.key(), destination_stake_account_info.key()) {
         return Err(ProgramError::InvalidArgument);
     }
 
-    log!("Checking if destination stake is mergeable");
-    let destination_merge_kind = MergeKind::get_if_mergeable(
-        // MergeKind is a enum
-        &*get_stake_state(destination_stake_account_info)?,
+    // both states are classified through the borrows they are written through
+    #[cfg(feature = "logging")]
+    pinocchio::msg!("Checking if destination stake is mergeable");
+    let mut destination_state = try_get_stake_state_mut(destination_stake_account_info)?;
+    let destination_merge_kind = MergeKind::for_state(
+        &destination_state,
         destination_stake_account_info.lamports(),
         &clock,
-        stake_history,
+        sysvars,
     )?;
 
     // Authorized staker is allowed to split/merge accounts
     destination_merge_kind
         .meta() // implementation of state.rs
         .authorized
-        .check(&signers_arr, StakeAuthorize::Staker) // implementation of state.rs
+        .check(signers, StakeAuthorize::Staker) // implementation of state.rs
         .map_err(|_| ProgramError::MissingRequiredSignature)?;
 
-    log!("Checking if source stake is mergeable");
-    let source_merge_kind = MergeKind::get_if_mergeable(
-        &*get_stake_state(source_stake_account_info)?,
+    #[cfg(feature = "logging")]
+    pinocchio::msg!("Checking if source stake is mergeable");
+    let mut source_state = try_get_stake_state_mut(source_stake_account_info)?;
+    let source_merge_kind = MergeKind::for_state(
+        &source_state,
         source_stake_account_info.lamports(),
         &clock,
-        stake_history,
+        sysvars,
     )?;
 
-    log!("Merging stake accounts");
+    #[cfg(feature = "logging")]
+    pinocchio::msg!("Merging stake accounts");
     if let Some(merged_state) = destination_merge_kind.merge(source_merge_kind, &clock)? {
-        set_stake_state(destination_stake_account_info, &merged_state)?;
+        *destination_state = merged_state;
     }
+    #[cfg(feature = "extensions")]
+    set_stake_return_data(&[delegated_stake(&destination_state)]);
 
     // Source is about to be drained, deinitialize it's state
-    set_stake_state(source_stake_account_info, &StakeStateV2::Uninitialized)?;
+    *source_state = StakeStateV2::Uninitialized;
 
     // Drain the source stake account and transfer the lamports to the destination stake account
     relocate_lamports(
@@ -1,12 +1,21 @@
 use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
 
-use crate::{
-    helpers::MergeKind,
-    state::{move_stake_or_lamports_shared_checks, relocate_lamports},
+use crate::state::{
+    move_stake_or_lamports_shared_checks, relocate_lamports, MergeKind, SyscallSysvars,
+    SysvarReader,
 };
 
 pub fn process_move_lamports(accounts: &[AccountInfo], lamports: u64) -> ProgramResult {
-    if lamports <= 0 {
+    process_move_lamports_with(accounts, lamports, &SyscallSysvars)
+}
+
+/// `process_move_lamports` with the stake history read from `sysvars`.
+pub fn process_move_lamports_with<S: SysvarReader>(
+    accounts: &[AccountInfo],
+    lamports: u64,
+    sysvars: &S,
+) -> ProgramResult {
+    if lamports == 0 {
         return Err(ProgramError::InvalidArgument);
     }
     let [source_stake_account_info, destination_stake_account_info, stake_authority_info, _remaining @ ..] =
@@ -19,10 +28,11 @@ pub fn process_move_lamports(accounts: &[AccountInfo], lamports: u64) -> Program
         source_stake_account_info,
         destination_stake_account_info,
         stake_authority_info,
+        sysvars,
     )?;
 
     let source_free_lamports = match source_merge_kind {
-        MergeKind::FullyActive(source_meta, source_stake) => source_stake_account_info
+        MergeKind::FullyActive(source_meta, source_stake, _) => source_stake_account_info
             .lamports()
             .saturating_sub(u64::from_le_bytes(source_stake.delegation.stake))
             .saturating_sub(u64::from_le_bytes(source_meta.rent_exempt_reserve)),
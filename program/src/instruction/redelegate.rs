@@ -2,7 +2,7 @@ use pinocchio::{
     account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
 };
 
-use pinocchio::instruction::{Seed, Signer};
+use pinocchio_stake_interface::cpi::SignerSeeds;
 
 use pinocchio_token::{
     instructions::TransferChecked,
@@ -55,7 +55,7 @@ pub fn process_start_redelegation(accounts: &[AccountInfo], data: &[u8]) -> Prog
         return Err(ProgramError::InvalidArgument);
     }
 
-    redelegate_state.start_redelegation(&ix_data)
+    redelegate_state.start_redelegation(ix_data)
 }
 
 pub fn process_complete_redelegation(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
@@ -114,23 +114,21 @@ pub fn process_complete_redelegation(accounts: &[AccountInfo], data: &[u8]) -> P
         })
         .invoke()
     } else {
-        let bump = &[ix_data.bump];
-        let seeds = &[
-            Seed::from(RedelegateState::SEED.as_bytes()),
-            Seed::from(owner_acc.key().as_ref()),
-            Seed::from(state_acc.key().as_ref()),
-            Seed::from(bump),
-        ];
-        let signer = Signer::from(seeds);
-
-        (TransferChecked {
-            from: vault,
-            to: owner_ata,
-            mint: mint_to_stake,
-            authority: state_acc,
-            amount: vault_acc.amount() - stake_amount,
-            decimals: mint_state.decimals(),
+        let seeds = SignerSeeds::new(ix_data.bump)
+            .seed(RedelegateState::SEED.as_bytes())?
+            .seed(owner_acc.key())?
+            .seed(state_acc.key())?;
+
+        seeds.with_signer(|signer| {
+            (TransferChecked {
+                from: vault,
+                to: owner_ata,
+                mint: mint_to_stake,
+                authority: state_acc,
+                amount: vault_acc.amount() - stake_amount,
+                decimals: mint_state.decimals(),
+            })
+            .invoke_signed(&[signer])
         })
-        .invoke_signed(&[signer])
     }
 }
@@ -1,185 +1,66 @@
 use pinocchio::{
-    account_info::AccountInfo,
-    program_error::ProgramError,
-    pubkey::Pubkey,
-    sysvars::{clock::Clock, Sysvar},
-    ProgramResult,
+    account_info::AccountInfo, program_error::ProgramError, sysvars::clock::Clock, ProgramResult,
 };
 
 use crate::{
     error::to_program_error,
+    helpers::pubkey_eq,
+    instruction::LockupArgs,
     state::{
-        get_stake_state, try_get_stake_state_mut, Epoch, SetLockupSignerArgs, StakeStateV2,
-        UnixTimestamp,
+        get_clock, try_get_stake_state_mut, ClusterLockup, SetLockupSignerArgs, StakeStateMut,
+        StakeStateV2,
     },
 };
 
-#[cfg(not(test))]
-#[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct LockupArgs {
-    pub unix_timestamp: Option<UnixTimestamp>,
-    pub epoch: Option<Epoch>,
-    pub custodian: Option<Pubkey>,
-}
-
-#[cfg(test)]
-#[repr(C)]
-#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
-pub struct LockupArgs {
-    pub unix_timestamp: Option<UnixTimestamp>,
-    pub epoch: Option<Epoch>,
-    pub custodian: Option<Pubkey>,
-}
-
-impl LockupArgs {
-    pub fn from_data(data: &[u8]) -> Result<Self, ProgramError> {
-        match data.len() {
-            // all none: 1 + 1 + 1
-            3 => {
-                if (data[0] == 1) || (data[1] == 1) || (data[2] == 1) {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-                Ok(LockupArgs {
-                    unix_timestamp: None,
-                    epoch: None,
-                    custodian: None,
-                })
-            }
-            // (unix_timestamp - some, other - none) or (epoch - some, other none): 9 + 1 + 1
-            11 => {
-                if !(((data[0] == 1) && (data[9] == 0) && (data[10] == 0))
-                    || ((data[0] == 0) && (data[1] == 1) && (data[10] == 0)))
-                {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-                if data[0] == 1 {
-                    Ok(LockupArgs {
-                        unix_timestamp: Some(unsafe {
-                            *(data[1..=8].as_ptr() as *const UnixTimestamp)
-                        }),
-                        epoch: None,
-                        custodian: None,
-                    })
-                } else {
-                    Ok(LockupArgs {
-                        unix_timestamp: None,
-                        epoch: Some(unsafe { *(data[2..=9].as_ptr() as *const Epoch) }),
-                        custodian: None,
-                    })
-                }
-            }
-            // (unix_timestamp and epoch - some, custodian - none): 9 + 9 + 1
-            19 => {
-                if !((data[0] == 1) && (data[9] == 1) && (data[18] == 0)) {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-                Ok(LockupArgs {
-                    unix_timestamp: Some(unsafe {
-                        *(data[1..=8].as_ptr() as *const UnixTimestamp)
-                    }),
-                    epoch: Some(unsafe { *(data[10..=17].as_ptr() as *const Epoch) }),
-                    custodian: None,
-                })
-            }
-            // (custodian - some, other - none): 1 + 1 + 33
-            35 => {
-                if !((data[0] == 0) && (data[1] == 0) && (data[2] == 1)) {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-                Ok(LockupArgs {
-                    unix_timestamp: None,
-                    epoch: None,
-                    custodian: Some(unsafe { *(data[3..=34].as_ptr() as *const Pubkey) }),
-                })
-            }
-            // (custodian - some, either unix_timestamp or epoch - none): 9 + 1 + 33
-            43 => {
-                if !(((data[0] == 0) && (data[1] == 1) && (data[10] == 1))
-                    || ((data[0] == 1) && (data[9] == 0) && (data[10] == 1)))
-                {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-                if data[0] == 1 {
-                    Ok(LockupArgs {
-                        unix_timestamp: Some(unsafe {
-                            *(data[1..=8].as_ptr() as *const UnixTimestamp)
-                        }),
-                        epoch: None,
-                        custodian: Some(unsafe { *(data[11..=42].as_ptr() as *const Pubkey) }),
-                    })
-                } else {
-                    Ok(LockupArgs {
-                        unix_timestamp: None,
-                        epoch: Some(unsafe { *(data[2..=9].as_ptr() as *const Epoch) }),
-                        custodian: Some(unsafe { *(data[11..=42].as_ptr() as *const Pubkey) }),
-                    })
-                }
-            }
-            // all some: 9 + 9 + 33
-            51 => {
-                if !((data[0] == 1) && (data[9] == 1) && (data[18] == 1)) {
-                    return Err(ProgramError::InvalidInstructionData);
-                }
-                Ok(unsafe { *(data.as_ptr() as *const Self) })
-            }
-            _ => return Err(ProgramError::InvalidInstructionData),
-        }
-    }
-}
-
-pub fn process_set_lockup(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
-    let lockup_args = LockupArgs::from_data(data)?;
-
+pub fn process_set_lockup(accounts: &[AccountInfo], lockup_args: LockupArgs) -> ProgramResult {
     let [stake_account_info, _remaining @ ..] = accounts else {
         return Err(ProgramError::NotEnoughAccountKeys);
     };
 
-    let signer_args = get_set_lockup_signer_args(stake_account_info, accounts)?;
+    // the signers are checked against the same borrow the lockup is set through
+    let mut stake_account = try_get_stake_state_mut(stake_account_info)?;
+    let signer_args = get_set_lockup_signer_args(&stake_account, accounts)?;
 
-    let clock = Clock::get()?;
+    let clock = get_clock()?;
 
-    do_set_lookup(stake_account_info, &lockup_args, signer_args, &clock)?;
+    do_set_lookup(&mut stake_account, &lockup_args, signer_args, &clock)?;
 
     Ok(())
 }
 
 fn do_set_lookup(
-    stake_account_info: &AccountInfo,
+    stake_account: &mut StakeStateMut,
     lockup: &LockupArgs,
     signer_args: SetLockupSignerArgs,
     clock: &Clock,
 ) -> ProgramResult {
-    let mut stake_account: pinocchio::account_info::RefMut<'_, StakeStateV2> =
-        try_get_stake_state_mut(stake_account_info)?;
-    match *stake_account {
+    match **stake_account {
         StakeStateV2::Initialized(ref mut meta) => meta
-            .set_lockup(lockup, signer_args, clock)
+            .set_lockup::<ClusterLockup>(lockup, signer_args, clock)
             .map_err(to_program_error),
         StakeStateV2::Stake(ref mut meta, _stake, _stake_flags) => meta
-            .set_lockup(lockup, signer_args, clock)
+            .set_lockup::<ClusterLockup>(lockup, signer_args, clock)
             .map_err(to_program_error),
         _ => Err(ProgramError::InvalidAccountData),
     }
 }
 
 fn get_set_lockup_signer_args(
-    stake_account_info: &AccountInfo,
+    stake_account: &StakeStateV2,
     accounts: &[AccountInfo],
 ) -> Result<SetLockupSignerArgs, ProgramError> {
-    let stake_account: pinocchio::account_info::Ref<'_, StakeStateV2> =
-        get_stake_state(stake_account_info)?;
-
     let mut has_custodian_signer = false;
     let mut has_withdrawer_signer = false;
-    match *stake_account {
+    match stake_account {
         StakeStateV2::Initialized(ref meta) | StakeStateV2::Stake(ref meta, _, _) => {
             for account in accounts {
                 if account.is_signer() {
-                    if meta.lockup.custodian == *account.key() {
+                    if meta.lockup.has_custodian()
+                        && pubkey_eq(&meta.lockup.custodian, account.key())
+                    {
                         has_custodian_signer = true;
                     }
-                    if meta.authorized.withdrawer == *account.key() {
+                    if pubkey_eq(&meta.authorized.withdrawer, account.key()) {
                         has_withdrawer_signer = true;
                     }
                 }
@@ -194,74 +75,3 @@ fn get_set_lockup_signer_args(
         has_withdrawer_signer,
     })
 }
-
-#[cfg(test)]
-mod test {
-    use super::LockupArgs;
-    use bincode::serialize;
-
-    #[test]
-    fn test_instruction_data() {
-        let args_arr = [
-            LockupArgs {
-                unix_timestamp: None,
-                epoch: None,
-                custodian: None,
-            },
-            LockupArgs {
-                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
-                epoch: None,
-                custodian: None,
-            },
-            LockupArgs {
-                unix_timestamp: None,
-                epoch: Some(9464321479845648u64.to_le_bytes()),
-                custodian: None,
-            },
-            LockupArgs {
-                unix_timestamp: None,
-                epoch: None,
-                custodian: Some([
-                    13, 54, 98, 123, 59, 67, 165, 78, 03, 12, 23, 45, 67, 89, 01, 02, 03, 04, 05,
-                    06, 07, 08, 09, 10, 11, 12, 13, 14, 15, 16, 17, 18,
-                ]),
-            },
-            LockupArgs {
-                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
-                epoch: Some(9464321479845648u64.to_le_bytes()),
-                custodian: None,
-            },
-            LockupArgs {
-                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
-                epoch: None,
-                custodian: Some([
-                    13, 54, 98, 123, 59, 67, 165, 78, 03, 12, 23, 45, 67, 89, 01, 02, 03, 04, 05,
-                    06, 07, 08, 09, 10, 11, 12, 13, 14, 15, 16, 17, 18,
-                ]),
-            },
-            LockupArgs {
-                unix_timestamp: None,
-                epoch: Some(9464321479845648u64.to_le_bytes()),
-                custodian: Some([
-                    13, 54, 98, 123, 59, 67, 165, 78, 03, 12, 23, 45, 67, 89, 01, 02, 03, 04, 05,
-                    06, 07, 08, 09, 10, 11, 12, 13, 14, 15, 16, 17, 18,
-                ]),
-            },
-            LockupArgs {
-                unix_timestamp: Some(3609733389592650838i64.to_le_bytes()),
-                epoch: Some(9464321479845648u64.to_le_bytes()),
-                custodian: Some([
-                    13, 54, 98, 123, 59, 67, 165, 78, 03, 12, 23, 45, 67, 89, 01, 02, 03, 04, 05,
-                    06, 07, 08, 09, 10, 11, 12, 13, 14, 15, 16, 17, 18,
-                ]),
-            },
-        ];
-
-        for args in args_arr {
-            let data = serialize(&args).unwrap();
-
-            let args_new = LockupArgs::from_data(data.as_ref()).unwrap();
-            assert_eq!(args, args_new);
-        }
-    }
-}
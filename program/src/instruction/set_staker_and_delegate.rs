@@ -0,0 +1,39 @@
+use pinocchio::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
+};
+
+use crate::{
+    consts::MAX_SIGNERS,
+    instruction::process_delegate,
+    state::{clock_from_account_info, collect_signers, do_authorize, StakeAuthorize},
+};
+
+/// `DelegateStake` followed by `Authorize` of the staker to `new_staker`,
+/// each with its own checks, so onboarding a stake into a pool whose PDA
+/// takes over as staker needs one instruction rather than two transactions.
+pub fn process_set_staker_and_delegate(
+    accounts: &[AccountInfo],
+    new_staker: &Pubkey,
+) -> ProgramResult {
+    let [stake_account_info, _vote_account_info, clock_info, ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // delegated while the current staker still holds the authority, since
+    // the new one, a PDA, can't sign here
+    process_delegate(accounts)?;
+
+    let mut signers = [Pubkey::default(); MAX_SIGNERS];
+    let signers_len = collect_signers(accounts, &mut signers)?;
+    let clock = clock_from_account_info(clock_info)?;
+
+    // the staker is never under lockup, so no custodian
+    do_authorize(
+        stake_account_info,
+        &signers[..signers_len],
+        new_staker,
+        StakeAuthorize::Staker,
+        None,
+        &clock,
+    )
+}
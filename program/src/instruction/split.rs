@@ -1,17 +1,17 @@
 use crate::{
-    consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    consts::MAX_SIGNERS,
     error::StakeError,
+    helpers::set_stake_return_data,
     state::{
-        bytes_to_u64, get_minimum_delegation, relocate_lamports, to_program_error,
-        try_get_stake_state_mut, validate_split_amount, StakeAuthorize, StakeHistorySysvar,
-        StakeStateV2,
+        bytes_to_u64, check_stake_account_owner, delegated_stake, get_clock, get_rent, relocate_lamports,
+        split_stake_amounts, to_program_error, try_get_stake_state_mut, validate_split_amount,
+        StakeAuthorize, StakeHistoryAccountOrSyscall, StakeStateV2, SysvarReader,
     },
 };
 use pinocchio::{
     account_info::AccountInfo,
     program_error::ProgramError,
     pubkey::Pubkey,
-    sysvars::{clock::Clock, Sysvar},
     ProgramResult,
 };
 
@@ -24,16 +24,38 @@ use crate::state::utils::collect_signers;
 // in the future, we may decide to tighten the interface and break badly formed transactions
 
 pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramResult {
-    let mut signers_arr = [Pubkey::default(); 32];
-    let _signers = collect_signers(accounts, &mut signers_arr)?;
+    // native split takes no stake history account, but old clients pass one
+    let sysvars = StakeHistoryAccountOrSyscall::from_accounts(accounts)?;
+    process_split_with(accounts, split_lamports, &sysvars)
+}
+
+/// `process_split` with the stake history and cluster features read from
+/// `sysvars`.
+pub fn process_split_with<S: SysvarReader>(
+    accounts: &[AccountInfo],
+    split_lamports: u64,
+    sysvars: &S,
+) -> ProgramResult {
+    let mut signers_arr = [Pubkey::default(); MAX_SIGNERS];
+    let signers_len = collect_signers(accounts, &mut signers_arr)?;
+    let signers = &signers_arr[..signers_len];
 
     let [source_stake_account_info, destination_stake_account_info, _rest @ ..] = accounts else {
         return Err(ProgramError::NotEnoughAccountKeys);
     };
+    check_stake_account_owner(source_stake_account_info)?;
 
-    let clock = Clock::get()?;
-    let stake_history = &StakeHistorySysvar(clock.epoch);
+    let clock = get_clock()?;
+    let stake_history = &sysvars.stake_history(&clock);
+    let feature_set = sysvars.feature_set();
 
+    // a destination of another program is refused before its size, as
+    // native does, with the error it gives a spoofed program id
+    if !destination_stake_account_info.is_owned_by(&crate::ID) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    // exactly the stake state, as native: neither short, nor with trailing
+    // bytes some runtimes let an account be allocated with
     let destination_data_len = destination_stake_account_info.data_len();
     if destination_data_len != StakeStateV2::size_of() {
         return Err(ProgramError::InvalidAccountData);
@@ -46,10 +68,8 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
         return Err(ProgramError::InsufficientFunds);
     }
 
-    let mut source_stake_account: pinocchio::account_info::RefMut<'_, StakeStateV2> =
-        try_get_stake_state_mut(source_stake_account_info)?;
-    let mut dest_stake_account: pinocchio::account_info::RefMut<'_, StakeStateV2> =
-        try_get_stake_state_mut(destination_stake_account_info)?;
+    let mut source_stake_account = try_get_stake_state_mut(source_stake_account_info)?;
+    let mut dest_stake_account = try_get_stake_state_mut(destination_stake_account_info)?;
 
     if let StakeStateV2::Uninitialized = *dest_stake_account {
         // we can split into this
@@ -61,20 +81,19 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
         StakeStateV2::Stake(source_meta, mut source_stake, stake_flags) => {
             source_meta
                 .authorized
-                .check(&signers_arr, StakeAuthorize::Staker)
+                .check(signers, StakeAuthorize::Staker)
                 .map_err(to_program_error)?;
 
-            let minimum_delegation = get_minimum_delegation();
+            let minimum_delegation = feature_set.minimum_delegation();
 
             let status = source_stake.delegation.stake_activating_and_deactivating(
-                clock.epoch.to_be_bytes(),
+                clock.epoch.to_le_bytes(),
                 stake_history,
-                PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+                feature_set.new_rate_activation_epoch(),
             );
 
             let is_active = bytes_to_u64(status.effective) > 0;
 
-            // NOTE this function also internally summons Rent via syscall
             let validated_split_info = validate_split_amount(
                 source_lamport_balance,
                 destination_lamport_balance,
@@ -83,48 +102,24 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
                 destination_data_len,
                 minimum_delegation,
                 is_active,
+                &get_rent()?,
             )?;
 
-            // split the stake, subtract rent_exempt_balance unless
-            // the destination account already has those lamports
-            // in place.
-            // this means that the new stake account will have a stake equivalent to
-            // lamports minus rent_exempt_reserve if it starts out with a zero balance
-            let (remaining_stake_delta, split_stake_amount) =
-                if validated_split_info.source_remaining_balance == 0 {
-                    // If split amount equals the full source stake (as implied by 0
-                    // source_remaining_balance), the new split stake must equal the same
-                    // amount, regardless of any current lamport balance in the split account.
-                    // Since split accounts retain the state of their source account, this
-                    // prevents any magic activation of stake by prefunding the split account.
-                    //
-                    // The new split stake also needs to ignore any positive delta between the
-                    // original rent_exempt_reserve and the split_rent_exempt_reserve, in order
-                    // to prevent magic activation of stake by splitting between accounts of
-                    // different sizes.
-                    let remaining_stake_delta = split_lamports
-                        .saturating_sub(u64::from_le_bytes(source_meta.rent_exempt_reserve));
-                    (remaining_stake_delta, remaining_stake_delta)
-                } else {
-                    // Otherwise, the new split stake should reflect the entire split
-                    // requested, less any lamports needed to cover the
-                    // split_rent_exempt_reserve.
-                    if u64::from_le_bytes(source_stake.delegation.stake)
-                        .saturating_sub(split_lamports)
-                        < minimum_delegation
-                    {
-                        return Err(StakeError::InsufficientDelegation.into());
-                    }
-
-                    (
-                        split_lamports,
-                        split_lamports.saturating_sub(
-                            validated_split_info
-                                .destination_rent_exempt_reserve
-                                .saturating_sub(destination_lamport_balance),
-                        ),
-                    )
-                };
+            // native checks the delegation left behind before the one split off
+            if validated_split_info.source_remaining_balance != 0
+                && u64::from_le_bytes(source_stake.delegation.stake)
+                    .saturating_sub(split_lamports)
+                    < minimum_delegation
+            {
+                return Err(StakeError::InsufficientDelegation.into());
+            }
+
+            let (remaining_stake_delta, split_stake_amount) = split_stake_amounts(
+                split_lamports,
+                u64::from_le_bytes(source_meta.rent_exempt_reserve),
+                destination_lamport_balance,
+                &validated_split_info,
+            );
 
             if split_stake_amount < minimum_delegation {
                 return Err(StakeError::InsufficientDelegation.into());
@@ -136,7 +131,7 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
             let mut destination_meta = source_meta;
             destination_meta.rent_exempt_reserve = validated_split_info
                 .destination_rent_exempt_reserve
-                .to_be_bytes();
+                .to_le_bytes();
 
             *source_stake_account = StakeStateV2::Stake(source_meta, source_stake, stake_flags);
 
@@ -146,10 +141,9 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
         StakeStateV2::Initialized(source_meta) => {
             source_meta
                 .authorized
-                .check(&signers_arr, StakeAuthorize::Staker)
+                .check(signers, StakeAuthorize::Staker)
                 .map_err(to_program_error)?;
 
-            // NOTE this function also internally summons Rent via syscall
             let validated_split_info = validate_split_amount(
                 source_lamport_balance,
                 destination_lamport_balance,
@@ -158,6 +152,7 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
                 destination_data_len,
                 0,     // additional_required_lamports
                 false, // is_active
+                &get_rent()?,
             )?;
 
             let mut destination_meta = source_meta;
@@ -177,6 +172,10 @@ pub fn process_split(accounts: &[AccountInfo], split_lamports: u64) -> ProgramRe
     if split_lamports == source_lamport_balance {
         *source_stake_account = StakeStateV2::Uninitialized;
     }
+    set_stake_return_data(&[
+        delegated_stake(&source_stake_account),
+        delegated_stake(&dest_stake_account),
+    ]);
     relocate_lamports(
         source_stake_account_info,
         destination_stake_account_info,
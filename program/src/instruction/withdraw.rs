@@ -0,0 +1,124 @@
+use pinocchio::{account_info::AccountInfo, program_error::ProgramError, ProgramResult};
+
+use crate::{
+    error::StakeError,
+    helpers::pubkey_eq,
+    state::{
+        check_sysvar_account, clock_from_account_info, collect_signers_checked,
+        get_withdraw_reserve, relocate_lamports, stake_history, to_program_error,
+        try_get_stake_state_mut, validate_withdraw_amount, ClusterLockup, Lockup, LockupEvaluator,
+        StakeAuthorize, StakeHistoryAccountOrSyscall, StakeStateV2, SysvarReader,
+    },
+};
+
+pub fn process_withdraw(accounts: &[AccountInfo], withdraw_lamports: u64) -> ProgramResult {
+    let sysvars = StakeHistoryAccountOrSyscall::from_accounts(accounts)?;
+    process_withdraw_with(accounts, withdraw_lamports, &sysvars)
+}
+
+/// `process_withdraw` with the stake history and cluster features read from
+/// `sysvars`.
+pub fn process_withdraw_with<S: SysvarReader>(
+    accounts: &[AccountInfo],
+    withdraw_lamports: u64,
+    sysvars: &S,
+) -> ProgramResult {
+    // the entrypoint refuses most instructions during distribution already;
+    // this keeps the processor safe to call on its own
+    if sysvars.epoch_rewards_active() {
+        return Err(StakeError::EpochRewardsActive.into());
+    }
+
+    // native asserts: 5 accounts (2 sysvars)
+    let [source_stake_account_info, destination_info, clock_info, stake_history_info, withdraw_authority_info, remaining @ ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+
+    // other accounts
+    let option_lockup_authority_info = remaining.first();
+
+    let clock = clock_from_account_info(clock_info)?;
+    // the account's bytes are read by `process_withdraw`, when it has any
+    check_sysvar_account(stake_history_info, &stake_history::ID)?;
+    let stake_history = &sysvars.stake_history(&clock);
+
+    let (signers, custodian, signers_count) =
+        collect_signers_checked(Some(withdraw_authority_info), option_lockup_authority_info)?;
+    let signers = &signers[..signers_count];
+
+    // held until the end, so a full withdrawal resets the state it validated
+    let mut stake_state = try_get_stake_state_mut(source_stake_account_info)?;
+    let (lockup, reserve, is_staked, vesting_lamports) = match *stake_state {
+        StakeStateV2::Stake(meta, stake, _stake_flags) => {
+            meta.authorized
+                .check(signers, StakeAuthorize::Withdrawer)
+                .map_err(to_program_error)?;
+            let (reserve, is_staked) = get_withdraw_reserve(
+                &meta,
+                &stake,
+                clock.epoch,
+                stake_history,
+                sysvars.feature_set().new_rate_activation_epoch(),
+            )?;
+            // what a vesting lockup vests from, fixed until a split or merge
+            let vesting_lamports = meta
+                .rent_exempt_reserve()
+                .saturating_add(u64::from_le_bytes(stake.delegation.stake));
+            (meta.lockup, reserve, is_staked, Some(vesting_lamports))
+        }
+        StakeStateV2::Initialized(meta) => {
+            meta.authorized
+                .check(signers, StakeAuthorize::Withdrawer)
+                .map_err(to_program_error)?;
+            // stake accounts must have a balance >= rent_exempt_reserve
+            (meta.lockup, meta.rent_exempt_reserve(), false, None)
+        }
+        StakeStateV2::Uninitialized => {
+            if !signers
+                .iter()
+                .any(|signer| pubkey_eq(signer, source_stake_account_info.key()))
+            {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            (Lockup::default(), 0, false, None) // no lockup, no restrictions
+        }
+        _ => return Err(ProgramError::InvalidAccountData),
+    };
+
+    let stake_account_lamports = source_stake_account_info.lamports();
+
+    // verify that lockup has expired or that the withdrawal is signed by the
+    // custodian both epoch and unix_timestamp must have passed
+    ClusterLockup::from(lockup).check_withdraw(
+        &clock,
+        custodian,
+        withdraw_lamports,
+        stake_account_lamports,
+        vesting_lamports,
+    )?;
+
+    validate_withdraw_amount(
+        stake_account_lamports,
+        withdraw_lamports,
+        reserve,
+        is_staked,
+    )?;
+
+    if withdraw_lamports == stake_account_lamports {
+        // deinitialize state upon zero balance
+        *stake_state = StakeStateV2::Uninitialized;
+    }
+
+    // like native, the destination can be any account the runtime lets the
+    // program credit, whatever its owner or balance: gaining lamports never
+    // breaks its rent exemption
+    relocate_lamports(
+        source_stake_account_info,
+        destination_info,
+        withdraw_lamports,
+    )?;
+
+    Ok(())
+}
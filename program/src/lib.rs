@@ -1,16 +1,16 @@
 #![cfg_attr(not(test), no_std)]
 
 #[cfg(not(feature = "no-entrypoint"))]
-mod entrypoint;
+pub mod entrypoint;
 
 #[cfg(feature = "std")]
 extern crate std;
 extern crate alloc;
 
 pub mod consts;
-pub mod error;
 pub mod helpers;
 pub mod instruction;
+pub mod processor;
 pub mod state;
 
-pinocchio_pubkey::declare_id!("Stake11111111111111111111111111111111111111");
+pub use pinocchio_stake_interface::{check_id, error, id, ID};
@@ -0,0 +1,222 @@
+//! Instruction dispatch, outside the `no-entrypoint` gate so that hosts
+//! linking the program as a library, the tests among them, go through the
+//! same checks the entrypoint does.
+
+use crate::{
+    error::StakeError,
+    instruction::{self, StakeInstruction},
+    state::epoch_rewards_active,
+};
+use pinocchio::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
+};
+
+#[inline(always)]
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    // without a discriminant there is nothing to dispatch, fail before any
+    // other work
+    let Some((ix_disc, instruction_data)) = instruction_data.split_first() else {
+        return Err(ProgramError::InvalidInstructionData);
+    };
+
+    // convenience so we can safely use id() everywhere
+    if *program_id != crate::ID {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Second variant, test CUs usage
+    // let (ix_disc, instruction_data) = instruction_data
+    //     .split_at_checked(4)
+    //     .ok_or(ProgramError::InvalidInstructionData)?;
+
+    #[cfg(feature = "extensions")]
+    if let Ok(extension) = instruction::ExtensionInstruction::try_from(ix_disc) {
+        if epoch_rewards_active() {
+            return Err(StakeError::EpochRewardsActive.into());
+        }
+        return match extension {
+            instruction::ExtensionInstruction::SetStakerAndDelegate => {
+                #[cfg(feature = "logging")]
+                pinocchio::msg!("Instruction: SetStakerAndDelegate");
+
+                let new_staker = instruction::new_staker_from_data(instruction_data)?;
+                extension.check_account_count(accounts)?;
+                instruction::process_set_staker_and_delegate(accounts, &new_staker)
+            }
+        };
+    }
+
+    let instruction = StakeInstruction::try_from(ix_disc)?;
+
+    if epoch_rewards_active() && !matches!(instruction, StakeInstruction::GetMinimumDelegation) {
+        return Err(StakeError::EpochRewardsActive.into());
+    }
+
+    #[cfg(feature = "metrics")]
+    let compute_units_at_start = crate::helpers::remaining_compute_units();
+    #[cfg(feature = "canary")]
+    let lamports_at_start = crate::helpers::total_lamports(accounts);
+
+    let result = match instruction {
+        StakeInstruction::Initialize => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: Initialize");
+
+            let args = instruction::InitializeArgs::from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_initialize(accounts, args)
+        }
+        StakeInstruction::Authorize => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: Authorize");
+
+            let args = instruction::AuthorizeArgs::from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_authorize(accounts, args)
+        }
+        StakeInstruction::DelegateStake => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: DelegateStake");
+
+            instruction::check_trailing_bytes(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_delegate(accounts)
+        }
+        StakeInstruction::Split => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: Split");
+
+            let lamports = instruction::lamports_from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_split(accounts, lamports)
+        }
+        StakeInstruction::Withdraw => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: Withdraw");
+
+            let lamports = instruction::lamports_from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_withdraw(accounts, lamports)
+        }
+        StakeInstruction::Deactivate => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: Deactivate");
+
+            instruction.check_account_count(accounts)?;
+            todo!()
+        }
+        StakeInstruction::SetLockup => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: SetLockup");
+
+            let lockup_args = instruction::LockupArgs::from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_set_lockup(accounts, lockup_args)
+        }
+        StakeInstruction::Merge => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: Merge");
+
+            instruction::check_trailing_bytes(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_merge(accounts)
+        }
+        StakeInstruction::AuthorizeWithSeed => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: AuthorizeWithSeed");
+
+            let args = instruction::AuthorizeWithSeedArgs::from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_authorize_with_seed(accounts, args)
+        }
+        StakeInstruction::InitializeChecked => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: InitializeChecked");
+
+            instruction::check_trailing_bytes(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_initialize_checked(accounts)
+        }
+        StakeInstruction::AuthorizeChecked => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: AuthorizeChecked");
+
+            let stake_authorize = instruction::stake_authorize_arg_from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_authorize_checked(accounts, stake_authorize)
+        }
+        StakeInstruction::AuthorizeCheckedWithSeed => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: AuthorizeCheckedWithSeed");
+
+            let args = instruction::AuthorizeCheckedWithSeedArgs::from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_authorize_checked_with_seed(accounts, args)
+        }
+        StakeInstruction::SetLockupChecked => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: SetLockupChecked");
+
+            instruction.check_account_count(accounts)?;
+            todo!()
+        }
+        StakeInstruction::GetMinimumDelegation => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: GetMinimumDelegation");
+
+            instruction.check_account_count(accounts)?;
+            todo!()
+        }
+        StakeInstruction::DeactivateDelinquent => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: DeactivateDelinquent");
+
+            instruction.check_account_count(accounts)?;
+            todo!()
+        }
+        #[allow(deprecated)]
+        StakeInstruction::Redelegate => Err(ProgramError::InvalidInstructionData),
+        // NOTE we assume the program is going live after `move_stake_and_move_lamports_ixs` is
+        // activated
+        StakeInstruction::MoveStake => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: MoveStake");
+
+            instruction.check_account_count(accounts)?;
+            todo!()
+        }
+        StakeInstruction::MoveLamports => {
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Instruction: MoveLamports");
+
+            let lamports = instruction::lamports_from_data(instruction_data)?;
+            instruction.check_account_count(accounts)?;
+            instruction::process_move_lamports(accounts, lamports)
+        }
+    };
+
+    #[cfg(feature = "metrics")]
+    if result.is_ok() {
+        crate::helpers::InstructionMetrics {
+            instruction,
+            lamports: crate::helpers::lamports_moved(instruction, instruction_data),
+            compute_units: compute_units_at_start
+                .saturating_sub(crate::helpers::remaining_compute_units()),
+        }
+        .log();
+    }
+
+    #[cfg(feature = "canary")]
+    if result.is_ok() {
+        crate::helpers::canary_assert!(
+            crate::helpers::total_lamports(accounts) == lamports_at_start,
+            "lamports created or destroyed"
+        );
+    }
+
+    result
+}
@@ -0,0 +1,33 @@
+use pinocchio::program_error::ProgramError;
+
+use crate::{consts::EPOCH_REWARDS_ID, state::get_sysvar};
+
+/// Bincode offset of `EpochRewards::active`, past
+/// distribution_starting_block_height, num_partitions, parent_blockhash,
+/// total_points, total_rewards and distributed_rewards. It is the last byte of
+/// the sysvar.
+pub const EPOCH_REWARDS_ACTIVE_OFFSET: u64 = 8 + 8 + 32 + 16 + 8 + 8;
+
+/// Reads fields of the epoch rewards sysvar through `sol_get_sysvar`, one at
+/// a time, the way `StakeHistorySysvar` reads single entries, so the rest of
+/// the sysvar is never copied.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EpochRewardsSysvar;
+
+impl EpochRewardsSysvar {
+    /// The `active` flag, set while partitioned rewards are being
+    /// distributed. Reads that one byte only.
+    pub fn active(&self) -> Result<bool, ProgramError> {
+        let mut active = [0u8; 1];
+        get_sysvar(&mut active, &EPOCH_REWARDS_ID, EPOCH_REWARDS_ACTIVE_OFFSET, 1)?;
+        Ok(active[0] != 0)
+    }
+}
+
+/// Whether partitioned epoch rewards are currently being distributed.
+///
+/// Like the native program, a sysvar that can't be read is treated as
+/// inactive.
+pub fn epoch_rewards_active() -> bool {
+    EpochRewardsSysvar.active().unwrap_or(false)
+}
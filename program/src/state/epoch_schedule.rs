@@ -0,0 +1,276 @@
+//! The epoch schedule sysvar and the slot to epoch arithmetic of
+//! `solana_epoch_schedule`, without the sdk.
+//!
+//! With warmup, epochs start at `MINIMUM_SLOTS_PER_EPOCH` slots and double
+//! until they reach `slots_per_epoch`, at `first_normal_epoch`; every epoch
+//! from there on is `slots_per_epoch` long.
+
+use pinocchio::{
+    program_error::ProgramError,
+    sysvars::clock::{Epoch, Slot},
+};
+
+use crate::{consts::EPOCH_SCHEDULE_ID, state::get_sysvar};
+
+/// Length of the shortest epoch, the first one of a warmup.
+pub const MINIMUM_SLOTS_PER_EPOCH: u64 = 32;
+
+/// Bincode length of the sysvar: three `u64`s either side of the warmup flag.
+pub const EPOCH_SCHEDULE_LEN: usize = 8 + 8 + 1 + 8 + 8;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EpochSchedule {
+    /// Length of every epoch from `first_normal_epoch` on.
+    pub slots_per_epoch: u64,
+    /// How many slots before an epoch its leader schedule is set.
+    pub leader_schedule_slot_offset: u64,
+    /// Whether epochs start short and double up to `slots_per_epoch`.
+    pub warmup: bool,
+    /// The first epoch `slots_per_epoch` long: log2(slots_per_epoch) -
+    /// log2(MINIMUM_SLOTS_PER_EPOCH) with warmup, otherwise 0.
+    pub first_normal_epoch: Epoch,
+    /// The first slot of `first_normal_epoch`: MINIMUM_SLOTS_PER_EPOCH *
+    /// (2^first_normal_epoch - 1).
+    pub first_normal_slot: Slot,
+}
+
+impl EpochSchedule {
+    /// A schedule of `slots_per_epoch`, with the warmup epochs it implies.
+    /// None if the epochs are shorter than `MINIMUM_SLOTS_PER_EPOCH`.
+    pub fn new(
+        slots_per_epoch: u64,
+        leader_schedule_slot_offset: u64,
+        warmup: bool,
+    ) -> Option<Self> {
+        if slots_per_epoch < MINIMUM_SLOTS_PER_EPOCH {
+            return None;
+        }
+        let (first_normal_epoch, first_normal_slot) = if warmup {
+            let next_power_of_two = slots_per_epoch.checked_next_power_of_two()?;
+            let log2_slots_per_epoch = next_power_of_two
+                .trailing_zeros()
+                .saturating_sub(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros());
+            (
+                u64::from(log2_slots_per_epoch),
+                next_power_of_two.saturating_sub(MINIMUM_SLOTS_PER_EPOCH),
+            )
+        } else {
+            (0, 0)
+        };
+        Some(Self {
+            slots_per_epoch,
+            leader_schedule_slot_offset,
+            warmup,
+            first_normal_epoch,
+            first_normal_slot,
+        })
+    }
+
+    /// Reads the sysvar through `sol_get_sysvar`.
+    pub fn get() -> Result<Self, ProgramError> {
+        let mut data = [0u8; EPOCH_SCHEDULE_LEN];
+        get_sysvar(&mut data, &EPOCH_SCHEDULE_ID, 0, EPOCH_SCHEDULE_LEN as u64)?;
+        Self::from_bytes(&data)
+    }
+
+    /// Parses the bincode the sysvar account holds, e.g. as fetched over RPC.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ProgramError> {
+        let data: &[u8; EPOCH_SCHEDULE_LEN] = data
+            .get(..EPOCH_SCHEDULE_LEN)
+            .and_then(|data| data.try_into().ok())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let warmup = match data[16] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Self {
+            slots_per_epoch: u64_at(0),
+            leader_schedule_slot_offset: u64_at(8),
+            warmup,
+            first_normal_epoch: u64_at(17),
+            first_normal_slot: u64_at(25),
+        })
+    }
+
+    /// Number of slots in `epoch`.
+    pub fn get_slots_in_epoch(&self, epoch: Epoch) -> u64 {
+        if epoch < self.first_normal_epoch {
+            2u64.saturating_pow(
+                (epoch as u32).saturating_add(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros()),
+            )
+        } else {
+            self.slots_per_epoch
+        }
+    }
+
+    /// The epoch whose leader schedule is set by `slot`.
+    pub fn get_leader_schedule_epoch(&self, slot: Slot) -> Epoch {
+        if slot < self.first_normal_slot {
+            // until the epochs are normal, the leader schedule is the next
+            // epoch's
+            self.get_epoch_and_slot_index(slot).0.saturating_add(1)
+        } else {
+            let new_slots_since_first_normal_slot = slot.saturating_sub(self.first_normal_slot);
+            let new_first_normal_leader_schedule_slot =
+                new_slots_since_first_normal_slot.saturating_add(self.leader_schedule_slot_offset);
+            let new_epochs_since_first_normal_leader_schedule =
+                new_first_normal_leader_schedule_slot
+                    .checked_div(self.slots_per_epoch)
+                    .unwrap_or(0);
+            self.first_normal_epoch
+                .saturating_add(new_epochs_since_first_normal_leader_schedule)
+        }
+    }
+
+    /// The epoch `slot` is in.
+    pub fn get_epoch(&self, slot: Slot) -> Epoch {
+        self.get_epoch_and_slot_index(slot).0
+    }
+
+    /// The epoch `slot` is in, and how far into it `slot` is.
+    pub fn get_epoch_and_slot_index(&self, slot: Slot) -> (Epoch, u64) {
+        if slot < self.first_normal_slot {
+            let epoch = slot
+                .saturating_add(MINIMUM_SLOTS_PER_EPOCH)
+                .saturating_add(1)
+                .next_power_of_two()
+                .trailing_zeros()
+                .saturating_sub(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros())
+                .saturating_sub(1);
+
+            let epoch_len =
+                2u64.saturating_pow(epoch.saturating_add(MINIMUM_SLOTS_PER_EPOCH.trailing_zeros()));
+
+            (
+                u64::from(epoch),
+                slot.saturating_sub(epoch_len.saturating_sub(MINIMUM_SLOTS_PER_EPOCH)),
+            )
+        } else {
+            let normal_slot_index = slot.saturating_sub(self.first_normal_slot);
+            let normal_epoch_index = normal_slot_index
+                .checked_div(self.slots_per_epoch)
+                .unwrap_or(0);
+            let epoch = self.first_normal_epoch.saturating_add(normal_epoch_index);
+            let slot_index = normal_slot_index
+                .checked_rem(self.slots_per_epoch)
+                .unwrap_or(0);
+            (epoch, slot_index)
+        }
+    }
+
+    /// The first slot of `epoch`.
+    pub fn get_first_slot_in_epoch(&self, epoch: Epoch) -> Slot {
+        if epoch <= self.first_normal_epoch {
+            2u64.saturating_pow(epoch as u32)
+                .saturating_sub(1)
+                .saturating_mul(MINIMUM_SLOTS_PER_EPOCH)
+        } else {
+            epoch
+                .saturating_sub(self.first_normal_epoch)
+                .saturating_mul(self.slots_per_epoch)
+                .saturating_add(self.first_normal_slot)
+        }
+    }
+
+    /// The last slot of `epoch`.
+    pub fn get_last_slot_in_epoch(&self, epoch: Epoch) -> Slot {
+        self.get_first_slot_in_epoch(epoch)
+            .saturating_add(self.get_slots_in_epoch(epoch))
+            .saturating_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::{mock_get_sysvar_syscall, take_mock_get_sysvar_calls};
+    use solana_sdk::epoch_schedule::EpochSchedule as SdkEpochSchedule;
+
+    fn schedules() -> Vec<(EpochSchedule, SdkEpochSchedule)> {
+        let mut schedules = Vec::new();
+        for slots_per_epoch in [32, 33, 100, 8192, 432_000] {
+            for warmup in [false, true] {
+                schedules.push((
+                    EpochSchedule::new(slots_per_epoch, slots_per_epoch, warmup).unwrap(),
+                    SdkEpochSchedule::custom(slots_per_epoch, slots_per_epoch, warmup),
+                ));
+            }
+        }
+        schedules
+    }
+
+    #[test]
+    fn test_matches_sdk() {
+        for (schedule, sdk) in schedules() {
+            assert_eq!(schedule.first_normal_epoch, sdk.first_normal_epoch);
+            assert_eq!(schedule.first_normal_slot, sdk.first_normal_slot);
+
+            for epoch in (0..40).chain([u32::MAX as u64, u64::MAX]) {
+                assert_eq!(
+                    schedule.get_slots_in_epoch(epoch),
+                    sdk.get_slots_in_epoch(epoch)
+                );
+                assert_eq!(
+                    schedule.get_first_slot_in_epoch(epoch),
+                    sdk.get_first_slot_in_epoch(epoch)
+                );
+                assert_eq!(
+                    schedule.get_last_slot_in_epoch(epoch),
+                    sdk.get_last_slot_in_epoch(epoch)
+                );
+            }
+
+            let slots = (0..20_000).step_by(7).chain([
+                431_999,
+                432_000,
+                1_000_000_007,
+                u64::MAX - 1,
+                u64::MAX,
+            ]);
+            for slot in slots {
+                assert_eq!(
+                    schedule.get_epoch_and_slot_index(slot),
+                    sdk.get_epoch_and_slot_index(slot),
+                    "slot {slot} of {sdk:?}"
+                );
+                assert_eq!(
+                    schedule.get_leader_schedule_epoch(slot),
+                    sdk.get_leader_schedule_epoch(slot)
+                );
+            }
+        }
+
+        assert_eq!(
+            EpochSchedule::new(MINIMUM_SLOTS_PER_EPOCH - 1, 0, true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get() {
+        let (schedule, sdk) = schedules().pop().unwrap();
+        let data = bincode::serialize(&sdk).unwrap();
+        assert_eq!(data.len(), EPOCH_SCHEDULE_LEN);
+
+        mock_get_sysvar_syscall(&data);
+        take_mock_get_sysvar_calls();
+        assert_eq!(EpochSchedule::get(), Ok(schedule));
+        assert_eq!(take_mock_get_sysvar_calls(), 1);
+        assert_eq!(EpochSchedule::from_bytes(&data), Ok(schedule));
+
+        // short, or with a warmup flag that isn't a bool
+        assert_eq!(
+            EpochSchedule::from_bytes(&data[..EPOCH_SCHEDULE_LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut data = data;
+        data[16] = 2;
+        assert_eq!(
+            EpochSchedule::from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! The cluster features stake behaviour depends on.
+//!
+//! A build targets one cluster, and `FeatureSet::COMPILED` is what that
+//! cluster has activated, from the constants in `consts`. Programs can't
+//! query the runtime's feature set, and feature gate accounts only reach a
+//! program when the caller passes them, so the caller could leave out the
+//! gate of a feature that restricts it. Processors therefore read the
+//! compiled set on chain; a `SysvarReader` off chain can stand in a cluster's
+//! set, and the helpers below read a gate account's activation for it.
+
+use pinocchio::{
+    account_info::AccountInfo,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::clock::{Epoch, Slot},
+};
+use pinocchio_pubkey::pubkey;
+
+use crate::{
+    consts::{
+        minimum_delegation, FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL,
+        PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    },
+    helpers::pubkey_eq,
+    state::EpochSchedule,
+};
+
+/// Owner of every feature gate account.
+pub const FEATURE_PROGRAM_ID: Pubkey = pubkey!("Feature111111111111111111111111111111111111");
+/// `reduce_stake_warmup_cooldown`: stake warms up and cools down at
+/// `NEW_WARMUP_COOLDOWN_RATE` from the epoch it was activated in.
+pub const REDUCE_STAKE_WARMUP_COOLDOWN_ID: Pubkey =
+    pubkey!("GwtDQBghCTBgmX2cpEGNPxTEBUTQRaDMGTr5qychdGMj");
+/// `stake_raise_minimum_delegation_to_1_sol`.
+pub const STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL_ID: Pubkey =
+    pubkey!("9onWzzvCzNC2jfhxxeqRgs5q7nFAAKpCUvkj6T6GJK9i");
+
+/// The features processors branch on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeatureSet {
+    /// Epoch the reduced warmup/cooldown rate applies from, None while
+    /// `reduce_stake_warmup_cooldown` is inactive.
+    pub new_warmup_cooldown_rate_epoch: Option<Epoch>,
+    /// Whether `stake_raise_minimum_delegation_to_1_sol` is active.
+    pub raise_minimum_delegation_to_1_sol: bool,
+}
+
+impl FeatureSet {
+    /// The features of the cluster this build targets.
+    pub const COMPILED: Self = Self {
+        new_warmup_cooldown_rate_epoch: match PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH {
+            Some(epoch) => Some(u64::from_le_bytes(epoch)),
+            None => None,
+        },
+        raise_minimum_delegation_to_1_sol: FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL,
+    };
+
+    /// Smallest stake a delegation may hold.
+    #[inline(always)]
+    pub fn minimum_delegation(&self) -> u64 {
+        minimum_delegation(self.raise_minimum_delegation_to_1_sol)
+    }
+
+    /// `new_warmup_cooldown_rate_epoch` as `Delegation` takes it.
+    #[inline(always)]
+    pub fn new_rate_activation_epoch(&self) -> Option<[u8; 8]> {
+        self.new_warmup_cooldown_rate_epoch.map(u64::to_le_bytes)
+    }
+}
+
+impl Default for FeatureSet {
+    fn default() -> Self {
+        Self::COMPILED
+    }
+}
+
+/// Slot a feature gate account records its feature was activated at, None
+/// while pending. The data is a bincode `Option<u64>`.
+pub fn feature_activated_at(data: &[u8]) -> Result<Option<Slot>, ProgramError> {
+    match data.split_first() {
+        Some((0, _)) => Ok(None),
+        Some((1, slot)) => slot
+            .get(..8)
+            .map(|slot| Some(u64::from_le_bytes(slot.try_into().unwrap())))
+            .ok_or(ProgramError::InvalidAccountData),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Epoch the reduced warmup/cooldown rate applies from, as native
+/// `FeatureSet::new_warmup_cooldown_rate_epoch` works it out: the epoch
+/// `epoch_schedule` puts the activation slot of `reduce_stake_warmup_cooldown`
+/// in. None while the feature is pending.
+///
+/// `feature_account_info` has to be the feature's gate account, owned by the
+/// feature program.
+pub fn new_warmup_cooldown_rate_epoch(
+    feature_account_info: &AccountInfo,
+    epoch_schedule: &EpochSchedule,
+) -> Result<Option<Epoch>, ProgramError> {
+    if !pubkey_eq(feature_account_info.key(), &REDUCE_STAKE_WARMUP_COOLDOWN_ID) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if !feature_account_info.is_owned_by(&FEATURE_PROGRAM_ID) {
+        return Err(ProgramError::InvalidAccountOwner);
+    }
+    feature_activation_epoch(&feature_account_info.try_borrow_data()?, epoch_schedule)
+}
+
+/// Epoch of the activation slot in feature gate account `data`, for callers
+/// holding the bytes rather than the account, e.g. fetched over RPC.
+pub fn feature_activation_epoch(
+    data: &[u8],
+    epoch_schedule: &EpochSchedule,
+) -> Result<Option<Epoch>, ProgramError> {
+    Ok(feature_activated_at(data)?.map(|slot| epoch_schedule.get_epoch(slot)))
+}
@@ -1,48 +1,170 @@
-use pinocchio::{pubkey::Pubkey, sysvars::clock::Clock};
-
-use super::{Epoch, UnixTimestamp};
-
-#[repr(C)]
-#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
-pub struct Lockup {
-    /// UnixTimestamp at which this stake will allow withdrawal, unless the
-    ///   transaction is signed by the custodian
-    pub unix_timestamp: UnixTimestamp, //i64
-    /// epoch height at which this stake will allow withdrawal, unless the
-    ///   transaction is signed by the custodian
-    pub epoch: Epoch,
-    /// custodian signature on a transaction exempts the operation from
-    ///  lockup constraints
-    pub custodian: Pubkey,
+use pinocchio::sysvars::clock::Clock;
+#[cfg(feature = "linear-unlock")]
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+
+#[cfg(feature = "linear-unlock")]
+use crate::error::StakeError;
+
+use super::{Lockup, LockupEvaluator};
+
+/// The lockup rules this build enforces, built from each account's stored
+/// `Lockup`. Every handler that checks a lockup, on withdrawal, authorize,
+/// set_lockup and merge, goes through it. The default build enforces
+/// mainnet's; a `linear-unlock` build vests the epoch lockup linearly.
+#[cfg(not(feature = "linear-unlock"))]
+pub type ClusterLockup = Lockup;
+
+#[cfg(feature = "linear-unlock")]
+pub type ClusterLockup = LinearUnlock;
+
+/// Whether `lockup` is in force under `ClusterLockup`, with no custodian
+/// signing.
+#[inline(always)]
+pub fn lockup_in_force(lockup: Lockup, clock: &Clock) -> bool {
+    LockupEvaluator::is_in_force(&ClusterLockup::from(lockup), clock, None)
 }
 
-impl Lockup {
-    #[inline(always)]
-    pub fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
-        self.unix_timestamp = unix_timestamp.to_le_bytes();
+/// A lockup whose epoch vests a delegated account linearly, from epoch 0
+/// until the lockup epoch, for private clusters. It vests the reserve and
+/// delegated stake, which withdrawals don't lower, so the part still locked
+/// only shrinks with the epochs. An account with no delegation has no such
+/// amount and keeps mainnet's cliff. The unix timestamp stays a cliff and the
+/// custodian lifts both, as on mainnet; the lockup is in force on the same
+/// terms, so only how much a withdrawal may take differs.
+#[cfg(feature = "linear-unlock")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinearUnlock(Lockup);
+
+#[cfg(feature = "linear-unlock")]
+impl From<Lockup> for LinearUnlock {
+    fn from(lockup: Lockup) -> Self {
+        Self(lockup)
     }
+}
 
+#[cfg(feature = "linear-unlock")]
+impl LockupEvaluator for LinearUnlock {
     #[inline(always)]
-    pub fn unix_timestamp(&self) -> i64 {
-        i64::from_le_bytes(self.unix_timestamp)
+    fn is_in_force(&self, clock: &Clock, custodian: Option<&Pubkey>) -> bool {
+        self.0.is_in_force(clock, custodian)
     }
 
-    #[inline(always)]
-    pub fn set_epoch(&mut self, epoch: u64) {
-        self.epoch = epoch.to_le_bytes();
+    fn check_withdraw(
+        &self,
+        clock: &Clock,
+        custodian: Option<&Pubkey>,
+        withdraw_lamports: u64,
+        lamports: u64,
+        vesting_lamports: Option<u64>,
+    ) -> Result<(), ProgramError> {
+        if !self.is_in_force(clock, custodian) {
+            return Ok(());
+        }
+        let Some(vesting_lamports) = vesting_lamports else {
+            return Err(StakeError::LockupInForce.into());
+        };
+        if self.0.unix_timestamp() > clock.unix_timestamp {
+            return Err(StakeError::LockupInForce.into());
+        }
+        // in force by epoch alone, so the lockup epoch is past the current
+        // one and not zero
+        let lockup_epoch = self.0.epoch() as u128;
+        let locked = (vesting_lamports as u128 * (lockup_epoch - clock.epoch as u128))
+            .div_ceil(lockup_epoch) as u64;
+        if lamports.saturating_sub(withdraw_lamports) < locked {
+            return Err(StakeError::LockupInForce.into());
+        }
+        Ok(())
     }
+}
 
-    #[inline(always)]
-    pub fn epoch(&self) -> u64 {
-        u64::from_le_bytes(self.epoch)
+#[cfg(all(test, feature = "linear-unlock"))]
+mod tests {
+    use super::*;
+
+    const CUSTODIAN: Pubkey = [1; 32];
+    const VESTING: u64 = 1_000;
+
+    /// Locked until epoch 10, so a tenth vests each epoch.
+    fn linear_unlock() -> LinearUnlock {
+        let mut lockup = Lockup {
+            custodian: CUSTODIAN,
+            ..Lockup::default()
+        };
+        lockup.set_epoch(10);
+        LinearUnlock::from(lockup)
+    }
+
+    fn clock(epoch: u64) -> Clock {
+        Clock {
+            epoch,
+            ..Clock::default()
+        }
+    }
+
+    #[test]
+    fn test_vests_linearly() {
+        let linear = linear_unlock();
+        let check = |withdraw, lamports| {
+            linear.check_withdraw(&clock(5), None, withdraw, lamports, Some(VESTING))
+        };
+        assert_eq!(check(500, VESTING), Ok(()));
+        assert_eq!(check(501, VESTING), Err(StakeError::LockupInForce.into()));
+        // lamports beyond the vesting amount, rewards say, are free
+        assert_eq!(check(700, VESTING + 200), Ok(()));
+        assert_eq!(
+            linear.check_withdraw(&clock(5), Some(&CUSTODIAN), VESTING, VESTING, Some(VESTING)),
+            Ok(())
+        );
+        assert_eq!(
+            linear.check_withdraw(&clock(10), None, VESTING, VESTING, Some(VESTING)),
+            Ok(())
+        );
     }
 
-    pub fn is_in_force(&self, clock: &Clock, custodian: Option<&Pubkey>) -> bool {
-        if custodian == Some(&self.custodian) {
-            return false;
+    #[test]
+    fn test_withdrawals_dont_lower_what_is_locked() {
+        // taking everything vested each epoch leaves exactly what is still
+        // locked, and a lamport more is refused every time
+        let linear = linear_unlock();
+        let mut lamports = VESTING;
+        for epoch in 1..10 {
+            let vested = VESTING * epoch / 10;
+            let withdrawable = lamports - (VESTING - vested);
+            assert_eq!(
+                linear.check_withdraw(
+                    &clock(epoch),
+                    None,
+                    withdrawable + 1,
+                    lamports,
+                    Some(VESTING)
+                ),
+                Err(StakeError::LockupInForce.into())
+            );
+            assert_eq!(
+                linear.check_withdraw(&clock(epoch), None, withdrawable, lamports, Some(VESTING)),
+                Ok(())
+            );
+            lamports -= withdrawable;
+            assert_eq!(lamports, VESTING - vested);
         }
+    }
+
+    #[test]
+    fn test_undelegated_account_is_a_cliff() {
+        assert_eq!(
+            linear_unlock().check_withdraw(&clock(9), None, 1, VESTING, None),
+            Err(StakeError::LockupInForce.into())
+        );
+    }
 
-        i64::from_le_bytes(self.unix_timestamp) > clock.unix_timestamp
-            || u64::from_le_bytes(self.epoch) > clock.epoch
+    #[test]
+    fn test_timestamp_is_a_cliff() {
+        let mut lockup = linear_unlock().0;
+        lockup.set_unix_timestamp(1);
+        assert_eq!(
+            LinearUnlock::from(lockup).check_withdraw(&clock(9), None, 1, VESTING, Some(VESTING)),
+            Err(StakeError::LockupInForce.into())
+        );
     }
 }
@@ -1,18 +1,21 @@
-use crate::{consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH, error::StakeError};
+use crate::error::StakeError;
 use pinocchio::{
+    account_info::AccountInfo,
     program_error::ProgramError,
     sysvars::clock::{Clock, Epoch},
     ProgramResult,
 };
-use pinocchio_log::log;
 
-use super::{checked_add, Delegation, Meta, Stake, StakeFlags, StakeHistoryGetEntry, StakeStateV2};
+use super::{
+    checked_add, get_stake_state, lockup_in_force, Delegation, Meta, Stake, StakeFlags,
+    StakeHistoryGetEntry, StakeStateV2, SysvarReader,
+};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum MergeKind {
     Inactive(Meta, u64, StakeFlags),
     ActivationEpoch(Meta, Stake, StakeFlags),
-    FullyActive(Meta, Stake),
+    FullyActive(Meta, Stake, StakeFlags),
 }
 
 impl MergeKind {
@@ -20,7 +23,7 @@ impl MergeKind {
         match self {
             Self::Inactive(meta, _, _) => meta,
             Self::ActivationEpoch(meta, _, _) => meta,
-            Self::FullyActive(meta, _) => meta,
+            Self::FullyActive(meta, _, _) => meta,
         }
     }
 
@@ -28,25 +31,61 @@ impl MergeKind {
         match self {
             Self::Inactive(_, _, _) => None,
             Self::ActivationEpoch(_, stake, _) => Some(stake),
-            Self::FullyActive(_, stake) => Some(stake),
+            Self::FullyActive(_, stake, _) => Some(stake),
         }
     }
 
-    pub fn get_if_mergeable<T: StakeHistoryGetEntry>(
+    /// Classifies a stake account for Merge, MoveStake and MoveLamports
+    /// alike, with its effective stake read from the stake history as of
+    /// `clock`'s epoch, so an account on an epoch boundary is never transient
+    /// to one instruction and settled to another.
+    pub fn for_account<S: SysvarReader>(
+        stake_account_info: &AccountInfo,
+        clock: &Clock,
+        sysvars: &S,
+    ) -> Result<Self, ProgramError> {
+        Self::for_state(
+            &get_stake_state(stake_account_info)?,
+            stake_account_info.lamports(),
+            clock,
+            sysvars,
+        )
+    }
+
+    /// `for_account` of a state the caller has already read, as one it is
+    /// about to write through the same borrow.
+    pub fn for_state<S: SysvarReader>(
         stake_state: &StakeStateV2,
         stake_lamports: u64,
         clock: &Clock,
+        sysvars: &S,
+    ) -> Result<Self, ProgramError> {
+        Self::get_if_mergeable(
+            stake_state,
+            stake_lamports,
+            clock.epoch,
+            &sysvars.stake_history(clock),
+            sysvars.feature_set().new_rate_activation_epoch(),
+        )
+    }
+
+    /// Classifies `stake_state` holding `stake_lamports` at `epoch`. Reads no
+    /// sysvars, the caller passes in the history and warmup rate epoch.
+    pub fn get_if_mergeable<T: StakeHistoryGetEntry>(
+        stake_state: &StakeStateV2,
+        stake_lamports: u64,
+        epoch: Epoch,
         stake_history: &T,
+        new_rate_activation_epoch: Option<[u8; 8]>,
     ) -> Result<Self, ProgramError> {
         match stake_state {
             StakeStateV2::Stake(meta, stake, stake_flags) => {
                 // stake must not be in a transient state, Transient here meaning
                 // activating or deactivating with non-zero effective stake
-                let epoch_bytes = clock.epoch.to_le_bytes(); // converts u64 to [u8; 8]
                 let status = stake.delegation.stake_activating_and_deactivating(
-                    epoch_bytes,
+                    epoch.to_le_bytes(),
                     stake_history,
-                    PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+                    new_rate_activation_epoch,
                 );
 
                 let effective = u64::from_le_bytes(status.effective);
@@ -56,7 +95,7 @@ impl MergeKind {
                 match (effective, activating, deactivating) {
                     (0, 0, 0) => Ok(Self::Inactive(*meta, stake_lamports, *stake_flags)),
                     (0, _, _) => Ok(Self::ActivationEpoch(*meta, *stake, *stake_flags)),
-                    (_, 0, 0) => Ok(Self::FullyActive(*meta, *stake)),
+                    (_, 0, 0) => Ok(Self::FullyActive(*meta, *stake, *stake_flags)),
                     _ => {
                         let err = StakeError::MergeTransientStake;
                         // log!("{}", err.into());
@@ -74,7 +113,7 @@ impl MergeKind {
     pub fn metas_can_merge(stake: &Meta, source: &Meta, clock: &Clock) -> ProgramResult {
         // lockups may mismatch so long as both have expired
         let can_merge_lockups = stake.lockup == source.lockup
-            || (!stake.lockup.is_in_force(clock, None) && !source.lockup.is_in_force(clock, None));
+            || (!lockup_in_force(stake.lockup, clock) && !lockup_in_force(source.lockup, clock));
         // `rent_exempt_reserve` has no bearing on the mergeability of accounts,
         // as the source account will be culled by runtime once the operation
         // succeeds. Considering it here would needlessly prevent merging stake
@@ -83,21 +122,24 @@ impl MergeKind {
         if stake.authorized == source.authorized && can_merge_lockups {
             Ok(())
         } else {
-            log!("Unable to merge due to metadata mismatch");
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Unable to merge due to metadata mismatch");
             Err(StakeError::MergeMismatch.into())
         }
     }
 
     pub fn active_delegation_can_merge(stake: &Delegation, source: &Delegation) -> ProgramResult {
         if stake.voter_pubkey != source.voter_pubkey {
-            log!("Unable to merge due to voter mismatch");
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Unable to merge due to voter mismatch");
             Err(StakeError::MergeMismatch.into())
         } else if u64::from_le_bytes(stake.deactivation_epoch) == Epoch::MAX
             && u64::from_le_bytes(source.deactivation_epoch) == Epoch::MAX
         {
             Ok(())
         } else {
-            log!("Unable to merge due to stake deactivation");
+            #[cfg(feature = "logging")]
+            pinocchio::msg!("Unable to merge due to stake deactivation");
             Err(StakeError::MergeMismatch.into())
         }
     }
@@ -115,10 +157,10 @@ impl MergeKind {
             (Self::Inactive(_, _, _), Self::ActivationEpoch(_, _, _)) => None,
             (
                 Self::ActivationEpoch(meta, mut stake, stake_flags),
-                Self::Inactive(_, source_Lamports, source_stake_flags),
+                Self::Inactive(_, source_lamports, source_stake_flags),
             ) => {
                 stake.delegation.stake =
-                    checked_add(stake.delegation.stake, source_Lamports.to_le_bytes())?;
+                    checked_add(stake.delegation.stake, source_lamports.to_le_bytes())?;
                 Some(StakeStateV2::Stake(
                     meta,
                     stake,
@@ -144,7 +186,10 @@ impl MergeKind {
                     stake_flags.union(source_stake_flags),
                 ))
             }
-            (Self::FullyActive(meta, mut stake), Self::FullyActive(_, source_stake)) => {
+            (
+                Self::FullyActive(meta, mut stake, stake_flags),
+                Self::FullyActive(_, source_stake, source_stake_flags),
+            ) => {
                 // Don't stake the source account's `rent_exempt_reserve` to
                 // protect against the magic activation loophole. It will
                 // instead be moved into the destination account as extra,
@@ -154,9 +199,22 @@ impl MergeKind {
                     source_stake.delegation.stake,
                     source_stake.credits_observed().to_le_bytes(),
                 )?;
-                Some(StakeStateV2::Stake(meta, stake, StakeFlags::empty()))
+                // fully active stake has no use for any known flag, but bits
+                // this program doesn't know about are left for the runtime
+                Some(StakeStateV2::Stake(
+                    meta,
+                    stake,
+                    stake_flags.union(source_stake_flags).unknown(),
+                ))
+            }
+            // active stake can only absorb stake in the same activation
+            // state, and inactive stake can't take on any active stake
+            (Self::Inactive(_, _, _), Self::FullyActive(_, _, _))
+            | (Self::ActivationEpoch(_, _, _), Self::FullyActive(_, _, _))
+            | (Self::FullyActive(_, _, _), Self::Inactive(_, _, _))
+            | (Self::FullyActive(_, _, _), Self::ActivationEpoch(_, _, _)) => {
+                return Err(StakeError::MergeMismatch.into())
             }
-            _ => return Err(StakeError::MergeMismatch.into()),
         };
         Ok(merged_state)
     }
@@ -219,7 +277,10 @@ pub(crate) fn merge_delegation_stake_and_credits_observed(
 ///
 /// (For this discussion, we omitted irrelevant variables, including distance
 ///  calculation against vote_account and point indirection.)
-pub(crate) fn stake_weighted_credits_observed(
+///
+/// Equal credits are kept as they are. Otherwise None if the two stakes
+/// overflow a `u64` together, or are both zero.
+pub fn stake_weighted_credits_observed(
     stake: &Stake,
     absorbed_lamports: [u8; 8],
     absorbed_credits_observed: [u8; 8],
@@ -248,3 +309,238 @@ pub(crate) fn stake_weighted_credits_observed(
 
 // ================= tests ==========================
 // #[cfg(test)]
+
+#[cfg(test)]
+mod test {
+    use pinocchio::{program_error::ProgramError, sysvars::clock::Clock};
+
+    use super::{stake_weighted_credits_observed, MergeKind};
+    use crate::{
+        error::StakeError,
+        state::{
+            Delegation, FeatureSet, Meta, Stake, StakeFlags, StakeHistory, StakeHistoryEntry,
+            StakeStateV2,
+        },
+    };
+
+    #[test]
+    fn test_fully_active_merge_keeps_unknown_bits() {
+        let meta = Meta::default();
+        let stake = Stake {
+            delegation: Delegation::new(&[1; 32], 1_000, 0u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        let clock = Clock::default();
+
+        let destination = MergeKind::FullyActive(meta, stake, StakeFlags::from_bits(0b0100_0001));
+        let source = MergeKind::FullyActive(meta, stake, StakeFlags::from_bits(0b0000_0100));
+
+        let Some(StakeStateV2::Stake(_, merged, stake_flags)) =
+            destination.merge(source, &clock).unwrap()
+        else {
+            panic!("fully active merge must produce a stake");
+        };
+        assert_eq!(merged.delegation.stake, 2_000u64.to_le_bytes());
+        assert_eq!(stake_flags.bits(), 0b0100_0100);
+    }
+
+    const RESERVE: u64 = 1_000;
+    const STAKE: u64 = 5_000;
+    const INACTIVE_LAMPORTS: u64 = RESERVE + 700;
+
+    fn inactive() -> MergeKind {
+        MergeKind::Inactive(meta(), INACTIVE_LAMPORTS, StakeFlags::empty())
+    }
+
+    fn activation_epoch() -> MergeKind {
+        MergeKind::ActivationEpoch(meta(), stake(), StakeFlags::empty())
+    }
+
+    fn fully_active() -> MergeKind {
+        MergeKind::FullyActive(meta(), stake(), StakeFlags::empty())
+    }
+
+    fn meta() -> Meta {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(RESERVE);
+        meta
+    }
+
+    fn stake() -> Stake {
+        Stake {
+            delegation: Delegation::new(&[1; 32], STAKE, 10u64.to_le_bytes()),
+            ..Stake::default()
+        }
+    }
+
+    fn merged_stake(merged: Option<StakeStateV2>) -> Option<u64> {
+        merged.map(|state| u64::from_le_bytes(state.delegation().unwrap().stake))
+    }
+
+    #[test]
+    fn test_merge_kind_pairings() {
+        let clock = Clock::default();
+        let mismatch = Err(ProgramError::from(StakeError::MergeMismatch));
+
+        // (destination, source, merged delegation stake)
+        let table: [(MergeKind, MergeKind, Result<Option<u64>, ProgramError>); 9] = [
+            (inactive(), inactive(), Ok(None)),
+            (inactive(), activation_epoch(), Ok(None)),
+            (inactive(), fully_active(), mismatch.clone()),
+            // all of the source's lamports start activating
+            (activation_epoch(), inactive(), Ok(Some(STAKE + INACTIVE_LAMPORTS))),
+            // the source's reserve is staked too, it is activating anyway
+            (activation_epoch(), activation_epoch(), Ok(Some(STAKE + RESERVE + STAKE))),
+            (activation_epoch(), fully_active(), mismatch.clone()),
+            (fully_active(), inactive(), mismatch.clone()),
+            (fully_active(), activation_epoch(), mismatch.clone()),
+            (fully_active(), fully_active(), Ok(Some(STAKE + STAKE))),
+        ];
+
+        for (destination, source, expected) in table {
+            assert_eq!(
+                destination.merge(source, &clock).map(merged_stake),
+                expected,
+                "{destination:?} <- {source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_transient_stake_is_not_mergeable() {
+        let epoch = 11;
+        let new_rate_activation_epoch = FeatureSet::COMPILED.new_rate_activation_epoch();
+        // the cluster only lets part of the stake warm up in its first epoch
+        let mut stake_history = StakeHistory::default();
+        stake_history.add(
+            10,
+            StakeHistoryEntry {
+                effective: 10_000u64.to_le_bytes(),
+                activating: STAKE.to_le_bytes(),
+                ..StakeHistoryEntry::default()
+            },
+        );
+
+        let activating = StakeStateV2::Stake(meta(), stake(), StakeFlags::empty());
+        assert_eq!(
+            MergeKind::get_if_mergeable(
+                &activating,
+                RESERVE + STAKE,
+                epoch,
+                &stake_history,
+                new_rate_activation_epoch
+            ),
+            Err(StakeError::MergeTransientStake.into())
+        );
+
+        // deactivating with stake still effective
+        let mut deactivating = stake();
+        deactivating.delegation.set_activation_epoch(0);
+        deactivating.delegation.set_deactivation_epoch(11);
+        let deactivating = StakeStateV2::Stake(meta(), deactivating, StakeFlags::empty());
+        assert_eq!(
+            MergeKind::get_if_mergeable(
+                &deactivating,
+                RESERVE + STAKE,
+                epoch,
+                &stake_history,
+                new_rate_activation_epoch
+            ),
+            Err(StakeError::MergeTransientStake.into())
+        );
+
+        // each side is classified on its own, so the Initialized source a
+        // transient destination would take is still inactive; it is the
+        // destination that fails `process_merge`, see `tests/merge.rs`
+        assert_eq!(
+            MergeKind::get_if_mergeable(
+                &StakeStateV2::Initialized(meta()),
+                INACTIVE_LAMPORTS,
+                epoch,
+                &stake_history,
+                new_rate_activation_epoch
+            ),
+            Ok(inactive())
+        );
+    }
+
+    #[test]
+    fn test_classified_at_epoch() {
+        // with no history the cluster warms and cools everything at once, so
+        // only the epochs the stake was delegated and deactivated in count
+        let stake_history = StakeHistory::default();
+        let mut deactivated = stake();
+        deactivated.delegation.set_deactivation_epoch(12);
+        let state = StakeStateV2::Stake(meta(), deactivated, StakeFlags::empty());
+        let classify = |epoch| {
+            MergeKind::get_if_mergeable(&state, RESERVE + STAKE, epoch, &stake_history, None)
+        };
+
+        assert_eq!(
+            classify(10),
+            Ok(MergeKind::ActivationEpoch(meta(), deactivated, StakeFlags::empty()))
+        );
+        assert_eq!(
+            classify(11),
+            Ok(MergeKind::FullyActive(meta(), deactivated, StakeFlags::empty()))
+        );
+        assert_eq!(
+            classify(13),
+            Ok(MergeKind::Inactive(meta(), RESERVE + STAKE, StakeFlags::empty()))
+        );
+    }
+
+    fn stake_with_credits(stake: u64, credits_observed: u64) -> Stake {
+        let mut stake = Stake {
+            delegation: Delegation::new(&[1; 32], stake, 0u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        stake.set_credits_observed(credits_observed);
+        stake
+    }
+
+    fn weighted(stake: u64, credits: u64, absorbed: u64, absorbed_credits: u64) -> Option<u64> {
+        stake_weighted_credits_observed(
+            &stake_with_credits(stake, credits),
+            absorbed.to_le_bytes(),
+            absorbed_credits.to_le_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_stake_weighted_credits_observed_zero_credits() {
+        assert_eq!(weighted(100, 0, 100, 0), Some(0));
+        // rounded up, so merging never earns a fraction of a credit
+        assert_eq!(weighted(100, 0, 100, 1), Some(1));
+        assert_eq!(weighted(300, 0, 100, 10), Some(3));
+        assert_eq!(weighted(100, 10, 300, 0), Some(3));
+        // nothing absorbed, or nothing to absorb into
+        assert_eq!(weighted(100, 0, 0, 10), Some(0));
+        assert_eq!(weighted(0, 0, 100, 10), Some(10));
+        // with no stake on either side there is nothing to weigh
+        assert_eq!(weighted(0, 0, 0, 10), None);
+    }
+
+    #[test]
+    fn test_stake_weighted_credits_observed_equal_credits() {
+        for credits in [0, 1, 1_000, u64::MAX] {
+            assert_eq!(weighted(100, credits, 300, credits), Some(credits));
+            assert_eq!(weighted(0, credits, 0, credits), Some(credits));
+            // taken as is, even where the stake itself overflows
+            assert_eq!(weighted(u64::MAX, credits, 1, credits), Some(credits));
+        }
+    }
+
+    #[test]
+    fn test_stake_weighted_credits_observed_max_credits() {
+        assert_eq!(weighted(1, u64::MAX, 1, 0), Some(1 << 63));
+        assert_eq!(weighted(1, u64::MAX - 1, 1, u64::MAX), Some(u64::MAX));
+        assert_eq!(weighted(u64::MAX - 1, u64::MAX, 1, 0), Some(u64::MAX - 1));
+        assert_eq!(
+            weighted(u64::MAX / 2, 0, u64::MAX / 2 + 1, u64::MAX),
+            Some(1 << 63)
+        );
+        // the total stake has to fit
+        assert_eq!(weighted(u64::MAX, u64::MAX, 1, 0), None);
+    }
+}
@@ -1,72 +1,102 @@
-pub mod authorized;
-pub mod delegation;
+pub mod epoch_rewards;
+pub mod epoch_schedule;
+pub mod feature_set;
 pub mod lockup;
 pub mod merge;
-pub mod meta;
 pub mod redelegate_state;
-pub mod stake;
-pub mod stake_authorize;
-pub mod stake_flags;
-pub mod stake_history;
 pub mod stake_history_sysvar;
-pub mod stake_state_v2;
 pub mod vote_state_v3;
 pub mod authorized_voters;
 pub mod utils;
 
-pub use authorized::*;
-pub use delegation::*;
 pub use vote_state_v3::*;
 pub use authorized_voters::*;
+pub use epoch_rewards::*;
+pub use epoch_schedule::*;
+pub use feature_set::*;
 pub use lockup::*;
 pub use merge::*;
-pub use meta::*;
+use core::ops::{Deref, DerefMut};
+
 use pinocchio::{
-    account_info::{ AccountInfo, Ref, RefMut },
+    account_info::{ AccountInfo, RefMut },
     program_error::ProgramError,
     ProgramResult,
 };
-pub use stake::*;
-pub use stake_authorize::*;
-pub use stake_flags::*;
-pub use stake_history::*;
 pub use stake_history_sysvar::*;
-pub use stake_state_v2::*;
 pub use utils::*;
 
-use crate::consts::VOTE_PROGRAM_ID;
+use crate::{
+    consts::VOTE_PROGRAM_ID,
+    helpers::{copy_bytes, log_stake_state},
+};
+use pinocchio_stake_interface::state::layout::{
+    META_END, STAKE_END, STAKE_FLAGS_OFFSET, STAKE_STATE_LEN,
+};
 pub use redelegate_state::*;
 
-pub type Epoch = [u8; 8]; //u64
-pub type UnixTimestamp = [u8; 8]; //i64;
+pub use pinocchio_stake_interface::state::*;
 
-pub fn get_stake_state(
-    stake_account_info: &AccountInfo
-) -> Result<Ref<StakeStateV2>, ProgramError> {
-    if stake_account_info.is_owned_by(&crate::ID) {
+/// Fails with `InvalidAccountOwner` unless the stake program owns the
+/// account. Native checks this of the account an instruction operates on
+/// before anything else about it, its size included.
+pub fn check_stake_account_owner(stake_account_info: &AccountInfo) -> ProgramResult {
+    if !stake_account_info.is_owned_by(&crate::ID) {
         return Err(ProgramError::InvalidAccountOwner);
     }
+    Ok(())
+}
+
+/// Copies the stake state out of the account, whose data needn't be aligned.
+pub fn get_stake_state(stake_account_info: &AccountInfo) -> Result<StakeStateV2, ProgramError> {
+    check_stake_account_owner(stake_account_info)?;
 
-    StakeStateV2::from_account_info(stake_account_info)
+    StakeStateV2::read_from(&stake_account_info.try_borrow_data()?)
 }
 
 pub fn set_stake_state(
     stake_account_info: &AccountInfo,
     new_state: &StakeStateV2
 ) -> Result<(), ProgramError> {
-    let new_state_size = core::mem::size_of::<StakeStateV2>();
-    let data = stake_account_info.try_borrow_mut_data()?;
-    if data.len() < new_state_size {
-        return Err(ProgramError::AccountDataTooSmall);
-    }
-    let mut new_state_bytes = [0u8; core::mem::size_of::<StakeStateV2>()];
-    new_state_bytes.copy_from_slice(unsafe {
-        core::slice::from_raw_parts(new_state as *const StakeStateV2 as *const u8, new_state_size)
-    });
-    stake_account_info.try_borrow_mut_data()?.copy_from_slice(&new_state_bytes);
+    let mut data = stake_account_info.try_borrow_mut_data()?;
+    let (data, _) = data
+        .split_first_chunk_mut::<STAKE_STATE_LEN>()
+        .ok_or(ProgramError::AccountDataTooSmall)?;
+    write_stake_state(data, new_state);
+    crate::helpers::canary_assert!(
+        StakeStateV2::read_from(data).as_ref() == Ok(new_state),
+        "stake state doesn't read back as written"
+    );
     Ok(())
 }
 
+/// Writes `state` to `data` as `StakeStateV2::write_to` does, but as one
+/// copy of its bytes up to the end of its variant, which `sol_memcpy` makes
+/// on chain for a `Meta` or more. `StakeStateV2` is `repr(C)` over byte
+/// arrays, so on a little-endian target those bytes are the layout.
+#[inline(always)]
+pub fn write_stake_state(data: &mut [u8; STAKE_STATE_LEN], state: &StakeStateV2) {
+    match state {
+        StakeStateV2::Initialized(_) => write_repr_prefix::<META_END>(data, state),
+        StakeStateV2::Stake(..) => write_repr_prefix::<STAKE_END>(data, state),
+        StakeStateV2::Uninitialized | StakeStateV2::RewardsPool => {
+            write_repr_prefix::<{ core::mem::size_of::<u32>() }>(data, state)
+        }
+    }
+}
+
+/// Copies the first `N` bytes of `state`, which must all belong to its
+/// variant, to the start of `data`.
+#[inline(always)]
+fn write_repr_prefix<const N: usize>(data: &mut [u8; STAKE_STATE_LEN], state: &StakeStateV2) {
+    const { assert!(N <= STAKE_STATE_LEN) };
+    let (data, _) = data.split_first_chunk_mut::<N>().unwrap();
+    // SAFETY: the caller passes the end of `state`'s variant, and the tag and
+    // fields before it are plain bytes with no padding between them
+    let repr = unsafe { &*(state as *const StakeStateV2 as *const [u8; N]) };
+    copy_bytes(data, repr);
+}
+
 /// # Safety
 ///
 /// The caller must ensure that it is safe to borrow the account data – e.g., there are
@@ -81,14 +111,92 @@ pub unsafe fn get_stake_state_unchecked(
     StakeStateV2::from_account_info_unchecked(stake_account_info)
 }
 
+#[inline]
 pub fn try_get_stake_state_mut(
     stake_account_info: &AccountInfo
-) -> Result<RefMut<StakeStateV2>, ProgramError> {
-    if stake_account_info.is_owned_by(&crate::ID) {
-        return Err(ProgramError::InvalidAccountOwner);
+) -> Result<StakeStateMut<'_>, ProgramError> {
+    check_stake_account_owner(stake_account_info)?;
+
+    let data = stake_account_info.try_borrow_mut_data()?;
+    let state = StakeStateV2::read_from(&data)?;
+    Ok(StakeStateMut { data, state, modified: false })
+}
+
+/// A stake account's state, copied out of data that needn't be aligned. The
+/// data stays mutably borrowed, as through a `RefMut`, and any change is
+/// written back when this is dropped.
+pub struct StakeStateMut<'a> {
+    data: RefMut<'a, [u8]>,
+    state: StakeStateV2,
+    modified: bool,
+}
+
+impl Deref for StakeStateMut<'_> {
+    type Target = StakeStateV2;
+
+    fn deref(&self) -> &StakeStateV2 {
+        &self.state
     }
+}
 
-    StakeStateV2::try_from_account_info_mut(stake_account_info)
+impl DerefMut for StakeStateMut<'_> {
+    fn deref_mut(&mut self) -> &mut StakeStateV2 {
+        self.modified = true;
+        &mut self.state
+    }
+}
+
+impl StakeStateMut<'_> {
+    /// The flags of a delegated account, read from their byte. None for any
+    /// other state, which has no flags.
+    pub fn flags(&self) -> Option<StakeFlags> {
+        match self.state {
+            StakeStateV2::Stake(..) => Some(StakeFlags::from_bits(self.data[STAKE_FLAGS_OFFSET])),
+            _ => None,
+        }
+    }
+
+    /// Sets `flag` on a delegated account, writing only the flags byte.
+    pub fn set_flag(&mut self, flag: StakeFlags) -> ProgramResult {
+        self.update_flags(|flags| flags.set(flag))
+    }
+
+    /// Clears `flag` on a delegated account, writing only the flags byte.
+    pub fn clear_flag(&mut self, flag: StakeFlags) -> ProgramResult {
+        self.update_flags(|flags| flags.remove(flag))
+    }
+
+    fn update_flags(&mut self, update: impl FnOnce(&mut StakeFlags)) -> ProgramResult {
+        let StakeStateV2::Stake(_, _, flags) = &mut self.state else {
+            return Err(ProgramError::InvalidAccountData);
+        };
+        // the copy is kept in step, but isn't marked modified, so the rest of
+        // the state isn't rewritten for a flag
+        update(flags);
+        self.data[STAKE_FLAGS_OFFSET] = flags.bits();
+        Ok(())
+    }
+}
+
+impl Drop for StakeStateMut<'_> {
+    fn drop(&mut self) {
+        if self.modified {
+            // the length was checked when the state was read
+            if let Some((data, _)) = self.data.split_first_chunk_mut::<STAKE_STATE_LEN>() {
+                write_stake_state(data, &self.state);
+            }
+            // every handler writes through here, so a `debug` build logs
+            // each state it leaves, even one a failing instruction discards
+            log_stake_state(&self.state);
+        }
+    }
+}
+
+/// Delegated stake of `stake_state`, zero unless it is delegated.
+pub fn delegated_stake(stake_state: &StakeStateV2) -> u64 {
+    stake_state
+        .delegation_ref()
+        .map_or(0, |delegation| u64::from_le_bytes(delegation.stake))
 }
 
 // dont call this "move" because we have an instruction MoveLamports
@@ -97,30 +205,61 @@ pub fn relocate_lamports(
     destination_account_info: &AccountInfo,
     lamports: u64
 ) -> ProgramResult {
-    {
-        let mut source_lamports = source_account_info.try_borrow_mut_lamports()?;
-        *source_lamports = source_lamports
-            .checked_sub(lamports)
-            .ok_or(ProgramError::InsufficientFunds)?;
+    // both balances are worked out before either is written, so a failure
+    // leaves the accounts as they were
+    let source_lamports = source_account_info
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    if source_account_info.key() == destination_account_info.key() {
+        // the same account, debited and credited the same amount
+        return Ok(());
+    }
+    let destination_lamports = destination_account_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    *source_account_info.try_borrow_mut_lamports()? = source_lamports;
+    *destination_account_info.try_borrow_mut_lamports()? = destination_lamports;
+
+    Ok(())
+}
+
+/// The credits of a vote account stake is being delegated to, which the new
+/// stake starts observing from.
+pub fn get_vote_credits(vote_account_info: &AccountInfo) -> Result<u64, ProgramError> {
+    validate_vote_account(vote_account_info)?;
+
+    VoteState::credits_from_bytes(&vote_account_info.try_borrow_data()?)
+}
+
+/// Checks a vote account can be delegated to: owned by the vote program and
+/// still funded. An account drained earlier in the transaction keeps its
+/// data until the transaction ends, and RPC keeps returning closed accounts
+/// for a while, so neither may be trusted. Its length is left to the reader
+/// of its data, as accounts still holding an older, shorter vote state are
+/// as valid to native as current ones.
+pub fn validate_vote_account(vote_account_info: &AccountInfo) -> ProgramResult {
+    if !vote_account_info.is_owned_by(&VOTE_PROGRAM_ID) {
+        return Err(ProgramError::IncorrectProgramId);
     }
 
-    {
-        let mut destination_lamports = destination_account_info.try_borrow_mut_lamports()?;
-        *destination_lamports = destination_lamports
-            .checked_add(lamports)
-            .ok_or(ProgramError::ArithmeticOverflow)?;
+    if vote_account_info.lamports() == 0 {
+        return Err(ProgramError::InvalidAccountData);
     }
 
     Ok(())
 }
 
-pub fn get_vote_state(vote_account_info: &AccountInfo) -> Result<Ref<VoteState>, ProgramError> {
-    if vote_account_info.is_owned_by(&VOTE_PROGRAM_ID) {
+/// Reads the node identity and commission of a vote account owned by the
+/// vote program.
+pub fn get_vote_identity(vote_account_info: &AccountInfo) -> Result<VoteIdentity, ProgramError> {
+    if !vote_account_info.is_owned_by(&VOTE_PROGRAM_ID) {
         return Err(ProgramError::IncorrectProgramId);
     }
 
-    let vote_state = VoteState::from_account_info(vote_account_info)?;
-    return Ok(vote_state);
+    VoteIdentity::from_bytes(&vote_account_info.try_borrow_data()?)
 }
 
 pub fn checked_add(a: [u8; 8], b: [u8; 8]) -> Result<[u8; 8], ProgramError> {
@@ -130,3 +269,38 @@ pub fn checked_add(a: [u8; 8], b: [u8; 8]) -> Result<[u8; 8], ProgramError> {
         .map(|result| result.to_le_bytes())
         .ok_or(ProgramError::InsufficientFunds)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_stake_state_matches_write_to() {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(2_282_880);
+        meta.authorized.staker = [1; 32];
+        meta.authorized.withdrawer = [2; 32];
+        meta.lockup.set_unix_timestamp(-5);
+        meta.lockup.set_epoch(400);
+        meta.lockup.custodian = [3; 32];
+        let mut stake = Stake {
+            delegation: Delegation::new(&[4; 32], 10_000, 300u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        stake.set_credits_observed(77);
+
+        for state in [
+            StakeStateV2::Uninitialized,
+            StakeStateV2::Initialized(meta),
+            StakeStateV2::Stake(meta, stake, StakeFlags::from_bits(0b10)),
+            StakeStateV2::RewardsPool,
+        ] {
+            // bytes past the variant are left as they were, by both
+            let mut expected = [0xa5; STAKE_STATE_LEN];
+            state.write_to(&mut expected);
+            let mut data = [0xa5; STAKE_STATE_LEN];
+            write_stake_state(&mut data, &state);
+            assert_eq!(data, expected);
+        }
+    }
+}
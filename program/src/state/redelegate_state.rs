@@ -1,6 +1,5 @@
-use super::utils::{load_acc_mut_unchecked, DataLen, Initialized};
+use super::utils::{DataLen, Initialized};
 use pinocchio::{
-    account_info::AccountInfo,
     program_error::ProgramError,
     pubkey::{self, Pubkey},
     ProgramResult,
@@ -12,28 +12,196 @@
 //! [`SysvarId::id`]: https://docs.rs/solana-sysvar-id/latest/solana_sysvar_id/trait.SysvarId.html
 //! [`SysvarId::check_id`]: https://docs.rs/solana-sysvar-id/latest/solana_sysvar_id/trait.SysvarId.html#tymethod.check_id
 
-use pinocchio::sysvars::clock::Epoch;
+use core::cell::RefCell;
 
-pub mod stake_history_id {
-    pinocchio_pubkey::declare_id!("SysvarS1otHistory11111111111111111111111111");
-}
-
-pub use stake_history_id::{check_id, id, ID};
-pub const MAX_ENTRIES: usize = 512; // it should never take as many as 512 epochs to warm up or cool down
+use pinocchio::{
+    account_info::{AccountInfo, Ref},
+    program_error::ProgramError,
+    sysvars::clock::{Clock, Epoch},
+};
 
-use crate::state::get_sysvar;
+use crate::{
+    helpers::pubkey_eq,
+    state::{epoch_rewards_active, get_clock, get_sysvar, FeatureSet},
+};
 
-use super::{StakeHistoryEntry, StakeHistoryGetEntry};
+use super::{
+    stake_history::id, StakeHistory, StakeHistoryData, StakeHistoryEntry, StakeHistoryGetEntry,
+    MAX_ENTRIES,
+};
 
 // we do not provide Default because this requires the real current epoch
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct StakeHistorySysvar(pub Epoch);
 
+impl StakeHistorySysvar {
+    /// Handle for the epoch of an already loaded `clock`, so handlers read the
+    /// clock once for both.
+    #[inline(always)]
+    pub fn from_clock(clock: &Clock) -> Self {
+        Self(clock.epoch)
+    }
+
+    /// Handle for the current epoch, read from the clock sysvar. Callers that
+    /// need the clock themselves should read it once and use `from_clock`.
+    #[inline(always)]
+    pub fn from_clock_sysvar() -> Result<Self, ProgramError> {
+        get_clock().map(|clock| Self::from_clock(&clock))
+    }
+
+    /// Handle as if `epoch` were the current epoch.
+    #[inline(always)]
+    pub fn for_epoch(epoch: Epoch) -> Self {
+        Self(epoch)
+    }
+
+    /// How many entries the sysvar holds, read from its length prefix alone.
+    /// Up to `MAX_ENTRIES`, and fewer on a cluster younger than that.
+    pub fn len(&self) -> Result<usize, ProgramError> {
+        let mut len = [0; core::mem::size_of::<u64>()];
+        get_sysvar(&mut len, &id(), 0, core::mem::size_of::<u64>() as u64)?;
+        Ok(u64::from_le_bytes(len) as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, ProgramError> {
+        self.len().map(|len| len == 0)
+    }
+}
+
+/// Where processors read the stake history from. On chain it is always the
+/// syscall, `SyscallSysvars`; tests and off-chain tools can pass an in-memory
+/// `StakeHistory`, or the sysvar account's bytes as `StakeHistoryData`.
+pub trait SysvarReader {
+    type StakeHistory<'a>: StakeHistoryGetEntry
+    where
+        Self: 'a;
+
+    /// The stake history as of `clock`'s epoch.
+    fn stake_history(&self, clock: &Clock) -> Self::StakeHistory<'_>;
+
+    /// Whether partitioned epoch rewards are being distributed, during which
+    /// withdrawals are refused. Read from the epoch rewards sysvar unless a
+    /// reader overrides it.
+    fn epoch_rewards_active(&self) -> bool {
+        epoch_rewards_active()
+    }
+
+    /// The cluster features to process the instruction under. On chain
+    /// these are always the ones this build was compiled for, which no
+    /// account passed with the instruction changes; off-chain readers can
+    /// stand in another cluster's.
+    fn feature_set(&self) -> FeatureSet {
+        FeatureSet::COMPILED
+    }
+}
+
+/// Reads sysvars through `sol_get_sysvar`.
+pub struct SyscallSysvars;
+
+impl SysvarReader for SyscallSysvars {
+    type StakeHistory<'a> = WindowedStakeHistory;
+
+    #[inline(always)]
+    fn stake_history(&self, clock: &Clock) -> WindowedStakeHistory {
+        WindowedStakeHistory::from_clock(clock)
+    }
+}
+
+impl SysvarReader for StakeHistory {
+    type StakeHistory<'a> = &'a StakeHistory;
+
+    fn stake_history(&self, _clock: &Clock) -> &StakeHistory {
+        self
+    }
+}
+
+impl SysvarReader for StakeHistoryData<'_> {
+    type StakeHistory<'a>
+        = StakeHistoryData<'a>
+    where
+        Self: 'a;
+
+    fn stake_history(&self, _clock: &Clock) -> StakeHistoryData<'_> {
+        *self
+    }
+}
+
+/// Reads the stake history from the sysvar account, for clients that still
+/// pass it, and through the syscall when none is passed or it holds no
+/// entries. The account bytes are read in place, with no syscall at all.
+pub struct StakeHistoryAccountOrSyscall<'a> {
+    data: Option<Ref<'a, [u8]>>,
+}
+
+impl<'a> StakeHistoryAccountOrSyscall<'a> {
+    /// Uses the first of `accounts` keyed to the stake history sysvar, if
+    /// any. Whether the instruction should have passed it is left to the
+    /// processor.
+    pub fn from_accounts(accounts: &'a [AccountInfo]) -> Result<Self, ProgramError> {
+        let data = accounts
+            .iter()
+            .find(|account_info| pubkey_eq(account_info.key(), &id()))
+            .map(AccountInfo::try_borrow_data)
+            .transpose()?;
+        Ok(Self { data })
+    }
+}
+
+/// The stake history `StakeHistoryAccountOrSyscall` serves.
+// one per instruction, on the stack either way, and there is no allocator to
+// box the window with
+#[allow(clippy::large_enum_variant)]
+pub enum AccountOrSyscallStakeHistory<'a> {
+    Account(StakeHistoryData<'a>),
+    Syscall(WindowedStakeHistory),
+}
+
+impl StakeHistoryGetEntry for AccountOrSyscallStakeHistory<'_> {
+    fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
+        match self {
+            Self::Account(stake_history) => stake_history.get_entry(epoch),
+            Self::Syscall(stake_history) => stake_history.get_entry(epoch),
+        }
+    }
+}
+
+impl SysvarReader for StakeHistoryAccountOrSyscall<'_> {
+    type StakeHistory<'b>
+        = AccountOrSyscallStakeHistory<'b>
+    where
+        Self: 'b;
+
+    fn stake_history(&self, clock: &Clock) -> AccountOrSyscallStakeHistory<'_> {
+        match self.data.as_deref().map(StakeHistoryData::new) {
+            Some(Ok(stake_history)) if !stake_history.is_empty() => {
+                AccountOrSyscallStakeHistory::Account(stake_history)
+            }
+            _ => AccountOrSyscallStakeHistory::Syscall(WindowedStakeHistory::from_clock(clock)),
+        }
+    }
+}
+
 // precompute so we can statically allocate buffer
 const EPOCH_AND_ENTRY_SERIALIZED_SIZE: u64 = 32;
+const ENTRY_SERIALIZED_SIZE: u64 = 24;
+
+impl StakeHistorySysvar {
+    /// Whether `target_epoch`, older than the `MAX_ENTRIES` epochs the
+    /// runtime keeps, still has a record because the sysvar has grown past
+    /// that. Records are contiguous and newest first whatever their number,
+    /// so the offsets hold; only the bound is read from the length prefix.
+    /// That costs a syscall, so it is only asked for epochs the fixed bound
+    /// rules out.
+    fn holds_beyond_max_entries(&self, target_epoch: Epoch) -> bool {
+        let Some(age) = self.0.checked_sub(target_epoch) else {
+            return false;
+        };
+        self.len()
+            .is_ok_and(|len| len > MAX_ENTRIES && age <= len as u64)
+    }
 
-impl StakeHistoryGetEntry for StakeHistorySysvar {
-    fn get_entry(&self, target_epoch: Epoch) -> Option<StakeHistoryEntry> {
+    /// Offset of `target_epoch`'s record in the sysvar, if it has one.
+    fn record_offset(&self, target_epoch: Epoch) -> Option<u64> {
         let current_epoch = self.0;
 
         // if current epoch is zero this returns None because there is no history yet
@@ -41,7 +209,7 @@ impl StakeHistoryGetEntry for StakeHistorySysvar {
         let oldest_historical_epoch = current_epoch.saturating_sub(MAX_ENTRIES as u64);
 
         // target epoch is old enough to have fallen off history; presume fully active/deactive
-        if target_epoch < oldest_historical_epoch {
+        if target_epoch < oldest_historical_epoch && !self.holds_beyond_max_entries(target_epoch) {
             return None;
         }
 
@@ -50,116 +218,383 @@ impl StakeHistoryGetEntry for StakeHistorySysvar {
         let epoch_delta = newest_historical_epoch.checked_sub(target_epoch)?;
 
         // offset is the number of bytes to our desired entry, including eight for vector length
-        let offset = epoch_delta
+        epoch_delta
             .checked_mul(EPOCH_AND_ENTRY_SERIALIZED_SIZE)?
-            .checked_add(core::mem::size_of::<u64>() as u64)?;
-
-        let mut entry_buf = [0; EPOCH_AND_ENTRY_SERIALIZED_SIZE as usize];
-        let result = get_sysvar(
-            &mut entry_buf,
-            &id(),
-            offset,
-            EPOCH_AND_ENTRY_SERIALIZED_SIZE,
-        );
+            .checked_add(core::mem::size_of::<u64>() as u64)
+    }
 
-        match result {
-            Ok(()) => {
-                // All safe because `entry_buf` is a 32-length array
-                let entry_epoch: [u8; 8] = entry_buf[0..8].try_into().unwrap();
-                let effective = entry_buf[8..16].try_into().unwrap();
-                let activating = entry_buf[16..24].try_into().unwrap();
-                let deactivating = entry_buf[24..32].try_into().unwrap();
-
-                // this would only fail if stake history skipped an epoch or the binary format of the sysvar changed
-                assert_eq!(u64::from_le_bytes(entry_epoch), target_epoch);
-
-                Some(StakeHistoryEntry {
-                    effective,
-                    activating,
-                    deactivating,
-                })
-            }
-            _ => None,
+    /// `get_entry` without the check that the record found is
+    /// `target_epoch`'s: only the 24 bytes of the entry are read, and a
+    /// misplaced record is returned as if it were the one asked for.
+    ///
+    /// For callers that can't spare the compute, and only sound with a
+    /// well-formed sysvar, one entry per epoch with none skipped, as the
+    /// runtime writes it.
+    pub fn get_entry_unchecked(&self, target_epoch: Epoch) -> Option<StakeHistoryEntry> {
+        let offset = self.record_offset(target_epoch)? + core::mem::size_of::<u64>() as u64;
+        let mut entry = [0; ENTRY_SERIALIZED_SIZE as usize];
+        get_sysvar(&mut entry, &id(), offset, ENTRY_SERIALIZED_SIZE).ok()?;
+
+        let (effective, rest) = entry.split_first_chunk::<8>()?;
+        let (activating, rest) = rest.split_first_chunk::<8>()?;
+        let (deactivating, _) = rest.split_first_chunk::<8>()?;
+        Some(StakeHistoryEntry {
+            effective: *effective,
+            activating: *activating,
+            deactivating: *deactivating,
+        })
+    }
+}
+
+impl StakeHistoryGetEntry for StakeHistorySysvar {
+    fn get_entry(&self, target_epoch: Epoch) -> Option<StakeHistoryEntry> {
+        let offset = self.record_offset(target_epoch)?;
+        let record = read_record(offset)?;
+        // stake history skipping or repeating an epoch, which the runtime
+        // never does, leaves the record nearby
+        decode_record(&record, target_epoch)
+            .or_else(|| find_record(target_epoch, offset, record_epoch(&record)))
+    }
+}
+
+/// How many records either side of the computed offset `find_record` looks
+/// through before giving up.
+const MISPLACED_RECORD_SCAN: u64 = 4;
+
+fn read_record(offset: u64) -> Option<[u8; EPOCH_AND_ENTRY_SERIALIZED_SIZE as usize]> {
+    let mut record = [0; EPOCH_AND_ENTRY_SERIALIZED_SIZE as usize];
+    get_sysvar(&mut record, &id(), offset, EPOCH_AND_ENTRY_SERIALIZED_SIZE).ok()?;
+    Some(record)
+}
+
+/// Looks for the record of `target_epoch` in the records next to `offset`,
+/// where the record of `found_epoch` was instead. Records are newest first, so
+/// an older epoch means the target is nearer the front, and a newer one that
+/// it is further back. None if the scan passes the target without finding
+/// it, leaves the sysvar, or runs out of records to look at.
+fn find_record(target_epoch: Epoch, offset: u64, found_epoch: Epoch) -> Option<StakeHistoryEntry> {
+    let towards_front = found_epoch < target_epoch;
+    let mut offset = offset;
+    for _ in 0..MISPLACED_RECORD_SCAN {
+        offset = if towards_front {
+            offset
+                .checked_sub(EPOCH_AND_ENTRY_SERIALIZED_SIZE)
+                .filter(|offset| *offset >= core::mem::size_of::<u64>() as u64)?
+        } else {
+            offset.checked_add(EPOCH_AND_ENTRY_SERIALIZED_SIZE)?
+        };
+        let record = read_record(offset)?;
+        if let Some(entry) = decode_record(&record, target_epoch) {
+            return Some(entry);
+        }
+        if (record_epoch(&record) < target_epoch) != towards_front {
+            return None;
         }
     }
+    None
+}
+
+/// Entries fetched per syscall by the windowed readers. Records of
+/// consecutive epochs are contiguous, and the syscall costs about the same for
+/// one record as for a window of them.
+pub const STAKE_HISTORY_WINDOW: usize = 16;
+
+/// The records of up to `STAKE_HISTORY_WINDOW` consecutive epochs, read with
+/// one syscall. Sized for the stack, as the program has no allocator.
+#[derive(Clone)]
+struct Window {
+    first_epoch: Epoch,
+    len: usize,
+    // newest first, as in the sysvar
+    records: [u8; STAKE_HISTORY_WINDOW * EPOCH_AND_ENTRY_SERIALIZED_SIZE as usize],
 }
 
-/*
+impl Window {
+    fn get(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
+        let index = epoch.checked_sub(self.first_epoch)? as usize;
+        if index >= self.len {
+            return None;
+        }
+        let size = EPOCH_AND_ENTRY_SERIALIZED_SIZE as usize;
+        let start = (self.len - 1 - index) * size;
+        decode_record(&self.records[start..start + size], epoch)
+    }
+}
+
+impl StakeHistorySysvar {
+    /// Reads the records of the `count` epochs from `start_epoch` on, capped
+    /// at the window size and at the newest historical epoch. None if
+    /// `start_epoch` has no entry, as `get_entry` would return for it.
+    fn read_window(&self, start_epoch: Epoch, count: usize) -> Option<Window> {
+        let current_epoch = self.0;
+        let oldest_historical_epoch = current_epoch.saturating_sub(MAX_ENTRIES as u64);
+        // past `MAX_ENTRIES`, `get_entry_windowed` falls back to `get_entry`,
+        // which finds any record a grown sysvar still holds
+        if start_epoch < oldest_historical_epoch || start_epoch >= current_epoch || count == 0 {
+            return None;
+        }
+
+        // records are stored newest first, so the range is read from its end
+        let count = count.min(STAKE_HISTORY_WINDOW);
+        let end_epoch = start_epoch.saturating_add(count as u64).min(current_epoch);
+        let len = (end_epoch - start_epoch) as usize;
+        let offset = (current_epoch - end_epoch) * EPOCH_AND_ENTRY_SERIALIZED_SIZE
+            + core::mem::size_of::<u64>() as u64;
+        let length = len as u64 * EPOCH_AND_ENTRY_SERIALIZED_SIZE;
+
+        let mut window = Window {
+            first_epoch: start_epoch,
+            len,
+            records: [0; STAKE_HISTORY_WINDOW * EPOCH_AND_ENTRY_SERIALIZED_SIZE as usize],
+        };
+        get_sysvar(&mut window.records, &id(), offset, length).ok()?;
+        Some(window)
+    }
+
+    /// `epoch`'s entry, from `window` if it holds it, or else from a new
+    /// window of up to `count` epochs from `epoch` on, which replaces it.
+    fn get_entry_windowed(
+        &self,
+        window: &mut Option<Window>,
+        epoch: Epoch,
+        count: usize,
+    ) -> Option<StakeHistoryEntry> {
+        if let Some(entry) = window.as_ref().and_then(|window| window.get(epoch)) {
+            return Some(entry);
+        }
+        let fetched = self.read_window(epoch, count);
+        let entry = fetched.as_ref().and_then(|fetched| fetched.get(epoch));
+        if fetched.is_some() {
+            *window = fetched;
+        }
+        // a sysvar shorter than the current epoch implies, or one that skipped
+        // an epoch, may still hold the record elsewhere
+        entry.or_else(|| self.get_entry(epoch))
+    }
+
+    /// The entries of the `count` epochs from `start_epoch` on, oldest first,
+    /// read a window of `STAKE_HISTORY_WINDOW` contiguous records per syscall
+    /// rather than one per epoch. Stops early, as repeated `get_entry` calls
+    /// would, at the first epoch without an entry: one at or after the current
+    /// epoch, or any if `start_epoch` has already fallen off history.
+    pub fn get_entries(&self, start_epoch: Epoch, count: usize) -> StakeHistoryEntries {
+        StakeHistoryEntries {
+            stake_history: self.clone(),
+            window: None,
+            next_epoch: start_epoch,
+            remaining: count,
+        }
+    }
+}
+
+/// Entries read by `StakeHistorySysvar::get_entries`, oldest first.
+pub struct StakeHistoryEntries {
+    stake_history: StakeHistorySysvar,
+    window: Option<Window>,
+    next_epoch: Epoch,
+    remaining: usize,
+}
+
+impl Iterator for StakeHistoryEntries {
+    type Item = StakeHistoryEntry;
+
+    fn next(&mut self) -> Option<StakeHistoryEntry> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.stake_history.get_entry_windowed(
+            &mut self.window,
+            self.next_epoch,
+            self.remaining,
+        )?;
+        self.next_epoch += 1;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+/// `StakeHistorySysvar` that fetches a window of entries from the one asked
+/// for onwards, and serves the following epochs from it. The warmup and
+/// cooldown walks ask for consecutive epochs, so a long walk costs one
+/// syscall per window instead of one per epoch.
+pub struct WindowedStakeHistory {
+    stake_history: StakeHistorySysvar,
+    window: RefCell<Option<Window>>,
+}
+
+impl WindowedStakeHistory {
+    #[inline(always)]
+    pub fn from_clock(clock: &Clock) -> Self {
+        Self {
+            stake_history: StakeHistorySysvar::from_clock(clock),
+            window: RefCell::new(None),
+        }
+    }
+}
+
+impl StakeHistoryGetEntry for WindowedStakeHistory {
+    fn get_entry(&self, epoch: Epoch) -> Option<StakeHistoryEntry> {
+        self.stake_history.get_entry_windowed(
+            &mut self.window.borrow_mut(),
+            epoch,
+            STAKE_HISTORY_WINDOW,
+        )
+    }
+}
+
+fn record_epoch(record: &[u8]) -> Epoch {
+    u64::from_le_bytes(record[0..8].try_into().unwrap())
+}
+
+/// Decodes the serialized `(Epoch, StakeHistoryEntry)` at the start of
+/// `record`, expected to be `epoch`'s. None if it is another epoch's.
+fn decode_record(record: &[u8], epoch: Epoch) -> Option<StakeHistoryEntry> {
+    let effective = record[8..16].try_into().unwrap();
+    let activating = record[16..24].try_into().unwrap();
+    let deactivating = record[24..32].try_into().unwrap();
+
+    // this would only fail if stake history skipped an epoch or the binary format of the sysvar changed
+    if record_epoch(record) != epoch {
+        return None;
+    }
+
+    Some(StakeHistoryEntry {
+        effective,
+        activating,
+        deactivating,
+    })
+}
 
-//---------------------------- Fix Tests Later ----------------------------------------
 #[cfg(test)]
-mod tests {
-    use crate::state::StakeHistory;
+mod test {
+    use pinocchio::sysvars::clock::Clock;
+    use solana_sdk::stake_history::{
+        StakeHistory as SdkStakeHistory, StakeHistoryEntry as SdkStakeHistoryEntry,
+    };
 
     use super::*;
+    use crate::state::{mock_get_sysvar_syscall, take_mock_get_sysvar_calls, Delegation};
+
+    /// The same history as the sysvar holds it, in the sdk's types, so it is
+    /// serialized exactly as the runtime does.
+    fn sdk_history(history: &StakeHistory) -> SdkStakeHistory {
+        let mut sdk_history = SdkStakeHistory::default();
+        for (epoch, entry) in history.iter().rev() {
+            sdk_history.add(
+                *epoch,
+                SdkStakeHistoryEntry {
+                    effective: u64::from_le_bytes(entry.effective),
+                    activating: u64::from_le_bytes(entry.activating),
+                    deactivating: u64::from_le_bytes(entry.deactivating),
+                },
+            );
+        }
+        sdk_history
+    }
+
+    fn mock_history(history: &StakeHistory) {
+        mock_get_sysvar_syscall(&bincode::serialize(&sdk_history(history)).unwrap());
+    }
+
+    fn activating(epoch: u64) -> StakeHistoryEntry {
+        StakeHistoryEntry {
+            activating: epoch.to_le_bytes(),
+            ..StakeHistoryEntry::default()
+        }
+    }
 
     #[test]
-    fn test_stake_history() {
+    fn test_constructors() {
+        let clock = Clock {
+            epoch: 300,
+            ..Clock::default()
+        };
+        assert_eq!(
+            StakeHistorySysvar::from_clock(&clock),
+            StakeHistorySysvar::for_epoch(300)
+        );
+        assert_eq!(StakeHistorySysvar::for_epoch(300).0, 300);
+
+        let mut clock_data = vec![0; core::mem::size_of::<Clock>()];
+        clock_data[16..24].copy_from_slice(&300u64.to_le_bytes());
+        mock_get_sysvar_syscall(&clock_data);
+        take_mock_get_sysvar_calls();
+        assert_eq!(
+            StakeHistorySysvar::from_clock_sysvar(),
+            Ok(StakeHistorySysvar::for_epoch(300))
+        );
+        assert_eq!(take_mock_get_sysvar_calls(), 1);
+    }
+
+    #[test]
+    fn test_len() {
+        mock_history(&StakeHistory::default());
+        assert_eq!(StakeHistorySysvar(0).len(), Ok(0));
+        assert_eq!(StakeHistorySysvar(0).is_empty(), Ok(true));
+
         let mut stake_history = StakeHistory::default();
+        for i in 0..10 {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        take_mock_get_sysvar_calls();
+        assert_eq!(StakeHistorySysvar(10).len(), Ok(10));
+        assert_eq!(take_mock_get_sysvar_calls(), 1);
+        assert_eq!(StakeHistorySysvar(10).is_empty(), Ok(false));
+
+        for i in 10..MAX_ENTRIES as u64 + 5 {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        assert_eq!(
+            StakeHistorySysvar(MAX_ENTRIES as u64 + 5).len(),
+            Ok(MAX_ENTRIES)
+        );
+    }
 
+    #[test]
+    fn test_stake_history() {
+        let mut stake_history = StakeHistory::default();
         for i in 0..MAX_ENTRIES as u64 + 1 {
-            stake_history.add(
-                i,
-                StakeHistoryEntry {
-                    activating: i,
-                    ..StakeHistoryEntry::default()
-                },
-            );
+            stake_history.add(i, activating(i));
         }
         assert_eq!(stake_history.len(), MAX_ENTRIES);
         assert_eq!(stake_history.iter().map(|entry| entry.0).min().unwrap(), 1);
         assert_eq!(stake_history.get(0), None);
-        assert_eq!(
-            stake_history.get(1),
-            Some(&StakeHistoryEntry {
-                activating: 1,
-                ..StakeHistoryEntry::default()
-            })
-        );
+        assert_eq!(stake_history.get(1), Some(&activating(1)));
     }
 
     #[test]
     fn test_id() {
-        assert_eq!(StakeHistory::id(), crate::helpers::stake_history::id());
+        assert_eq!(id(), solana_sdk::sysvar::stake_history::ID.to_bytes());
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_size_of() {
         let mut stake_history = StakeHistory::default();
         for i in 0..MAX_ENTRIES as u64 {
-            stake_history.add(
-                i,
-                StakeHistoryEntry {
-                    activating: i,
-                    ..StakeHistoryEntry::default()
-                },
-            );
+            stake_history.add(i, activating(i));
         }
+        let sdk_history = sdk_history(&stake_history);
 
         assert_eq!(
-            bincode::serialized_size(&stake_history).unwrap() as usize,
-
+            bincode::serialized_size(&sdk_history).unwrap() as usize,
             StakeHistory::size_of()
         );
-
-        let stake_history_inner: Vec<(Epoch, StakeHistoryEntry)> =
-            bincode::deserialize(&bincode::serialize(&stake_history).unwrap()).unwrap();
-        let epoch_entry = stake_history_inner.into_iter().next().unwrap();
-
+        let epoch_entry = sdk_history.iter().next().unwrap();
         assert_eq!(
-            bincode::serialized_size(&epoch_entry).unwrap(),
+            bincode::serialized_size(epoch_entry).unwrap(),
             EPOCH_AND_ENTRY_SERIALIZED_SIZE
         );
     }
 
-    // TODO
-    //#[serial]
     #[test]
     fn test_stake_history_get_entry() {
         let unique_entry_for_epoch = |epoch: u64| StakeHistoryEntry {
-            activating: epoch.saturating_mul(2),
-            deactivating: epoch.saturating_mul(3),
-            effective: epoch.saturating_mul(5),
+            activating: epoch.saturating_mul(2).to_le_bytes(),
+            deactivating: epoch.saturating_mul(3).to_le_bytes(),
+            effective: epoch.saturating_mul(5).to_le_bytes(),
         };
 
         let current_epoch = MAX_ENTRIES.saturating_add(2) as u64;
@@ -172,13 +607,7 @@ mod tests {
         assert_eq!(stake_history.len(), MAX_ENTRIES);
         assert_eq!(stake_history.iter().map(|entry| entry.0).min().unwrap(), 2);
 
-        // set up sol_get_sysvar
-
-        // TODO
-
-        //mock_get_sysvar_syscall(&bincode::serialize(&stake_history).unwrap());
-
-        // make a syscall interface object
+        mock_history(&stake_history);
         let stake_history_sysvar = StakeHistorySysvar(current_epoch);
 
         // now test the stake history interfaces
@@ -198,16 +627,39 @@ mod tests {
         for i in 2..current_epoch {
             let entry = Some(unique_entry_for_epoch(i));
 
-            assert_eq!(stake_history.get(i), entry.as_ref(),);
+            assert_eq!(stake_history.get(i), entry.as_ref());
+            assert_eq!(stake_history.get_entry(i), entry);
+            assert_eq!(stake_history_sysvar.get_entry(i), entry);
+        }
+    }
 
-            assert_eq!(stake_history.get_entry(i), entry,);
+    #[test]
+    fn test_stake_history_get_entry_references() {
+        fn effective<T: StakeHistoryGetEntry>(history: T, delegation: &Delegation) -> u64 {
+            delegation.stake(20u64.to_le_bytes(), &history, None)
+        }
 
-            assert_eq!(stake_history_sysvar.get_entry(i), entry,);
+        let mut stake_history = StakeHistory::default();
+        for i in 0..20 {
+            stake_history.add(
+                i,
+                StakeHistoryEntry::with_effective_and_activating(
+                    1_000u64.to_le_bytes(),
+                    1_000u64.to_le_bytes(),
+                ),
+            );
         }
+        mock_history(&stake_history);
+        let mut stake_history_sysvar = StakeHistorySysvar(20);
+        let delegation = Delegation::new(&[1; 32], 1_000, 18u64.to_le_bytes());
+
+        // handed down by reference, shared or not, without a copy
+        let expected = effective(stake_history_sysvar.clone(), &delegation);
+        assert!(expected > 0 && expected < 1_000);
+        assert_eq!(effective(&stake_history_sysvar, &delegation), expected);
+        assert_eq!(effective(&mut stake_history_sysvar, &delegation), expected);
     }
 
-    // TODO
-    //#[serial]
     #[test]
     fn test_stake_history_get_entry_zero() {
         let mut current_epoch = 0;
@@ -216,7 +668,7 @@ mod tests {
         let stake_history = StakeHistory::default();
         assert_eq!(stake_history.len(), 0);
 
-        //mock_get_sysvar_syscall(&bincode::serialize(&stake_history).unwrap());
+        mock_history(&stake_history);
         let stake_history_sysvar = StakeHistorySysvar(current_epoch);
 
         assert_eq!(stake_history.get(0), None);
@@ -225,7 +677,7 @@ mod tests {
 
         // next test that we can get a zeroth entry in the first epoch
         let entry_zero = StakeHistoryEntry {
-            effective: 100,
+            effective: 100u64.to_le_bytes(),
             ..StakeHistoryEntry::default()
         };
         let entry = Some(entry_zero.clone());
@@ -235,8 +687,7 @@ mod tests {
         assert_eq!(stake_history.len(), 1);
         current_epoch = current_epoch.saturating_add(1);
 
-        // TODO
-        // mock_get_sysvar_syscall(&bincode::serialize(&stake_history).unwrap());
+        mock_history(&stake_history);
         let stake_history_sysvar = StakeHistorySysvar(current_epoch);
 
         assert_eq!(stake_history.get(0), entry.as_ref());
@@ -248,13 +699,311 @@ mod tests {
         assert_eq!(stake_history.len(), 2);
         current_epoch = current_epoch.saturating_add(1);
 
-        // TODO
-        // mock_get_sysvar_syscall(&bincode::serialize(&stake_history).unwrap());
+        mock_history(&stake_history);
         let stake_history_sysvar = StakeHistorySysvar(current_epoch);
 
         assert_eq!(stake_history.get(0), entry.as_ref());
         assert_eq!(stake_history.get_entry(0), entry);
         assert_eq!(stake_history_sysvar.get_entry(0), entry);
     }
+
+    #[test]
+    fn test_get_entries() {
+        let current_epoch = MAX_ENTRIES as u64 + 10;
+        let mut stake_history = StakeHistory::default();
+        for i in 0..current_epoch {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        let stake_history_sysvar = StakeHistorySysvar(current_epoch);
+        let oldest_epoch = current_epoch - MAX_ENTRIES as u64;
+
+        // (start epoch, count, entries)
+        let cases = [
+            (oldest_epoch, 3, 3),
+            (current_epoch - 5, 5, 5),
+            (current_epoch - 1, 1, 1),
+            (oldest_epoch, MAX_ENTRIES, MAX_ENTRIES),
+            // cut short at the current epoch
+            (current_epoch - 2, 10, 2),
+            (current_epoch - 2, usize::MAX, 2),
+            (current_epoch, 1, 0),
+            // already fallen off history
+            (oldest_epoch - 1, 3, 0),
+            (current_epoch - 5, 0, 0),
+        ];
+        for (start_epoch, count, len) in cases {
+            take_mock_get_sysvar_calls();
+            let entries: Vec<StakeHistoryEntry> = stake_history_sysvar
+                .get_entries(start_epoch, count)
+                .collect();
+            let calls = take_mock_get_sysvar_calls();
+
+            let expected: Vec<StakeHistoryEntry> = (start_epoch..)
+                .take(count)
+                .map_while(|epoch| stake_history_sysvar.get_entry(epoch))
+                .collect();
+            assert_eq!(entries.len(), len, "{start_epoch} {count}");
+            assert_eq!(entries, expected, "{start_epoch} {count}");
+            // an epoch past `MAX_ENTRIES` costs a read of the length prefix,
+            // in case the sysvar has grown
+            let prefix_reads = usize::from(start_epoch < oldest_epoch);
+            assert_eq!(
+                calls,
+                len.div_ceil(STAKE_HISTORY_WINDOW) + prefix_reads,
+                "{start_epoch} {count}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_entries_without_history() {
+        mock_history(&StakeHistory::default());
+        assert_eq!(StakeHistorySysvar(0).get_entries(0, 1).count(), 0);
+
+        // the sysvar is shorter than the current epoch implies
+        let mut stake_history = StakeHistory::default();
+        stake_history.add(9, activating(9));
+        mock_history(&stake_history);
+        assert_eq!(
+            StakeHistorySysvar(10).get_entries(9, 1).collect::<Vec<_>>(),
+            vec![activating(9)]
+        );
+        assert_eq!(StakeHistorySysvar(10).get_entries(8, 2).count(), 0);
+    }
+
+    #[test]
+    fn test_windowed_stake_history() {
+        let current_epoch = MAX_ENTRIES as u64 + 10;
+        let mut stake_history = StakeHistory::default();
+        for i in 0..current_epoch {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        let stake_history_sysvar = StakeHistorySysvar(current_epoch);
+        let windowed = WindowedStakeHistory::from_clock(&Clock {
+            epoch: current_epoch,
+            ..Clock::default()
+        });
+
+        take_mock_get_sysvar_calls();
+        for epoch in 0..current_epoch + 2 {
+            assert_eq!(
+                windowed.get_entry(epoch),
+                stake_history_sysvar.get_entry(epoch),
+                "{epoch}"
+            );
+        }
+        // served again from the window, not re-read
+        assert_eq!(
+            windowed.get_entry(current_epoch - 1),
+            Some(activating(current_epoch - 1))
+        );
+
+        // one read per window over history, one per epoch for plain lookups,
+        // and a read of the length prefix by each for epochs past history
+        let calls = take_mock_get_sysvar_calls();
+        let plain_calls = MAX_ENTRIES + 2 * (current_epoch as usize - MAX_ENTRIES);
+        assert_eq!(
+            calls - plain_calls,
+            MAX_ENTRIES.div_ceil(STAKE_HISTORY_WINDOW)
+        );
+    }
+
+    #[test]
+    fn test_windowed_stake_history_delegation() {
+        // a large delegation warming up against a small cluster, for as many
+        // epochs as history holds
+        let current_epoch = MAX_ENTRIES as u64 + 10;
+        let mut stake_history = StakeHistory::default();
+        for i in 0..current_epoch {
+            stake_history.add(
+                i,
+                StakeHistoryEntry::with_effective_and_activating(
+                    1_000u64.to_le_bytes(),
+                    u64::MAX.to_le_bytes(),
+                ),
+            );
+        }
+        mock_history(&stake_history);
+        let clock = Clock {
+            epoch: current_epoch,
+            ..Clock::default()
+        };
+        let delegation = Delegation::new(
+            &[1; 32],
+            u64::MAX / 2,
+            (current_epoch - MAX_ENTRIES as u64).to_le_bytes(),
+        );
+
+        take_mock_get_sysvar_calls();
+        let expected = delegation.stake_activating_and_deactivating(
+            current_epoch.to_le_bytes(),
+            &StakeHistorySysvar::from_clock(&clock),
+            None,
+        );
+        let plain_calls = take_mock_get_sysvar_calls();
+        let status = delegation.stake_activating_and_deactivating(
+            current_epoch.to_le_bytes(),
+            &WindowedStakeHistory::from_clock(&clock),
+            None,
+        );
+        let windowed_calls = take_mock_get_sysvar_calls();
+
+        assert_eq!(status, expected);
+        assert_eq!(plain_calls, MAX_ENTRIES);
+        assert_eq!(windowed_calls, MAX_ENTRIES.div_ceil(STAKE_HISTORY_WINDOW));
+    }
+
+    #[test]
+    fn test_stake_history_skipped_epochs() {
+        // epoch 15 never made it into history, nor did 5 through 10
+        let current_epoch = 20;
+        let mut stake_history = StakeHistory::default();
+        for i in (0..current_epoch).filter(|i| *i != 15 && !(5..=10).contains(i)) {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        let stake_history_sysvar = StakeHistorySysvar(current_epoch);
+        let windowed = WindowedStakeHistory::from_clock(&Clock {
+            epoch: current_epoch,
+            ..Clock::default()
+        });
+
+        for epoch in 0..current_epoch {
+            let expected = match epoch {
+                // at their offsets, or found next to them
+                11..=14 | 16..=19 => Some(activating(epoch)),
+                // missing, or too far from their offsets to be found, and
+                // either way not a panic
+                _ => None,
+            };
+            assert_eq!(stake_history_sysvar.get_entry(epoch), expected, "{epoch}");
+            assert_eq!(windowed.get_entry(epoch), expected, "{epoch}");
+        }
+
+        // a range stops at the first epoch it can't place
+        assert_eq!(
+            stake_history_sysvar.get_entries(12, 8).collect::<Vec<_>>(),
+            vec![activating(12), activating(13), activating(14)]
+        );
+    }
+
+    /// Stake history as the sysvar would hold it with `len` entries, up to
+    /// the one before `current_epoch`, serialized by hand as the sdk type
+    /// never grows past `MAX_ENTRIES`.
+    fn raw_history(current_epoch: u64, len: u64) -> Vec<u8> {
+        let mut data = len.to_le_bytes().to_vec();
+        for epoch in (current_epoch - len..current_epoch).rev() {
+            data.extend_from_slice(&epoch.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&epoch.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_stake_history_beyond_max_entries() {
+        let current_epoch = 1_000;
+        let len = MAX_ENTRIES as u64 + 100;
+        mock_get_sysvar_syscall(&raw_history(current_epoch, len));
+        let stake_history_sysvar = StakeHistorySysvar(current_epoch);
+        let windowed = WindowedStakeHistory::from_clock(&Clock {
+            epoch: current_epoch,
+            ..Clock::default()
+        });
+
+        // the records past `MAX_ENTRIES` are found where they are, and those
+        // within it cost no extra read
+        take_mock_get_sysvar_calls();
+        assert_eq!(
+            stake_history_sysvar.get_entry(current_epoch - 1),
+            Some(activating(current_epoch - 1))
+        );
+        assert_eq!(take_mock_get_sysvar_calls(), 1);
+        for epoch in current_epoch - len..current_epoch {
+            assert_eq!(
+                stake_history_sysvar.get_entry(epoch),
+                Some(activating(epoch))
+            );
+            assert_eq!(
+                stake_history_sysvar.get_entry_unchecked(epoch),
+                Some(activating(epoch))
+            );
+            assert_eq!(
+                windowed.get_entry(epoch),
+                Some(activating(epoch)),
+                "{epoch}"
+            );
+        }
+        assert_eq!(
+            stake_history_sysvar.get_entry(current_epoch - len - 1),
+            None
+        );
+        assert_eq!(
+            stake_history_sysvar
+                .get_entries(current_epoch - len - 1, 2)
+                .count(),
+            0
+        );
+        assert_eq!(
+            stake_history_sysvar
+                .get_entries(current_epoch - len, 200)
+                .collect::<Vec<_>>(),
+            (current_epoch - len..current_epoch - len + 200)
+                .map(activating)
+                .collect::<Vec<_>>()
+        );
+
+        // a length prefix claiming more records than there are finds none
+        // past the end
+        let mut data = raw_history(current_epoch, MAX_ENTRIES as u64);
+        data[..8].copy_from_slice(&(MAX_ENTRIES as u64 + 10).to_le_bytes());
+        mock_get_sysvar_syscall(&data);
+        let oldest = current_epoch - MAX_ENTRIES as u64;
+        assert_eq!(
+            stake_history_sysvar.get_entry(oldest),
+            Some(activating(oldest))
+        );
+        assert_eq!(stake_history_sysvar.get_entry(oldest - 1), None);
+        assert_eq!(stake_history_sysvar.get_entry_unchecked(oldest - 1), None);
+
+        // at the usual length, epochs past it are ruled out as before
+        mock_get_sysvar_syscall(&raw_history(current_epoch, MAX_ENTRIES as u64));
+        assert_eq!(stake_history_sysvar.get_entry(oldest - 1), None);
+    }
+
+    #[test]
+    fn test_get_entry_unchecked() {
+        let current_epoch = MAX_ENTRIES as u64 + 10;
+        let mut stake_history = StakeHistory::default();
+        for i in 0..current_epoch {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        let stake_history_sysvar = StakeHistorySysvar(current_epoch);
+
+        for epoch in 0..current_epoch + 2 {
+            assert_eq!(
+                stake_history_sysvar.get_entry_unchecked(epoch),
+                stake_history_sysvar.get_entry(epoch),
+                "{epoch}"
+            );
+        }
+
+        // with epoch 15 skipped, 14's offset holds 13's record, which is taken
+        // for it
+        let mut stake_history = StakeHistory::default();
+        for i in (0..20).filter(|i| *i != 15) {
+            stake_history.add(i, activating(i));
+        }
+        mock_history(&stake_history);
+        let stake_history_sysvar = StakeHistorySysvar(20);
+        assert_eq!(
+            stake_history_sysvar.get_entry_unchecked(14),
+            Some(activating(13))
+        );
+        assert_eq!(stake_history_sysvar.get_entry(14), Some(activating(14)));
+    }
 }
- */
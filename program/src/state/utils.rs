@@ -1,25 +1,20 @@
 use pinocchio::{
-    account_info::{ AccountInfo, Ref },
+    account_info::AccountInfo,
     program_error::ProgramError,
     pubkey::Pubkey,
-    sysvars::{clock::Clock, rent::Rent, Sysvar},
-    ProgramResult, SUCCESS,
+    sysvars::{clock::Clock, rent::{Rent, RENT_ID}},
+    ProgramResult,
 };
 
 extern crate alloc;
 use super::{
-    get_stake_state, try_get_stake_state_mut, Delegation, Meta, Stake, StakeAuthorize, StakeHistorySysvar, StakeStateV2, VoteState, DEFAULT_WARMUP_COOLDOWN_RATE
-};
-use crate::{
-    consts::{
-        FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL, LAMPORTS_PER_SOL, MAX_SIGNERS,
-        NEW_WARMUP_COOLDOWN_RATE,
-    },
-    helpers::MergeKind,
+    bytes_to_u64, try_get_stake_state_mut, ClusterLockup, Delegation, MergeKind, Meta, Stake,
+    StakeAuthorize, StakeHistoryGetEntry, StakeStateV2, SysvarReader,
 };
+use crate::consts::MAX_SIGNERS;
 use crate::{consts::{
-    CLOCK_ID, HASH_BYTES, MAX_BASE58_LEN, PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH
-}, error::StakeError};
+    CLOCK_ID, HASH_BYTES, MAX_BASE58_LEN
+}, error::StakeError, helpers::{get_sysvar_slice, pubkey_eq}};
 use alloc::boxed::Box;
 use core::{ cell::UnsafeCell, fmt, str::from_utf8 };
 
@@ -31,6 +26,9 @@ pub trait Initialized {
     fn is_initialized(&self) -> bool;
 }
 
+/// # Safety
+///
+/// The caller must ensure that `bytes` contains a valid representation of `T`.
 #[inline(always)]
 pub unsafe fn load_acc<T: DataLen + Initialized>(bytes: &[u8]) -> Result<&T, ProgramError> {
     load_acc_unchecked::<T>(bytes).and_then(|acc| {
@@ -38,14 +36,20 @@ pub unsafe fn load_acc<T: DataLen + Initialized>(bytes: &[u8]) -> Result<&T, Pro
     })
 }
 
+/// # Safety
+///
+/// The caller must ensure that `bytes` contains a valid representation of `T`.
 #[inline(always)]
 pub unsafe fn load_acc_unchecked<T: DataLen>(bytes: &[u8]) -> Result<&T, ProgramError> {
-    if bytes.len() != T::LEN {
+    if bytes.len() != T::LEN || !is_aligned_for::<T>(bytes) {
         return Err(ProgramError::InvalidAccountData);
     }
     Ok(&*(bytes.as_ptr() as *const T))
 }
 
+/// # Safety
+///
+/// The caller must ensure that `bytes` contains a valid representation of `T`.
 #[inline(always)]
 pub unsafe fn load_acc_mut<T: DataLen + Initialized>(
     bytes: &mut [u8]
@@ -55,26 +59,46 @@ pub unsafe fn load_acc_mut<T: DataLen + Initialized>(
     })
 }
 
+/// # Safety
+///
+/// The caller must ensure that `bytes` contains a valid representation of `T`.
 #[inline(always)]
 pub unsafe fn load_acc_mut_unchecked<T: DataLen>(bytes: &mut [u8]) -> Result<&mut T, ProgramError> {
-    if bytes.len() != T::LEN {
+    if bytes.len() != T::LEN || !is_aligned_for::<T>(bytes) {
         return Err(ProgramError::InvalidAccountData);
     }
     Ok(&mut *(bytes.as_mut_ptr() as *mut T))
 }
 
+/// # Safety
+///
+/// The caller must ensure that `bytes` contains a valid representation of `T`.
 #[inline(always)]
 pub unsafe fn load_ix_data<T: DataLen>(bytes: &[u8]) -> Result<&T, ProgramError> {
-    if bytes.len() != T::LEN {
-        return Err(ProgramError::InvalidInstructionData.into());
+    if bytes.len() != T::LEN || !is_aligned_for::<T>(bytes) {
+        return Err(ProgramError::InvalidInstructionData);
     }
     Ok(&*(bytes.as_ptr() as *const T))
 }
 
+/// Whether `bytes` may be referenced as a `T`. Neither account nor
+/// instruction data is guaranteed to be aligned, and a misaligned reference is
+/// undefined behavior even on runtimes that tolerate unaligned loads.
+#[inline(always)]
+pub fn is_aligned_for<T>(bytes: &[u8]) -> bool {
+    bytes.as_ptr().cast::<T>().is_aligned()
+}
+
+/// # Safety
+///
+/// `T` must not contain padding bytes.
 pub unsafe fn to_bytes<T: DataLen>(data: &T) -> &[u8] {
     core::slice::from_raw_parts(data as *const T as *const u8, T::LEN)
 }
 
+/// # Safety
+///
+/// `T` must not contain padding bytes and every byte pattern written must be valid for `T`.
 pub unsafe fn to_mut_bytes<T: DataLen>(data: &mut T) -> &mut [u8] {
     core::slice::from_raw_parts_mut(data as *mut T as *mut u8, T::LEN)
 }
@@ -106,29 +130,6 @@ pub fn next_account_info<'a, I: Iterator<Item = &'a AccountInfo>>(
     iter.next().ok_or(ProgramError::NotEnoughAccountKeys)
 }
 
-#[macro_export]
-macro_rules! impl_sysvar_id {
-    ($type:ty) => {
-        impl $crate::state::stake_history::SysvarId for $type {
-            fn id() -> Pubkey {
-                id()
-            }
-
-            fn check_id(pubkey: &Pubkey) -> bool {
-                check_id(pubkey)
-            }
-        }
-    };
-}
-
-#[macro_export]
-macro_rules! declare_sysvar_id {
-    ($name:expr, $type:ty) => (
-        pinocchio_pubkey::declare_id!($name);
-        $crate::impl_sysvar_id!($type);
-    );
-}
-
 /// After calling `validate_split_amount()`, this struct contains calculated
 /// values that are used by the caller.
 #[derive(Copy, Clone, Debug, Default)]
@@ -141,6 +142,8 @@ pub(crate) struct ValidatedSplitInfo {
 /// accounts meet the minimum balance requirements, which is the rent exempt
 /// reserve plus the minimum stake delegation, and that the source account has
 /// enough lamports for the request split amount.  If not, return an error.
+/// The destination's reserve comes from `rent`, read by the caller.
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn validate_split_amount(
     source_lamports: u64,
     destination_lamports: u64,
@@ -148,7 +151,8 @@ pub(crate) fn validate_split_amount(
     source_meta: &Meta,
     destination_data_len: usize,
     additional_required_lamports: u64,
-    source_is_active: bool
+    source_is_active: bool,
+    rent: &Rent
 ) -> Result<ValidatedSplitInfo, ProgramError> {
     // Split amount has to be something
     if split_lamports == 0 {
@@ -179,7 +183,6 @@ pub(crate) fn validate_split_amount(
         // nothing to do here
     }
 
-    let rent = Rent::get()?;
     let destination_rent_exempt_reserve = rent.minimum_balance(destination_data_len);
 
     // If the source is active stake, one of these criteria must be met:
@@ -215,6 +218,93 @@ pub(crate) fn validate_split_amount(
     })
 }
 
+/// How a validated split of delegated stake divides the delegation: the
+/// stake taken off the source, and the stake the destination starts with.
+///
+/// A split that empties the source moves its whole delegation, which is the
+/// split less the source's rent exempt reserve, however much the destination
+/// already holds: prefunding can't activate stake. Otherwise the full split
+/// comes off the source, and the destination's rent exempt reserve is paid
+/// out of it, less what the destination was prefunded with.
+pub(crate) fn split_stake_amounts(
+    split_lamports: u64,
+    source_rent_exempt_reserve: u64,
+    destination_lamports: u64,
+    validated_split_info: &ValidatedSplitInfo,
+) -> (u64, u64) {
+    if validated_split_info.source_remaining_balance == 0 {
+        // this also ignores any positive delta between the source's reserve
+        // and the destination's, so stake can't be activated by splitting
+        // between accounts of different sizes
+        let stake = split_lamports.saturating_sub(source_rent_exempt_reserve);
+        (stake, stake)
+    } else {
+        let destination_rent_deficit = validated_split_info
+            .destination_rent_exempt_reserve
+            .saturating_sub(destination_lamports);
+        (split_lamports, split_lamports.saturating_sub(destination_rent_deficit))
+    }
+}
+
+/// Lamports a delegated stake account must keep on a partial withdraw: the
+/// rent exempt reserve plus all stake that is not yet fully cooled down. The
+/// returned flag is set when any stake is still counted, which forbids
+/// withdrawing the whole balance.
+pub fn get_withdraw_reserve<T: StakeHistoryGetEntry>(
+    meta: &Meta,
+    stake: &Stake,
+    epoch: u64,
+    stake_history: &T,
+    new_rate_activation_epoch: Option<[u8; 8]>,
+) -> Result<(u64, bool), ProgramError> {
+    // if we have a deactivation epoch and we're in cooldown
+    let staked = if epoch >= bytes_to_u64(stake.delegation.deactivation_epoch) {
+        stake.delegation.stake(
+            epoch.to_le_bytes(),
+            stake_history,
+            new_rate_activation_epoch,
+        )
+    } else {
+        // Assume full stake if the stake account hasn't been
+        //  de-activated, because in the future the exposed stake
+        //  might be higher than stake.stake() due to warmup
+        // so activating stake is never liquid, however little of it is
+        //  effective yet
+        bytes_to_u64(stake.delegation.stake)
+    };
+
+    let staked_and_reserve = staked
+        .checked_add(bytes_to_u64(meta.rent_exempt_reserve))
+        .ok_or(ProgramError::InsufficientFunds)?;
+    Ok((staked_and_reserve, staked != 0))
+}
+
+/// Ensure the withdraw amount is valid. A full withdraw closes the account and
+/// is only allowed once nothing is staked; a partial withdraw must leave at
+/// least `reserve` lamports behind.
+pub fn validate_withdraw_amount(
+    stake_account_lamports: u64,
+    withdraw_lamports: u64,
+    reserve: u64,
+    is_staked: bool,
+) -> ProgramResult {
+    if withdraw_lamports == stake_account_lamports {
+        // if the stake is active, we mustn't allow the account to go away
+        if is_staked {
+            return Err(ProgramError::InsufficientFunds);
+        }
+    } else {
+        // a partial withdrawal must not deplete the reserve
+        let withdraw_lamports_and_reserve = withdraw_lamports
+            .checked_add(reserve)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        if withdraw_lamports_and_reserve > stake_account_lamports {
+            return Err(ProgramError::InsufficientFunds);
+        }
+    }
+    Ok(())
+}
+
 //-------------- Solana Program Sysvar Copies ---------------
 
 //---------------- This Get Sysvar was assisted by AI, needs to be checked ----------------------
@@ -242,10 +332,21 @@ impl<T> Lazy<T> {
             value.as_ref().unwrap()
         }
     }
+
+    pub fn replace(&self, new_value: T) -> Option<T> {
+        // SAFETY: see `get_or_init`
+        unsafe { (*self.value.get()).replace(new_value) }
+    }
 }
 
 static SYSCALL_STUBS: Lazy<Box<dyn SyscallStubs>> = Lazy::new();
 
+impl<T> Default for Lazy<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 unsafe impl<T> Sync for Lazy<T> {} //although this is telling that is available for multithreading, we know it wont happen
 
 /// Builtin return values occupy the upper 32 bits
@@ -268,12 +369,74 @@ pub trait SyscallStubs: Sync + Send {
     ) -> u64 {
         UNSUPPORTED_SYSVAR
     }
+
+    fn sol_set_return_data(&self, _data: *const u8, _length: u64) {}
 }
 
 pub struct DefaultSyscallStubs {}
 
 impl SyscallStubs for DefaultSyscallStubs {}
 
+/// Installs the syscall stubs used off-chain, returning the previous ones.
+pub fn set_syscall_stubs(syscall_stubs: Box<dyn SyscallStubs>) -> Box<dyn SyscallStubs> {
+    SYSCALL_STUBS
+        .replace(syscall_stubs)
+        .unwrap_or_else(|| Box::new(DefaultSyscallStubs {}))
+}
+
+#[cfg(test)]
+std::thread_local! {
+    static MOCK_SYSVAR_DATA: core::cell::RefCell<Option<std::vec::Vec<u8>>> =
+        const { core::cell::RefCell::new(None) };
+    static MOCK_SYSVAR_CALLS: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+/// Serves the data passed to `mock_get_sysvar_syscall` on the calling thread.
+#[cfg(test)]
+struct MockGetSysvarSyscall;
+
+#[cfg(test)]
+impl SyscallStubs for MockGetSysvarSyscall {
+    fn sol_get_sysvar(
+        &self,
+        _sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64
+    ) -> u64 {
+        MOCK_SYSVAR_CALLS.set(MOCK_SYSVAR_CALLS.get() + 1);
+        MOCK_SYSVAR_DATA.with_borrow(|data| {
+            let Some(data) = data else {
+                return UNSUPPORTED_SYSVAR;
+            };
+            let (offset, length) = (offset as usize, length as usize);
+            let Some(src) = data.get(offset..offset.saturating_add(length)) else {
+                return crate::helpers::OFFSET_LENGTH_EXCEEDS_SYSVAR;
+            };
+            // SAFETY: `get_sysvar_slice` passes the length of `var_addr`
+            unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), var_addr, length) };
+            pinocchio::SUCCESS
+        })
+    }
+}
+
+/// Makes `data` what `get_sysvar` reads on the calling thread, whichever
+/// sysvar is asked for, so unit tests running in parallel each see their own.
+#[cfg(test)]
+pub(crate) fn mock_get_sysvar_syscall(data: &[u8]) {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        set_syscall_stubs(Box::new(MockGetSysvarSyscall));
+    });
+    MOCK_SYSVAR_DATA.with_borrow_mut(|mock| *mock = Some(data.to_vec()));
+}
+
+/// Calls to the mocked syscall on the calling thread since the last call.
+#[cfg(test)]
+pub(crate) fn take_mock_get_sysvar_calls() -> usize {
+    MOCK_SYSVAR_CALLS.replace(0)
+}
+
 #[allow(dead_code)]
 pub(crate) fn sol_get_sysvar(
     sysvar_id_addr: *const u8,
@@ -289,10 +452,15 @@ pub(crate) fn sol_get_sysvar(
     )
 }
 
+#[allow(dead_code)]
+pub(crate) fn sol_set_return_data(data: *const u8, length: u64) {
+    SYSCALL_STUBS.get_or_init(|| Box::new(DefaultSyscallStubs {})).sol_set_return_data(data, length)
+}
+
 //---------------- End of AI assistance ----------------------
 
 /// Handler for retrieving a slice of sysvar data from the `sol_get_sysvar`
-/// syscall.
+/// syscall. See `get_sysvar_slice` to tell its errors apart.
 pub fn get_sysvar(
     dst: &mut [u8],
     sysvar_id: &Pubkey,
@@ -301,41 +469,12 @@ pub fn get_sysvar(
 ) -> Result<(), ProgramError> {
     // Check that the provided destination buffer is large enough to hold the
     // requested data.
-    if dst.len() < (length as usize) {
-        return Err(ProgramError::InvalidArgument);
-    }
-
-    let sysvar_id = sysvar_id as *const _ as *const u8;
-    let var_addr = dst as *mut _ as *mut u8;
-
-    //if on Solana call the actual syscall
-    #[cfg(target_os = "solana")]
-    let result = unsafe {
-        pinocchio::syscalls::sol_get_sysvar(sysvar_id, var_addr, offset, length)
-    };
-
-    //if not on chain use the mock
-    #[cfg(not(target_os = "solana"))]
-    let result = sol_get_sysvar(sysvar_id, var_addr, offset, length);
-
-    match result {
-        SUCCESS => Ok(()),
-        e => Err(e.into()),
-    }
+    let dst = dst.get_mut(..length as usize).ok_or(ProgramError::InvalidArgument)?;
+    get_sysvar_slice(dst, sysvar_id, offset).map_err(ProgramError::from)
 }
 
 pub fn to_program_error(e: ProgramError) -> ProgramError {
-    ProgramError::try_from(e).unwrap_or(ProgramError::InvalidAccountData)
-}
-
-#[inline(always)]
-pub fn get_minimum_delegation() -> u64 {
-    if FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL {
-        const MINIMUM_DELEGATION_SOL: u64 = 1;
-        MINIMUM_DELEGATION_SOL * LAMPORTS_PER_SOL
-    } else {
-        1
-    }
+    e
 }
 
 pub fn do_authorize(
@@ -346,12 +485,11 @@ pub fn do_authorize(
     custodian: Option<&Pubkey>,
     clock: &Clock,
 ) -> ProgramResult {
-    let mut stake_account: pinocchio::account_info::RefMut<'_, StakeStateV2> =
-        try_get_stake_state_mut(stake_account_info)?;
+    let mut stake_account = try_get_stake_state_mut(stake_account_info)?;
     match *stake_account {
         StakeStateV2::Initialized(mut meta) => {
             meta.authorized
-                .authorize(
+                .authorize::<ClusterLockup>(
                     signers,
                     new_authority,
                     authority_type,
@@ -363,7 +501,7 @@ pub fn do_authorize(
         }
         StakeStateV2::Stake(mut meta, stake, stake_flags) => {
             meta.authorized
-                .authorize(
+                .authorize::<ClusterLockup>(
                     signers,
                     new_authority,
                     authority_type,
@@ -378,31 +516,10 @@ pub fn do_authorize(
     }
 }
 
-// Means that no more than RATE of current effective stake may be added or subtracted per
-// epoch.
-
-pub fn warmup_cooldown_rate(
-    current_epoch: [u8; 8],
-    new_rate_activation_epoch: Option<[u8; 8]>
-) -> f64 {
-    let current = bytes_to_u64(current_epoch);
-    let activation = new_rate_activation_epoch.map(bytes_to_u64).unwrap_or(u64::MAX);
-
-    if current < activation {
-        DEFAULT_WARMUP_COOLDOWN_RATE
-    } else {
-        NEW_WARMUP_COOLDOWN_RATE
-    }
-}
-
 pub fn add_le_bytes(lhs: [u8; 8], rhs: [u8; 8]) -> [u8; 8] {
     u64::from_le_bytes(lhs).saturating_add(u64::from_le_bytes(rhs)).to_le_bytes()
 }
 
-pub fn bytes_to_u64(bytes: [u8; 8]) -> u64 {
-    u64::from_le_bytes(bytes)
-}
-
 // MoveStake, MoveLamports, Withdraw, and AuthorizeWithSeed assemble signers explicitly
 pub fn collect_signers_checked<'a>(
     authority_info: Option<&'a AccountInfo>,
@@ -415,14 +532,14 @@ pub fn collect_signers_checked<'a>(
         if !authority_info.is_signer() {
             return Err(ProgramError::MissingRequiredSignature);
         }
-        add_signer(&mut signers, &mut signers_count, authority_info.key());
+        add_signer(&mut signers, &mut signers_count, authority_info.key())?;
     }
 
     let custodian = if let Some(custodian_info) = custodian_info {
         if !custodian_info.is_signer() {
             return Err(ProgramError::MissingRequiredSignature);
         }
-        add_signer(&mut signers, &mut signers_count, &custodian_info.key());
+        add_signer(&mut signers, &mut signers_count, custodian_info.key())?;
         Some(custodian_info.key())
     } else {
         None
@@ -436,6 +553,10 @@ pub fn add_signer(
     signers_count: &mut usize,
     account_key: &Pubkey,
 ) -> Result<(), ProgramError> {
+    // the same account may fill several roles, e.g. withdrawer and custodian
+    if signers[..*signers_count].iter().any(|signer| pubkey_eq(signer, account_key)) {
+        return Ok(());
+    }
     if *signers_count >= MAX_SIGNERS {
         return Err(ProgramError::MaxAccountsDataAllocationsExceeded);
     }
@@ -444,16 +565,17 @@ pub fn add_signer(
     Ok(())
 }
 
-pub fn move_stake_or_lamports_shared_checks(
+pub(crate) fn move_stake_or_lamports_shared_checks<S: SysvarReader>(
     source_stake_account_info: &AccountInfo,
     destination_stake_account_info: &AccountInfo,
     stake_authority_info: &AccountInfo,
+    sysvars: &S,
 ) -> Result<(MergeKind, MergeKind), ProgramError> {
     // authority must sign
     let (signers, _, _) = collect_signers_checked(Some(stake_authority_info), None)?;
 
     // confirm not the same account
-    if *source_stake_account_info.key() == *destination_stake_account_info.key() {
+    if pubkey_eq(source_stake_account_info.key(), destination_stake_account_info.key()) {
         return Err(ProgramError::InvalidInstructionData);
     }
 
@@ -464,17 +586,11 @@ pub fn move_stake_or_lamports_shared_checks(
         return Err(ProgramError::InvalidInstructionData);
     }
 
-    let clock = Clock::get()?;
-    let stake_history = StakeHistorySysvar(clock.epoch);
+    let clock = get_clock()?;
 
     // get_if_mergeable ensures accounts are not partly activated or in any form of deactivating
-    // we still need to exclude activating state ourselves
-    let source_merge_kind = MergeKind::get_if_mergeable(
-        &*get_stake_state(source_stake_account_info)?,
-        source_stake_account_info.lamports(),
-        &clock,
-        &stake_history,
-    )?;
+    // we still need to exclude activating state ourselves; for_account is what Merge uses too
+    let source_merge_kind = MergeKind::for_account(source_stake_account_info, &clock, sysvars)?;
 
     // Authorized staker is allowed to move stake
     source_merge_kind
@@ -484,12 +600,8 @@ pub fn move_stake_or_lamports_shared_checks(
         .map_err(to_program_error)?;
 
     // same transient assurance as with source
-    let destination_merge_kind = MergeKind::get_if_mergeable(
-        &*get_stake_state(destination_stake_account_info)?,
-        destination_stake_account_info.lamports(),
-        &clock,
-        &stake_history,
-    )?;
+    let destination_merge_kind =
+        MergeKind::for_account(destination_stake_account_info, &clock, sysvars)?;
 
     // ensure all authorities match and lockups match if lockup is in force
     MergeKind::metas_can_merge(
@@ -501,21 +613,58 @@ pub fn move_stake_or_lamports_shared_checks(
     Ok((source_merge_kind, destination_merge_kind))
 }
 
+/// Reads the clock through `get_sysvar`, which unlike `Clock::get` can be
+/// stubbed off-chain.
+pub fn get_clock() -> Result<Clock, ProgramError> {
+    let mut clock = Clock::default();
+    let clock_size = core::mem::size_of::<Clock>();
+    // SAFETY: `Clock` is `repr(C)` and made up of 8-byte integers only
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(&mut clock as *mut Clock as *mut u8, clock_size)
+    };
+    get_sysvar(dst, &CLOCK_ID, 0, clock_size as u64)?;
+    Ok(clock)
+}
+
+/// Reads the rent sysvar through `get_sysvar`, see `get_clock`.
+pub fn get_rent() -> Result<Rent, ProgramError> {
+    let mut data = [0u8; Rent::LEN];
+    get_sysvar(&mut data, &RENT_ID, 0, Rent::LEN as u64)?;
+    Ok(Rent {
+        lamports_per_byte_year: u64::from_le_bytes(data[0..8].try_into().unwrap()),
+        exemption_threshold: f64::from_le_bytes(data[8..16].try_into().unwrap()),
+        burn_percent: data[16],
+    })
+}
+
 //from_account_info helper for Clock while not implemente by Pinocchio
-pub fn clock_from_account_info(account_info: &AccountInfo) -> Result<Ref<Clock>, ProgramError> {
-    if account_info.data_len() != core::mem::size_of::<Clock>() {
-        return Err(ProgramError::InvalidAccountData);
+/// Checks a sysvar account that is read through the syscall instead, but
+/// which legacy clients still pass in its old slot. Anything else in the slot
+/// is rejected as native would have when it read the account.
+pub fn check_sysvar_account(account_info: &AccountInfo, sysvar_id: &Pubkey) -> ProgramResult {
+    if !pubkey_eq(account_info.key(), sysvar_id) {
+        return Err(ProgramError::InvalidArgument);
     }
+    Ok(())
+}
 
-    if account_info.key() != &CLOCK_ID {
-        return Err(ProgramError::InvalidAccountData);
+pub fn clock_from_account_info(account_info: &AccountInfo) -> Result<Clock, ProgramError> {
+    // native fails both checks with `InvalidArgument`, as sysvars do
+    if !pubkey_eq(account_info.key(), &CLOCK_ID) {
+        return Err(ProgramError::InvalidArgument);
     }
 
-    let data = account_info.try_borrow_data()?;
+    clock_from_data(&account_info.try_borrow_data()?)
+}
 
-    Ok(Ref::map(data, |data| unsafe {
-        &*(data.as_ptr() as *const Clock)
-    }))
+/// Copies the clock out of clock sysvar data, which needn't be aligned.
+pub fn clock_from_data(data: &[u8]) -> Result<Clock, ProgramError> {
+    if data.len() != core::mem::size_of::<Clock>() {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // SAFETY: the length is checked and `Clock` is made up of integers only
+    Ok(unsafe { core::ptr::read_unaligned(data.as_ptr() as *const Clock) })
 }
 
 /// After calling `validate_delegated_amount()`, this struct contains calculated
@@ -527,7 +676,7 @@ pub(crate) struct ValidatedDelegatedInfo {
 pub(crate) fn new_stake(
     stake: [u8; 8],
     voter_pubkey: &Pubkey,
-    vote_state: &VoteState,
+    credits: u64,
     activation_epoch: [u8; 8]
 ) -> Stake {
     Stake {
@@ -536,7 +685,7 @@ pub(crate) fn new_stake(
             bytes_to_u64(stake),
             activation_epoch
         ),
-        credits_observed: vote_state.credits().to_le_bytes(),
+        credits_observed: credits.to_le_bytes(),
     }
 }
 
@@ -545,44 +694,68 @@ pub(crate) fn new_stake(
 /// an error.
 pub(crate) fn validate_delegated_amount(
     account: &AccountInfo,
-    meta: &Meta
+    meta: &Meta,
+    minimum_delegation: u64
 ) -> Result<ValidatedDelegatedInfo, ProgramError> {
-    let stake_amount = account.lamports().saturating_sub(bytes_to_u64(meta.rent_exempt_reserve)); // can't stake the rent
+    let stake_amount = delegatable_stake(account.lamports(), meta, minimum_delegation)?;
+    Ok(ValidatedDelegatedInfo { stake_amount: stake_amount.to_le_bytes() })
+}
+
+/// Stake an account holding `lamports` delegates: all of it but the rent
+/// exempt reserve, which has to meet `minimum_delegation`. Only checked when
+/// delegating, so delegations made under a lower minimum carry on as they are.
+pub fn delegatable_stake(
+    lamports: u64,
+    meta: &Meta,
+    minimum_delegation: u64
+) -> Result<u64, ProgramError> {
+    let stake_amount = lamports.saturating_sub(bytes_to_u64(meta.rent_exempt_reserve)); // can't stake the rent
 
     // Stake accounts may be initialized with a stake amount below the minimum
     // delegation so check that the minimum is met before delegation.
-    if stake_amount < get_minimum_delegation() {
+    if stake_amount < minimum_delegation {
+        // the same error as native either way, but an account never funded
+        // past its reserve and one funded short are different client bugs
+        #[cfg(feature = "logging")]
+        if stake_amount == 0 {
+            pinocchio::msg!("Delegation: no lamports above the rent exempt reserve to delegate");
+        } else {
+            pinocchio::msg!("Delegation: stake above the rent exempt reserve is below the minimum delegation");
+        }
         return Err(StakeError::InsufficientDelegation.into());
     }
-    Ok(ValidatedDelegatedInfo { stake_amount: stake_amount.to_be_bytes() })
+    Ok(stake_amount)
 }
 
-pub(crate) fn redelegate_stake(
+/// Delegating stake that is still effective is only allowed to undo a
+/// deactivation made this same epoch, by delegating again to the same voter:
+/// the deactivation is rescinded and the stake carries on as if it had never
+/// been deactivated. Anything else is too soon to redelegate.
+pub fn revive_deactivating_stake(
+    stake: &mut Stake,
+    voter_pubkey: &Pubkey,
+    epoch: [u8; 8]
+) -> Result<(), ProgramError> {
+    if stake.delegation.voter_pubkey == *voter_pubkey && epoch == stake.delegation.deactivation_epoch {
+        stake.delegation.deactivation_epoch = u64::MAX.to_le_bytes();
+        Ok(())
+    } else {
+        Err(StakeError::TooSoonToRedelegate.into())
+    }
+}
+
+pub(crate) fn redelegate_stake<T: StakeHistoryGetEntry>(
     stake: &mut Stake,
     stake_lamports: [u8; 8],
     voter_pubkey: &Pubkey,
-    vote_state: &VoteState,
+    credits: u64,
     epoch: [u8;8],
-    stake_history: &StakeHistorySysvar
+    stake_history: &T,
+    new_rate_activation_epoch: Option<[u8; 8]>
 ) -> Result<(), ProgramError> {
     // If stake is currently active:
-    if
-        stake.stake(epoch, stake_history, PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH) !=
-        0
-    {
-        // If pubkey of new voter is the same as current,
-        // and we are scheduled to start deactivating this epoch,
-        // we rescind deactivation
-        if
-            stake.delegation.voter_pubkey == *voter_pubkey &&
-            epoch == stake.delegation.deactivation_epoch
-        {
-            stake.delegation.deactivation_epoch = u64::MAX.to_le_bytes();
-            return Ok(());
-        } else {
-            // can't redelegate to another pubkey if stake is active.
-            return Err(StakeError::TooSoonToRedelegate.into());
-        }
+    if stake.stake(epoch, stake_history, new_rate_activation_epoch) != 0 {
+        return revive_deactivating_stake(stake, voter_pubkey, epoch);
     }
     // Either the stake is freshly activated, is active but has been
     // deactivated this epoch, or has fully de-activated.
@@ -592,13 +765,12 @@ pub(crate) fn redelegate_stake(
     stake.delegation.activation_epoch = epoch;
     stake.delegation.deactivation_epoch = u64::MAX.to_le_bytes();
     stake.delegation.voter_pubkey = *voter_pubkey;
-    stake.credits_observed = vote_state.credits().to_be_bytes();
+    stake.credits_observed = credits.to_le_bytes();
     Ok(())
 }
 
 // --- Hash struct and impls ----
 
-#[cfg_attr(feature = "bytemuck", derive(Pod, Zeroable))]
 #[derive(Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[repr(transparent)]
 pub struct Hash(pub(crate) [u8; HASH_BYTES]);
@@ -756,4 +928,198 @@ impl Hash {
     pub fn toBytes(&self) -> Box<[u8]> {
         self.0.clone().into()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const VOTER: Pubkey = [1; 32];
+    const OTHER_VOTER: Pubkey = [2; 32];
+
+    fn deactivated_stake(deactivation_epoch: u64) -> Stake {
+        let mut stake = Stake {
+            delegation: Delegation::new(&VOTER, 1_000_000_000, 10u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        stake.deactivate(deactivation_epoch.to_le_bytes()).unwrap();
+        stake
+    }
+
+    const RESERVE: u64 = 2_282_880;
+
+    fn validated(source_remaining_balance: u64) -> ValidatedSplitInfo {
+        ValidatedSplitInfo {
+            source_remaining_balance,
+            destination_rent_exempt_reserve: RESERVE,
+        }
+    }
+
+    #[test]
+    fn test_split_stake_amounts_partial() {
+        let split = 5_000_000_000;
+        // prefunded with the reserve, or more: the whole split is stake
+        for destination_lamports in [RESERVE, RESERVE + 1, 2 * RESERVE] {
+            assert_eq!(
+                split_stake_amounts(split, RESERVE, destination_lamports, &validated(1)),
+                (split, split)
+            );
+        }
+        // empty, or short of the reserve: the split makes up the difference
+        assert_eq!(
+            split_stake_amounts(split, RESERVE, 0, &validated(1)),
+            (split, split - RESERVE)
+        );
+        assert_eq!(
+            split_stake_amounts(split, RESERVE, RESERVE - 1_000, &validated(1)),
+            (split, split - 1_000)
+        );
+        // and the source gives up all of it either way
+        assert_eq!(
+            split_stake_amounts(RESERVE - 1, RESERVE, 0, &validated(1)),
+            (RESERVE - 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_split_stake_amounts_whole_source() {
+        let split = RESERVE + 5_000_000_000;
+        // the destination gets the source's delegation, prefunded or not
+        for destination_lamports in [0, RESERVE - 1, RESERVE, 2 * RESERVE] {
+            assert_eq!(
+                split_stake_amounts(split, RESERVE, destination_lamports, &validated(0)),
+                (split - RESERVE, split - RESERVE)
+            );
+        }
+        // nor does a larger source reserve than the destination's move more
+        assert_eq!(
+            split_stake_amounts(split, RESERVE + 1_000, 0, &validated(0)),
+            (split - RESERVE - 1_000, split - RESERVE - 1_000)
+        );
+    }
+
+    const MINIMUM_DELEGATION: u64 = 1_000_000_000;
+
+    // mainnet's, which puts the reserve of a stake account at `RESERVE`
+    const RENT: Rent = Rent {
+        lamports_per_byte_year: 3_480,
+        exemption_threshold: 2.0,
+        burn_percent: 50,
+    };
+
+    fn split_meta() -> Meta {
+        let mut meta = Meta::default();
+        meta.set_rent_exempt_reserve(RESERVE);
+        meta
+    }
+
+    fn validate_split(
+        source_lamports: u64,
+        destination_lamports: u64,
+        split_lamports: u64,
+        source_is_active: bool,
+        rent: &Rent
+    ) -> Result<ValidatedSplitInfo, ProgramError> {
+        validate_split_amount(
+            source_lamports,
+            destination_lamports,
+            split_lamports,
+            &split_meta(),
+            StakeStateV2::size_of(),
+            MINIMUM_DELEGATION,
+            source_is_active,
+            rent
+        )
+    }
+
+    #[test]
+    fn test_validate_split_amount() {
+        let source = 2 * (RESERVE + MINIMUM_DELEGATION);
+        let info = validate_split(source, 0, RESERVE + MINIMUM_DELEGATION, false, &RENT).unwrap();
+        assert_eq!(info.source_remaining_balance, RESERVE + MINIMUM_DELEGATION);
+        assert_eq!(info.destination_rent_exempt_reserve, RESERVE);
+
+        // nothing, more than the source holds, or leaving it short
+        for split in [0, source + 1, source - RESERVE] {
+            assert_eq!(
+                validate_split(source, 0, split, false, &RENT).map(|_| ()),
+                Err(ProgramError::InsufficientFunds),
+                "{split}"
+            );
+        }
+        // the whole source may go, leaving nothing behind
+        let info = validate_split(source, 0, source, true, &RENT).unwrap();
+        assert_eq!(info.source_remaining_balance, 0);
+    }
+
+    #[test]
+    fn test_validate_split_amount_destination_reserve() {
+        let source = 2 * (RESERVE + MINIMUM_DELEGATION);
+        let split = RESERVE + MINIMUM_DELEGATION;
+
+        // splitting active stake needs a prefunded destination
+        assert_eq!(
+            validate_split(source, 0, split, true, &RENT).map(|_| ()),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert!(validate_split(source, RESERVE, split, true, &RENT).is_ok());
+
+        // the reserve is the rent passed in, not the source's
+        let dearer = Rent {
+            lamports_per_byte_year: 2 * RENT.lamports_per_byte_year,
+            ..RENT
+        };
+        let info = validate_split(source, 2 * RESERVE, split, true, &dearer).unwrap();
+        assert_eq!(info.destination_rent_exempt_reserve, 2 * RESERVE);
+        assert_eq!(
+            validate_split(source, RESERVE, split, true, &dearer).map(|_| ()),
+            Err(ProgramError::InsufficientFunds)
+        );
+    }
+
+    #[test]
+    fn test_revive_to_same_voter_in_deactivation_epoch() {
+        let mut stake = deactivated_stake(300);
+        assert_eq!(revive_deactivating_stake(&mut stake, &VOTER, 300u64.to_le_bytes()), Ok(()));
+        assert_eq!(stake.delegation.deactivation_epoch, u64::MAX.to_le_bytes());
+
+        // nothing else about the delegation changes
+        let mut expected = deactivated_stake(300);
+        expected.delegation.deactivation_epoch = u64::MAX.to_le_bytes();
+        assert_eq!(stake, expected);
+    }
+
+    #[test]
+    fn test_revive_to_other_voter() {
+        let mut stake = deactivated_stake(300);
+        assert_eq!(
+            revive_deactivating_stake(&mut stake, &OTHER_VOTER, 300u64.to_le_bytes()),
+            Err(StakeError::TooSoonToRedelegate.into())
+        );
+        assert_eq!(stake, deactivated_stake(300));
+    }
+
+    #[test]
+    fn test_revive_after_deactivation_epoch() {
+        // a later epoch, the stake is already cooling down
+        let mut stake = deactivated_stake(300);
+        assert_eq!(
+            revive_deactivating_stake(&mut stake, &VOTER, 301u64.to_le_bytes()),
+            Err(StakeError::TooSoonToRedelegate.into())
+        );
+        assert_eq!(stake, deactivated_stake(300));
+    }
+
+    #[test]
+    fn test_revive_active_stake() {
+        // never deactivated, so there is nothing to rescind
+        let mut stake = Stake {
+            delegation: Delegation::new(&VOTER, 1_000_000_000, 10u64.to_le_bytes()),
+            ..Stake::default()
+        };
+        assert_eq!(
+            revive_deactivating_stake(&mut stake, &VOTER, 300u64.to_le_bytes()),
+            Err(StakeError::TooSoonToRedelegate.into())
+        );
+    }
+}
@@ -1,5 +1,4 @@
 use pinocchio::{
-    account_info::{ AccountInfo, Ref },
     program_error::ProgramError,
     pubkey::Pubkey,
     sysvars::{ clock::{ Clock, Epoch, Slot, UnixTimestamp }, rent::Rent },
@@ -76,18 +75,53 @@ impl VoteState {
         3762 // see test_vote_state_size_of.
     }
 
-    #[inline]
-    pub fn from_account_info(account_info: &AccountInfo) -> Result<Ref<VoteState>, ProgramError> {
-        if account_info.data_len() != Self::size_of() {
-            return Err(ProgramError::InvalidAccountData);
+    /// The `credits()` of a serialized vote account, read in place: the
+    /// account holds a bincode `VoteStateVersions`, whose vectors can't be
+    /// cast to. Any of its versions is read, and fails as native's
+    /// deserialization would.
+    pub fn credits_from_bytes(data: &[u8]) -> Result<u64, ProgramError> {
+        // `(Pubkey, Epoch, Epoch)` entries, and an index and empty flag
+        const PRIOR_VOTERS_LEN: usize = MAX_ITEMS * (32 + 8 + 8) + 8 + 1;
+        // `(Pubkey, Epoch, Epoch, Slot)` entries and an index
+        const PRIOR_VOTERS_0_23_5_LEN: usize = MAX_ITEMS * (32 + 8 + 8 + 8) + 8;
+        const LOCKOUT_LEN: usize = 8 + 4;
+
+        let mut reader = BincodeReader { data, offset: 0 };
+        match reader.u32()? {
+            // V0_23_5
+            0 => {
+                // node, authorized voter and its epoch, prior voters,
+                // authorized withdrawer and commission
+                reader.skip(32 + 32 + 8 + PRIOR_VOTERS_0_23_5_LEN + 32 + 1)?;
+                reader.skip_vec(LOCKOUT_LEN)?;
+                reader.skip_option(8)?;
+            }
+            // V1_14_11 and Current, which adds a latency byte to each vote
+            version @ (1 | 2) => {
+                // node, authorized withdrawer and commission
+                reader.skip(32 + 32 + 1)?;
+                reader.skip_vec(if version == 2 { 1 + LOCKOUT_LEN } else { LOCKOUT_LEN })?;
+                reader.skip_option(8)?;
+                // authorized voters
+                reader.skip_vec(8 + 32)?;
+                reader.skip(PRIOR_VOTERS_LEN)?;
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
         }
-        let data = account_info.try_borrow_data()?;
-        Ok(Ref::map(data, |data| unsafe { Self::from_bytes(data) }))
-    }
 
-    #[inline(always)]
-    pub unsafe fn from_bytes(bytes: &[u8]) -> &Self {
-        &*(bytes.as_ptr() as *const Self)
+        // `(Epoch, credits, prev_credits)`, the latest last
+        let epoch_credits_len = reader.u64()?;
+        if epoch_credits_len == 0 {
+            return Ok(0);
+        }
+        reader.skip(
+            usize::try_from(epoch_credits_len - 1)
+                .ok()
+                .and_then(|len| len.checked_mul(24))
+                .ok_or(ProgramError::InvalidAccountData)?,
+        )?;
+        reader.skip(8)?;
+        reader.u64()
     }
 
     /// Number of "credits" owed to this account from the mining pool. Submit this
@@ -101,8 +135,121 @@ impl VoteState {
     }
 }
 
+/// Validator identity fields read straight from the serialized vote account,
+/// without decoding the variable-length vote history behind them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoteIdentity {
+    pub node_pubkey: Pubkey,
+    pub authorized_withdrawer: Pubkey,
+    pub commission: u8,
+}
+
+impl VoteIdentity {
+    // bincode `VoteStateVersions` tags whose layout starts with
+    // node_pubkey, authorized_withdrawer and commission
+    const V1_14_11: u32 = 1;
+    const CURRENT: u32 = 2;
+
+    const NODE_PUBKEY_OFFSET: usize = 4;
+    const AUTHORIZED_WITHDRAWER_OFFSET: usize = Self::NODE_PUBKEY_OFFSET + 32;
+    const COMMISSION_OFFSET: usize = Self::AUTHORIZED_WITHDRAWER_OFFSET + 32;
+
+    /// Reads the identity of a `V1_14_11` or `Current` vote account. The
+    /// pre-1.14 layout is rejected, as are uninitialized accounts.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ProgramError> {
+        let header = data
+            .get(..=Self::COMMISSION_OFFSET)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let version = u32::from_le_bytes(header[..4].try_into().unwrap());
+        if version != Self::V1_14_11 && version != Self::CURRENT {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Self::authorized_voters_len(data, version)? == 0 {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(Self {
+            node_pubkey: header[Self::NODE_PUBKEY_OFFSET..Self::AUTHORIZED_WITHDRAWER_OFFSET]
+                .try_into()
+                .unwrap(),
+            authorized_withdrawer: header
+                [Self::AUTHORIZED_WITHDRAWER_OFFSET..Self::COMMISSION_OFFSET]
+                .try_into()
+                .unwrap(),
+            commission: header[Self::COMMISSION_OFFSET],
+        })
+    }
+
+    /// Skips the votes and root slot to reach the authorized voters map, which
+    /// is empty only for an uninitialized account.
+    fn authorized_voters_len(data: &[u8], version: u32) -> Result<u64, ProgramError> {
+        // `LandedVote` adds a latency byte to each `Lockout`
+        let vote_len: u64 = if version == Self::CURRENT { 1 + 8 + 4 } else { 8 + 4 };
+        let read_u64 = |offset: usize| {
+            data.get(offset..offset.saturating_add(8))
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or(ProgramError::InvalidAccountData)
+        };
+
+        let votes_offset = Self::COMMISSION_OFFSET + 1;
+        let votes_len = read_u64(votes_offset)?;
+        let root_slot_offset = votes_len
+            .checked_mul(vote_len)
+            .and_then(|len| usize::try_from(len).ok())
+            .and_then(|len| len.checked_add(votes_offset + 8))
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let authorized_voters_offset = match data.get(root_slot_offset) {
+            Some(0) => root_slot_offset + 1,
+            Some(1) => root_slot_offset + 1 + 8,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        read_u64(authorized_voters_offset)
+    }
+}
+
+/// Reads bincode's fixed-width little-endian encoding, failing with
+/// `InvalidAccountData` past the end of `data`.
+struct BincodeReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BincodeReader<'a> {
+    fn skip(&mut self, len: usize) -> Result<&'a [u8], ProgramError> {
+        let end = self.offset.checked_add(len).ok_or(ProgramError::InvalidAccountData)?;
+        let bytes = self.data.get(self.offset..end).ok_or(ProgramError::InvalidAccountData)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ProgramError> {
+        Ok(u32::from_le_bytes(self.skip(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ProgramError> {
+        Ok(u64::from_le_bytes(self.skip(8)?.try_into().unwrap()))
+    }
+
+    /// Skips a `Vec` or `VecDeque` of `item_len` byte items.
+    fn skip_vec(&mut self, item_len: usize) -> Result<(), ProgramError> {
+        let len = usize::try_from(self.u64()?)
+            .ok()
+            .and_then(|len| len.checked_mul(item_len))
+            .ok_or(ProgramError::InvalidAccountData)?;
+        self.skip(len).map(|_| ())
+    }
+
+    /// Skips an `Option` of a `value_len` byte value.
+    fn skip_option(&mut self, value_len: usize) -> Result<(), ProgramError> {
+        match self.skip(1)?[0] {
+            0 => Ok(()),
+            1 => self.skip(value_len).map(|_| ()),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
 // -------------solana-vote-interface/src/state/mod.rs------------------
-/// Vote state
+// Vote state
 
 use super::AuthorizedVoters;
 
@@ -1242,3 +1389,79 @@ impl<I> CircBuf<I> {
 //         assert_eq!(circ_buf.last(), None);
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    #![allow(deprecated)]
+
+    use solana_sdk::{
+        clock::Clock,
+        pubkey::Pubkey,
+        vote::state::{VoteInit, VoteState1_14_11, VoteStateVersions},
+    };
+
+    use super::VoteIdentity;
+    use pinocchio::program_error::ProgramError;
+
+    fn vote_state() -> solana_sdk::vote::state::VoteState {
+        let mut vote_state = solana_sdk::vote::state::VoteState::new(
+            &VoteInit {
+                node_pubkey: Pubkey::new_from_array([1; 32]),
+                authorized_voter: Pubkey::new_from_array([2; 32]),
+                authorized_withdrawer: Pubkey::new_from_array([3; 32]),
+                commission: 7,
+            },
+            &Clock::default(),
+        );
+        vote_state.process_next_vote_slot(1, 0, 1);
+        vote_state.root_slot = Some(1);
+        vote_state
+    }
+
+    fn expected() -> VoteIdentity {
+        VoteIdentity {
+            node_pubkey: [1; 32],
+            authorized_withdrawer: [3; 32],
+            commission: 7,
+        }
+    }
+
+    #[test]
+    fn test_identity_from_current_and_v1_14_11() {
+        let data = bincode::serialize(&VoteStateVersions::new_current(vote_state())).unwrap();
+        assert_eq!(VoteIdentity::from_bytes(&data), Ok(expected()));
+
+        let old = VoteStateVersions::V1_14_11(Box::new(VoteState1_14_11::from(vote_state())));
+        let data = bincode::serialize(&old).unwrap();
+        assert_eq!(VoteIdentity::from_bytes(&data), Ok(expected()));
+    }
+
+    #[test]
+    fn test_identity_rejects_uninitialized_and_truncated() {
+        let data = bincode::serialize(&VoteStateVersions::new_current(
+            solana_sdk::vote::state::VoteState::default(),
+        ))
+        .unwrap();
+        assert_eq!(
+            VoteIdentity::from_bytes(&data),
+            Err(ProgramError::UninitializedAccount)
+        );
+
+        let data = bincode::serialize(&VoteStateVersions::new_current(vote_state())).unwrap();
+        assert_eq!(
+            VoteIdentity::from_bytes(&data[..60]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            VoteIdentity::from_bytes(&data[..80]),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let mut data = data;
+        data[0] = 0;
+        assert_eq!(
+            VoteIdentity::from_bytes(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use solana_pinocchio_starter::instruction::StakeInstruction;
+
+/// Instruction data that parses for `instruction`, so the account count is
+/// what it fails on.
+fn valid_data(instruction: StakeInstruction) -> Vec<u8> {
+    let mut data = vec![instruction as u8];
+    match instruction {
+        StakeInstruction::Split | StakeInstruction::Withdraw | StakeInstruction::MoveLamports => {
+            data.extend_from_slice(&1u64.to_le_bytes())
+        }
+        StakeInstruction::SetLockup => data.extend_from_slice(&[0, 0, 0]),
+        StakeInstruction::Initialize => data.extend_from_slice(&[0; 112]),
+        StakeInstruction::Authorize => data.extend_from_slice(&[0; 36]),
+        StakeInstruction::AuthorizeChecked => data.extend_from_slice(&[0; 4]),
+        StakeInstruction::AuthorizeWithSeed => {
+            data.extend_from_slice(&[7; 32]);
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&0u64.to_le_bytes());
+            data.extend_from_slice(&[8; 32]);
+        }
+        StakeInstruction::AuthorizeCheckedWithSeed => {
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&0u64.to_le_bytes());
+            data.extend_from_slice(&[8; 32]);
+        }
+        _ => {}
+    }
+    data
+}
+
+fn accounts(count: usize) -> Vec<Input> {
+    (0..count)
+        .map(|i| {
+            TestAccount::new([i as u8 + 1; 32], Pubkey::default(), 0, vec![])
+                .signer()
+                .writable()
+                .into()
+        })
+        .collect()
+}
+
+#[test]
+fn test_not_enough_account_keys() {
+    for discriminant in 0..=u8::MAX {
+        let Ok(instruction) = StakeInstruction::try_from(&discriminant) else {
+            continue;
+        };
+        let data = valid_data(instruction);
+        for count in 0..instruction.min_accounts() {
+            let result = with_accounts(&accounts(count), |accounts| process(accounts, &data));
+            assert_eq!(
+                result,
+                Err(ProgramError::NotEnoughAccountKeys),
+                "{instruction:?} with {count} accounts"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_enough_account_keys() {
+    // processed instructions get past the count with exactly enough, and
+    // fail on the accounts themselves instead
+    for instruction in [
+        StakeInstruction::Initialize,
+        StakeInstruction::InitializeChecked,
+        StakeInstruction::Authorize,
+        StakeInstruction::AuthorizeChecked,
+        StakeInstruction::DelegateStake,
+        StakeInstruction::Split,
+        StakeInstruction::Withdraw,
+        StakeInstruction::SetLockup,
+        StakeInstruction::Merge,
+        StakeInstruction::AuthorizeWithSeed,
+        StakeInstruction::AuthorizeCheckedWithSeed,
+        StakeInstruction::MoveLamports,
+    ] {
+        let count = instruction.min_accounts();
+        let result = with_accounts(&accounts(count), |accounts| {
+            process(accounts, &valid_data(instruction))
+        });
+        assert!(
+            !matches!(result, Ok(()) | Err(ProgramError::NotEnoughAccountKeys)),
+            "{instruction:?} with {count} accounts: {result:?}"
+        );
+    }
+}
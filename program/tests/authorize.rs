@@ -0,0 +1,411 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use pinocchio_stake_interface::{client, consts::CLOCK_ID};
+use solana_pinocchio_starter::{
+    error::StakeError,
+    helpers::create_with_seed,
+    state::{get_stake_state, Authorized, Lockup, Meta, StakeAuthorize, StakeStateV2},
+};
+
+const STAKE: Pubkey = [1; 32];
+const STAKER: Pubkey = [2; 32];
+const WITHDRAWER: Pubkey = [3; 32];
+const CUSTODIAN: Pubkey = [4; 32];
+const NEW_AUTHORITY: Pubkey = [5; 32];
+const OTHER: Pubkey = [6; 32];
+const BASE: Pubkey = [7; 32];
+const SEED_OWNER: Pubkey = [8; 32];
+
+const SEED: &str = "authorize";
+
+/// An initialized stake whose lockup runs out at `lockup_epoch`.
+fn initialized(lockup_epoch: u64) -> StakeStateV2 {
+    StakeStateV2::Initialized(Meta {
+        rent_exempt_reserve: STAKE_RENT_EXEMPT_RESERVE.to_le_bytes(),
+        authorized: Authorized {
+            staker: STAKER,
+            withdrawer: WITHDRAWER,
+        },
+        lockup: Lockup {
+            unix_timestamp: 0i64.to_le_bytes(),
+            epoch: lockup_epoch.to_le_bytes(),
+            custodian: CUSTODIAN,
+        },
+    })
+}
+
+fn authorized(state: StakeStateV2) -> Authorized {
+    match state {
+        StakeStateV2::Initialized(meta) | StakeStateV2::Stake(meta, ..) => meta.authorized,
+        _ => panic!("no authorities: {state:?}"),
+    }
+}
+
+/// Runs `instruction` on a stake in `state`, with every account it marks
+/// signing but `unsigned`, returning the result and the state left behind.
+fn run(
+    instruction: &client::Instruction,
+    state: StakeStateV2,
+    unsigned: Option<Pubkey>,
+) -> (Result<(), ProgramError>, StakeStateV2) {
+    set_clock(&clock());
+    let inputs: Vec<Input> = instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            let mut account = if meta.pubkey == STAKE {
+                stake_account(STAKE, 10 * STAKE_RENT_EXEMPT_RESERVE, state)
+            } else if meta.pubkey == CLOCK_ID {
+                clock_account(&clock())
+            } else {
+                TestAccount::new(meta.pubkey, Pubkey::default(), 0, vec![])
+            };
+            account.is_signer = meta.is_signer && Some(meta.pubkey) != unsigned;
+            account.into()
+        })
+        .collect();
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &instruction.data);
+        (result, get_stake_state(&accounts[0]).unwrap())
+    })
+}
+
+/// Hands `stake_authorize` of `state` to `NEW_AUTHORITY`, signed by
+/// `authority` and, if there is one, by `custodian` when it is
+/// `custodian_signs`, returning the result and the state left behind.
+fn authorize_with(
+    state: StakeStateV2,
+    authority: Pubkey,
+    stake_authorize: StakeAuthorize,
+    custodian: Option<Pubkey>,
+    custodian_signs: bool,
+) -> (Result<(), ProgramError>, StakeStateV2) {
+    let instruction = client::authorize(
+        &STAKE,
+        &authority,
+        &NEW_AUTHORITY,
+        stake_authorize,
+        custodian.as_ref(),
+    );
+    run(&instruction, state, custodian.filter(|_| !custodian_signs))
+}
+
+fn authorize(
+    state: StakeStateV2,
+    authority: Pubkey,
+    stake_authorize: StakeAuthorize,
+) -> (Result<(), ProgramError>, StakeStateV2) {
+    authorize_with(state, authority, stake_authorize, None, false)
+}
+
+#[test]
+fn test_authorize_staker() {
+    // either authority may hand over the staker, lockup or not
+    for authority in [STAKER, WITHDRAWER] {
+        let (result, state) = authorize(
+            initialized(CURRENT_EPOCH + 100),
+            authority,
+            StakeAuthorize::Staker,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            authorized(state),
+            Authorized {
+                staker: NEW_AUTHORITY,
+                withdrawer: WITHDRAWER,
+            }
+        );
+    }
+
+    let (result, state) = authorize(initialized(0), OTHER, StakeAuthorize::Staker);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_stake_state_eq(&state, &initialized(0));
+}
+
+#[test]
+fn test_authorize_withdrawer() {
+    let (result, state) = authorize(initialized(0), WITHDRAWER, StakeAuthorize::Withdrawer);
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        authorized(state),
+        Authorized {
+            staker: STAKER,
+            withdrawer: NEW_AUTHORITY,
+        }
+    );
+
+    // only the withdrawer can replace itself
+    for authority in [STAKER, CUSTODIAN] {
+        let (result, state) = authorize(initialized(0), authority, StakeAuthorize::Withdrawer);
+        assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+        assert_stake_state_eq(&state, &initialized(0));
+    }
+}
+
+#[test]
+fn test_authorize_withdrawer_under_lockup() {
+    let locked = initialized(CURRENT_EPOCH + 1);
+
+    // without the custodian, or with it passed but not signing
+    for (custodian, expected) in [
+        (None, StakeError::CustodianMissing),
+        (Some(CUSTODIAN), StakeError::CustodianSignatureMissing),
+    ] {
+        let (result, state) = authorize_with(
+            locked,
+            WITHDRAWER,
+            StakeAuthorize::Withdrawer,
+            custodian,
+            false,
+        );
+        assert_eq!(result, Err(expected.into()), "{custodian:?}");
+        assert_stake_state_eq(&state, &locked);
+    }
+
+    // someone else signing as custodian
+    let (result, state) = authorize_with(
+        locked,
+        WITHDRAWER,
+        StakeAuthorize::Withdrawer,
+        Some(OTHER),
+        true,
+    );
+    assert_eq!(result, Err(StakeError::LockupInForce.into()));
+    assert_stake_state_eq(&state, &locked);
+
+    let (result, state) = authorize_with(
+        locked,
+        WITHDRAWER,
+        StakeAuthorize::Withdrawer,
+        Some(CUSTODIAN),
+        true,
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(authorized(state).withdrawer, NEW_AUTHORITY);
+
+    // the custodian alone can't
+    let (result, _) = authorize_with(
+        locked,
+        CUSTODIAN,
+        StakeAuthorize::Withdrawer,
+        Some(CUSTODIAN),
+        true,
+    );
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+
+    // a lockup that ran out this epoch no longer asks for it
+    let (result, _) = authorize(
+        initialized(CURRENT_EPOCH),
+        WITHDRAWER,
+        StakeAuthorize::Withdrawer,
+    );
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn test_authorize_checked() {
+    for (stake_authorize, authority) in [
+        (StakeAuthorize::Staker, STAKER),
+        (StakeAuthorize::Withdrawer, WITHDRAWER),
+    ] {
+        let instruction =
+            client::authorize_checked(&STAKE, &authority, &NEW_AUTHORITY, stake_authorize, None);
+        let (result, state) = run(&instruction, initialized(0), None);
+        assert_eq!(result, Ok(()), "{stake_authorize:?}");
+        let authorized = authorized(state);
+        assert_eq!(
+            match stake_authorize {
+                StakeAuthorize::Staker => authorized.staker,
+                StakeAuthorize::Withdrawer => authorized.withdrawer,
+            },
+            NEW_AUTHORITY
+        );
+
+        // the new authority has to sign, and so does the current one
+        for unsigned in [NEW_AUTHORITY, authority] {
+            let (result, state) = run(&instruction, initialized(0), Some(unsigned));
+            assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+            assert_stake_state_eq(&state, &initialized(0));
+        }
+    }
+}
+
+#[test]
+fn test_authorize_checked_withdrawer_under_lockup() {
+    let locked = initialized(CURRENT_EPOCH + 1);
+    let instruction = |custodian: Option<&Pubkey>| {
+        client::authorize_checked(
+            &STAKE,
+            &WITHDRAWER,
+            &NEW_AUTHORITY,
+            StakeAuthorize::Withdrawer,
+            custodian,
+        )
+    };
+
+    let (result, _) = run(&instruction(None), locked, None);
+    assert_eq!(result, Err(StakeError::CustodianMissing.into()));
+    let (result, _) = run(&instruction(Some(&CUSTODIAN)), locked, Some(CUSTODIAN));
+    assert_eq!(result, Err(StakeError::CustodianSignatureMissing.into()));
+    let (result, _) = run(&instruction(Some(&OTHER)), locked, None);
+    assert_eq!(result, Err(StakeError::LockupInForce.into()));
+
+    let (result, state) = run(&instruction(Some(&CUSTODIAN)), locked, None);
+    assert_eq!(result, Ok(()));
+    assert_eq!(authorized(state).withdrawer, NEW_AUTHORITY);
+}
+
+/// `initialized`, but with both authorities derived from `BASE`, so only its
+/// signature counts.
+fn with_seed_authority(lockup_epoch: u64) -> StakeStateV2 {
+    let derived = create_with_seed(&BASE, SEED.as_bytes(), &SEED_OWNER).unwrap();
+    let mut state = initialized(lockup_epoch);
+    if let StakeStateV2::Initialized(meta) = &mut state {
+        meta.authorized = Authorized {
+            staker: derived,
+            withdrawer: derived,
+        };
+    }
+    state
+}
+
+#[test]
+fn test_authorize_with_seed() {
+    let instruction = |seed: &str, stake_authorize, custodian: Option<&Pubkey>| {
+        client::authorize_with_seed(
+            &STAKE,
+            &BASE,
+            seed,
+            &SEED_OWNER,
+            &NEW_AUTHORITY,
+            stake_authorize,
+            custodian,
+        )
+    };
+
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        let (result, state) = run(
+            &instruction(SEED, stake_authorize, None),
+            with_seed_authority(0),
+            None,
+        );
+        assert_eq!(result, Ok(()), "{stake_authorize:?}");
+        let authorized = authorized(state);
+        assert_eq!(
+            match stake_authorize {
+                StakeAuthorize::Staker => authorized.staker,
+                StakeAuthorize::Withdrawer => authorized.withdrawer,
+            },
+            NEW_AUTHORITY
+        );
+
+        // the base not signing, or a seed deriving someone else
+        for (seed, unsigned) in [(SEED, Some(BASE)), ("other", None)] {
+            let (result, state) = run(
+                &instruction(seed, stake_authorize, None),
+                with_seed_authority(0),
+                unsigned,
+            );
+            assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+            assert_stake_state_eq(&state, &with_seed_authority(0));
+        }
+    }
+
+    // the custodian signs for itself under lockup, the seed doesn't cover it
+    let locked = with_seed_authority(CURRENT_EPOCH + 1);
+    let (result, _) = run(
+        &instruction(SEED, StakeAuthorize::Withdrawer, None),
+        locked,
+        None,
+    );
+    assert_eq!(result, Err(StakeError::CustodianMissing.into()));
+    // unlike `Authorize`, a custodian passed unsigned is refused outright
+    let (result, _) = run(
+        &instruction(SEED, StakeAuthorize::Withdrawer, Some(&CUSTODIAN)),
+        locked,
+        Some(CUSTODIAN),
+    );
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    let (result, state) = run(
+        &instruction(SEED, StakeAuthorize::Withdrawer, Some(&CUSTODIAN)),
+        locked,
+        None,
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(authorized(state).withdrawer, NEW_AUTHORITY);
+}
+
+#[test]
+fn test_authorize_checked_with_seed() {
+    let instruction = |seed: &str, stake_authorize, custodian: Option<&Pubkey>| {
+        client::authorize_checked_with_seed(
+            &STAKE,
+            &BASE,
+            seed,
+            &SEED_OWNER,
+            &NEW_AUTHORITY,
+            stake_authorize,
+            custodian,
+        )
+    };
+
+    for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+        let (result, state) = run(
+            &instruction(SEED, stake_authorize, None),
+            with_seed_authority(0),
+            None,
+        );
+        assert_eq!(result, Ok(()), "{stake_authorize:?}");
+        let authorized = authorized(state);
+        assert_eq!(
+            match stake_authorize {
+                StakeAuthorize::Staker => authorized.staker,
+                StakeAuthorize::Withdrawer => authorized.withdrawer,
+            },
+            NEW_AUTHORITY
+        );
+
+        // the base and the new authority both have to sign, and the seed has
+        // to derive the current authority
+        for (seed, unsigned) in [
+            (SEED, Some(BASE)),
+            (SEED, Some(NEW_AUTHORITY)),
+            ("other", None),
+        ] {
+            let (result, state) = run(
+                &instruction(seed, stake_authorize, None),
+                with_seed_authority(0),
+                unsigned,
+            );
+            assert_eq!(
+                result,
+                Err(ProgramError::MissingRequiredSignature),
+                "{seed} {unsigned:?}"
+            );
+            assert_stake_state_eq(&state, &with_seed_authority(0));
+        }
+    }
+
+    let locked = with_seed_authority(CURRENT_EPOCH + 1);
+    let (result, _) = run(
+        &instruction(SEED, StakeAuthorize::Withdrawer, None),
+        locked,
+        None,
+    );
+    assert_eq!(result, Err(StakeError::CustodianMissing.into()));
+    let (result, _) = run(
+        &instruction(SEED, StakeAuthorize::Withdrawer, Some(&CUSTODIAN)),
+        locked,
+        Some(CUSTODIAN),
+    );
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    let (result, state) = run(
+        &instruction(SEED, StakeAuthorize::Withdrawer, Some(&CUSTODIAN)),
+        locked,
+        None,
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(authorized(state).withdrawer, NEW_AUTHORITY);
+}
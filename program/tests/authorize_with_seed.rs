@@ -0,0 +1,238 @@
+//! A stake pool controlling stake accounts through authorities derived with
+//! seeds from its PDA, rotating them with `AuthorizeWithSeed` over CPI.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use solana_pinocchio_starter::state::{Authorized, StakeAuthorize, StakeStateV2};
+
+/// Stand-in for the pool program. There is no runtime to `invoke_signed`
+/// into off-chain, so `invoke_signed` here does the runtime's part: marks the
+/// accounts the signer seeds derive as signers of the stake instruction.
+mod pool {
+    use pinocchio_stake_interface::{client, consts::CLOCK_ID};
+    use solana_sdk::pubkey::Pubkey as SdkPubkey;
+
+    use super::*;
+
+    pub const PROGRAM_ID: Pubkey = [0x50; 32];
+    const AUTHORITY_SEED: &[u8] = b"stake_authority";
+
+    /// The pool's PDA, the base of every authority it hands out.
+    pub fn authority(program_id: &Pubkey) -> (Pubkey, u8) {
+        let (authority, bump) = SdkPubkey::find_program_address(
+            &[AUTHORITY_SEED],
+            &SdkPubkey::new_from_array(*program_id),
+        );
+        (authority.to_bytes(), bump)
+    }
+
+    /// Authority of one of the pool's stake accounts.
+    pub fn stake_authority(program_id: &Pubkey, seed: &str) -> Pubkey {
+        let base = SdkPubkey::new_from_array(authority(program_id).0);
+        SdkPubkey::create_with_seed(&base, seed, &SdkPubkey::new_from_array(*program_id))
+            .unwrap()
+            .to_bytes()
+    }
+
+    /// The pool instruction that moves `stake_authorize` of `stake` from the
+    /// authority derived with `seed` to the one derived with `new_seed`,
+    /// issued by `program_id`.
+    pub fn rotate(
+        program_id: &Pubkey,
+        stake: &mut TestAccount,
+        stake_authorize: StakeAuthorize,
+        seed: &str,
+        new_seed: &str,
+    ) -> ProgramResult {
+        let (authority, bump) = authority(program_id);
+        let instruction = client::authorize_with_seed(
+            &stake.key,
+            &authority,
+            seed,
+            program_id,
+            &stake_authority(program_id, new_seed),
+            stake_authorize,
+            None,
+        );
+        invoke_signed(
+            program_id,
+            &instruction,
+            stake,
+            &[&[AUTHORITY_SEED, &[bump]]],
+        )
+    }
+
+    pub fn invoke_signed(
+        program_id: &Pubkey,
+        instruction: &client::Instruction,
+        stake: &mut TestAccount,
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let pdas: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| {
+                SdkPubkey::create_program_address(seeds, &SdkPubkey::new_from_array(*program_id))
+                    .unwrap()
+                    .to_bytes()
+            })
+            .collect();
+
+        let inputs: Vec<Input> = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let account = if meta.pubkey == stake.key {
+                    stake.clone()
+                } else if meta.pubkey == CLOCK_ID {
+                    clock_account(&clock())
+                } else {
+                    TestAccount::new(meta.pubkey, Pubkey::default(), 0, vec![])
+                };
+                let mut account = if meta.is_writable {
+                    account.writable()
+                } else {
+                    account
+                };
+                account.is_signer = meta.is_signer && pdas.contains(&meta.pubkey);
+                account.into()
+            })
+            .collect();
+
+        with_accounts(&inputs, |accounts| {
+            let result = process(accounts, &instruction.data);
+            stake.data = accounts[0].try_borrow_data().unwrap().to_vec();
+            result
+        })
+    }
+}
+
+const STAKE: Pubkey = [1; 32];
+const OTHER_POOL: Pubkey = [0x51; 32];
+
+/// A stake account of the pool whose staker and withdrawer are derived with
+/// `seed`.
+fn pool_stake_account(seed: &str) -> TestAccount {
+    let authority = pool::stake_authority(&pool::PROGRAM_ID, seed);
+    stake_account(
+        STAKE,
+        STAKE_RENT_EXEMPT_RESERVE,
+        StakeStateV2::Initialized(initialized_meta(authority, authority)),
+    )
+}
+
+fn authorized(stake: &TestAccount) -> Authorized {
+    match StakeStateV2::try_from_bytes(&stake.data).unwrap() {
+        StakeStateV2::Initialized(meta) => meta.authorized,
+        state => panic!("unexpected state {state:?}"),
+    }
+}
+
+#[test]
+fn test_pool_rotates_authorities() {
+    let mut stake = pool_stake_account("validator-a");
+
+    assert_eq!(
+        pool::rotate(
+            &pool::PROGRAM_ID,
+            &mut stake,
+            StakeAuthorize::Staker,
+            "validator-a",
+            "validator-a/1",
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        authorized(&stake).staker,
+        pool::stake_authority(&pool::PROGRAM_ID, "validator-a/1")
+    );
+
+    // the rotated authority is just as usable
+    assert_eq!(
+        pool::rotate(
+            &pool::PROGRAM_ID,
+            &mut stake,
+            StakeAuthorize::Staker,
+            "validator-a/1",
+            "validator-a/2",
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        pool::rotate(
+            &pool::PROGRAM_ID,
+            &mut stake,
+            StakeAuthorize::Withdrawer,
+            "validator-a",
+            "validator-a/2",
+        ),
+        Ok(())
+    );
+    let rotated = pool::stake_authority(&pool::PROGRAM_ID, "validator-a/2");
+    assert_eq!(authorized(&stake).staker, rotated);
+    assert_eq!(authorized(&stake).withdrawer, rotated);
+}
+
+#[test]
+fn test_rotate_with_stale_seed() {
+    let mut stake = pool_stake_account("validator-a");
+    assert_eq!(
+        pool::rotate(
+            &pool::PROGRAM_ID,
+            &mut stake,
+            StakeAuthorize::Staker,
+            "validator-b",
+            "validator-b/1",
+        ),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        authorized(&stake),
+        authorized(&pool_stake_account("validator-a"))
+    );
+}
+
+#[test]
+fn test_other_program_cannot_rotate() {
+    // another program signing for its own PDA with the same seeds derives
+    // other authorities
+    let mut stake = pool_stake_account("validator-a");
+    assert_eq!(
+        pool::rotate(
+            &OTHER_POOL,
+            &mut stake,
+            StakeAuthorize::Withdrawer,
+            "validator-a",
+            "validator-a/1",
+        ),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        authorized(&stake),
+        authorized(&pool_stake_account("validator-a"))
+    );
+}
+
+#[test]
+fn test_rotate_without_signing_for_base() {
+    let mut stake = pool_stake_account("validator-a");
+    let (authority, _) = pool::authority(&pool::PROGRAM_ID);
+    let instruction = pinocchio_stake_interface::client::authorize_with_seed(
+        &STAKE,
+        &authority,
+        "validator-a",
+        &pool::PROGRAM_ID,
+        &[9; 32],
+        StakeAuthorize::Staker,
+        None,
+    );
+    assert_eq!(
+        pool::invoke_signed(&pool::PROGRAM_ID, &instruction, &mut stake, &[]),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+    assert_eq!(
+        authorized(&stake),
+        authorized(&pool_stake_account("validator-a"))
+    );
+}
@@ -0,0 +1,87 @@
+//! The invariants the `canary` feature checks, and that the instructions
+//! keep them; run with `--features canary` to have the entrypoint assert them.
+
+mod common;
+
+use common::*;
+use pinocchio::{pubkey::Pubkey, sysvars::clock::Clock};
+use solana_pinocchio_starter::{
+    helpers::total_lamports, instruction::StakeInstruction, state::StakeStateV2,
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+
+#[test]
+fn test_total_lamports_counts_duplicates_once() {
+    let inputs: Vec<Input> = vec![
+        TestAccount::new(SOURCE, Pubkey::default(), 10, vec![]).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 32, vec![]).into(),
+        Input::Duplicate(0),
+        Input::Duplicate(1),
+    ];
+    with_accounts(&inputs, |accounts| {
+        assert_eq!(accounts.len(), 4);
+        assert_eq!(total_lamports(accounts), 42);
+    });
+}
+
+#[test]
+fn test_move_lamports_conserves_lamports() {
+    set_clock(&Clock::default());
+
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            SOURCE,
+            STAKE_RENT_EXEMPT_RESERVE + 1_000,
+            StakeStateV2::Initialized(meta),
+        )
+        .into(),
+        stake_account(
+            DESTINATION,
+            STAKE_RENT_EXEMPT_RESERVE,
+            StakeStateV2::Initialized(meta),
+        )
+        .into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    let mut data = vec![StakeInstruction::MoveLamports as u8];
+    data.extend_from_slice(&1_000u64.to_le_bytes());
+
+    with_accounts(&inputs, |accounts| {
+        let before = total_lamports(accounts);
+        assert_eq!(process(accounts, &data), Ok(()));
+        assert_eq!(total_lamports(accounts), before);
+        assert_eq!(accounts[1].lamports(), STAKE_RENT_EXEMPT_RESERVE + 1_000);
+    });
+}
+
+#[test]
+#[should_panic(expected = "MoveLamports created 1 lamports")]
+fn test_harness_catches_created_lamports() {
+    let data = [StakeInstruction::MoveLamports as u8];
+    assert_lamports_conserved(41, 42, &data);
+}
+
+#[test]
+#[should_panic(expected = "Withdraw destroyed 2 lamports")]
+fn test_harness_catches_destroyed_lamports() {
+    let data = [StakeInstruction::Withdraw as u8];
+    assert_lamports_conserved(42, 40, &data);
+}
+
+#[test]
+#[should_panic(
+    expected = "expected: Initialized staker=03030303 withdrawer=04040404\n  actual:   Uninitialized"
+)]
+fn test_harness_summarizes_differing_states() {
+    assert_stake_state_eq(
+        &StakeStateV2::Uninitialized,
+        &StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+    );
+}
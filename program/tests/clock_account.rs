@@ -0,0 +1,87 @@
+//! Clock accounts passed to the handlers that still read the clock from one.
+//! Native fails an account that isn't the clock sysvar, or doesn't hold a
+//! clock, with `InvalidArgument`, as it does any sysvar account, rather than
+//! `InvalidAccountData`.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::state::{clock_from_account_info, StakeAuthorize, StakeStateV2};
+
+const STAKE: Pubkey = [1; 32];
+const STAKER: Pubkey = [2; 32];
+const WITHDRAWER: Pubkey = [3; 32];
+const NEW_STAKER: Pubkey = [4; 32];
+const NOT_CLOCK: Pubkey = [5; 32];
+
+fn read(account: TestAccount) -> Result<(u64, u64, i64), ProgramError> {
+    with_accounts(&[account.into()], |accounts| {
+        clock_from_account_info(&accounts[0])
+            .map(|clock| (clock.slot, clock.epoch, clock.unix_timestamp))
+    })
+}
+
+#[test]
+fn test_clock_account_read() {
+    let clock = Clock {
+        slot: 10,
+        ..clock()
+    };
+    assert_eq!(
+        read(clock_account(&clock)),
+        Ok((10, CURRENT_EPOCH, CURRENT_TIMESTAMP))
+    );
+}
+
+#[test]
+fn test_clock_account_of_another_key() {
+    let mut account = clock_account(&clock());
+    account.key = NOT_CLOCK;
+    assert_eq!(read(account), Err(ProgramError::InvalidArgument));
+}
+
+#[test]
+fn test_clock_account_of_wrong_size() {
+    let len = clock_account(&clock()).data.len();
+    for size in [0, 8, len - 1, len + 1] {
+        let mut account = clock_account(&clock());
+        account.data.resize(size, 0);
+        assert_eq!(
+            read(account),
+            Err(ProgramError::InvalidArgument),
+            "{size} bytes"
+        );
+    }
+}
+
+#[test]
+fn test_instruction_with_bad_clock_account() {
+    set_clock(&clock());
+
+    let mut not_clock = clock_account(&clock());
+    not_clock.key = NOT_CLOCK;
+    let mut truncated = clock_account(&clock());
+    truncated.data.truncate(8);
+
+    for clock_account in [not_clock, truncated] {
+        let inputs = [
+            stake_account(
+                STAKE,
+                2 * STAKE_RENT_EXEMPT_RESERVE,
+                StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+            )
+            .into(),
+            clock_account.into(),
+            TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+                .signer()
+                .into(),
+        ];
+        let data =
+            client::authorize(&STAKE, &STAKER, &NEW_STAKER, StakeAuthorize::Staker, None).data;
+        with_accounts(&inputs, |accounts| {
+            assert_eq!(process(accounts, &data), Err(ProgramError::InvalidArgument));
+        });
+    }
+}
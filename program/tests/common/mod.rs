@@ -0,0 +1,657 @@
+//! Host-side harness for driving processors with real `AccountInfo`s.
+//!
+//! Accounts are serialized into the same input layout the runtime hands to
+//! the program and then read back with pinocchio's own `deserialize`, so
+//! borrow tracking and duplicate accounts behave as they do on chain.
+#![allow(dead_code)]
+
+use core::{cell::RefCell, mem::MaybeUninit};
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    sync::{Mutex, Once},
+};
+
+use flate2::read::GzDecoder;
+use mollusk_svm::{result::InstructionResult, Mollusk};
+use pinocchio_stake_interface::client;
+
+use pinocchio::{
+    account_info::{AccountInfo, MAX_PERMITTED_DATA_INCREASE},
+    entrypoint::deserialize,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::{
+        clock::Clock,
+        rent::{
+            Rent, DEFAULT_BURN_PERCENT, DEFAULT_EXEMPTION_THRESHOLD,
+            DEFAULT_LAMPORTS_PER_BYTE_YEAR, RENT_ID,
+        },
+    },
+    ProgramResult, SUCCESS,
+};
+use solana_pinocchio_starter::{
+    consts::{CLOCK_ID, VOTE_PROGRAM_ID},
+    error::{FromPrimitive, StakeError},
+    helpers::{total_lamports, StakeStateSummary, OFFSET_LENGTH_EXCEEDS_SYSVAR},
+    instruction::StakeInstruction,
+    processor::process_instruction,
+    state::{
+        set_syscall_stubs, stake_history, Meta, StakeHistoryEntry, StakeStateV2, SyscallStubs,
+        VoteState, UNSUPPORTED_SYSVAR,
+    },
+};
+use solana_sdk::account::Account;
+
+const MAX_ACCOUNTS: usize = 16;
+const NON_DUP_MARKER: u8 = u8::MAX;
+
+pub const STAKE_RENT_EXEMPT_RESERVE: u64 = 2_282_880;
+/// What the default rent asks of a vote account.
+pub const VOTE_ACCOUNT_LAMPORTS: u64 = 27_074_400;
+
+#[derive(Clone, Debug)]
+pub struct TestAccount {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+impl TestAccount {
+    pub fn new(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) -> Self {
+        Self {
+            key,
+            owner,
+            lamports,
+            data,
+            is_signer: false,
+            is_writable: false,
+        }
+    }
+
+    pub fn signer(mut self) -> Self {
+        self.is_signer = true;
+        self
+    }
+
+    pub fn writable(mut self) -> Self {
+        self.is_writable = true;
+        self
+    }
+}
+
+/// An entry of the instruction account list: either a new account or a
+/// repeat of an earlier one, referenced by its position.
+pub enum Input {
+    Account(TestAccount),
+    Duplicate(u8),
+}
+
+impl From<TestAccount> for Input {
+    fn from(account: TestAccount) -> Self {
+        Input::Account(account)
+    }
+}
+
+fn serialize(inputs: &[Input], instruction_data: &[u8], program_id: &Pubkey) -> Vec<u64> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(inputs.len() as u64).to_le_bytes());
+
+    for input in inputs {
+        match input {
+            Input::Account(account) => {
+                bytes.push(NON_DUP_MARKER);
+                bytes.push(account.is_signer as u8);
+                bytes.push(account.is_writable as u8);
+                bytes.push(0); // executable
+                bytes.extend_from_slice(&[0; 4]); // original_data_len
+                bytes.extend_from_slice(&account.key);
+                bytes.extend_from_slice(&account.owner);
+                bytes.extend_from_slice(&account.lamports.to_le_bytes());
+                bytes.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+                bytes.extend_from_slice(&account.data);
+                bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+                bytes.resize(bytes.len().next_multiple_of(8), 0);
+                bytes.extend_from_slice(&u64::MAX.to_le_bytes()); // rent_epoch
+            }
+            Input::Duplicate(index) => {
+                bytes.push(*index);
+                bytes.extend_from_slice(&[0; 7]);
+            }
+        }
+    }
+
+    bytes.extend_from_slice(&(instruction_data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(instruction_data);
+    bytes.extend_from_slice(program_id);
+
+    // u64 backing storage keeps the buffer aligned like the runtime's
+    let mut buffer = vec![0u64; bytes.len().div_ceil(8)];
+    unsafe {
+        core::ptr::copy_nonoverlapping(bytes.as_ptr(), buffer.as_mut_ptr() as *mut u8, bytes.len());
+    }
+    buffer
+}
+
+/// Runs `f` over the `AccountInfo`s for `inputs`, as they would be passed to
+/// the processor by the entrypoint.
+pub fn with_accounts<R>(inputs: &[Input], f: impl FnOnce(&[AccountInfo]) -> R) -> R {
+    assert!(inputs.len() <= MAX_ACCOUNTS);
+
+    let mut buffer = serialize(inputs, &[], &solana_pinocchio_starter::ID);
+    let mut accounts: [MaybeUninit<AccountInfo>; MAX_ACCOUNTS] =
+        core::array::from_fn(|_| MaybeUninit::uninit());
+
+    let (_, count, _) =
+        unsafe { deserialize::<MAX_ACCOUNTS>(buffer.as_mut_ptr() as *mut u8, &mut accounts) };
+    let accounts =
+        unsafe { core::slice::from_raw_parts(accounts.as_ptr() as *const AccountInfo, count) };
+
+    f(accounts)
+}
+
+/// Where `process` tallies failed instructions, one `<instruction> <error>`
+/// line each, for the report in `tests/error_coverage.rs`.
+pub const ERROR_COVERAGE_LOG: &str = concat!(env!("CARGO_TARGET_TMPDIR"), "/error_coverage.log");
+
+/// Runs `instruction_data` through the program entrypoint, recording the
+/// error it fails with, if any, and checking it conserved lamports.
+pub fn process(accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+    let before = total_lamports(accounts);
+    let result = process_instruction(&solana_pinocchio_starter::ID, accounts, instruction_data);
+    assert_lamports_conserved(before, total_lamports(accounts), instruction_data);
+    if let Err(error) = &result {
+        let line = format!(
+            "{} {}\n",
+            instruction_name(instruction_data),
+            error_name(error)
+        );
+        // a single small append, so lines from parallel tests don't interleave
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(ERROR_COVERAGE_LOG)
+            .unwrap();
+        log.write_all(line.as_bytes()).unwrap();
+    }
+    result
+}
+
+/// Fails the test unless an instruction left the lamports of its accounts,
+/// each counted once, at the `before` total. Neither `process` nor mollusk
+/// charges fees, so nothing may leave, let alone be created, and this holds
+/// whether the instruction succeeded or not.
+pub fn assert_lamports_conserved(before: u128, after: u128, instruction_data: &[u8]) {
+    let instruction = instruction_name(instruction_data);
+    assert!(
+        after <= before,
+        "{instruction} created {} lamports",
+        after - before
+    );
+    assert_eq!(
+        after,
+        before,
+        "{instruction} destroyed {} lamports",
+        before - after
+    );
+}
+
+fn instruction_name(instruction_data: &[u8]) -> String {
+    match instruction_data.first().map(StakeInstruction::try_from) {
+        Some(Ok(instruction)) => format!("{instruction:?}"),
+        Some(Err(_)) => "Unknown".to_string(),
+        None => "Empty".to_string(),
+    }
+}
+
+/// `mollusk.process_instruction`, checked with `assert_lamports_conserved`
+/// like `process`.
+pub fn process_in_mollusk(
+    mollusk: &Mollusk,
+    instruction: &solana_sdk::instruction::Instruction,
+    accounts: &[(solana_sdk::pubkey::Pubkey, Account)],
+) -> InstructionResult {
+    let result = mollusk.process_instruction(instruction, accounts);
+    assert_lamports_conserved(
+        keyed_total_lamports(accounts),
+        keyed_total_lamports(&result.resulting_accounts),
+        &instruction.data,
+    );
+    result
+}
+
+/// `total_lamports` of keyed accounts, as mollusk takes and returns them.
+fn keyed_total_lamports(accounts: &[(solana_sdk::pubkey::Pubkey, Account)]) -> u128 {
+    accounts
+        .iter()
+        .enumerate()
+        .filter(|(index, (key, _))| !accounts[..*index].iter().any(|(earlier, _)| earlier == key))
+        .map(|(_, (_, account))| account.lamports as u128)
+        .sum()
+}
+
+pub fn error_name(error: &ProgramError) -> String {
+    match error {
+        ProgramError::Custom(code) => match StakeError::from_u64(*code as u64) {
+            Some(stake_error) => format!("StakeError::{stake_error:?}"),
+            None => format!("Custom({code})"),
+        },
+        error => format!("ProgramError::{error:?}"),
+    }
+}
+
+/// When tests run, unless they need another time.
+pub const CURRENT_EPOCH: u64 = 300;
+pub const CURRENT_TIMESTAMP: i64 = 1_700_000_000;
+
+/// A clock in `CURRENT_EPOCH` at `CURRENT_TIMESTAMP`.
+pub fn clock() -> Clock {
+    Clock {
+        epoch: CURRENT_EPOCH,
+        unix_timestamp: CURRENT_TIMESTAMP,
+        ..Clock::default()
+    }
+}
+
+/// A clock in `epoch`, with the rest of it zeroed.
+pub fn clock_at(epoch: u64) -> Clock {
+    Clock {
+        epoch,
+        ..Clock::default()
+    }
+}
+
+pub fn clock_account(clock: &Clock) -> TestAccount {
+    let mut data = Vec::with_capacity(core::mem::size_of::<Clock>());
+    data.extend_from_slice(&clock.slot.to_le_bytes());
+    data.extend_from_slice(&clock.epoch_start_timestamp.to_le_bytes());
+    data.extend_from_slice(&clock.epoch.to_le_bytes());
+    data.extend_from_slice(&clock.leader_schedule_epoch.to_le_bytes());
+    data.extend_from_slice(&clock.unix_timestamp.to_le_bytes());
+    TestAccount::new(CLOCK_ID, Pubkey::default(), 1, data)
+}
+
+/// `clock` moved to `epoch`, with the wall clock left where it was. Lockups
+/// expire on both, so tests need to move one without the other.
+pub fn warp_to_epoch(clock: &Clock, epoch: u64) -> Clock {
+    Clock {
+        epoch,
+        leader_schedule_epoch: epoch + 1,
+        ..*clock
+    }
+}
+
+/// `clock` moved to `unix_timestamp`, with the epoch left where it was.
+pub fn warp_to_timestamp(clock: &Clock, unix_timestamp: i64) -> Clock {
+    Clock {
+        unix_timestamp,
+        ..*clock
+    }
+}
+
+pub fn stake_history_account() -> TestAccount {
+    TestAccount::new(
+        solana_pinocchio_starter::state::stake_history::ID,
+        Pubkey::default(),
+        1,
+        vec![0; 8],
+    )
+}
+
+pub fn stake_state_data(state: StakeStateV2) -> Vec<u8> {
+    // a `Vec<u8>` isn't aligned for a `StakeStateV2`, so serialize rather
+    // than write through a reference
+    let mut data = [0u8; StakeStateV2::size_of()];
+    state.write_to(&mut data);
+    data.to_vec()
+}
+
+/// Fails the test unless `actual` is `expected`, naming each field that
+/// differs rather than dumping both states.
+#[track_caller]
+pub fn assert_stake_state_eq(actual: &StakeStateV2, expected: &StakeStateV2) {
+    let diffs = client::diff_stake_states(expected, actual);
+    if !diffs.is_empty() {
+        let fields: Vec<String> = diffs.iter().map(|diff| format!("  {diff}")).collect();
+        panic!(
+            "stake state differs\n  expected: {}\n  actual:   {}\nexpected -> actual:\n{}",
+            StakeStateSummary(expected),
+            StakeStateSummary(actual),
+            fields.join("\n")
+        );
+    }
+    // anything the diff can't name, such as padding, still fails
+    assert_eq!(actual, expected);
+}
+
+/// `assert_stake_state_eq` on stake account data, which must decode.
+#[track_caller]
+pub fn assert_stake_data_eq(data: &[u8], expected: &StakeStateV2) {
+    match client::stake_state_from_data(data) {
+        Ok(actual) => assert_stake_state_eq(&actual, expected),
+        Err(error) => panic!("stake account data doesn't decode: {error:?}"),
+    }
+    assert_eq!(data, stake_state_data(*expected));
+}
+
+/// Calls `f` with a copy of `data` starting at each offset from an 8-byte
+/// aligned address in turn, so readers meet the data at every alignment
+/// account data may have.
+pub fn with_misaligned(data: &[u8], mut f: impl FnMut(&[u8], usize)) {
+    // u64 backing storage pins down the alignment of offset 0
+    let mut buffer = vec![0u64; data.len().div_ceil(8) + 1];
+    let bytes = unsafe {
+        core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, buffer.len() * 8)
+    };
+    for offset in 0..8 {
+        bytes.fill(0);
+        bytes[offset..offset + data.len()].copy_from_slice(data);
+        f(&bytes[offset..offset + data.len()], offset);
+    }
+}
+
+/// The data of `instruction`, whose only argument is an amount of lamports.
+pub fn amount_data(instruction: StakeInstruction, lamports: u64) -> Vec<u8> {
+    let mut data = vec![instruction as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    data
+}
+
+/// Runs `data` over `inputs` in the `CURRENT_EPOCH`, checking it fails with
+/// `error` and leaves every balance as it was.
+pub fn assert_rejected(inputs: &[Input], data: &[u8], error: ProgramError, case: &str) {
+    set_clock(&clock());
+    set_default_rent();
+    with_accounts(inputs, |accounts| {
+        let before: Vec<u64> = accounts.iter().map(|account| account.lamports()).collect();
+        assert_eq!(process(accounts, data), Err(error), "{case}");
+        let after: Vec<u64> = accounts.iter().map(|account| account.lamports()).collect();
+        assert_eq!(before, after, "{case}");
+    });
+}
+
+pub fn stake_account(key: Pubkey, lamports: u64, state: StakeStateV2) -> TestAccount {
+    TestAccount::new(
+        key,
+        solana_pinocchio_starter::ID,
+        lamports,
+        stake_state_data(state),
+    )
+    .writable()
+}
+
+/// A rent exempt vote account, holding a current vote state that has earned
+/// `epoch_credits`.
+pub fn vote_account(key: Pubkey, epoch_credits: &[(u64, u64, u64)]) -> TestAccount {
+    #[allow(deprecated)]
+    use solana_sdk::vote::state::{VoteState as NativeVoteState, VoteStateVersions};
+
+    let vote_state = NativeVoteState {
+        epoch_credits: epoch_credits.to_vec(),
+        ..NativeVoteState::default()
+    };
+    TestAccount::new(
+        key,
+        VOTE_PROGRAM_ID,
+        VOTE_ACCOUNT_LAMPORTS,
+        vote_account_data(&VoteStateVersions::new_current(vote_state)),
+    )
+}
+
+/// `versions` serialized into an account sized for the current vote state.
+#[allow(deprecated)]
+pub fn vote_account_data(versions: &solana_sdk::vote::state::VoteStateVersions) -> Vec<u8> {
+    let mut data = vec![0; VoteState::size_of()];
+    bincode::serialize_into(&mut data[..], versions).unwrap();
+    data
+}
+
+pub fn initialized_meta(staker: Pubkey, withdrawer: Pubkey) -> Meta {
+    let mut meta = Meta {
+        rent_exempt_reserve: STAKE_RENT_EXEMPT_RESERVE.to_le_bytes(),
+        ..Meta::default()
+    };
+    meta.authorized.staker = staker;
+    meta.authorized.withdrawer = withdrawer;
+    meta
+}
+
+/// A stake account of `lamports`, initialized with `staker` and `withdrawer`
+/// and no lockup.
+pub fn initialized_account(
+    key: Pubkey,
+    lamports: u64,
+    staker: Pubkey,
+    withdrawer: Pubkey,
+) -> TestAccount {
+    stake_account(
+        key,
+        lamports,
+        StakeStateV2::Initialized(initialized_meta(staker, withdrawer)),
+    )
+}
+
+/// A wallet that only signs.
+pub fn signer(key: Pubkey) -> TestAccount {
+    TestAccount::new(key, Pubkey::default(), 0, vec![]).signer()
+}
+
+static SYSVARS: Mutex<Vec<(Pubkey, Vec<u8>)>> = Mutex::new(Vec::new());
+static INSTALL_STUBS: Once = Once::new();
+
+/// A read of the stake history sysvar, with the epoch of the record it was
+/// served from, if any.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StakeHistoryRead {
+    pub offset: u64,
+    pub length: u64,
+    pub epoch: Option<u64>,
+}
+
+thread_local! {
+    // processors run on the test's own thread, so reads are kept per thread
+    // to tell apart the tests running in parallel
+    static STAKE_HISTORY_READS: RefCell<Vec<StakeHistoryRead>> = const { RefCell::new(Vec::new()) };
+    static RETURN_DATA: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Serves the sysvars registered with `set_sysvar`, anything else stays
+/// unsupported, and keeps the return data the program sets.
+struct SysvarStubs;
+
+impl SyscallStubs for SysvarStubs {
+    fn sol_get_sysvar(
+        &self,
+        sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64,
+    ) -> u64 {
+        let sysvar_id = unsafe { &*(sysvar_id_addr as *const Pubkey) };
+        let sysvars = SYSVARS.lock().unwrap();
+        let Some((_, data)) = sysvars.iter().find(|(id, _)| id == sysvar_id) else {
+            return UNSUPPORTED_SYSVAR;
+        };
+
+        let (offset, length) = (offset as usize, length as usize);
+        let src = data.get(offset..offset.saturating_add(length));
+        if *sysvar_id == stake_history::ID {
+            let read = StakeHistoryRead {
+                offset: offset as u64,
+                length: length as u64,
+                epoch: src
+                    .and_then(|src| src.first_chunk::<8>())
+                    .map(|epoch| u64::from_le_bytes(*epoch)),
+            };
+            STAKE_HISTORY_READS.with_borrow_mut(|reads| reads.push(read));
+        }
+        let Some(src) = src else {
+            return OFFSET_LENGTH_EXCEEDS_SYSVAR;
+        };
+        unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), var_addr, length) };
+        SUCCESS
+    }
+
+    fn sol_set_return_data(&self, data: *const u8, length: u64) {
+        let data = unsafe { core::slice::from_raw_parts(data, length as usize) };
+        RETURN_DATA.with_borrow_mut(|return_data| *return_data = Some(data.to_vec()));
+    }
+}
+
+fn install_stubs() {
+    INSTALL_STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(SysvarStubs));
+    });
+}
+
+/// Makes `data` the contents of sysvar `id` for `get_sysvar` in this test
+/// binary. The stubs are process-wide, so tests running in parallel must
+/// agree on the value.
+pub fn set_sysvar(id: Pubkey, data: Vec<u8>) {
+    install_stubs();
+    let mut sysvars = SYSVARS.lock().unwrap();
+    sysvars.retain(|(existing, _)| *existing != id);
+    sysvars.push((id, data));
+}
+
+/// Registers stake history as the runtime holds it during `current_epoch`.
+/// The entry for an epoch is only written once the epoch ends, so there is
+/// never one for the current epoch or later.
+pub fn set_stake_history(current_epoch: u64, entries: &[(u64, StakeHistoryEntry)]) {
+    assert_no_current_epoch_entry(current_epoch, entries);
+    set_sysvar(stake_history::ID, stake_history_data(entries));
+}
+
+pub fn assert_no_current_epoch_entry(current_epoch: u64, entries: &[(u64, StakeHistoryEntry)]) {
+    for (epoch, _) in entries {
+        assert!(
+            *epoch < current_epoch,
+            "stake history entry for epoch {epoch}, at or after the current epoch {current_epoch}"
+        );
+    }
+}
+
+/// The return data last set on this thread since the last call, if any.
+pub fn take_return_data() -> Option<Vec<u8>> {
+    install_stubs();
+    RETURN_DATA.with_borrow_mut(Option::take)
+}
+
+/// The delegated stakes `extensions` builds of Split and Merge report in
+/// their return data, as they decode from `take_return_data`.
+pub fn take_returned_stakes() -> Option<Vec<u64>> {
+    take_return_data().map(|data| {
+        data.chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    })
+}
+
+/// Stake history reads made on this thread since the last call.
+pub fn take_stake_history_reads() -> Vec<StakeHistoryRead> {
+    STAKE_HISTORY_READS.with_borrow_mut(core::mem::take)
+}
+
+/// Asserts the stake history reads made on this thread since the last call
+/// each fetched whole records, the newest of an epoch before `current_epoch`. A request
+/// for the current epoch has no record to land on, and would read whatever
+/// lies at the offset it computed. Reads of the length prefix alone, made
+/// for epochs past `MAX_ENTRIES`, are let through. Returns how many reads
+/// there were.
+pub fn assert_stake_history_reads_before(current_epoch: u64) -> usize {
+    let reads = take_stake_history_reads();
+    for read in &reads {
+        if (read.offset, read.length) == (0, 8) {
+            continue;
+        }
+        assert!(
+            read.length > 0 && read.length % 32 == 0,
+            "{read:?} is not whole records"
+        );
+        assert_eq!((read.offset - 8) % 32, 0, "{read:?} is not at a record");
+        if let Some(epoch) = read.epoch {
+            assert!(
+                epoch < current_epoch,
+                "{read:?} read epoch {epoch} during epoch {current_epoch}"
+            );
+        }
+    }
+    reads.len()
+}
+
+pub fn set_clock(clock: &Clock) {
+    set_sysvar(CLOCK_ID, clock_account(clock).data);
+}
+
+/// Registers the default rent, under which a stake account's reserve is
+/// `STAKE_RENT_EXEMPT_RESERVE`.
+pub fn default_rent() -> Rent {
+    let rent = Rent {
+        lamports_per_byte_year: DEFAULT_LAMPORTS_PER_BYTE_YEAR,
+        exemption_threshold: DEFAULT_EXEMPTION_THRESHOLD,
+        burn_percent: DEFAULT_BURN_PERCENT,
+    };
+    assert_eq!(
+        rent.minimum_balance(StakeStateV2::size_of()),
+        STAKE_RENT_EXEMPT_RESERVE
+    );
+    rent
+}
+
+pub fn rent_data(rent: &Rent) -> Vec<u8> {
+    let mut data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+    data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+    data.push(rent.burn_percent);
+    data
+}
+
+pub fn set_default_rent() {
+    set_sysvar(RENT_ID, rent_data(&default_rent()));
+}
+
+/// The rent sysvar account, which legacy clients pass to `Initialize`.
+pub fn rent_account() -> TestAccount {
+    TestAccount::new(RENT_ID, Pubkey::default(), 1, rent_data(&default_rent()))
+}
+
+/// Directory of the gzip'd fixtures, see `tests/fixtures/README.md`.
+pub const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+/// Embeds the gzip'd fixture `tests/fixtures/<name>.bin.gz` in the test
+/// binary at build time and decompresses it.
+#[macro_export]
+macro_rules! fixture {
+    ($name:literal) => {
+        $crate::common::decompress_fixture(include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/",
+            $name,
+            ".bin.gz"
+        )))
+    };
+}
+
+pub fn decompress_fixture(blob: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(blob).read_to_end(&mut data).unwrap();
+    data
+}
+
+/// Serializes stake history as the sysvar holds it: a `u64` length, then
+/// `(epoch, entry)` pairs, newest epoch first.
+pub fn stake_history_data(entries: &[(u64, StakeHistoryEntry)]) -> Vec<u8> {
+    let mut entries = entries.to_vec();
+    entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut data = (entries.len() as u64).to_le_bytes().to_vec();
+    for (epoch, entry) in entries {
+        data.extend_from_slice(&epoch.to_le_bytes());
+        data.extend_from_slice(&entry.effective);
+        data.extend_from_slice(&entry.activating);
+        data.extend_from_slice(&entry.deactivating);
+    }
+    data
+}
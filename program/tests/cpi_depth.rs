@@ -0,0 +1,396 @@
+//! The stake program reached through nested invocations, transaction →
+//! program A → program B → stake, with authorities that are PDAs of the
+//! programs along the way.
+//!
+//! The handlers only see `is_signer` on their accounts, and rely on the
+//! runtime to have set it the same way at any depth. `invoke_chain` plays
+//! the runtime's part for every hop: an account may be marked a signer of
+//! the next instruction only if it signed the current one or is a PDA of
+//! the current program, signed for with its seeds; anything else is a
+//! privilege escalation and the invocation fails before the callee runs.
+
+mod common;
+
+use common::*;
+use pinocchio::{
+    program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock, ProgramResult,
+};
+use pinocchio_stake_interface::{
+    client::{self, AccountMeta, Instruction},
+    consts::CLOCK_ID,
+};
+use solana_pinocchio_starter::{
+    instruction::StakeInstruction,
+    state::{stake_history, Authorized, Lockup, Meta, StakeAuthorize, StakeStateV2},
+};
+use solana_sdk::pubkey::Pubkey as SdkPubkey;
+
+const PROGRAM_A: Pubkey = [0xa0; 32];
+const PROGRAM_B: Pubkey = [0xb0; 32];
+const STAKE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const FEE_PAYER: Pubkey = [3; 32];
+
+const VAULT_SEED: &[u8] = b"vault";
+const CUSTODY_SEED: &[u8] = b"custody";
+
+const STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + 1_000_000_000;
+
+fn pda(program_id: &Pubkey, seed: &[u8]) -> (Pubkey, u8) {
+    let (address, bump) =
+        SdkPubkey::find_program_address(&[seed], &SdkPubkey::new_from_array(*program_id));
+    (address.to_bytes(), bump)
+}
+
+/// A program between the transaction and the stake program.
+struct Hop {
+    program_id: Pubkey,
+    /// The single-seed PDAs it signs for with `invoke_signed`.
+    signs_for: Vec<&'static [u8]>,
+    /// The accounts it marks as signers of the instruction it issues. The
+    /// last hop issues the stake instruction, whose metas say this instead.
+    marks: Vec<Pubkey>,
+}
+
+impl Hop {
+    fn new(program_id: Pubkey) -> Self {
+        Self {
+            program_id,
+            signs_for: vec![],
+            marks: vec![],
+        }
+    }
+
+    fn signing_for(mut self, seed: &'static [u8]) -> Self {
+        self.signs_for.push(seed);
+        self
+    }
+
+    fn marking(mut self, key: Pubkey) -> Self {
+        self.marks.push(key);
+        self
+    }
+
+    fn pdas(&self) -> Vec<Pubkey> {
+        self.signs_for
+            .iter()
+            .map(|seed| {
+                let (address, bump) = pda(&self.program_id, seed);
+                let derived = SdkPubkey::create_program_address(
+                    &[seed, &[bump]],
+                    &SdkPubkey::new_from_array(self.program_id),
+                )
+                .unwrap();
+                assert_eq!(derived.to_bytes(), address);
+                address
+            })
+            .collect()
+    }
+}
+
+/// The runtime refusing an invocation that marks as signer an account the
+/// caller has no signature for.
+#[derive(Debug, PartialEq, Eq)]
+struct PrivilegeEscalation {
+    caller: Pubkey,
+    key: Pubkey,
+}
+
+/// Runs `instruction` as issued by the last of `hops`, each invoked by the
+/// one before, and the first by a transaction signed by the fee payer alone.
+/// The stake program runs at depth `hops.len() + 1`. `stake` is updated with
+/// the stake account as the instruction left it.
+fn invoke_chain(
+    hops: &[Hop],
+    instruction: &Instruction,
+    stake: &mut TestAccount,
+    clock: &Clock,
+) -> Result<ProgramResult, PrivilegeEscalation> {
+    let mut signers = vec![FEE_PAYER];
+    for (depth, hop) in hops.iter().enumerate() {
+        let marks: Vec<Pubkey> = if depth + 1 == hops.len() {
+            instruction
+                .accounts
+                .iter()
+                .filter(|meta| meta.is_signer)
+                .map(|meta| meta.pubkey)
+                .collect()
+        } else {
+            hop.marks.clone()
+        };
+        let privileged: Vec<Pubkey> = signers.iter().copied().chain(hop.pdas()).collect();
+        if let Some(key) = marks.iter().find(|key| !privileged.contains(key)) {
+            return Err(PrivilegeEscalation {
+                caller: hop.program_id,
+                key: *key,
+            });
+        }
+        signers = marks;
+    }
+
+    let inputs: Vec<Input> = instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            let account = if meta.pubkey == stake.key {
+                stake.clone()
+            } else if meta.pubkey == CLOCK_ID {
+                clock_account(clock)
+            } else if meta.pubkey == stake_history::ID {
+                stake_history_account()
+            } else {
+                TestAccount::new(meta.pubkey, Pubkey::default(), 0, vec![])
+            };
+            let mut account = if meta.is_writable {
+                account.writable()
+            } else {
+                account
+            };
+            account.is_signer = signers.contains(&meta.pubkey);
+            account.into()
+        })
+        .collect();
+
+    Ok(with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &instruction.data);
+        stake.lamports = accounts[0].lamports();
+        stake.data = accounts[0].try_borrow_data().unwrap().to_vec();
+        result
+    }))
+}
+
+fn withdraw(withdrawer: &Pubkey, custodian: Option<&Pubkey>, lamports: u64) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::writable(STAKE),
+        AccountMeta::writable(DESTINATION),
+        AccountMeta::readonly(CLOCK_ID),
+        AccountMeta::readonly(stake_history::ID),
+        AccountMeta::readonly_signer(*withdrawer),
+    ];
+    if let Some(custodian) = custodian {
+        accounts.push(AccountMeta::readonly_signer(*custodian));
+    }
+    let mut data = vec![StakeInstruction::Withdraw as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    Instruction {
+        program_id: solana_pinocchio_starter::ID,
+        accounts,
+        data,
+    }
+}
+
+fn initialized_stake(meta: Meta) -> TestAccount {
+    stake_account(STAKE, STAKE_LAMPORTS, StakeStateV2::Initialized(meta))
+}
+
+fn authorized(stake: &TestAccount) -> Authorized {
+    match StakeStateV2::try_from_bytes(&stake.data).unwrap() {
+        StakeStateV2::Initialized(meta) => meta.authorized,
+        state => panic!("unexpected state {state:?}"),
+    }
+}
+
+#[test]
+fn test_withdraw_signed_by_pda_at_each_depth() {
+    // depth 2: A signs for its vault and invokes stake directly
+    let (vault_a, _) = pda(&PROGRAM_A, VAULT_SEED);
+    let mut stake = initialized_stake(initialized_meta(vault_a, vault_a));
+    let hops = [Hop::new(PROGRAM_A).signing_for(VAULT_SEED)];
+    assert_eq!(
+        invoke_chain(&hops, &withdraw(&vault_a, None, 1), &mut stake, &clock()),
+        Ok(Ok(()))
+    );
+    assert_eq!(stake.lamports, STAKE_LAMPORTS - 1);
+
+    // depth 3: A signs for its vault and B passes the signature on
+    let mut stake = initialized_stake(initialized_meta(vault_a, vault_a));
+    let hops = [
+        Hop::new(PROGRAM_A).signing_for(VAULT_SEED).marking(vault_a),
+        Hop::new(PROGRAM_B),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &withdraw(&vault_a, None, 1), &mut stake, &clock()),
+        Ok(Ok(()))
+    );
+    assert_eq!(stake.lamports, STAKE_LAMPORTS - 1);
+
+    // depth 3: B signs for its own vault, whatever A did
+    let (vault_b, _) = pda(&PROGRAM_B, VAULT_SEED);
+    let mut stake = initialized_stake(initialized_meta(vault_b, vault_b));
+    let hops = [
+        Hop::new(PROGRAM_A),
+        Hop::new(PROGRAM_B).signing_for(VAULT_SEED),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &withdraw(&vault_b, None, 1), &mut stake, &clock()),
+        Ok(Ok(()))
+    );
+    assert_eq!(stake.lamports, STAKE_LAMPORTS - 1);
+}
+
+#[test]
+fn test_withdraw_signature_not_passed_on() {
+    let (vault_a, _) = pda(&PROGRAM_A, VAULT_SEED);
+    let meta = initialized_meta(vault_a, vault_a);
+
+    // A signs, but B is handed the vault as a plain account, so it can't
+    // mark it a signer of the withdraw
+    let mut stake = initialized_stake(meta);
+    let hops = [
+        Hop::new(PROGRAM_A).signing_for(VAULT_SEED),
+        Hop::new(PROGRAM_B),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &withdraw(&vault_a, None, 1), &mut stake, &clock()),
+        Err(PrivilegeEscalation {
+            caller: PROGRAM_B,
+            key: vault_a,
+        })
+    );
+
+    // B signing with A's seeds derives its own address, not A's vault
+    let hops = [
+        Hop::new(PROGRAM_A),
+        Hop::new(PROGRAM_B).signing_for(VAULT_SEED),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &withdraw(&vault_a, None, 1), &mut stake, &clock()),
+        Err(PrivilegeEscalation {
+            caller: PROGRAM_B,
+            key: vault_a,
+        })
+    );
+
+    // the vault reaching the stake program unsigned is refused by the
+    // handler itself
+    let mut instruction = withdraw(&vault_a, None, 1);
+    instruction.accounts[4].is_signer = false;
+    let hops = [
+        Hop::new(PROGRAM_A).signing_for(VAULT_SEED).marking(vault_a),
+        Hop::new(PROGRAM_B),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &instruction, &mut stake, &clock()),
+        Ok(Err(ProgramError::MissingRequiredSignature))
+    );
+    assert_eq!(stake.lamports, STAKE_LAMPORTS);
+}
+
+#[test]
+fn test_withdraw_with_custodian_signed_at_another_depth() {
+    // the withdrawer is B's vault, signed at depth 3, and the custodian of
+    // the lockup is A's custody account, signed at depth 2 and passed on
+    let (custody_a, _) = pda(&PROGRAM_A, CUSTODY_SEED);
+    let (vault_b, _) = pda(&PROGRAM_B, VAULT_SEED);
+    let mut meta = initialized_meta(vault_b, vault_b);
+    meta.lockup = Lockup {
+        custodian: custody_a,
+        ..Lockup::default()
+    };
+    meta.lockup.set_epoch(CURRENT_EPOCH + 1);
+
+    let mut stake = initialized_stake(meta);
+    let hops = [
+        Hop::new(PROGRAM_A).signing_for(CUSTODY_SEED),
+        Hop::new(PROGRAM_B).signing_for(VAULT_SEED),
+    ];
+    let instruction = withdraw(&vault_b, Some(&custody_a), 1);
+    assert_eq!(
+        invoke_chain(&hops, &instruction, &mut stake, &clock()),
+        Err(PrivilegeEscalation {
+            caller: PROGRAM_B,
+            key: custody_a,
+        })
+    );
+
+    let hops = [
+        Hop::new(PROGRAM_A)
+            .signing_for(CUSTODY_SEED)
+            .marking(custody_a),
+        Hop::new(PROGRAM_B).signing_for(VAULT_SEED),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &instruction, &mut stake, &clock()),
+        Ok(Ok(()))
+    );
+    assert_eq!(stake.lamports, STAKE_LAMPORTS - 1);
+
+    // without the custodian the lockup holds
+    assert_eq!(
+        invoke_chain(&hops, &withdraw(&vault_b, None, 1), &mut stake, &clock()),
+        Ok(Err(
+            solana_pinocchio_starter::error::StakeError::LockupInForce.into()
+        ))
+    );
+}
+
+#[test]
+fn test_authorize_with_seed_at_depth_three() {
+    // the authorities are derived from A's vault; A signs for it and B,
+    // which holds the seeds' bookkeeping, issues the AuthorizeWithSeed
+    let (vault_a, _) = pda(&PROGRAM_A, VAULT_SEED);
+    let base = SdkPubkey::new_from_array(vault_a);
+    let owner = SdkPubkey::new_from_array(PROGRAM_B);
+    let authority = |seed: &str| {
+        SdkPubkey::create_with_seed(&base, seed, &owner)
+            .unwrap()
+            .to_bytes()
+    };
+
+    let mut stake = initialized_stake(initialized_meta(authority("stake:0"), authority("stake:0")));
+    let original = authorized(&stake);
+    let instruction = client::authorize_with_seed(
+        &STAKE,
+        &vault_a,
+        "stake:0",
+        &PROGRAM_B,
+        &authority("stake:1"),
+        StakeAuthorize::Staker,
+        None,
+    );
+
+    let hops = [
+        Hop::new(PROGRAM_A).signing_for(VAULT_SEED),
+        Hop::new(PROGRAM_B),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &instruction, &mut stake, &clock()),
+        Err(PrivilegeEscalation {
+            caller: PROGRAM_B,
+            key: vault_a,
+        })
+    );
+    assert_eq!(authorized(&stake), original);
+
+    let hops = [
+        Hop::new(PROGRAM_A).signing_for(VAULT_SEED).marking(vault_a),
+        Hop::new(PROGRAM_B),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &instruction, &mut stake, &clock()),
+        Ok(Ok(()))
+    );
+    assert_eq!(authorized(&stake).staker, authority("stake:1"));
+    assert_eq!(authorized(&stake).withdrawer, authority("stake:0"));
+
+    // a base B signs for itself derives other authorities
+    let (vault_b, _) = pda(&PROGRAM_B, VAULT_SEED);
+    let instruction = client::authorize_with_seed(
+        &STAKE,
+        &vault_b,
+        "stake:0",
+        &PROGRAM_B,
+        &vault_b,
+        StakeAuthorize::Withdrawer,
+        None,
+    );
+    let hops = [
+        Hop::new(PROGRAM_A),
+        Hop::new(PROGRAM_B).signing_for(VAULT_SEED),
+    ];
+    assert_eq!(
+        invoke_chain(&hops, &instruction, &mut stake, &clock()),
+        Ok(Err(ProgramError::MissingRequiredSignature))
+    );
+    assert_eq!(authorized(&stake).withdrawer, authority("stake:0"));
+}
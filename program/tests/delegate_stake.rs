@@ -0,0 +1,290 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use solana_pinocchio_starter::{
+    consts::{LAMPORTS_PER_SOL, VOTE_PROGRAM_ID},
+    error::StakeError,
+    instruction::StakeInstruction,
+    state::{
+        get_stake_state, stake_history, Delegation, Stake, StakeFlags, StakeHistoryEntry,
+        StakeStateV2,
+    },
+};
+
+const STAKE: Pubkey = [1; 32];
+const VOTE: Pubkey = [2; 32];
+const OTHER_VOTE: Pubkey = [3; 32];
+const STAKER: Pubkey = [4; 32];
+const WITHDRAWER: Pubkey = [5; 32];
+const STAKE_CONFIG: Pubkey = [6; 32];
+
+const DELEGATED: u64 = LAMPORTS_PER_SOL;
+const LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + DELEGATED;
+const CREDITS: u64 = 1_250;
+
+fn epoch_credits() -> Vec<(u64, u64, u64)> {
+    vec![
+        (CURRENT_EPOCH - 2, 800, 400),
+        (CURRENT_EPOCH - 1, CREDITS, 800),
+    ]
+}
+
+fn staked(delegation: Delegation) -> StakeStateV2 {
+    StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        Stake {
+            delegation,
+            credits_observed: 7u64.to_le_bytes(),
+        },
+        StakeFlags::empty(),
+    )
+}
+
+fn initialized() -> StakeStateV2 {
+    StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER))
+}
+
+/// Delegates `state` to `vote`, signed by `signer`, returning the result and
+/// the state left behind.
+fn delegate_with(
+    state: StakeStateV2,
+    lamports: u64,
+    vote: TestAccount,
+    signer: Pubkey,
+) -> (Result<(), ProgramError>, StakeStateV2) {
+    set_clock(&clock());
+    set_default_rent();
+
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, lamports, state).into(),
+        vote.into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(STAKE_CONFIG, Pubkey::default(), 0, vec![]).into(),
+        TestAccount::new(signer, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &[StakeInstruction::DelegateStake as u8]);
+        (result, get_stake_state(&accounts[0]).unwrap())
+    })
+}
+
+fn delegate(state: StakeStateV2, vote: Pubkey) -> (Result<(), ProgramError>, StakeStateV2) {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    delegate_with(
+        state,
+        LAMPORTS,
+        vote_account(vote, &epoch_credits()),
+        STAKER,
+    )
+}
+
+#[test]
+fn test_delegate_initialized() {
+    let (result, state) = delegate(initialized(), VOTE);
+    assert_eq!(result, Ok(()));
+    assert_stake_state_eq(
+        &state,
+        &StakeStateV2::Stake(
+            initialized_meta(STAKER, WITHDRAWER),
+            Stake {
+                delegation: Delegation::new(&VOTE, DELEGATED, CURRENT_EPOCH.to_le_bytes()),
+                credits_observed: CREDITS.to_le_bytes(),
+            },
+            StakeFlags::empty(),
+        ),
+    );
+
+    // a vote account that hasn't earned anything yet
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let (result, state) = delegate_with(initialized(), LAMPORTS, vote_account(VOTE, &[]), STAKER);
+    assert_eq!(result, Ok(()));
+    let StakeStateV2::Stake(_, stake, _) = state else {
+        panic!("not delegated: {state:?}");
+    };
+    assert_eq!(stake.credits_observed, 0u64.to_le_bytes());
+}
+
+#[test]
+fn test_delegate_requires_staker() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let vote = || vote_account(VOTE, &epoch_credits());
+    for signer in [WITHDRAWER, VOTE] {
+        let (result, state) = delegate_with(initialized(), LAMPORTS, vote(), signer);
+        assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+        assert_stake_state_eq(&state, &initialized());
+    }
+}
+
+#[test]
+fn test_delegate_below_minimum_delegation() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    // nothing past the rent exempt reserve
+    let (result, state) = delegate_with(
+        initialized(),
+        STAKE_RENT_EXEMPT_RESERVE,
+        vote_account(VOTE, &epoch_credits()),
+        STAKER,
+    );
+    assert_eq!(result, Err(StakeError::InsufficientDelegation.into()));
+    assert_stake_state_eq(&state, &initialized());
+}
+
+#[test]
+fn test_delegate_to_invalid_vote_account() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let mut not_vote = vote_account(VOTE, &epoch_credits());
+    not_vote.owner = Pubkey::default();
+    let (result, _) = delegate_with(initialized(), LAMPORTS, not_vote, STAKER);
+    assert_eq!(result, Err(ProgramError::IncorrectProgramId));
+
+    // owned by the vote program, but not holding a vote state
+    let mut garbage = vote_account(VOTE, &epoch_credits());
+    garbage.data[..4].copy_from_slice(&3u32.to_le_bytes());
+    let (result, state) = delegate_with(initialized(), LAMPORTS, garbage, STAKER);
+    assert_eq!(result, Err(ProgramError::InvalidAccountData));
+    assert_stake_state_eq(&state, &initialized());
+}
+
+#[test]
+fn test_redelegate_deactivating_stake() {
+    let deactivating = |voter: &Pubkey, deactivation_epoch: u64| {
+        let mut delegation = Delegation::new(voter, DELEGATED, 0u64.to_le_bytes());
+        delegation.deactivation_epoch = deactivation_epoch.to_le_bytes();
+        staked(delegation)
+    };
+
+    // deactivated this epoch: delegating to the same voter rescinds it, and
+    // the stake carries on as it was
+    let (result, state) = delegate(deactivating(&VOTE, CURRENT_EPOCH), VOTE);
+    assert_eq!(result, Ok(()));
+    assert_stake_state_eq(
+        &state,
+        &staked(Delegation::new(&VOTE, DELEGATED, 0u64.to_le_bytes())),
+    );
+
+    // to another voter, or once the stake has started cooling down, it is
+    // too soon
+    for (voter, deactivation_epoch) in [(OTHER_VOTE, CURRENT_EPOCH), (VOTE, CURRENT_EPOCH - 1)] {
+        let original = deactivating(&voter, deactivation_epoch);
+        // the cluster cooling down a little at a time keeps part of it effective
+        set_stake_history(
+            CURRENT_EPOCH,
+            &[(
+                CURRENT_EPOCH - 1,
+                StakeHistoryEntry {
+                    effective: (100 * DELEGATED).to_le_bytes(),
+                    activating: 0u64.to_le_bytes(),
+                    deactivating: (100 * DELEGATED).to_le_bytes(),
+                },
+            )],
+        );
+        let (result, state) = delegate_with(
+            original,
+            LAMPORTS,
+            vote_account(VOTE, &epoch_credits()),
+            STAKER,
+        );
+        assert_eq!(result, Err(StakeError::TooSoonToRedelegate.into()));
+        assert_stake_state_eq(&state, &original);
+    }
+}
+
+#[test]
+fn test_redelegate_inactive_stake() {
+    // deactivated long ago: delegated afresh, to whichever voter, observing
+    // the new vote account's credits
+    let mut delegation = Delegation::new(&VOTE, DELEGATED / 2, 0u64.to_le_bytes());
+    delegation.deactivation_epoch = 10u64.to_le_bytes();
+    let (result, state) = delegate(staked(delegation), OTHER_VOTE);
+    assert_eq!(result, Ok(()));
+    assert_stake_state_eq(
+        &state,
+        &StakeStateV2::Stake(
+            initialized_meta(STAKER, WITHDRAWER),
+            Stake {
+                delegation: Delegation::new(&OTHER_VOTE, DELEGATED, CURRENT_EPOCH.to_le_bytes()),
+                credits_observed: CREDITS.to_le_bytes(),
+            },
+            StakeFlags::empty(),
+        ),
+    );
+}
+
+#[test]
+fn test_delegate_checks_legacy_stake_history_account() {
+    set_clock(&clock());
+    set_default_rent();
+    set_stake_history(CURRENT_EPOCH, &[]);
+
+    let mut not_stake_history = stake_history_account();
+    not_stake_history.key = [7; 32];
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, LAMPORTS, initialized()).into(),
+        vote_account(VOTE, &epoch_credits()).into(),
+        clock_account(&clock()).into(),
+        not_stake_history.into(),
+        TestAccount::new(STAKE_CONFIG, Pubkey::default(), 0, vec![]).into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        assert_ne!(accounts[3].key(), &stake_history::ID);
+        assert_eq!(
+            process(accounts, &[StakeInstruction::DelegateStake as u8]),
+            Err(ProgramError::InvalidArgument)
+        );
+    });
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_delegate_to_v1_14_11_vote_account() {
+    use solana_sdk::vote::state::{
+        VoteState as NativeVoteState, VoteState1_14_11, VoteStateVersions,
+    };
+
+    // never resized since the 1.14.11 vote state, so 3731 bytes
+    let native = NativeVoteState {
+        epoch_credits: epoch_credits(),
+        ..NativeVoteState::default()
+    };
+    let mut data = vec![0; 3731];
+    bincode::serialize_into(
+        &mut data[..],
+        &VoteStateVersions::V1_14_11(Box::new(VoteState1_14_11::from(native))),
+    )
+    .unwrap();
+    let vote = TestAccount::new(VOTE, VOTE_PROGRAM_ID, VOTE_ACCOUNT_LAMPORTS, data);
+
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let (result, state) = delegate_with(initialized(), LAMPORTS, vote, STAKER);
+    assert_eq!(result, Ok(()));
+    let StakeStateV2::Stake(_, stake, _) = state else {
+        panic!("not delegated: {state:?}");
+    };
+    assert_eq!(stake.credits_observed, CREDITS.to_le_bytes());
+}
+
+#[test]
+fn test_delegated_stake_is_little_endian() {
+    // an amount whose bytes read differently in either order, as the
+    // delegated stake was once written big-endian
+    let stake = 1_234_567_891;
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let (result, state) = delegate_with(
+        initialized(),
+        STAKE_RENT_EXEMPT_RESERVE + stake,
+        vote_account(VOTE, &epoch_credits()),
+        STAKER,
+    );
+    assert_eq!(result, Ok(()));
+    let StakeStateV2::Stake(_, delegated, _) = state else {
+        panic!("not delegated: {state:?}");
+    };
+    assert_eq!(delegated.delegation.stake, stake.to_le_bytes());
+}
@@ -0,0 +1,554 @@
+//! Randomized differential testing against the native stake program.
+//!
+//! Each case is a short life of a few stake accounts: a random starting
+//! ledger, then a random sequence of the dispatched instructions with clock
+//! warps in between. Every instruction runs both through this program and
+//! through the upstream stake program's BPF build in mollusk, and the two
+//! must agree on the result and on every account after each step.
+//!
+//! Ignored by default, as it needs the upstream ELF, from `NATIVE_STAKE_ELF`,
+//! and takes a while:
+//!
+//! ```text
+//! NATIVE_STAKE_ELF=path/to/solana_stake_program.so \
+//!     cargo test --test differential -- --ignored --nocapture
+//! ```
+//!
+//! `DIFFERENTIAL_CASES` sets the number of cases. On divergence the case is
+//! cut down to the fewest steps that still diverge and reported with its
+//! seed; `DIFFERENTIAL_SEED` and `DIFFERENTIAL_STEPS` replay just that.
+
+mod common;
+
+use common::*;
+use mollusk_svm::{program::loader_keys::LOADER_V3, result::ProgramResult, Mollusk};
+use pinocchio::{pubkey::Pubkey, sysvars::clock::Clock};
+use pinocchio_stake_interface::{
+    client::{self, AccountMeta, Instruction},
+    consts::FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL,
+};
+use solana_pinocchio_starter::{
+    consts::CLOCK_ID,
+    helpers::create_with_seed,
+    instruction::StakeInstruction,
+    state::{
+        stake_history, Delegation, Lockup, Meta, Stake, StakeAuthorize, StakeFlags, StakeStateV2,
+    },
+};
+use solana_sdk::{account::Account, stake_history::StakeHistory};
+
+const STAKES: [Pubkey; 3] = [[1; 32], [2; 32], [3; 32]];
+const STAKER: Pubkey = [4; 32];
+const WITHDRAWER: Pubkey = [5; 32];
+const CUSTODIAN: Pubkey = [6; 32];
+const RECIPIENT: Pubkey = [7; 32];
+const VOTER: Pubkey = [8; 32];
+const BASE: Pubkey = [9; 32];
+const SEED_OWNER: Pubkey = [10; 32];
+const SEED: &str = "differential";
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+const DEFAULT_CASES: u64 = 10_000;
+const MAX_STEPS: u64 = 8;
+const SEED_BASE: u64 = 0xd1ff_5eed_0bad_cafe;
+
+/// `stake_raise_minimum_delegation_to_1_sol`, which mollusk enables along
+/// with every other feature.
+const RAISE_MINIMUM_DELEGATION_FEATURE: &str = "9onWzzvCzNC2jfhxxeqRgs5q7nFAAKpCUvkj6T6GJK9i";
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LedgerAccount {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+}
+
+#[derive(Clone)]
+struct World {
+    accounts: Vec<LedgerAccount>,
+    clock: Clock,
+}
+
+#[derive(Clone, Debug)]
+enum Step {
+    Warp { epochs: u64, seconds: i64 },
+    Instruction(Instruction),
+}
+
+/// What an instruction did: its result, zero for success, and the accounts
+/// it was given, as they were left.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    result: u64,
+    accounts: Vec<LedgerAccount>,
+}
+
+fn seeded_authority() -> Pubkey {
+    create_with_seed(&BASE, SEED.as_bytes(), &SEED_OWNER).unwrap()
+}
+
+fn authority(rng: &mut Rng) -> Pubkey {
+    rng.pick(&[STAKER, WITHDRAWER, seeded_authority()])
+}
+
+fn meta(rng: &mut Rng, clock: &Clock) -> Meta {
+    let mut meta = initialized_meta(authority(rng), authority(rng));
+    if rng.one_in(3) {
+        meta.lockup = Lockup {
+            custodian: rng.pick(&[CUSTODIAN, Pubkey::default()]),
+            ..Lockup::default()
+        };
+        meta.lockup.set_epoch(clock.epoch + rng.below(3));
+        meta.lockup
+            .set_unix_timestamp(clock.unix_timestamp + rng.below(3) as i64 * 3_600);
+    }
+    meta
+}
+
+fn stake_state(rng: &mut Rng, clock: &Clock, lamports: u64) -> StakeStateV2 {
+    match rng.below(3) {
+        0 => StakeStateV2::Uninitialized,
+        1 => StakeStateV2::Initialized(meta(rng, clock)),
+        _ => {
+            let meta = meta(rng, clock);
+            let stake = lamports.saturating_sub(STAKE_RENT_EXEMPT_RESERVE);
+            let activation_epoch = clock.epoch.saturating_sub(rng.below(3));
+            let mut delegation = Delegation::new(&VOTER, stake, activation_epoch.to_le_bytes());
+            if rng.one_in(2) {
+                delegation.set_deactivation_epoch(activation_epoch + rng.below(3));
+            }
+            let stake = Stake {
+                delegation,
+                ..Stake::default()
+            };
+            StakeStateV2::Stake(meta, stake, StakeFlags::empty())
+        }
+    }
+}
+
+fn world(rng: &mut Rng) -> World {
+    let clock = Clock {
+        slot: 1_000,
+        epoch: 100 + rng.below(10),
+        leader_schedule_epoch: 0,
+        epoch_start_timestamp: 0,
+        unix_timestamp: 1_700_000_000,
+    };
+    let accounts = STAKES
+        .iter()
+        .map(|key| {
+            let lamports = STAKE_RENT_EXEMPT_RESERVE
+                + rng.pick(&[0, 1, LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL]);
+            LedgerAccount {
+                key: *key,
+                owner: solana_pinocchio_starter::ID,
+                lamports,
+                data: stake_state_data(stake_state(rng, &clock, lamports)),
+            }
+        })
+        .collect();
+    World { accounts, clock }
+}
+
+fn amount(rng: &mut Rng) -> u64 {
+    match rng.below(5) {
+        0 => 0,
+        1 => 1,
+        2 => LAMPORTS_PER_SOL,
+        3 => STAKE_RENT_EXEMPT_RESERVE,
+        _ => rng.below(11 * LAMPORTS_PER_SOL),
+    }
+}
+
+fn instruction(
+    instruction: StakeInstruction,
+    args: &[u8],
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut data = vec![instruction as u8];
+    data.extend_from_slice(args);
+    Instruction {
+        program_id: solana_pinocchio_starter::ID,
+        accounts,
+        data,
+    }
+}
+
+fn step(rng: &mut Rng) -> Step {
+    let [stake, other] = [rng.pick(&STAKES), rng.pick(&STAKES)];
+    let custodian = rng.one_in(4).then_some(CUSTODIAN);
+    match rng.below(6) {
+        0 => Step::Warp {
+            epochs: rng.below(3),
+            seconds: rng.below(2) as i64 * 3_600,
+        },
+        1 => Step::Instruction(client::split(&stake, &authority(rng), amount(rng), &other)),
+        2 => {
+            let mut accounts = vec![
+                AccountMeta::writable(stake),
+                AccountMeta::writable(rng.pick(&[RECIPIENT, other])),
+                AccountMeta::readonly(CLOCK_ID),
+                AccountMeta::readonly(stake_history::ID),
+                AccountMeta::readonly_signer(authority(rng)),
+            ];
+            accounts.extend(custodian.map(AccountMeta::readonly_signer));
+            Step::Instruction(instruction(
+                StakeInstruction::Withdraw,
+                &amount(rng).to_le_bytes(),
+                accounts,
+            ))
+        }
+        3 => {
+            let mut args = vec![];
+            if rng.one_in(2) {
+                args.push(1);
+                args.extend_from_slice(&(rng.below(2) as i64 * 1_800).to_le_bytes());
+            } else {
+                args.push(0);
+            }
+            args.push(1);
+            args.extend_from_slice(&(100 + rng.below(20)).to_le_bytes());
+            args.push(0);
+            Step::Instruction(instruction(
+                StakeInstruction::SetLockup,
+                &args,
+                vec![
+                    AccountMeta::writable(stake),
+                    AccountMeta::readonly_signer(rng.pick(&[WITHDRAWER, CUSTODIAN])),
+                ],
+            ))
+        }
+        4 => Step::Instruction(instruction(
+            StakeInstruction::MoveLamports,
+            &amount(rng).to_le_bytes(),
+            vec![
+                AccountMeta::writable(stake),
+                AccountMeta::writable(other),
+                AccountMeta::readonly_signer(authority(rng)),
+            ],
+        )),
+        _ => Step::Instruction(client::authorize_with_seed(
+            &stake,
+            &BASE,
+            SEED,
+            &SEED_OWNER,
+            &authority(rng),
+            rng.pick(&[StakeAuthorize::Staker, StakeAuthorize::Withdrawer]),
+            custodian.as_ref(),
+        )),
+    }
+}
+
+fn case(seed: u64) -> (World, Vec<Step>) {
+    let mut rng = Rng(seed | 1);
+    let world = world(&mut rng);
+    let steps = (0..=rng.below(MAX_STEPS)).map(|_| step(&mut rng)).collect();
+    (world, steps)
+}
+
+fn account<'a>(world: &'a World, key: &Pubkey) -> Option<&'a LedgerAccount> {
+    world.accounts.iter().find(|account| account.key == *key)
+}
+
+/// Each distinct account of `instruction`, as the world holds it, or an
+/// empty wallet if it doesn't.
+fn instruction_accounts(world: &World, instruction: &Instruction) -> Vec<LedgerAccount> {
+    let mut accounts: Vec<LedgerAccount> = Vec::new();
+    for meta in &instruction.accounts {
+        if meta.pubkey == CLOCK_ID
+            || meta.pubkey == stake_history::ID
+            || accounts.iter().any(|account| account.key == meta.pubkey)
+        {
+            continue;
+        }
+        accounts.push(
+            account(world, &meta.pubkey)
+                .cloned()
+                .unwrap_or(LedgerAccount {
+                    key: meta.pubkey,
+                    owner: Pubkey::default(),
+                    lamports: 0,
+                    data: vec![],
+                }),
+        );
+    }
+    accounts
+}
+
+fn run_native(mollusk: &mut Mollusk, world: &World, instruction: &Instruction) -> Outcome {
+    mollusk.sysvars.clock = solana_sdk::clock::Clock {
+        slot: world.clock.slot,
+        epoch_start_timestamp: world.clock.epoch_start_timestamp,
+        epoch: world.clock.epoch,
+        leader_schedule_epoch: world.clock.leader_schedule_epoch,
+        unix_timestamp: world.clock.unix_timestamp,
+    };
+    mollusk.sysvars.stake_history = StakeHistory::default();
+
+    let accounts = instruction_accounts(world, instruction);
+    let mut keyed: Vec<(solana_sdk::pubkey::Pubkey, Account)> = accounts
+        .iter()
+        .map(|account| {
+            (
+                account.key.into(),
+                Account {
+                    lamports: account.lamports,
+                    data: account.data.clone(),
+                    owner: account.owner.into(),
+                    executable: false,
+                    rent_epoch: u64::MAX,
+                },
+            )
+        })
+        .collect();
+    keyed.push(mollusk.sysvars.keyed_account_for_clock_sysvar());
+    keyed.push(mollusk.sysvars.keyed_account_for_stake_history_sysvar());
+
+    let native_instruction = solana_sdk::instruction::Instruction {
+        program_id: instruction.program_id.into(),
+        accounts: instruction
+            .accounts
+            .iter()
+            .map(|meta| solana_sdk::instruction::AccountMeta {
+                pubkey: meta.pubkey.into(),
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: instruction.data.clone(),
+    };
+    let result = process_in_mollusk(mollusk, &native_instruction, &keyed);
+
+    Outcome {
+        result: match result.program_result {
+            ProgramResult::Success => 0,
+            ProgramResult::Failure(error) => u64::from(error),
+            ProgramResult::UnknownError(error) => panic!("mollusk failed: {error:?}"),
+        },
+        accounts: accounts
+            .iter()
+            .map(|before| {
+                let (_, after) = result
+                    .resulting_accounts
+                    .iter()
+                    .find(|(key, _)| key.to_bytes() == before.key)
+                    .unwrap();
+                LedgerAccount {
+                    key: before.key,
+                    owner: after.owner.to_bytes(),
+                    lamports: after.lamports,
+                    data: after.data.clone(),
+                }
+            })
+            .collect(),
+    }
+}
+
+fn run_program(world: &World, instruction: &Instruction) -> Outcome {
+    set_clock(&world.clock);
+    set_stake_history(world.clock.epoch, &[]);
+    set_default_rent();
+
+    let accounts = instruction_accounts(world, instruction);
+    let metas = &instruction.accounts;
+    let inputs: Vec<Input> = metas
+        .iter()
+        .enumerate()
+        .map(|(index, meta)| {
+            if let Some(first) = metas[..index]
+                .iter()
+                .position(|earlier| earlier.pubkey == meta.pubkey)
+            {
+                return Input::Duplicate(first as u8);
+            }
+            let mut account = match meta.pubkey {
+                CLOCK_ID => clock_account(&world.clock),
+                stake_history::ID => stake_history_account(),
+                key => {
+                    let account = accounts.iter().find(|account| account.key == key).unwrap();
+                    TestAccount::new(key, account.owner, account.lamports, account.data.clone())
+                }
+            };
+            let entries = metas.iter().filter(|other| other.pubkey == meta.pubkey);
+            account.is_signer = entries.clone().any(|entry| entry.is_signer);
+            account.is_writable = entries.clone().any(|entry| entry.is_writable);
+            account.into()
+        })
+        .collect();
+
+    with_accounts(&inputs, |infos| {
+        let result = process(infos, &instruction.data);
+        Outcome {
+            result: result.map_or_else(u64::from, |()| 0),
+            accounts: accounts
+                .iter()
+                .map(|before| {
+                    let info = infos.iter().find(|info| *info.key() == before.key).unwrap();
+                    LedgerAccount {
+                        key: before.key,
+                        // SAFETY: nothing reassigns the owner while it is read
+                        owner: unsafe { *info.owner() },
+                        lamports: info.lamports(),
+                        data: info.try_borrow_data().unwrap().to_vec(),
+                    }
+                })
+                .collect(),
+        }
+    })
+}
+
+/// Where and how the two programs parted ways.
+struct Divergence {
+    step: usize,
+    native: Outcome,
+    program: Outcome,
+}
+
+/// Runs `steps` from `world` on both programs, stopping at the first step
+/// where they disagree.
+fn run(mollusk: &mut Mollusk, world: &World, steps: &[Step]) -> Option<Divergence> {
+    let mut world = world.clone();
+    for (index, step) in steps.iter().enumerate() {
+        let instruction = match step {
+            Step::Warp { epochs, seconds } => {
+                world.clock = warp_to_epoch(&world.clock, world.clock.epoch + epochs);
+                world.clock.unix_timestamp += seconds;
+                continue;
+            }
+            Step::Instruction(instruction) => instruction,
+        };
+
+        let native = run_native(mollusk, &world, instruction);
+        let program = run_program(&world, instruction);
+        if native != program {
+            return Some(Divergence {
+                step: index,
+                native,
+                program,
+            });
+        }
+        // a failed instruction leaves the world as it was
+        if program.result == 0 {
+            for after in program.accounts {
+                match world
+                    .accounts
+                    .iter_mut()
+                    .find(|account| account.key == after.key)
+                {
+                    Some(account) => *account = after,
+                    None => world.accounts.push(after),
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The indices of the fewest of `steps` that still diverge, found by
+/// dropping one step at a time, latest first, while the rest still do.
+fn minimize(mollusk: &mut Mollusk, world: &World, steps: &[Step]) -> Vec<usize> {
+    let mut kept: Vec<usize> = (0..steps.len()).collect();
+    for candidate in (0..steps.len()).rev() {
+        let without: Vec<usize> = kept.iter().copied().filter(|i| *i != candidate).collect();
+        let replay: Vec<Step> = without.iter().map(|i| steps[*i].clone()).collect();
+        if run(mollusk, world, &replay).is_some() {
+            kept = without;
+        }
+    }
+    kept
+}
+
+fn native_mollusk() -> Option<Mollusk> {
+    let path = std::env::var_os("NATIVE_STAKE_ELF")?;
+    let elf = std::fs::read(&path)
+        .unwrap_or_else(|error| panic!("can't read {}: {error}", path.to_string_lossy()));
+
+    let mut mollusk = Mollusk::default();
+    mollusk.add_program_with_elf_and_loader(&solana_pinocchio_starter::ID.into(), &elf, &LOADER_V3);
+    // run native under the same minimum delegation this program is built for
+    if !FEATURE_STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL {
+        mollusk
+            .feature_set
+            .deactivate(&RAISE_MINIMUM_DELEGATION_FEATURE.parse().unwrap());
+    }
+    Some(mollusk)
+}
+
+fn env<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+#[test]
+#[ignore = "needs NATIVE_STAKE_ELF, and runs for minutes"]
+fn differential_random_scenarios() {
+    let Some(mut mollusk) = native_mollusk() else {
+        eprintln!("NATIVE_STAKE_ELF not set, skipping the differential run");
+        return;
+    };
+
+    let seeds: Vec<u64> = match env::<u64>("DIFFERENTIAL_SEED") {
+        Some(seed) => vec![seed],
+        None => (0..env("DIFFERENTIAL_CASES").unwrap_or(DEFAULT_CASES))
+            .map(|case: u64| SEED_BASE ^ case.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect(),
+    };
+    let only_steps: Option<Vec<usize>> = std::env::var("DIFFERENTIAL_STEPS").ok().map(|steps| {
+        steps
+            .split(',')
+            .map(|step| step.trim().parse().unwrap())
+            .collect()
+    });
+
+    for seed in seeds {
+        let (world, steps) = case(seed);
+        let indices: Vec<usize> = only_steps
+            .clone()
+            .unwrap_or_else(|| (0..steps.len()).collect());
+        let steps: Vec<Step> = indices.iter().map(|i| steps[*i].clone()).collect();
+        if run(&mut mollusk, &world, &steps).is_none() {
+            continue;
+        }
+
+        let kept = minimize(&mut mollusk, &world, &steps);
+        let replay: Vec<Step> = kept.iter().map(|i| steps[*i].clone()).collect();
+        let divergence = run(&mut mollusk, &world, &replay).unwrap();
+        let steps_arg = kept
+            .iter()
+            .map(|i| indices[*i].to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        panic!(
+            "diverged from native at step {} of {replay:#?}\n\
+             native: {:#?}\nprogram: {:#?}\n\
+             replay with DIFFERENTIAL_SEED={seed} DIFFERENTIAL_STEPS={steps_arg}",
+            divergence.step, divergence.native, divergence.program
+        );
+    }
+}
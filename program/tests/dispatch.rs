@@ -0,0 +1,70 @@
+//! Regression tests for instruction data the fuzzer truncated: the
+//! dispatcher must reject it with `InvalidInstructionData` before reading
+//! any account.
+
+mod common;
+
+use common::*;
+use pinocchio::program_error::ProgramError;
+use solana_pinocchio_starter::instruction::StakeInstruction;
+
+fn process_without_accounts(data: &[u8]) -> pinocchio::ProgramResult {
+    with_accounts(&[], |accounts| process(accounts, data))
+}
+
+#[test]
+fn test_empty_instruction_data() {
+    assert_eq!(
+        process_without_accounts(&[]),
+        Err(ProgramError::InvalidInstructionData)
+    );
+}
+
+#[test]
+fn test_unknown_discriminant() {
+    for tag in StakeInstruction::MoveLamports as u8 + 1..=u8::MAX {
+        for len in 0..=3 {
+            let mut data = vec![tag];
+            data.resize(1 + len, 0xff);
+            assert_eq!(
+                process_without_accounts(&data),
+                Err(ProgramError::InvalidInstructionData),
+                "tag {tag} with {len} argument bytes"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_truncated_amount() {
+    let amount = 1_000_000_000u64.to_le_bytes();
+    for instruction in [
+        StakeInstruction::Split,
+        StakeInstruction::Withdraw,
+        StakeInstruction::MoveLamports,
+    ] {
+        for len in 0..amount.len() {
+            let mut data = vec![instruction as u8];
+            data.extend_from_slice(&amount[..len]);
+            assert_eq!(
+                process_without_accounts(&data),
+                Err(ProgramError::InvalidInstructionData),
+                "{instruction:?} with {len} argument bytes"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_truncated_lockup_args() {
+    // each `None` takes one byte, so three are needed for the shortest args
+    for data in [vec![], vec![0], vec![0, 0], vec![1, 0]] {
+        let mut instruction_data = vec![StakeInstruction::SetLockup as u8];
+        instruction_data.extend_from_slice(&data);
+        assert_eq!(
+            process_without_accounts(&instruction_data),
+            Err(ProgramError::InvalidInstructionData),
+            "SetLockup with {data:?}"
+        );
+    }
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock};
+use solana_pinocchio_starter::{
+    consts::EPOCH_REWARDS_ID,
+    error::StakeError,
+    instruction::{process_withdraw_with, StakeInstruction},
+    state::{
+        epoch_rewards_active, set_syscall_stubs, EpochRewardsSysvar, StakeHistory, StakeStateV2,
+        SyscallStubs, EPOCH_REWARDS_ACTIVE_OFFSET, UNSUPPORTED_SYSVAR,
+    },
+};
+
+const STAKE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+
+const STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + 1_000_000_000;
+
+static REWARDS_ACTIVE: AtomicBool = AtomicBool::new(false);
+// bytes of the epoch rewards sysvar read so far
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+
+fn epoch_rewards_data(active: bool) -> Vec<u8> {
+    bincode::serialize(&solana_sdk::epoch_rewards::EpochRewards {
+        distribution_starting_block_height: 42,
+        num_partitions: 7,
+        total_points: u128::MAX,
+        total_rewards: 1_000,
+        distributed_rewards: 500,
+        active,
+        ..Default::default()
+    })
+    .unwrap()
+}
+
+/// Serves a synthetic epoch rewards sysvar whose `active` flag follows
+/// `REWARDS_ACTIVE`; every other sysvar stays unsupported.
+struct EpochRewardsStubs;
+
+impl SyscallStubs for EpochRewardsStubs {
+    fn sol_get_sysvar(
+        &self,
+        sysvar_id_addr: *const u8,
+        var_addr: *mut u8,
+        offset: u64,
+        length: u64,
+    ) -> u64 {
+        let sysvar_id = unsafe { &*(sysvar_id_addr as *const Pubkey) };
+        if *sysvar_id != EPOCH_REWARDS_ID {
+            return UNSUPPORTED_SYSVAR;
+        }
+
+        let data = epoch_rewards_data(REWARDS_ACTIVE.load(Ordering::SeqCst));
+        BYTES_READ.fetch_add(length, Ordering::SeqCst);
+
+        let (offset, length) = (offset as usize, length as usize);
+        let Some(src) = data.get(offset..offset + length) else {
+            return ProgramError::InvalidArgument.into();
+        };
+        unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), var_addr, length) };
+        0
+    }
+}
+
+fn withdraw_inputs() -> Vec<Input> {
+    vec![
+        stake_account(
+            STAKE,
+            STAKE_LAMPORTS,
+            StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+        )
+        .into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&Clock::default()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ]
+}
+
+fn withdraw_data(lamports: u64) -> Vec<u8> {
+    let mut data = vec![StakeInstruction::Withdraw as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_active_offset_matches_sdk_layout() {
+    let inactive = epoch_rewards_data(false);
+    let active = epoch_rewards_data(true);
+    let offset = EPOCH_REWARDS_ACTIVE_OFFSET as usize;
+
+    assert_eq!(inactive.len(), offset + 1);
+    assert_eq!((inactive[offset], active[offset]), (0, 1));
+    // nothing but the flag differs
+    assert_eq!(inactive[..offset], active[..offset]);
+}
+
+// a single test so the process-wide stubs aren't shared between threads
+#[test]
+fn test_instructions_blocked_while_epoch_rewards_active() {
+    // the sysvar is unavailable by default, which counts as inactive
+    assert_eq!(
+        EpochRewardsSysvar.active(),
+        Err(ProgramError::UnsupportedSysvar)
+    );
+    assert!(!epoch_rewards_active());
+
+    set_syscall_stubs(Box::new(EpochRewardsStubs));
+    assert!(!epoch_rewards_active());
+
+    REWARDS_ACTIVE.store(true, Ordering::SeqCst);
+    assert!(epoch_rewards_active());
+    // only the flag is fetched, not the whole sysvar
+    assert_eq!(BYTES_READ.load(Ordering::SeqCst), 2);
+
+    // everything but GetMinimumDelegation is rejected before it is processed
+    with_accounts(&withdraw_inputs(), |accounts| {
+        for discriminator in 0..=StakeInstruction::MoveLamports as u8 {
+            if discriminator == StakeInstruction::GetMinimumDelegation as u8 {
+                continue;
+            }
+            assert_eq!(
+                process(accounts, &[discriminator]),
+                Err(StakeError::EpochRewardsActive.into()),
+                "instruction {discriminator}"
+            );
+        }
+
+        assert_eq!(
+            process(accounts, &withdraw_data(1)),
+            Err(StakeError::EpochRewardsActive.into())
+        );
+        assert_eq!(accounts[0].lamports(), STAKE_LAMPORTS);
+        assert_eq!(accounts[1].lamports(), 0);
+    });
+
+    // the withdraw processor refuses too when called past the entrypoint
+    with_accounts(&withdraw_inputs(), |accounts| {
+        assert_eq!(EpochRewardsSysvar.active(), Ok(true));
+        assert_eq!(
+            process_withdraw_with(accounts, 1, &StakeHistory::default()),
+            Err(StakeError::EpochRewardsActive.into())
+        );
+        assert_eq!(accounts[0].lamports(), STAKE_LAMPORTS);
+    });
+
+    // malformed instructions are still reported as such
+    with_accounts(&withdraw_inputs(), |accounts| {
+        assert_eq!(
+            process(accounts, &[u8::MAX]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    });
+
+    // once distribution completes the same instruction goes through
+    REWARDS_ACTIVE.store(false, Ordering::SeqCst);
+    assert!(!epoch_rewards_active());
+
+    with_accounts(&withdraw_inputs(), |accounts| {
+        assert_eq!(
+            process_withdraw_with(accounts, 1, &StakeHistory::default()),
+            Ok(())
+        );
+        assert_eq!(process(accounts, &withdraw_data(1)), Ok(()));
+        assert_eq!(accounts[0].lamports(), STAKE_LAMPORTS - 2);
+        assert_eq!(accounts[1].lamports(), 2);
+    });
+}
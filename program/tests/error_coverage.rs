@@ -0,0 +1,54 @@
+//! Report of the errors the other integration tests made the program fail
+//! with, to spot rejection paths no test reaches. Run it after the suite:
+//!
+//! ```text
+//! cargo test && cargo test --test error_coverage -- --ignored --nocapture
+//! ```
+//!
+//! The report consumes the tally, so each report covers the runs since the
+//! previous one.
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{error_name, ERROR_COVERAGE_LOG};
+use pinocchio::program_error::ProgramError;
+use solana_pinocchio_starter::error::{FromPrimitive, StakeError};
+
+#[test]
+#[ignore = "reports on the tally left by the other tests"]
+fn report_error_coverage() {
+    let log = std::fs::read_to_string(ERROR_COVERAGE_LOG).unwrap_or_default();
+    let _ = std::fs::remove_file(ERROR_COVERAGE_LOG);
+
+    let mut by_error: BTreeMap<&str, BTreeMap<&str, usize>> = BTreeMap::new();
+    for line in log.lines() {
+        let Some((instruction, error)) = line.split_once(' ') else {
+            continue;
+        };
+        *by_error
+            .entry(error)
+            .or_default()
+            .entry(instruction)
+            .or_default() += 1;
+    }
+
+    println!("errors hit:");
+    for (error, instructions) in &by_error {
+        let instructions: Vec<String> = instructions
+            .iter()
+            .map(|(instruction, count)| format!("{instruction} x{count}"))
+            .collect();
+        println!("  {error}: {}", instructions.join(", "));
+    }
+
+    println!("stake errors never hit:");
+    let stake_errors = (0..).map_while(StakeError::from_u64);
+    for stake_error in stake_errors {
+        let name = error_name(&ProgramError::Custom(stake_error as u32));
+        if !by_error.contains_key(name.as_str()) {
+            println!("  {name}");
+        }
+    }
+}
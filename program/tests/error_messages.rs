@@ -0,0 +1,35 @@
+//! Error messages must read exactly as the native program's.
+
+use pinocchio_stake_interface::error::{FromPrimitive, StakeError};
+use solana_sdk::stake::instruction::StakeError as NativeStakeError;
+
+#[test]
+fn test_messages_match_native() {
+    // in code order
+    let natives = [
+        NativeStakeError::NoCreditsToRedeem,
+        NativeStakeError::LockupInForce,
+        NativeStakeError::AlreadyDeactivated,
+        NativeStakeError::TooSoonToRedelegate,
+        NativeStakeError::InsufficientStake,
+        NativeStakeError::MergeTransientStake,
+        NativeStakeError::MergeMismatch,
+        NativeStakeError::CustodianMissing,
+        NativeStakeError::CustodianSignatureMissing,
+        NativeStakeError::InsufficientReferenceVotes,
+        NativeStakeError::VoteAddressMismatch,
+        NativeStakeError::MinimumDelinquentEpochsForDeactivationNotMet,
+        NativeStakeError::InsufficientDelegation,
+        NativeStakeError::RedelegateTransientOrInactiveStake,
+        NativeStakeError::RedelegateToSameVoteAccount,
+        NativeStakeError::RedelegatedStakeMustFullyActivateBeforeDeactivationIsPermitted,
+        NativeStakeError::EpochRewardsActive,
+    ];
+
+    for (code, native) in natives.iter().enumerate() {
+        assert_eq!(native.clone() as usize, code);
+        let error = StakeError::from_u64(code as u64).unwrap();
+        assert_eq!(error.to_string(), native.to_string(), "{error:?}");
+    }
+    assert_eq!(StakeError::from_u64(natives.len() as u64), None);
+}
@@ -0,0 +1,267 @@
+//! Instructions of the `extensions` build, run with
+//! `cargo test --features extensions`.
+#![cfg(feature = "extensions")]
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    consts::LAMPORTS_PER_SOL,
+    error::StakeError,
+    state::{get_stake_state, Authorized, Delegation, Stake, StakeFlags, StakeStateV2},
+};
+
+const STAKE: Pubkey = [1; 32];
+const VOTE: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const POOL_AUTHORITY: Pubkey = [5; 32];
+const DESTINATION: Pubkey = [6; 32];
+
+const CREDITS: u64 = 1_250;
+const DELEGATED: u64 = LAMPORTS_PER_SOL;
+// enough that either side of a split can keep the minimum delegation
+const SPLIT_SOURCE_STAKE: u64 = 4 * DELEGATED;
+
+fn initialized() -> StakeStateV2 {
+    StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER))
+}
+
+/// Onboards a stake of `lamports` in `state` into the pool, signed by
+/// `signer`, returning the result and the state left behind.
+fn set_staker_and_delegate(
+    state: StakeStateV2,
+    lamports: u64,
+    signer: Pubkey,
+) -> (Result<(), ProgramError>, StakeStateV2) {
+    set_clock(&clock());
+    set_stake_history(CURRENT_EPOCH, &[]);
+    set_default_rent();
+
+    let instruction = client::set_staker_and_delegate(&STAKE, &signer, &VOTE, &POOL_AUTHORITY);
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, lamports, state).into(),
+        vote_account(VOTE, &[(CURRENT_EPOCH - 1, CREDITS, 0)]).into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(instruction.accounts[4].pubkey, Pubkey::default(), 0, vec![]).into(),
+        TestAccount::new(signer, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &instruction.data);
+        (result, get_stake_state(&accounts[0]).unwrap())
+    })
+}
+
+#[test]
+fn test_onboard_into_pool() {
+    let (result, state) =
+        set_staker_and_delegate(initialized(), STAKE_RENT_EXEMPT_RESERVE + DELEGATED, STAKER);
+    assert_eq!(result, Ok(()));
+
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.authorized = Authorized {
+        staker: POOL_AUTHORITY,
+        withdrawer: WITHDRAWER,
+    };
+    assert_stake_state_eq(
+        &state,
+        &StakeStateV2::Stake(
+            meta,
+            Stake {
+                delegation: Delegation::new(&VOTE, DELEGATED, CURRENT_EPOCH.to_le_bytes()),
+                credits_observed: CREDITS.to_le_bytes(),
+            },
+            StakeFlags::empty(),
+        ),
+    );
+}
+
+#[test]
+fn test_onboard_checks_delegation() {
+    // the withdrawer may hand over the staker, but not delegate
+    let (result, state) = set_staker_and_delegate(
+        initialized(),
+        STAKE_RENT_EXEMPT_RESERVE + DELEGATED,
+        WITHDRAWER,
+    );
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_stake_state_eq(&state, &initialized());
+
+    let (result, state) = set_staker_and_delegate(initialized(), STAKE_RENT_EXEMPT_RESERVE, STAKER);
+    assert_eq!(result, Err(StakeError::InsufficientDelegation.into()));
+    assert_stake_state_eq(&state, &initialized());
+
+    let (result, _) = set_staker_and_delegate(
+        StakeStateV2::Uninitialized,
+        STAKE_RENT_EXEMPT_RESERVE + DELEGATED,
+        STAKER,
+    );
+    assert_eq!(result, Err(ProgramError::InvalidAccountData));
+}
+
+#[test]
+fn test_onboard_instruction_data() {
+    set_clock(&clock());
+    let mut data = client::set_staker_and_delegate(&STAKE, &STAKER, &VOTE, &POOL_AUTHORITY).data;
+    data.truncate(data.len() - 1);
+    let inputs: Vec<Input> = vec![];
+    with_accounts(&inputs, |accounts| {
+        assert_eq!(
+            process(accounts, &data),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        data.resize(33, 0);
+        assert_eq!(
+            process(accounts, &data),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    });
+}
+
+/// `stake` lamports delegated to `VOTE` since `activation_epoch`.
+fn staked(stake: u64, activation_epoch: u64) -> StakeStateV2 {
+    StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        Stake {
+            delegation: Delegation::new(&VOTE, stake, activation_epoch.to_le_bytes()),
+            credits_observed: CREDITS.to_le_bytes(),
+        },
+        StakeFlags::empty(),
+    )
+}
+
+fn delegated(state: &StakeStateV2) -> u64 {
+    match state {
+        StakeStateV2::Stake(_, stake, _) => u64::from_le_bytes(stake.delegation.stake),
+        _ => 0,
+    }
+}
+
+/// Runs `instruction_data` over `inputs`, returning the result, the stakes
+/// it set as return data, and the delegated stake the first two accounts
+/// are left with.
+fn process_returning(
+    inputs: &[Input],
+    instruction_data: &[u8],
+) -> (ProgramResult, Option<Vec<u64>>, [u64; 2]) {
+    set_clock(&clock());
+    set_stake_history(CURRENT_EPOCH, &[]);
+    set_default_rent();
+    take_return_data();
+
+    with_accounts(inputs, |accounts| {
+        let result = process(accounts, instruction_data);
+        let left = [0, 1].map(|index| {
+            get_stake_state(&accounts[index])
+                .map(|state| delegated(&state))
+                .unwrap_or_default()
+        });
+        (result, take_returned_stakes(), left)
+    })
+}
+
+/// Splits `split_lamports` off a fully active stake of `SPLIT_SOURCE_STAKE`
+/// into a destination prefunded with its rent exempt reserve.
+fn split(split_lamports: u64) -> (ProgramResult, Option<Vec<u64>>, [u64; 2]) {
+    let instruction = client::split(&STAKE, &STAKER, split_lamports, &DESTINATION);
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            STAKE,
+            STAKE_RENT_EXEMPT_RESERVE + SPLIT_SOURCE_STAKE,
+            staked(SPLIT_SOURCE_STAKE, 0),
+        )
+        .into(),
+        stake_account(
+            DESTINATION,
+            STAKE_RENT_EXEMPT_RESERVE,
+            StakeStateV2::Uninitialized,
+        )
+        .into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    process_returning(&inputs, &instruction.data)
+}
+
+/// Merges `source`, holding `source_lamports`, into `destination`.
+fn merge(
+    destination: StakeStateV2,
+    source: StakeStateV2,
+    source_lamports: u64,
+) -> (ProgramResult, Option<Vec<u64>>, [u64; 2]) {
+    let instruction = client::merge(&DESTINATION, &STAKE, &STAKER);
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            DESTINATION,
+            STAKE_RENT_EXEMPT_RESERVE + DELEGATED,
+            destination,
+        )
+        .into(),
+        stake_account(STAKE, source_lamports, source).into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    process_returning(&inputs, &instruction.data)
+}
+
+#[test]
+fn test_split_returns_both_stakes() {
+    let (result, returned, left) = split(DELEGATED);
+    assert_eq!(result, Ok(()));
+    // source first, in instruction account order
+    assert_eq!(left, [SPLIT_SOURCE_STAKE - DELEGATED, DELEGATED]);
+    assert_eq!(returned, Some(left.to_vec()));
+}
+
+#[test]
+fn test_split_all_returns_emptied_source() {
+    let (result, returned, left) = split(STAKE_RENT_EXEMPT_RESERVE + SPLIT_SOURCE_STAKE);
+    assert_eq!(result, Ok(()));
+    // the emptied source is Uninitialized, so it reports no stake, and the
+    // whole of it moves over, none of the destination's prefunding with it
+    assert_eq!(left, [0, SPLIT_SOURCE_STAKE]);
+    assert_eq!(returned, Some(vec![0, SPLIT_SOURCE_STAKE]));
+}
+
+#[test]
+fn test_merge_into_activation_epoch_returns_destination_stake() {
+    // an inactive source's lamports, reserve and all, join the stake of a
+    // destination delegated this epoch
+    let source_lamports = STAKE_RENT_EXEMPT_RESERVE + LAMPORTS_PER_SOL;
+    let (result, returned, left) = merge(
+        staked(DELEGATED, CURRENT_EPOCH),
+        initialized(),
+        source_lamports,
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(left, [DELEGATED + source_lamports, 0]);
+    assert_eq!(returned, Some(vec![DELEGATED + source_lamports]));
+}
+
+#[test]
+fn test_merge_fully_active_returns_destination_stake() {
+    let (result, returned, left) = merge(
+        staked(DELEGATED, 0),
+        staked(DELEGATED, 0),
+        STAKE_RENT_EXEMPT_RESERVE + DELEGATED,
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(left, [2 * DELEGATED, 0]);
+    assert_eq!(returned, Some(vec![2 * DELEGATED]));
+}
+
+#[test]
+fn test_failed_split_sets_no_return_data() {
+    let (result, returned, _) = split(2 * SPLIT_SOURCE_STAKE);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    assert_eq!(returned, None);
+}
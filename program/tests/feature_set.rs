@@ -0,0 +1,217 @@
+mod common;
+
+use common::*;
+use pinocchio::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock,
+    ProgramResult,
+};
+use solana_pinocchio_starter::{
+    consts::LAMPORTS_PER_SOL,
+    instruction::{process_split_with, StakeInstruction},
+    state::{
+        feature_activated_at, feature_activation_epoch, new_warmup_cooldown_rate_epoch, Delegation,
+        EpochSchedule, FeatureSet, Stake, StakeFlags, StakeStateV2, SysvarReader,
+        WindowedStakeHistory, FEATURE_PROGRAM_ID, REDUCE_STAKE_WARMUP_COOLDOWN_ID,
+        STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL_ID,
+    },
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const VOTER: Pubkey = [5; 32];
+
+const DELEGATED_STAKE: u64 = 2 * LAMPORTS_PER_SOL;
+const SLOTS_PER_EPOCH: u64 = 432_000;
+
+fn feature_account(id: Pubkey, owner: Pubkey, data: Vec<u8>) -> TestAccount {
+    TestAccount::new(id, owner, 1_000_000, data)
+}
+
+fn activated_at(slot: u64) -> Vec<u8> {
+    let mut data = vec![1];
+    data.extend_from_slice(&slot.to_le_bytes());
+    data
+}
+
+const PENDING: [u8; 9] = [0; 9];
+
+/// A cluster that has raised the minimum delegation, its other features as
+/// compiled.
+struct Raised;
+
+impl SysvarReader for Raised {
+    type StakeHistory<'a> = WindowedStakeHistory;
+
+    fn stake_history(&self, clock: &Clock) -> WindowedStakeHistory {
+        WindowedStakeHistory::from_clock(clock)
+    }
+
+    fn feature_set(&self) -> FeatureSet {
+        FeatureSet {
+            raise_minimum_delegation_to_1_sol: true,
+            ..FeatureSet::COMPILED
+        }
+    }
+}
+
+/// Splits half a SOL off a fully active stake of two, with `extra` passed
+/// after the instruction's own accounts, through `split`.
+fn split_half_sol(
+    extra: Vec<TestAccount>,
+    split: impl FnOnce(&[AccountInfo], &[u8]) -> ProgramResult,
+) -> ProgramResult {
+    set_clock(&Clock {
+        epoch: CURRENT_EPOCH,
+        ..Clock::default()
+    });
+    set_stake_history(CURRENT_EPOCH, &[]);
+    set_default_rent();
+
+    let stake = Stake {
+        delegation: Delegation::new(&VOTER, DELEGATED_STAKE, 0u64.to_le_bytes()),
+        ..Stake::default()
+    };
+    let source_state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        stake,
+        StakeFlags::empty(),
+    );
+    let mut inputs: Vec<Input> = vec![
+        stake_account(
+            SOURCE,
+            STAKE_RENT_EXEMPT_RESERVE + DELEGATED_STAKE,
+            source_state,
+        )
+        .into(),
+        stake_account(
+            DESTINATION,
+            STAKE_RENT_EXEMPT_RESERVE,
+            StakeStateV2::Uninitialized,
+        )
+        .into(),
+        signer(STAKER).into(),
+    ];
+    inputs.extend(extra.into_iter().map(Input::from));
+
+    let data = amount_data(StakeInstruction::Split, LAMPORTS_PER_SOL / 2);
+    with_accounts(&inputs, |accounts| split(accounts, &data))
+}
+
+#[test]
+fn test_compiled_feature_set() {
+    // the clusters this build targets
+    assert_eq!(
+        FeatureSet::COMPILED,
+        FeatureSet {
+            new_warmup_cooldown_rate_epoch: Some(0),
+            raise_minimum_delegation_to_1_sol: false,
+        }
+    );
+    assert_eq!(FeatureSet::COMPILED.minimum_delegation(), 1);
+    assert_eq!(
+        FeatureSet::COMPILED.new_rate_activation_epoch(),
+        Some(0u64.to_le_bytes())
+    );
+}
+
+#[test]
+fn test_split_under_raised_minimum_delegation() {
+    assert_eq!(split_half_sol(vec![], process), Ok(()));
+
+    // the raise lifts the minimum past what the destination would be left
+    // delegating
+    assert_eq!(
+        split_half_sol(vec![], |accounts, data| {
+            process_split_with(
+                accounts,
+                u64::from_le_bytes(data[1..].try_into().unwrap()),
+                &Raised,
+            )
+        }),
+        Err(ProgramError::InsufficientFunds)
+    );
+}
+
+#[test]
+fn test_feature_accounts_dont_move_gates() {
+    // the caller picks which accounts to pass, so a gate account passed or
+    // left out changes nothing; only the compiled set counts on chain
+    for data in [activated_at(1), PENDING.to_vec()] {
+        let feature = feature_account(
+            STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL_ID,
+            FEATURE_PROGRAM_ID,
+            data,
+        );
+        assert_eq!(split_half_sol(vec![feature], process), Ok(()));
+    }
+    let reduced = feature_account(
+        REDUCE_STAKE_WARMUP_COOLDOWN_ID,
+        FEATURE_PROGRAM_ID,
+        activated_at(1),
+    );
+    assert_eq!(split_half_sol(vec![reduced], process), Ok(()));
+}
+
+#[test]
+fn test_malformed_feature_account() {
+    assert_eq!(feature_activated_at(&PENDING), Ok(None));
+    assert_eq!(feature_activated_at(&activated_at(7)), Ok(Some(7)));
+    for data in [vec![], vec![1, 0, 0], vec![2; 9]] {
+        assert_eq!(
+            feature_activated_at(&data),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+}
+
+#[test]
+fn test_new_warmup_cooldown_rate_epoch() {
+    // a schedule with warmup, where early epochs are short
+    let schedule = EpochSchedule::new(SLOTS_PER_EPOCH, SLOTS_PER_EPOCH, true).unwrap();
+    let sdk =
+        solana_sdk::epoch_schedule::EpochSchedule::custom(SLOTS_PER_EPOCH, SLOTS_PER_EPOCH, true);
+    let rate_epoch = |feature: TestAccount| {
+        with_accounts(&[feature.into()], |accounts| {
+            new_warmup_cooldown_rate_epoch(&accounts[0], &schedule)
+        })
+    };
+
+    for slot in [0, 31, 32, 100, sdk.first_normal_slot, 1_000_000_000] {
+        let feature = feature_account(
+            REDUCE_STAKE_WARMUP_COOLDOWN_ID,
+            FEATURE_PROGRAM_ID,
+            activated_at(slot),
+        );
+        assert_eq!(rate_epoch(feature), Ok(Some(sdk.get_epoch(slot))));
+        assert_eq!(
+            feature_activation_epoch(&activated_at(slot), &schedule),
+            Ok(Some(sdk.get_epoch(slot)))
+        );
+    }
+
+    let pending = feature_account(
+        REDUCE_STAKE_WARMUP_COOLDOWN_ID,
+        FEATURE_PROGRAM_ID,
+        PENDING.to_vec(),
+    );
+    assert_eq!(rate_epoch(pending), Ok(None));
+
+    // only the feature's own gate account is taken
+    let other_feature = feature_account(
+        STAKE_RAISE_MINIMUM_DELEGATION_TO_1_SOL_ID,
+        FEATURE_PROGRAM_ID,
+        activated_at(1),
+    );
+    assert_eq!(
+        rate_epoch(other_feature),
+        Err(ProgramError::InvalidArgument)
+    );
+    let impostor = feature_account(
+        REDUCE_STAKE_WARMUP_COOLDOWN_ID,
+        Pubkey::default(),
+        activated_at(1),
+    );
+    assert_eq!(rate_epoch(impostor), Err(ProgramError::InvalidAccountOwner));
+}
@@ -0,0 +1,203 @@
+//! A stake account's balance can change within a transaction before the
+//! program sees it: the fee is taken from it when it pays for the
+//! transaction, and earlier instructions may move lamports in or out. The
+//! processors must check the balance the account holds when they run, not
+//! one they read earlier.
+//!
+//! Each test keeps one set of `AccountInfo`s across instructions, as the
+//! runtime does within a transaction, and moves lamports between them the
+//! way the fee or another program would.
+
+mod common;
+
+use common::*;
+use pinocchio::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, ProgramResult,
+};
+use solana_pinocchio_starter::{
+    instruction::StakeInstruction,
+    state::{Delegation, Stake, StakeFlags, StakeStateV2},
+};
+
+const STAKE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const VOTER: Pubkey = [5; 32];
+
+const CURRENT_EPOCH: u64 = 100;
+const FEE: u64 = 5_000;
+const DELEGATED_STAKE: u64 = 1_000_000_000;
+const FREE_LAMPORTS: u64 = 1_000_000_000;
+
+fn initialized() -> StakeStateV2 {
+    StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER))
+}
+
+/// Stake delegated long enough ago to be fully active.
+fn delegated() -> StakeStateV2 {
+    StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        Stake {
+            delegation: Delegation::new(&VOTER, DELEGATED_STAKE, 0u64.to_le_bytes()),
+            ..Stake::default()
+        },
+        StakeFlags::empty(),
+    )
+}
+
+/// Runs `f` over a stake account in `state` holding `lamports`, and, after
+/// it, the accounts `Withdraw` takes: a destination wallet, the clock, the
+/// stake history and the withdrawer, signing.
+fn with_stake_account<R>(
+    state: StakeStateV2,
+    lamports: u64,
+    f: impl FnOnce(&[AccountInfo]) -> R,
+) -> R {
+    set_clock(&clock_at(CURRENT_EPOCH));
+    set_stake_history(CURRENT_EPOCH, &[]);
+    set_default_rent();
+
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, lamports, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock_at(CURRENT_EPOCH)).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, f)
+}
+
+fn withdraw(accounts: &[AccountInfo], lamports: u64) -> ProgramResult {
+    process(accounts, &amount_data(StakeInstruction::Withdraw, lamports))
+}
+
+/// Takes `lamports` out of `account` outside of the program, as the fee or
+/// an earlier instruction would.
+fn debit(account: &AccountInfo, lamports: u64) {
+    *account.try_borrow_mut_lamports().unwrap() -= lamports;
+}
+
+fn credit(account: &AccountInfo, lamports: u64) {
+    *account.try_borrow_mut_lamports().unwrap() += lamports;
+}
+
+#[test]
+fn test_fee_payer_initialized_withdraw() {
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + FREE_LAMPORTS;
+    with_stake_account(initialized(), lamports, |accounts| {
+        debit(&accounts[0], FEE);
+
+        // the fee came out of the free lamports: the full balance from before
+        // it is gone, and so is leaving the reserve behind with it
+        assert_eq!(
+            withdraw(accounts, lamports),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(
+            withdraw(accounts, FREE_LAMPORTS),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(withdraw(accounts, FREE_LAMPORTS - FEE), Ok(()));
+        assert_eq!(accounts[0].lamports(), STAKE_RENT_EXEMPT_RESERVE);
+
+        // what is left can still close the account
+        assert_eq!(withdraw(accounts, STAKE_RENT_EXEMPT_RESERVE), Ok(()));
+        assert_eq!(accounts[0].lamports(), 0);
+        assert_eq!(accounts[1].lamports(), lamports - FEE);
+    });
+}
+
+#[test]
+fn test_fee_payer_eats_into_active_stake() {
+    // nothing free: the fee is taken from lamports that back the stake
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + DELEGATED_STAKE;
+    with_stake_account(delegated(), lamports, |accounts| {
+        assert_eq!(withdraw(accounts, 1), Err(ProgramError::InsufficientFunds));
+        debit(&accounts[0], FEE);
+
+        // none of it can be withdrawn, nor the whole balance, while active
+        assert_eq!(withdraw(accounts, 1), Err(ProgramError::InsufficientFunds));
+        assert_eq!(
+            withdraw(accounts, lamports - FEE),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(accounts[0].lamports(), lamports - FEE);
+    });
+}
+
+#[test]
+fn test_earlier_instruction_deposit() {
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + DELEGATED_STAKE;
+    with_stake_account(delegated(), lamports, |accounts| {
+        assert_eq!(withdraw(accounts, 1), Err(ProgramError::InsufficientFunds));
+
+        // a transfer earlier in the transaction is free to withdraw at once
+        credit(&accounts[0], FREE_LAMPORTS);
+        assert_eq!(
+            withdraw(accounts, FREE_LAMPORTS + 1),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(withdraw(accounts, FREE_LAMPORTS), Ok(()));
+        assert_eq!(accounts[0].lamports(), lamports);
+        assert_eq!(accounts[1].lamports(), FREE_LAMPORTS);
+    });
+}
+
+#[test]
+fn test_earlier_instruction_withdrawal() {
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + DELEGATED_STAKE + FREE_LAMPORTS;
+    with_stake_account(delegated(), lamports, |accounts| {
+        // the free lamports moved out by an earlier instruction of this
+        // program can't be withdrawn a second time
+        assert_eq!(withdraw(accounts, FREE_LAMPORTS), Ok(()));
+        assert_eq!(withdraw(accounts, 1), Err(ProgramError::InsufficientFunds));
+
+        // nor after the fee, which leaves the account short of its stake
+        debit(&accounts[0], FEE);
+        assert_eq!(withdraw(accounts, 1), Err(ProgramError::InsufficientFunds));
+    });
+}
+
+#[test]
+fn test_fee_payer_split() {
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + FREE_LAMPORTS;
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, lamports, initialized()).into(),
+        stake_account(
+            DESTINATION,
+            STAKE_RENT_EXEMPT_RESERVE,
+            StakeStateV2::Uninitialized,
+        )
+        .into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    set_default_rent();
+    with_accounts(&inputs, |accounts| {
+        debit(&accounts[0], FEE);
+
+        // splitting off the whole balance from before the fee overdraws
+        assert_eq!(
+            process(accounts, &amount_data(StakeInstruction::Split, lamports)),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(
+            process(
+                accounts,
+                &amount_data(StakeInstruction::Split, lamports - FEE)
+            ),
+            Ok(())
+        );
+        assert_eq!(accounts[0].lamports(), 0);
+        assert_eq!(
+            accounts[1].lamports(),
+            STAKE_RENT_EXEMPT_RESERVE + lamports - FEE
+        );
+    });
+}
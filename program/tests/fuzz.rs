@@ -0,0 +1,310 @@
+//! Structured fuzzing of the entrypoint.
+//!
+//! Each case starts from valid account fixtures, flips bits in the stake
+//! account data, perturbs balances, swaps or drops accounts and then runs
+//! one of the dispatched instructions with random arguments. The program must
+//! return rather than panic, and must never leave the accounts holding more
+//! lamports in total than they started with.
+//!
+//! Cases are derived from a fixed seed, so a failure names the case to rerun.
+//! `FUZZ_CASES` overrides the number of cases.
+
+mod common;
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use common::*;
+use pinocchio::{pubkey::Pubkey, sysvars::clock::Clock};
+use solana_pinocchio_starter::{
+    instruction::StakeInstruction,
+    state::{Delegation, Lockup, Meta, Stake, StakeFlags, StakeStateV2},
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const RECIPIENT: Pubkey = [3; 32];
+const STAKER: Pubkey = [4; 32];
+const WITHDRAWER: Pubkey = [5; 32];
+const CUSTODIAN: Pubkey = [6; 32];
+const VOTER: Pubkey = [7; 32];
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+const DEFAULT_CASES: u64 = 20_000;
+const SEED: u64 = 0x5eed_f00d_cafe_d00d;
+
+// positions in the account pool
+const SOURCE_ACCOUNT: usize = 0;
+const DESTINATION_ACCOUNT: usize = 1;
+const RECIPIENT_ACCOUNT: usize = 2;
+const CLOCK_ACCOUNT: usize = 3;
+const STAKE_HISTORY_ACCOUNT: usize = 4;
+const STAKER_ACCOUNT: usize = 5;
+const WITHDRAWER_ACCOUNT: usize = 6;
+const CUSTODIAN_ACCOUNT: usize = 7;
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+fn epoch(rng: &mut Rng) -> u64 {
+    rng.pick(&[0, 1, 299, 300, 301, u64::MAX - 1, u64::MAX])
+}
+
+fn meta(rng: &mut Rng) -> Meta {
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    if rng.one_in(2) {
+        meta.lockup = Lockup {
+            unix_timestamp: (rng.next() as i64).to_le_bytes(),
+            epoch: epoch(rng).to_le_bytes(),
+            custodian: rng.pick(&[CUSTODIAN, Pubkey::default()]),
+        };
+    }
+    meta
+}
+
+fn stake_state(rng: &mut Rng) -> StakeStateV2 {
+    match rng.below(4) {
+        0 => StakeStateV2::Uninitialized,
+        1 => StakeStateV2::Initialized(meta(rng)),
+        2 => {
+            let any = rng.next();
+            let mut delegation = Delegation::new(
+                &VOTER,
+                rng.pick(&[0, 1, LAMPORTS_PER_SOL, any]),
+                epoch(rng).to_le_bytes(),
+            );
+            delegation.deactivation_epoch = epoch(rng).to_le_bytes();
+            let stake = Stake {
+                delegation,
+                ..Stake::default()
+            };
+            StakeStateV2::Stake(meta(rng), stake, StakeFlags::empty())
+        }
+        _ => StakeStateV2::RewardsPool,
+    }
+}
+
+fn lamports(rng: &mut Rng) -> u64 {
+    let base =
+        STAKE_RENT_EXEMPT_RESERVE + rng.pick(&[0, 1, LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL]);
+    match rng.below(8) {
+        0 => rng.next(),
+        1 => base.saturating_sub(rng.below(3)),
+        2 => base.saturating_add(rng.below(3)),
+        _ => base,
+    }
+}
+
+/// Flips a few random bits anywhere in the stake state, tag included.
+fn flip_bits(rng: &mut Rng, data: &mut [u8]) {
+    for _ in 0..=rng.below(4) {
+        let bit = rng.below(data.len() as u64 * 8) as usize;
+        data[bit / 8] ^= 1 << (bit % 8);
+    }
+}
+
+fn account_pool(rng: &mut Rng) -> Vec<TestAccount> {
+    let clock = Clock {
+        epoch: epoch(rng),
+        unix_timestamp: rng.next() as i64,
+        ..Clock::default()
+    };
+    set_clock(&clock);
+
+    let mut pool = vec![
+        stake_account(SOURCE, lamports(rng), stake_state(rng)),
+        stake_account(DESTINATION, lamports(rng), stake_state(rng)),
+        TestAccount::new(RECIPIENT, Pubkey::default(), rng.below(3), vec![]).writable(),
+        clock_account(&clock),
+        stake_history_account(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![]).signer(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![]).signer(),
+        TestAccount::new(CUSTODIAN, Pubkey::default(), 0, vec![]).signer(),
+    ];
+
+    for account in &mut pool[..=DESTINATION_ACCOUNT] {
+        if rng.one_in(2) {
+            flip_bits(rng, &mut account.data);
+        }
+    }
+    for account in &mut pool {
+        if rng.one_in(16) {
+            account.is_signer = !account.is_signer;
+        }
+        if rng.one_in(16) {
+            account.is_writable = !account.is_writable;
+        }
+    }
+    pool
+}
+
+fn amount(rng: &mut Rng, pool: &[TestAccount]) -> u64 {
+    let source = pool[SOURCE_ACCOUNT].lamports;
+    match rng.below(6) {
+        0 => 0,
+        1 => 1,
+        2 => u64::MAX,
+        3 => source,
+        4 => source.saturating_sub(STAKE_RENT_EXEMPT_RESERVE),
+        _ => rng.below(source.saturating_add(1)),
+    }
+}
+
+fn lockup_args(rng: &mut Rng) -> Vec<u8> {
+    let mut data = Vec::new();
+    for len in [8, 8, 32] {
+        if rng.one_in(2) {
+            data.push(1);
+            data.extend((0..len).map(|_| rng.next() as u8));
+        } else {
+            data.push(0);
+        }
+    }
+    data
+}
+
+/// Picks an instruction and the pool accounts it is given.
+fn instruction(rng: &mut Rng, pool: &[TestAccount]) -> (Vec<u8>, Vec<usize>) {
+    let (instruction, mut data, accounts) = match rng.below(5) {
+        0 => (
+            StakeInstruction::Split,
+            amount(rng, pool).to_le_bytes().to_vec(),
+            vec![SOURCE_ACCOUNT, DESTINATION_ACCOUNT, STAKER_ACCOUNT],
+        ),
+        1 => (
+            StakeInstruction::Withdraw,
+            amount(rng, pool).to_le_bytes().to_vec(),
+            vec![
+                SOURCE_ACCOUNT,
+                RECIPIENT_ACCOUNT,
+                CLOCK_ACCOUNT,
+                STAKE_HISTORY_ACCOUNT,
+                WITHDRAWER_ACCOUNT,
+                CUSTODIAN_ACCOUNT,
+            ],
+        ),
+        2 => (
+            StakeInstruction::SetLockup,
+            lockup_args(rng),
+            vec![SOURCE_ACCOUNT, WITHDRAWER_ACCOUNT, CUSTODIAN_ACCOUNT],
+        ),
+        3 => (
+            StakeInstruction::MoveLamports,
+            amount(rng, pool).to_le_bytes().to_vec(),
+            vec![SOURCE_ACCOUNT, DESTINATION_ACCOUNT, STAKER_ACCOUNT],
+        ),
+        _ => {
+            #[allow(deprecated)]
+            let redelegate = StakeInstruction::Redelegate;
+            (
+                redelegate,
+                vec![],
+                vec![SOURCE_ACCOUNT, DESTINATION_ACCOUNT],
+            )
+        }
+    };
+
+    // truncated or overlong data
+    if rng.one_in(16) {
+        data.truncate(rng.below(data.len() as u64 + 1) as usize);
+    } else if rng.one_in(16) {
+        data.push(rng.next() as u8);
+    }
+
+    // swapped, duplicated or missing accounts
+    let mut accounts: Vec<usize> = accounts
+        .into_iter()
+        .map(|index| {
+            if rng.one_in(16) {
+                rng.below(pool.len() as u64) as usize
+            } else {
+                index
+            }
+        })
+        .collect();
+    if rng.one_in(16) {
+        accounts.truncate(rng.below(accounts.len() as u64) as usize);
+    }
+
+    data.insert(0, instruction as u8);
+    (data, accounts)
+}
+
+fn run_case(case: u64) {
+    let mut rng = Rng(SEED ^ case.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+    set_default_rent();
+
+    let pool = account_pool(&mut rng);
+    let (data, accounts) = instruction(&mut rng, &pool);
+
+    // repeated pool accounts become duplicates of their first position
+    let mut inputs: Vec<Input> = Vec::new();
+    let mut lamports_before: u128 = 0;
+    for (position, &index) in accounts.iter().enumerate() {
+        match accounts[..position]
+            .iter()
+            .position(|&other| other == index)
+        {
+            Some(first) => inputs.push(Input::Duplicate(first as u8)),
+            None => {
+                lamports_before += pool[index].lamports as u128;
+                inputs.push(pool[index].clone().into());
+            }
+        }
+    }
+
+    let lamports_after = with_accounts(&inputs, |accounts| {
+        let _ = process(accounts, &data);
+        inputs
+            .iter()
+            .zip(accounts)
+            .filter(|(input, _)| matches!(input, Input::Account(_)))
+            .map(|(_, account)| account.lamports() as u128)
+            .sum::<u128>()
+    });
+    assert!(
+        lamports_after <= lamports_before,
+        "lamports went from {lamports_before} to {lamports_after}"
+    );
+}
+
+#[test]
+fn fuzz_mutated_accounts() {
+    let cases = std::env::var("FUZZ_CASES")
+        .ok()
+        .and_then(|cases| cases.parse().ok())
+        .unwrap_or(DEFAULT_CASES);
+
+    for case in 0..cases {
+        if let Err(panic) = catch_unwind(AssertUnwindSafe(|| run_case(case))) {
+            std::panic::resume_unwind(Box::new(format!(
+                "case {case} failed: {}",
+                panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("<non-string panic>")
+            )));
+        }
+    }
+}
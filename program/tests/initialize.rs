@@ -0,0 +1,229 @@
+mod common;
+
+use common::*;
+use pinocchio::{
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::rent::{Rent, RENT_ID},
+};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    consts::CLOCK_ID,
+    state::{get_stake_state, Authorized, Lockup, Meta, StakeStateV2},
+};
+
+const STAKE: Pubkey = [1; 32];
+const STAKER: Pubkey = [2; 32];
+const WITHDRAWER: Pubkey = [3; 32];
+const CUSTODIAN: Pubkey = [4; 32];
+
+fn authorized() -> Authorized {
+    Authorized {
+        staker: STAKER,
+        withdrawer: WITHDRAWER,
+    }
+}
+
+fn lockup() -> Lockup {
+    Lockup {
+        unix_timestamp: 1_700_000_000i64.to_le_bytes(),
+        epoch: 400u64.to_le_bytes(),
+        custodian: CUSTODIAN,
+    }
+}
+
+/// Initializes `stake` with `authorized()` and `lockup()`, returning the
+/// result and the account's state after.
+fn initialize(
+    stake: TestAccount,
+    rent_account: TestAccount,
+) -> (Result<(), ProgramError>, Vec<u8>) {
+    let instruction = client::initialize(&stake.key, &authorized(), &lockup());
+    let inputs: Vec<Input> = vec![stake.into(), rent_account.into()];
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &instruction.data);
+        (result, accounts[0].try_borrow_data().unwrap().to_vec())
+    })
+}
+
+fn initialized_state() -> StakeStateV2 {
+    StakeStateV2::Initialized(Meta {
+        rent_exempt_reserve: STAKE_RENT_EXEMPT_RESERVE.to_le_bytes(),
+        authorized: authorized(),
+        lockup: lockup(),
+    })
+}
+
+#[test]
+fn test_initialize() {
+    set_default_rent();
+    for lamports in [STAKE_RENT_EXEMPT_RESERVE, 10 * STAKE_RENT_EXEMPT_RESERVE] {
+        let stake = stake_account(STAKE, lamports, StakeStateV2::Uninitialized);
+        let (result, data) = initialize(stake, rent_account());
+        assert_eq!(result, Ok(()));
+        assert_stake_data_eq(&data, &initialized_state());
+    }
+}
+
+#[test]
+fn test_initialize_below_rent_exempt_reserve() {
+    set_default_rent();
+    let stake = stake_account(
+        STAKE,
+        STAKE_RENT_EXEMPT_RESERVE - 1,
+        StakeStateV2::Uninitialized,
+    );
+    let (result, data) = initialize(stake, rent_account());
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    assert_stake_data_eq(&data, &StakeStateV2::Uninitialized);
+}
+
+#[test]
+fn test_initialize_reserve_follows_rent_sysvar() {
+    // rent doubled since the client was written: what was exempt no longer is
+    let rent = Rent {
+        lamports_per_byte_year: 2 * default_rent().lamports_per_byte_year,
+        ..default_rent()
+    };
+    set_sysvar(RENT_ID, rent_data(&rent));
+    let reserve = 2 * STAKE_RENT_EXEMPT_RESERVE;
+
+    let stake = stake_account(STAKE, reserve - 1, StakeStateV2::Uninitialized);
+    let (result, _) = initialize(stake, rent_account());
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+
+    let stake = stake_account(STAKE, reserve, StakeStateV2::Uninitialized);
+    let (result, data) = initialize(stake.clone(), rent_account());
+    assert_eq!(result, Ok(()));
+    let inputs: Vec<Input> = vec![TestAccount { data, ..stake }.into()];
+    with_accounts(&inputs, |accounts| {
+        let Ok(StakeStateV2::Initialized(meta)) = get_stake_state(&accounts[0]) else {
+            panic!("not initialized");
+        };
+        assert_eq!(meta.rent_exempt_reserve, reserve.to_le_bytes());
+    });
+}
+
+#[test]
+fn test_initialize_rejects_account() {
+    set_default_rent();
+    let lamports = 10 * STAKE_RENT_EXEMPT_RESERVE;
+
+    // anything but Uninitialized
+    for state in [
+        initialized_state(),
+        StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+        StakeStateV2::RewardsPool,
+    ] {
+        let stake = stake_account(STAKE, lamports, state);
+        let (result, data) = initialize(stake, rent_account());
+        assert_eq!(result, Err(ProgramError::InvalidAccountData));
+        assert_stake_data_eq(&data, &state);
+    }
+
+    // sized for anything but a stake state, even when large enough
+    for len in [0, StakeStateV2::size_of() - 1, StakeStateV2::size_of() + 1] {
+        let mut stake = stake_account(STAKE, lamports, StakeStateV2::Uninitialized);
+        stake.data.resize(len, 0);
+        let (result, _) = initialize(stake, rent_account());
+        assert_eq!(result, Err(ProgramError::InvalidAccountData), "{len} bytes");
+    }
+
+    let mut stake = stake_account(STAKE, lamports, StakeStateV2::Uninitialized);
+    stake.owner = Pubkey::default();
+    let (result, _) = initialize(stake, rent_account());
+    assert_eq!(result, Err(ProgramError::InvalidAccountOwner));
+}
+
+#[test]
+fn test_initialize_requires_rent_sysvar_account() {
+    set_default_rent();
+    let mut not_rent = rent_account();
+    not_rent.key = CLOCK_ID;
+    let stake = stake_account(
+        STAKE,
+        STAKE_RENT_EXEMPT_RESERVE,
+        StakeStateV2::Uninitialized,
+    );
+    let (result, data) = initialize(stake, not_rent);
+    assert_eq!(result, Err(ProgramError::InvalidArgument));
+    assert_stake_data_eq(&data, &StakeStateV2::Uninitialized);
+}
+
+/// Initializes `stake` through `InitializeChecked`, with `withdrawer` signing
+/// as asked, returning the result and the account's state after.
+fn initialize_checked(
+    stake: TestAccount,
+    rent_account: TestAccount,
+    withdrawer_signs: bool,
+) -> (Result<(), ProgramError>, Vec<u8>) {
+    let instruction = client::initialize_checked(&stake.key, &STAKER, &WITHDRAWER);
+    let mut withdrawer = TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![]);
+    withdrawer.is_signer = withdrawer_signs;
+    let inputs: Vec<Input> = vec![
+        stake.into(),
+        rent_account.into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![]).into(),
+        withdrawer.into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &instruction.data);
+        (result, accounts[0].try_borrow_data().unwrap().to_vec())
+    })
+}
+
+#[test]
+fn test_initialize_checked() {
+    set_default_rent();
+    // the authorities come from the accounts, with no lockup
+    let stake = stake_account(
+        STAKE,
+        STAKE_RENT_EXEMPT_RESERVE,
+        StakeStateV2::Uninitialized,
+    );
+    let (result, data) = initialize_checked(stake, rent_account(), true);
+    assert_eq!(result, Ok(()));
+    assert_stake_data_eq(
+        &data,
+        &StakeStateV2::Initialized(Meta {
+            rent_exempt_reserve: STAKE_RENT_EXEMPT_RESERVE.to_le_bytes(),
+            authorized: authorized(),
+            lockup: Lockup::default(),
+        }),
+    );
+
+    // the same account checks as `Initialize`
+    let stake = stake_account(
+        STAKE,
+        STAKE_RENT_EXEMPT_RESERVE - 1,
+        StakeStateV2::Uninitialized,
+    );
+    let (result, _) = initialize_checked(stake, rent_account(), true);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    let stake = stake_account(STAKE, STAKE_RENT_EXEMPT_RESERVE, initialized_state());
+    let (result, _) = initialize_checked(stake, rent_account(), true);
+    assert_eq!(result, Err(ProgramError::InvalidAccountData));
+}
+
+#[test]
+fn test_initialize_checked_requires_withdrawer_signature() {
+    set_default_rent();
+    let uninitialized = || {
+        stake_account(
+            STAKE,
+            STAKE_RENT_EXEMPT_RESERVE,
+            StakeStateV2::Uninitialized,
+        )
+    };
+    let (result, data) = initialize_checked(uninitialized(), rent_account(), false);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_stake_data_eq(&data, &StakeStateV2::Uninitialized);
+
+    // checked before the rent account, as native does
+    let mut not_rent = rent_account();
+    not_rent.key = CLOCK_ID;
+    let (result, _) = initialize_checked(uninitialized(), not_rent.clone(), false);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    let (result, _) = initialize_checked(uninitialized(), not_rent, true);
+    assert_eq!(result, Err(ProgramError::InvalidArgument));
+}
@@ -0,0 +1,194 @@
+//! Withdrawals under the linear unlock of the `linear-unlock` build, run with
+//! `cargo test --features linear-unlock`.
+#![cfg(feature = "linear-unlock")]
+
+mod common;
+
+use common::*;
+use pinocchio::{pubkey::Pubkey, ProgramResult};
+use solana_pinocchio_starter::{
+    consts::LAMPORTS_PER_SOL,
+    error::StakeError,
+    instruction::StakeInstruction,
+    state::{
+        lockup_in_force, Authorized, ClusterLockup, Delegation, Lockup, Stake, StakeAuthorize,
+        StakeFlags, StakeStateV2,
+    },
+};
+
+const STAKE: Pubkey = [1; 32];
+const VOTE: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const NEW_WITHDRAWER: Pubkey = [5; 32];
+const DESTINATION: Pubkey = [6; 32];
+const CUSTODIAN: Pubkey = [7; 32];
+
+const DELEGATED: u64 = LAMPORTS_PER_SOL;
+const VESTING: u64 = STAKE_RENT_EXEMPT_RESERVE + DELEGATED;
+
+/// Locked until twice the current epoch, so half the account has vested.
+fn half_vested() -> Lockup {
+    let mut lockup = Lockup {
+        custodian: CUSTODIAN,
+        ..Lockup::default()
+    };
+    lockup.set_epoch(2 * CURRENT_EPOCH);
+    lockup
+}
+
+/// `DELEGATED` lamports under `lockup`, deactivated long enough ago that none
+/// of them is still effective.
+fn cooled_down(lockup: Lockup) -> StakeStateV2 {
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.lockup = lockup;
+    let mut stake = Stake {
+        delegation: Delegation::new(&VOTE, DELEGATED, 0u64.to_le_bytes()),
+        ..Stake::default()
+    };
+    stake.delegation.deactivation_epoch = (CURRENT_EPOCH - 1).to_le_bytes();
+    StakeStateV2::Stake(meta, stake, StakeFlags::empty())
+}
+
+/// Withdraws each of `withdrawals` in turn from an account of `lamports` in
+/// `state`, signed by the withdrawer and any `custodian`, returning every
+/// result.
+fn withdraw_each(
+    state: StakeStateV2,
+    lamports: u64,
+    withdrawals: &[u64],
+    custodian: Option<Pubkey>,
+) -> Vec<ProgramResult> {
+    set_clock(&clock());
+    set_stake_history(CURRENT_EPOCH, &[]);
+
+    let mut inputs: Vec<Input> = vec![
+        stake_account(STAKE, lamports, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        signer(WITHDRAWER).into(),
+    ];
+    inputs.extend(custodian.map(|custodian| signer(custodian).into()));
+    with_accounts(&inputs, |accounts| {
+        withdrawals
+            .iter()
+            .map(|lamports| {
+                process(
+                    accounts,
+                    &amount_data(StakeInstruction::Withdraw, *lamports),
+                )
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn test_withdraw_under_linear_unlock() {
+    let vested = VESTING / 2;
+    assert_eq!(
+        withdraw_each(cooled_down(half_vested()), VESTING, &[vested + 1], None),
+        [Err(StakeError::LockupInForce.into())]
+    );
+    assert_eq!(
+        withdraw_each(cooled_down(half_vested()), VESTING, &[vested], None),
+        [Ok(())]
+    );
+
+    // the custodian lifts the rest, as on mainnet
+    assert_eq!(
+        withdraw_each(
+            cooled_down(half_vested()),
+            VESTING,
+            &[VESTING],
+            Some(CUSTODIAN)
+        ),
+        [Ok(())]
+    );
+}
+
+#[test]
+fn test_repeated_withdrawals_stop_at_the_schedule() {
+    // vesting follows the delegation, not the balance each withdrawal lowers,
+    // so small withdrawals add up to what has vested and no more
+    let step = VESTING / 8;
+    let results = withdraw_each(
+        cooled_down(half_vested()),
+        VESTING,
+        &[step, step, step, step, 1],
+        None,
+    );
+    assert_eq!(
+        results,
+        [
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Ok(()),
+            Err(StakeError::LockupInForce.into())
+        ]
+    );
+}
+
+#[test]
+fn test_rewards_are_not_locked() {
+    // lamports the delegation never held, rewards say, are free on top of
+    // what has vested
+    let rewards = LAMPORTS_PER_SOL / 10;
+    let vested = VESTING / 2;
+    assert_eq!(
+        withdraw_each(
+            cooled_down(half_vested()),
+            VESTING + rewards,
+            &[vested + rewards, 1],
+            None
+        ),
+        [Ok(()), Err(StakeError::LockupInForce.into())]
+    );
+}
+
+#[test]
+fn test_undelegated_account_keeps_the_cliff() {
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.lockup = half_vested();
+    assert_eq!(
+        withdraw_each(StakeStateV2::Initialized(meta), VESTING, &[1], None),
+        [Err(StakeError::LockupInForce.into())]
+    );
+}
+
+#[test]
+fn test_linear_unlock_keeps_the_timestamp_a_cliff() {
+    let mut lockup = half_vested();
+    lockup.set_unix_timestamp(clock().unix_timestamp + 1);
+    assert_eq!(
+        withdraw_each(cooled_down(lockup), VESTING, &[1], None),
+        [Err(StakeError::LockupInForce.into())]
+    );
+}
+
+#[test]
+fn test_linear_unlock_is_in_force_as_on_mainnet() {
+    // vesting only changes what a withdrawal may take; handing over the
+    // withdrawer still needs the custodian until the lockup ends
+    let mut authorized = Authorized {
+        staker: STAKER,
+        withdrawer: WITHDRAWER,
+    };
+    assert_eq!(
+        authorized.authorize::<ClusterLockup>(
+            &[WITHDRAWER],
+            &NEW_WITHDRAWER,
+            StakeAuthorize::Withdrawer,
+            Some((&half_vested(), &clock(), None)),
+        ),
+        Err(StakeError::CustodianMissing.into())
+    );
+    assert!(lockup_in_force(half_vested(), &clock()));
+    assert!(!lockup_in_force(
+        half_vested(),
+        &warp_to_epoch(&clock(), 2 * CURRENT_EPOCH)
+    ));
+}
@@ -0,0 +1,403 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    consts::LAMPORTS_PER_SOL,
+    error::StakeError,
+    state::{
+        get_stake_state, Delegation, Meta, Stake, StakeFlags, StakeHistoryEntry, StakeStateV2,
+    },
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const VOTER: Pubkey = [5; 32];
+const OTHER_VOTER: Pubkey = [6; 32];
+const CUSTODIAN: Pubkey = [7; 32];
+
+const DESTINATION_STAKE: u64 = 3 * LAMPORTS_PER_SOL;
+const SOURCE_STAKE: u64 = LAMPORTS_PER_SOL;
+const DESTINATION_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + DESTINATION_STAKE;
+const SOURCE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + SOURCE_STAKE;
+
+fn meta() -> Meta {
+    initialized_meta(STAKER, WITHDRAWER)
+}
+
+fn initialized() -> StakeStateV2 {
+    StakeStateV2::Initialized(meta())
+}
+
+/// `stake` lamports delegated to `voter` since `activation_epoch`, having
+/// observed `credits`.
+fn staked(voter: Pubkey, stake: u64, activation_epoch: u64, credits: u64) -> StakeStateV2 {
+    StakeStateV2::Stake(
+        meta(),
+        Stake {
+            delegation: Delegation::new(&voter, stake, activation_epoch.to_le_bytes()),
+            credits_observed: credits.to_le_bytes(),
+        },
+        StakeFlags::empty(),
+    )
+}
+
+/// Fully active, with no stake history holding it back.
+fn active(voter: Pubkey, stake: u64, credits: u64) -> StakeStateV2 {
+    staked(voter, stake, 0, credits)
+}
+
+/// Delegated this epoch, so nothing of it is effective yet.
+fn activating(stake: u64, credits: u64) -> StakeStateV2 {
+    staked(VOTER, stake, CURRENT_EPOCH, credits)
+}
+
+struct Merged {
+    result: ProgramResult,
+    destination: StakeStateV2,
+    destination_lamports: u64,
+    source: StakeStateV2,
+    source_lamports: u64,
+}
+
+/// Merges `source` into `destination` through the entrypoint, signed by
+/// `signer`.
+fn merge_signed(
+    destination: StakeStateV2,
+    destination_lamports: u64,
+    source: StakeStateV2,
+    source_lamports: u64,
+    signer: Pubkey,
+) -> Merged {
+    set_clock(&clock());
+    set_default_rent();
+
+    let instruction = client::merge(&DESTINATION, &SOURCE, &signer);
+    let inputs: Vec<Input> = vec![
+        stake_account(DESTINATION, destination_lamports, destination).into(),
+        stake_account(SOURCE, source_lamports, source).into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(signer, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| Merged {
+        result: process(accounts, &instruction.data),
+        destination: get_stake_state(&accounts[0]).unwrap(),
+        destination_lamports: accounts[0].lamports(),
+        source: get_stake_state(&accounts[1]).unwrap(),
+        source_lamports: accounts[1].lamports(),
+    })
+}
+
+fn merge(
+    destination: StakeStateV2,
+    destination_lamports: u64,
+    source: StakeStateV2,
+    source_lamports: u64,
+) -> Merged {
+    merge_signed(
+        destination,
+        destination_lamports,
+        source,
+        source_lamports,
+        STAKER,
+    )
+}
+
+/// Asserts the source was drained into the destination, which was left as
+/// `expected`.
+fn assert_merged(merged: &Merged, expected: &StakeStateV2, lamports: u64) {
+    assert_eq!(merged.result, Ok(()));
+    assert_stake_state_eq(&merged.destination, expected);
+    assert_eq!(merged.destination_lamports, lamports);
+    assert_stake_state_eq(&merged.source, &StakeStateV2::Uninitialized);
+    assert_eq!(merged.source_lamports, 0);
+}
+
+/// Asserts the merge failed with `error` and left both accounts alone.
+fn assert_not_merged(
+    merged: &Merged,
+    error: ProgramError,
+    destination: &StakeStateV2,
+    source: &StakeStateV2,
+) {
+    assert_eq!(merged.result, Err(error));
+    assert_stake_state_eq(&merged.destination, destination);
+    assert_stake_state_eq(&merged.source, source);
+}
+
+#[test]
+fn test_merge_inactive() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let total = DESTINATION_LAMPORTS + SOURCE_LAMPORTS;
+
+    let merged = merge(
+        initialized(),
+        DESTINATION_LAMPORTS,
+        initialized(),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(&merged, &initialized(), total);
+
+    // inactive stake takes in activating stake as plain lamports
+    let merged = merge(
+        initialized(),
+        DESTINATION_LAMPORTS,
+        activating(SOURCE_STAKE, 0),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(&merged, &initialized(), total);
+}
+
+#[test]
+fn test_merge_into_activation_epoch() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let total = DESTINATION_LAMPORTS + SOURCE_LAMPORTS;
+
+    // every lamport of an inactive source is delegated, its reserve too
+    let merged = merge(
+        activating(DESTINATION_STAKE, 100),
+        DESTINATION_LAMPORTS,
+        initialized(),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(
+        &merged,
+        &activating(DESTINATION_STAKE + SOURCE_LAMPORTS, 100),
+        total,
+    );
+
+    // as is the reserve of an activating one, weighing in its credits
+    let merged = merge(
+        activating(DESTINATION_STAKE, 100),
+        DESTINATION_LAMPORTS,
+        activating(SOURCE_STAKE, 100),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(
+        &merged,
+        &activating(DESTINATION_STAKE + SOURCE_LAMPORTS, 100),
+        total,
+    );
+}
+
+#[test]
+fn test_merge_fully_active() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+
+    // only the source's stake is delegated, its reserve is left withdrawable,
+    // and the credits are weighted by stake: (3 * 100 + 1 * 200) / 4
+    let merged = merge(
+        active(VOTER, DESTINATION_STAKE, 100),
+        DESTINATION_LAMPORTS,
+        active(VOTER, SOURCE_STAKE, 200),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(
+        &merged,
+        &active(VOTER, DESTINATION_STAKE + SOURCE_STAKE, 125),
+        DESTINATION_LAMPORTS + SOURCE_LAMPORTS,
+    );
+}
+
+#[test]
+fn test_merge_activation_state_mismatch() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let error: ProgramError = StakeError::MergeMismatch.into();
+    let fully_active = active(VOTER, DESTINATION_STAKE, 0);
+
+    for (destination, source) in [
+        (fully_active, initialized()),
+        (fully_active, activating(SOURCE_STAKE, 0)),
+        (initialized(), fully_active),
+        (activating(DESTINATION_STAKE, 0), fully_active),
+        // delegated to different voters
+        (fully_active, active(OTHER_VOTER, SOURCE_STAKE, 0)),
+    ] {
+        let merged = merge(destination, DESTINATION_LAMPORTS, source, SOURCE_LAMPORTS);
+        assert_not_merged(&merged, error.clone(), &destination, &source);
+    }
+}
+
+#[test]
+fn test_merge_meta_mismatch() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    let error: ProgramError = StakeError::MergeMismatch.into();
+
+    let other_withdrawer = StakeStateV2::Initialized(initialized_meta(STAKER, [9; 32]));
+    let merged = merge(
+        initialized(),
+        DESTINATION_LAMPORTS,
+        other_withdrawer,
+        SOURCE_LAMPORTS,
+    );
+    assert_not_merged(&merged, error.clone(), &initialized(), &other_withdrawer);
+
+    let locked_until = |epoch: u64| {
+        let mut meta = meta();
+        meta.lockup.epoch = epoch.to_le_bytes();
+        meta.lockup.custodian = CUSTODIAN;
+        StakeStateV2::Initialized(meta)
+    };
+    // lockups may differ once neither is in force
+    let merged = merge(
+        locked_until(CURRENT_EPOCH + 1),
+        DESTINATION_LAMPORTS,
+        initialized(),
+        SOURCE_LAMPORTS,
+    );
+    assert_not_merged(
+        &merged,
+        error,
+        &locked_until(CURRENT_EPOCH + 1),
+        &initialized(),
+    );
+    let merged = merge(
+        locked_until(CURRENT_EPOCH - 1),
+        DESTINATION_LAMPORTS,
+        initialized(),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(
+        &merged,
+        &locked_until(CURRENT_EPOCH - 1),
+        DESTINATION_LAMPORTS + SOURCE_LAMPORTS,
+    );
+
+    // and equal lockups always merge
+    let merged = merge(
+        locked_until(CURRENT_EPOCH + 1),
+        DESTINATION_LAMPORTS,
+        locked_until(CURRENT_EPOCH + 1),
+        SOURCE_LAMPORTS,
+    );
+    assert_merged(
+        &merged,
+        &locked_until(CURRENT_EPOCH + 1),
+        DESTINATION_LAMPORTS + SOURCE_LAMPORTS,
+    );
+}
+
+#[test]
+fn test_merge_transient_stake() {
+    // deactivated last epoch, while the cluster was cooling down a little at
+    // a time, so part of it is still effective
+    set_stake_history(
+        CURRENT_EPOCH,
+        &[(
+            CURRENT_EPOCH - 1,
+            StakeHistoryEntry {
+                effective: (100 * DESTINATION_STAKE).to_le_bytes(),
+                activating: 0u64.to_le_bytes(),
+                deactivating: (100 * DESTINATION_STAKE).to_le_bytes(),
+            },
+        )],
+    );
+    let mut deactivating = active(VOTER, DESTINATION_STAKE, 0);
+    if let StakeStateV2::Stake(_, stake, _) = &mut deactivating {
+        stake.delegation.deactivation_epoch = (CURRENT_EPOCH - 1).to_le_bytes();
+    }
+    let error: ProgramError = StakeError::MergeTransientStake.into();
+
+    let merged = merge(
+        deactivating,
+        DESTINATION_LAMPORTS,
+        initialized(),
+        SOURCE_LAMPORTS,
+    );
+    assert_not_merged(&merged, error.clone(), &deactivating, &initialized());
+    // the destination is classified first, so it is refused before a source
+    // that isn't mergeable at all is even looked at
+    let merged = merge(
+        deactivating,
+        DESTINATION_LAMPORTS,
+        StakeStateV2::RewardsPool,
+        SOURCE_LAMPORTS,
+    );
+    assert_not_merged(
+        &merged,
+        error.clone(),
+        &deactivating,
+        &StakeStateV2::RewardsPool,
+    );
+    let merged = merge(
+        initialized(),
+        DESTINATION_LAMPORTS,
+        deactivating,
+        SOURCE_LAMPORTS,
+    );
+    assert_not_merged(&merged, error, &initialized(), &deactivating);
+}
+
+#[test]
+fn test_merge_requires_staker() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+    for signer in [WITHDRAWER, CUSTODIAN] {
+        let merged = merge_signed(
+            initialized(),
+            DESTINATION_LAMPORTS,
+            initialized(),
+            SOURCE_LAMPORTS,
+            signer,
+        );
+        assert_not_merged(
+            &merged,
+            ProgramError::MissingRequiredSignature,
+            &initialized(),
+            &initialized(),
+        );
+        assert_eq!(merged.destination_lamports, DESTINATION_LAMPORTS);
+        assert_eq!(merged.source_lamports, SOURCE_LAMPORTS);
+    }
+}
+
+#[test]
+fn test_merge_by_default_staker_needs_signature() {
+    set_clock(&clock());
+    set_stake_history(CURRENT_EPOCH, &[]);
+
+    // a staker of all zeroes doesn't match the unused end of the signer array
+    let unsigned = StakeStateV2::Initialized(initialized_meta(Pubkey::default(), WITHDRAWER));
+    let instruction = client::merge(&DESTINATION, &SOURCE, &Pubkey::default());
+    let inputs: Vec<Input> = vec![
+        stake_account(DESTINATION, DESTINATION_LAMPORTS, unsigned).into(),
+        stake_account(SOURCE, SOURCE_LAMPORTS, unsigned).into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(Pubkey::default(), Pubkey::default(), 0, vec![]).into(),
+    ];
+    assert_rejected(
+        &inputs,
+        &instruction.data,
+        ProgramError::MissingRequiredSignature,
+        "default staker",
+    );
+}
+
+#[test]
+fn test_merge_into_itself() {
+    set_clock(&clock());
+    set_stake_history(CURRENT_EPOCH, &[]);
+
+    let instruction = client::merge(&DESTINATION, &DESTINATION, &STAKER);
+    let inputs: Vec<Input> = vec![
+        stake_account(DESTINATION, DESTINATION_LAMPORTS, initialized()).into(),
+        Input::Duplicate(0),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        assert_eq!(
+            process(accounts, &instruction.data),
+            Err(ProgramError::InvalidArgument)
+        );
+    });
+}
@@ -0,0 +1,108 @@
+//! Account data isn't guaranteed to be aligned on every runtime, so state is
+//! copied out with unaligned loads, and the readers that hand out references
+//! reject data they can't reference rather than read it anyway.
+//!
+//! `AccountInfo`s can't be misaligned without misaligning their header too,
+//! so the readers are fed misaligned copies of fixture data directly.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, sysvars::clock::Clock};
+use pinocchio_stake_interface::client::stake_state_from_data;
+use solana_pinocchio_starter::state::{
+    clock_from_data, load_acc_unchecked, Delegation, RedelegateState, Stake, StakeFlags,
+    StakeStateV2,
+};
+
+fn states() -> Vec<StakeStateV2> {
+    let meta = initialized_meta([1; 32], [2; 32]);
+    let mut delegation = Delegation::new(&[3; 32], 1_000_000_000, 10u64.to_le_bytes());
+    delegation.set_deactivation_epoch(u64::MAX - 1);
+    let stake = Stake {
+        delegation,
+        credits_observed: 0x0102_0304_0506_0708u64.to_le_bytes(),
+    };
+    vec![
+        StakeStateV2::Uninitialized,
+        StakeStateV2::Initialized(meta),
+        StakeStateV2::Stake(meta, stake, StakeFlags::from_bits(0b1)),
+        StakeStateV2::RewardsPool,
+    ]
+}
+
+#[test]
+fn test_stake_state_read_at_every_alignment() {
+    for state in states() {
+        with_misaligned(&stake_state_data(state), |data, offset| {
+            assert_eq!(StakeStateV2::read_from(data), Ok(state), "offset {offset}");
+            assert_eq!(stake_state_from_data(data), Ok(state), "offset {offset}");
+        });
+    }
+
+    // an unknown tag is still rejected wherever it sits
+    let mut data = stake_state_data(StakeStateV2::Uninitialized);
+    data[..4].copy_from_slice(&4u32.to_le_bytes());
+    with_misaligned(&data, |data, offset| {
+        assert_eq!(
+            StakeStateV2::read_from(data),
+            Err(ProgramError::InvalidAccountData),
+            "offset {offset}"
+        );
+    });
+}
+
+#[test]
+fn test_references_need_alignment() {
+    let state = states()[2];
+    with_misaligned(&stake_state_data(state), |data, offset| {
+        let expected = if offset % 4 == 0 {
+            Ok(&state)
+        } else {
+            Err(ProgramError::InvalidAccountData)
+        };
+        assert_eq!(
+            StakeStateV2::try_from_bytes(data),
+            expected,
+            "offset {offset}"
+        );
+    });
+
+    let data = vec![0u8; core::mem::size_of::<RedelegateState>()];
+    with_misaligned(&data, |data, offset| {
+        // SAFETY: zeroes make a valid `RedelegateState`, if it's aligned
+        let result = unsafe { load_acc_unchecked::<RedelegateState>(data) };
+        assert_eq!(result.is_ok(), offset == 0, "offset {offset}");
+    });
+}
+
+#[test]
+fn test_clock_read_at_every_alignment() {
+    let clock = Clock {
+        slot: 1,
+        epoch_start_timestamp: -2,
+        epoch: 300,
+        leader_schedule_epoch: 301,
+        unix_timestamp: 1_700_000_000,
+    };
+    let account = clock_account(&clock);
+
+    with_misaligned(&account.data, |data, offset| {
+        let read = clock_from_data(data).unwrap();
+        assert_eq!(
+            (
+                read.slot,
+                read.epoch_start_timestamp,
+                read.epoch,
+                read.leader_schedule_epoch,
+                read.unix_timestamp
+            ),
+            (1, -2, 300, 301, 1_700_000_000),
+            "offset {offset}"
+        );
+        assert_eq!(
+            clock_from_data(&data[1..]).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+    });
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use solana_pinocchio_starter::{
+    error::StakeError,
+    instruction::StakeInstruction,
+    state::{
+        Delegation, MergeKind, Meta, Stake, StakeFlags, StakeHistoryEntry, StakeStateV2,
+        SyscallSysvars,
+    },
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+
+const FREE_LAMPORTS: u64 = 1_000_000_000;
+const SOURCE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + FREE_LAMPORTS;
+const DESTINATION_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE;
+
+/// Moves `lamports` out of an Initialized source through the entrypoint and
+/// returns the result along with both balances afterwards.
+fn move_lamports(source_meta: Meta, lamports: u64) -> (ProgramResult, u64, u64) {
+    set_clock(&clock());
+
+    let destination_meta = initialized_meta(STAKER, WITHDRAWER);
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            SOURCE,
+            SOURCE_LAMPORTS,
+            StakeStateV2::Initialized(source_meta),
+        )
+        .into(),
+        stake_account(
+            DESTINATION,
+            DESTINATION_LAMPORTS,
+            StakeStateV2::Initialized(destination_meta),
+        )
+        .into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    let mut data = vec![StakeInstruction::MoveLamports as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &data);
+        (result, accounts[0].lamports(), accounts[1].lamports())
+    })
+}
+
+#[test]
+fn test_move_lamports_from_initialized() {
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+
+    let (result, source_lamports, destination_lamports) = move_lamports(meta, 1);
+    assert_eq!(result, Ok(()));
+    assert_eq!(source_lamports, SOURCE_LAMPORTS - 1);
+    assert_eq!(destination_lamports, DESTINATION_LAMPORTS + 1);
+
+    // everything above the reserve is free
+    let (result, source_lamports, destination_lamports) = move_lamports(meta, FREE_LAMPORTS);
+    assert_eq!(result, Ok(()));
+    assert_eq!(source_lamports, STAKE_RENT_EXEMPT_RESERVE);
+    assert_eq!(destination_lamports, DESTINATION_LAMPORTS + FREE_LAMPORTS);
+}
+
+#[test]
+fn test_move_lamports_from_initialized_keeps_reserve() {
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+
+    for lamports in [FREE_LAMPORTS + 1, SOURCE_LAMPORTS, u64::MAX] {
+        let (result, source_lamports, destination_lamports) = move_lamports(meta, lamports);
+        assert_eq!(result, Err(ProgramError::InvalidArgument), "{lamports}");
+        assert_eq!(source_lamports, SOURCE_LAMPORTS);
+        assert_eq!(destination_lamports, DESTINATION_LAMPORTS);
+    }
+
+    // a larger reserve leaves less to move
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.set_rent_exempt_reserve(SOURCE_LAMPORTS);
+    let (result, source_lamports, _) = move_lamports(meta, 1);
+    assert_eq!(result, Err(ProgramError::InvalidArgument));
+    assert_eq!(source_lamports, SOURCE_LAMPORTS);
+
+    let (result, _, _) = move_lamports(initialized_meta(STAKER, WITHDRAWER), 0);
+    assert_eq!(result, Err(ProgramError::InvalidArgument));
+}
+
+#[test]
+fn test_move_lamports_from_initialized_requires_staker() {
+    // the signer is only the withdrawer here
+    let meta = initialized_meta(WITHDRAWER, STAKER);
+    let (result, source_lamports, _) = move_lamports(meta, 1);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_eq!(source_lamports, SOURCE_LAMPORTS);
+}
+
+#[test]
+fn test_move_lamports_classifies_as_merge() {
+    set_clock(&clock());
+    const DELEGATED: u64 = 5_000_000_000;
+
+    // delegated the epoch before, so the stake history entry at the boundary
+    // decides how much of it is effective now
+    let activation_epoch = clock().epoch - 1;
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+    let stake = Stake {
+        delegation: Delegation::new(&[9; 32], DELEGATED, activation_epoch.to_le_bytes()),
+        ..Stake::default()
+    };
+    let source_lamports = STAKE_RENT_EXEMPT_RESERVE + DELEGATED + FREE_LAMPORTS;
+    let cluster = |effective: u64| {
+        vec![(
+            activation_epoch,
+            StakeHistoryEntry {
+                effective: effective.to_le_bytes(),
+                activating: DELEGATED.to_le_bytes(),
+                ..StakeHistoryEntry::default()
+            },
+        )]
+    };
+
+    // (history, fully active)
+    let cases = [
+        // the cluster only warms up part of the delegation in one epoch
+        (cluster(DELEGATED), false),
+        (cluster(100 * DELEGATED), true),
+        // nothing to hold it back once it has dropped out of history
+        (vec![], true),
+    ];
+
+    let mut data = vec![StakeInstruction::MoveLamports as u8];
+    data.extend_from_slice(&FREE_LAMPORTS.to_le_bytes());
+    for (entries, fully_active) in cases {
+        set_stake_history(clock().epoch, &entries);
+        let inputs: Vec<Input> = vec![
+            stake_account(
+                SOURCE,
+                source_lamports,
+                StakeStateV2::Stake(meta, stake, StakeFlags::empty()),
+            )
+            .into(),
+            stake_account(
+                DESTINATION,
+                DESTINATION_LAMPORTS,
+                StakeStateV2::Initialized(meta),
+            )
+            .into(),
+            TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+                .signer()
+                .into(),
+        ];
+
+        with_accounts(&inputs, |accounts| {
+            // as Merge would see the source
+            let merge_kind = MergeKind::for_account(&accounts[0], &clock(), &SyscallSysvars);
+            let result = process(accounts, &data);
+            if fully_active {
+                assert!(
+                    matches!(merge_kind, Ok(MergeKind::FullyActive(..))),
+                    "{entries:?}"
+                );
+                assert_eq!(result, Ok(()), "{entries:?}");
+                assert_eq!(accounts[0].lamports(), source_lamports - FREE_LAMPORTS);
+            } else {
+                assert_eq!(
+                    merge_kind,
+                    Err(StakeError::MergeTransientStake.into()),
+                    "{entries:?}"
+                );
+                assert_eq!(result, Err(StakeError::MergeTransientStake.into()));
+                assert_eq!(accounts[0].lamports(), source_lamports);
+            }
+        });
+    }
+}
@@ -0,0 +1,419 @@
+//! Negative unit tests of the native stake program, ported from
+//! solana-stake-program 1.18.0's `stake_instruction.rs` by
+//! `tests/port_native_negative.py`. Generated, rerun the script rather than
+//! editing by hand.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock};
+use pinocchio_stake_interface::{client, consts::STAKE_CONFIG_ID};
+use solana_pinocchio_starter::{
+    consts::VOTE_PROGRAM_ID,
+    instruction::StakeInstruction,
+    state::{Authorized, FeatureSet, Lockup, StakeAuthorize},
+};
+
+// native derives these from strings, any distinct keys will do
+const BAD_STAKE: Pubkey = [0xb5; 32];
+const BAD_VOTE: Pubkey = [0xb7; 32];
+const SPOOFED_STAKE: Pubkey = [0x5f; 32];
+const SPOOFED_PROGRAM: Pubkey = [0x5e; 32];
+const REWARDS_ID: Pubkey = solana_sdk::sysvar::rewards::ID.to_bytes();
+
+struct NativeCase {
+    test: &'static str,
+    note: &'static str,
+    data: Vec<u8>,
+    accounts: Vec<TestAccount>,
+    expected: ProgramError,
+}
+
+fn empty(key: Pubkey, owner: Pubkey) -> TestAccount {
+    TestAccount::new(key, owner, 0, vec![])
+}
+
+fn rewards_account() -> TestAccount {
+    TestAccount::new(REWARDS_ID, Pubkey::default(), 1, vec![0; 16])
+}
+
+fn config_account() -> TestAccount {
+    TestAccount::new(STAKE_CONFIG_ID, Pubkey::default(), 0, vec![0; 10])
+}
+
+// skipped, rerun the script once they are dispatched:
+// test_stake_process_instruction: deactivate_stake(new_unique, new_unique) (Deactivate isn't dispatched yet)
+// test_stake_process_instruction: deactivate_delinquent_stake(new_unique, new_unique, invalid_vote_state) (DeactivateDelinquent isn't dispatched yet)
+// test_stake_process_instruction: deactivate_delinquent_stake(new_unique, invalid_vote_state, new_unique) (DeactivateDelinquent isn't dispatched yet)
+// test_stake_process_instruction: deactivate_delinquent_stake(new_unique, invalid_vote_state, invalid_vote_state) (DeactivateDelinquent isn't dispatched yet)
+// test_spoofed_stake_accounts: deactivate_stake(spoofed_stake_state, new_unique) (Deactivate isn't dispatched yet)
+// test_spoofed_stake_accounts: deactivate_delinquent_stake(spoofed_stake_state, new_unique, new_unique) (DeactivateDelinquent isn't dispatched yet)
+// test_spoofed_stake_accounts: redelegate(spoofed_stake_state, new_unique, new_unique, new_unique)[2] (Redelegate is deprecated)
+// test_stake_process_instruction_decode_bail: Tests 2nd keyed account is of correct type (Clock instead of rewards) in deactivate (Deactivate isn't dispatched yet)
+// test_stake_process_instruction_decode_bail: Tests correct number of accounts are provided in deactivate (Deactivate isn't dispatched yet)
+// test_stake_process_instruction_decode_bail: Tests correct number of accounts are provided in deactivate_delinquent (DeactivateDelinquent isn't dispatched yet)
+// test_stake_process_instruction_decode_bail: unnamed case (DeactivateDelinquent isn't dispatched yet)
+// test_stake_process_instruction_decode_bail: unnamed case (DeactivateDelinquent isn't dispatched yet)
+fn cases() -> Vec<NativeCase> {
+    Vec::from([
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "initialize(new_unique, Authorized::default, Lockup::default)",
+            data: client::initialize(
+                &Pubkey::default(),
+                &Authorized::default(),
+                &Lockup::default(),
+            )
+            .data,
+            accounts: vec![
+                empty([16; 32], solana_pinocchio_starter::ID).writable(),
+                rent_account(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "authorize(new_unique, new_unique, new_unique, \
+                StakeAuthorize::Staker, None)",
+            data: client::authorize(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                &Pubkey::default(),
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+            accounts: vec![
+                empty([17; 32], solana_pinocchio_starter::ID).writable(),
+                clock_account(&Clock::default()),
+                empty([18; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "split(new_unique, new_unique, 100, invalid_stake_state)[2]",
+            data: amount_data(StakeInstruction::Split, 100),
+            accounts: vec![
+                empty([19; 32], solana_pinocchio_starter::ID).writable(),
+                empty(BAD_STAKE, solana_pinocchio_starter::ID).writable(),
+                empty([20; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "merge(new_unique, invalid_stake_state, new_unique)[0]",
+            data: vec![StakeInstruction::Merge as u8],
+            accounts: vec![
+                empty([21; 32], solana_pinocchio_starter::ID).writable(),
+                empty(BAD_STAKE, solana_pinocchio_starter::ID).writable(),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                empty([22; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "split_with_seed(new_unique, new_unique, 100, \
+                invalid_stake_state, new_unique, \"seed\")[1]",
+            data: amount_data(StakeInstruction::Split, 100),
+            accounts: vec![
+                empty([23; 32], solana_pinocchio_starter::ID).writable(),
+                empty(BAD_STAKE, solana_pinocchio_starter::ID).writable(),
+                empty([24; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "delegate_stake(new_unique, new_unique, invalid_vote_state)",
+            data: vec![StakeInstruction::DelegateStake as u8],
+            accounts: vec![
+                empty([25; 32], solana_pinocchio_starter::ID).writable(),
+                empty(BAD_VOTE, VOTE_PROGRAM_ID),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                config_account(),
+                empty([26; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "withdraw(new_unique, new_unique, new_unique, 100, None)",
+            data: amount_data(StakeInstruction::Withdraw, 100),
+            accounts: vec![
+                empty([27; 32], solana_pinocchio_starter::ID).writable(),
+                empty([28; 32], solana_pinocchio_starter::ID).writable(),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                empty([29; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction",
+            note: "set_lockup(new_unique, LockupArgs::default, new_unique)",
+            data: vec![StakeInstruction::SetLockup as u8, 0, 0, 0],
+            accounts: vec![
+                empty([30; 32], solana_pinocchio_starter::ID).writable(),
+                empty([31; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "initialize(spoofed_stake_state, Authorized::default, \
+                Lockup::default)",
+            data: client::initialize(
+                &Pubkey::default(),
+                &Authorized::default(),
+                &Lockup::default(),
+            )
+            .data,
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                rent_account(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "authorize(spoofed_stake_state, new_unique, new_unique, \
+                StakeAuthorize::Staker, None)",
+            data: client::authorize(
+                &Pubkey::default(),
+                &Pubkey::default(),
+                &Pubkey::default(),
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                clock_account(&Clock::default()),
+                empty([16; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "split(spoofed_stake_state, new_unique, 100, new_unique)[2]",
+            data: amount_data(StakeInstruction::Split, 100),
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([17; 32], solana_pinocchio_starter::ID).writable(),
+                empty([18; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "split(new_unique, new_unique, 100, spoofed_stake_state)[2]",
+            data: amount_data(StakeInstruction::Split, 100),
+            accounts: vec![
+                empty([19; 32], solana_pinocchio_starter::ID).writable(),
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([20; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::IncorrectProgramId,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "merge(spoofed_stake_state, new_unique, new_unique)[0]",
+            data: vec![StakeInstruction::Merge as u8],
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([21; 32], solana_pinocchio_starter::ID).writable(),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                empty([22; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "merge(new_unique, spoofed_stake_state, new_unique)[0]",
+            data: vec![StakeInstruction::Merge as u8],
+            accounts: vec![
+                empty([23; 32], solana_pinocchio_starter::ID).writable(),
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                empty([24; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::IncorrectProgramId,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "split_with_seed(spoofed_stake_state, new_unique, 100, \
+                new_unique, new_unique, \"seed\")[1]",
+            data: amount_data(StakeInstruction::Split, 100),
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([25; 32], solana_pinocchio_starter::ID).writable(),
+                empty([26; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "delegate_stake(spoofed_stake_state, new_unique, new_unique)",
+            data: vec![StakeInstruction::DelegateStake as u8],
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([27; 32], solana_pinocchio_starter::ID),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                config_account(),
+                empty([28; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "withdraw(spoofed_stake_state, new_unique, new_unique, 100, \
+                None)",
+            data: amount_data(StakeInstruction::Withdraw, 100),
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([29; 32], solana_pinocchio_starter::ID).writable(),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                empty([30; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_spoofed_stake_accounts",
+            note: "set_lockup(spoofed_stake_state, LockupArgs::default, \
+                new_unique)",
+            data: vec![StakeInstruction::SetLockup as u8, 0, 0, 0],
+            accounts: vec![
+                empty(SPOOFED_STAKE, SPOOFED_PROGRAM).writable(),
+                empty([31; 32], solana_pinocchio_starter::ID).signer(),
+            ],
+            expected: ProgramError::InvalidAccountOwner,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "gets the \"is_empty()\" check",
+            data: client::initialize(
+                &Pubkey::default(),
+                &Authorized::default(),
+                &Lockup::default(),
+            )
+            .data,
+            accounts: vec![],
+            expected: ProgramError::NotEnoughAccountKeys,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "no account for rent",
+            data: client::initialize(
+                &Pubkey::default(),
+                &Authorized::default(),
+                &Lockup::default(),
+            )
+            .data,
+            accounts: vec![empty([1; 32], solana_pinocchio_starter::ID).writable()],
+            expected: ProgramError::NotEnoughAccountKeys,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "fails to deserialize stake state",
+            data: client::initialize(
+                &Pubkey::default(),
+                &Authorized::default(),
+                &Lockup::default(),
+            )
+            .data,
+            accounts: vec![
+                empty([1; 32], solana_pinocchio_starter::ID).writable(),
+                rent_account(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "gets the first check in delegate, wrong number of accounts",
+            data: vec![StakeInstruction::DelegateStake as u8],
+            accounts: vec![empty([1; 32], solana_pinocchio_starter::ID).writable()],
+            expected: ProgramError::NotEnoughAccountKeys,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "gets the sub-check for number of args",
+            data: vec![StakeInstruction::DelegateStake as u8],
+            accounts: vec![empty([1; 32], solana_pinocchio_starter::ID).writable()],
+            expected: ProgramError::NotEnoughAccountKeys,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "gets the check non-deserialize-able account in \
+                delegate_stake",
+            data: vec![StakeInstruction::DelegateStake as u8],
+            accounts: vec![
+                empty([1; 32], solana_pinocchio_starter::ID)
+                    .signer()
+                    .writable(),
+                empty([2; 32], VOTE_PROGRAM_ID),
+                clock_account(&Clock::default()),
+                stake_history_account(),
+                config_account(),
+            ],
+            expected: ProgramError::InvalidAccountData,
+        },
+        // native: InvalidArgument, but accounts are counted before any is read
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "Tests 3rd keyed account is of correct type (Clock instead of \
+                rewards) in withdraw",
+            data: amount_data(
+                StakeInstruction::Withdraw,
+                STAKE_RENT_EXEMPT_RESERVE + FeatureSet::COMPILED.minimum_delegation(),
+            ),
+            accounts: vec![
+                empty([1; 32], solana_pinocchio_starter::ID).writable(),
+                empty([2; 32], VOTE_PROGRAM_ID),
+                rewards_account(),
+                stake_history_account(),
+            ],
+            expected: ProgramError::NotEnoughAccountKeys,
+        },
+        NativeCase {
+            test: "test_stake_process_instruction_decode_bail",
+            note: "Tests correct number of accounts are provided in withdraw",
+            data: amount_data(
+                StakeInstruction::Withdraw,
+                STAKE_RENT_EXEMPT_RESERVE + FeatureSet::COMPILED.minimum_delegation(),
+            ),
+            accounts: vec![empty([1; 32], solana_pinocchio_starter::ID).writable()],
+            expected: ProgramError::NotEnoughAccountKeys,
+        },
+    ])
+}
+
+#[test]
+fn test_native_negative_cases() {
+    set_clock(&Clock::default());
+    set_default_rent();
+
+    let mut mismatches = Vec::new();
+    for case in cases() {
+        let inputs: Vec<Input> = case.accounts.into_iter().map(Input::from).collect();
+        let result = with_accounts(&inputs, |accounts| process(accounts, &case.data));
+        if result != Err(case.expected.clone()) {
+            mismatches.push(format!(
+                "{}: {}\n  expected: {:?}\n  actual:   {:?}",
+                case.test,
+                case.note,
+                Err::<(), _>(case.expected),
+                result
+            ));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
@@ -0,0 +1,381 @@
+//! Rejection paths of every dispatched instruction, in the categories the
+//! native program's negative tests cover: missing signers, accounts owned by
+//! another program, wrong sysvars, stake accounts of the wrong size and
+//! missing accounts.
+//!
+//! Rather than listing cases by hand, each instruction has one baseline that
+//! succeeds, and the corpus is every single mutation of it in those
+//! categories. A new instruction only needs a baseline to be covered.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, sysvars::rent::RENT_ID};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    consts::CLOCK_ID,
+    helpers::create_with_seed,
+    instruction::StakeInstruction,
+    state::{stake_history, Authorized, Lockup, StakeAuthorize, StakeStateV2},
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const BASE: Pubkey = [5; 32];
+const SEED_OWNER: Pubkey = [6; 32];
+const NOT_CLOCK: Pubkey = [7; 32];
+const OTHER_PROGRAM: Pubkey = [8; 32];
+const NOT_STAKE_HISTORY: Pubkey = [10; 32];
+const VOTE: Pubkey = [11; 32];
+const NOT_RENT: Pubkey = [13; 32];
+const STAKE_CONFIG: Pubkey = [12; 32];
+
+const SEED: &str = "negative";
+const LAMPORTS: u64 = 2 * STAKE_RENT_EXEMPT_RESERVE + 1_000_000_000;
+
+struct Baseline {
+    name: &'static str,
+    // accounts native asserts are present, signers past them are found
+    // wherever they are
+    required_accounts: usize,
+    accounts: Vec<TestAccount>,
+    data: Vec<u8>,
+}
+
+struct Case {
+    name: String,
+    accounts: Vec<TestAccount>,
+    expected: ProgramError,
+}
+
+fn baselines() -> Vec<Baseline> {
+    let seed_authority = create_with_seed(&BASE, SEED.as_bytes(), &SEED_OWNER).unwrap();
+
+    vec![
+        Baseline {
+            name: "Initialize",
+            required_accounts: 2,
+            accounts: vec![
+                stake_account(SOURCE, LAMPORTS, StakeStateV2::Uninitialized),
+                rent_account(),
+            ],
+            data: client::initialize(
+                &SOURCE,
+                &Authorized {
+                    staker: STAKER,
+                    withdrawer: WITHDRAWER,
+                },
+                &Lockup::default(),
+            )
+            .data,
+        },
+        Baseline {
+            name: "InitializeChecked",
+            required_accounts: 4,
+            accounts: vec![
+                stake_account(SOURCE, LAMPORTS, StakeStateV2::Uninitialized),
+                rent_account(),
+                TestAccount::new(STAKER, Pubkey::default(), 0, vec![]),
+                signer(WITHDRAWER),
+            ],
+            data: client::initialize_checked(&SOURCE, &STAKER, &WITHDRAWER).data,
+        },
+        Baseline {
+            name: "Authorize",
+            required_accounts: 3,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                clock_account(&clock()),
+                signer(STAKER),
+            ],
+            data: client::authorize(&SOURCE, &STAKER, &[9; 32], StakeAuthorize::Staker, None).data,
+        },
+        Baseline {
+            name: "AuthorizeChecked",
+            required_accounts: 4,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                clock_account(&clock()),
+                signer(STAKER),
+                signer([9; 32]),
+            ],
+            data: client::authorize_checked(
+                &SOURCE,
+                &STAKER,
+                &[9; 32],
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+        },
+        Baseline {
+            name: "DelegateStake",
+            required_accounts: 5,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                vote_account(VOTE, &[]),
+                clock_account(&clock()),
+                stake_history_account(),
+                TestAccount::new(STAKE_CONFIG, Pubkey::default(), 0, vec![]),
+                signer(STAKER),
+            ],
+            data: vec![StakeInstruction::DelegateStake as u8],
+        },
+        Baseline {
+            name: "Split",
+            required_accounts: 2,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                stake_account(DESTINATION, 0, StakeStateV2::Uninitialized),
+                signer(STAKER),
+            ],
+            data: amount_data(StakeInstruction::Split, STAKE_RENT_EXEMPT_RESERVE + 1_000),
+        },
+        Baseline {
+            name: "Withdraw",
+            required_accounts: 5,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![]).writable(),
+                clock_account(&clock()),
+                stake_history_account(),
+                signer(WITHDRAWER),
+            ],
+            data: amount_data(StakeInstruction::Withdraw, 1),
+        },
+        Baseline {
+            name: "SetLockup",
+            required_accounts: 1,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                signer(WITHDRAWER),
+            ],
+            data: vec![StakeInstruction::SetLockup as u8, 0, 0, 0],
+        },
+        Baseline {
+            name: "MoveLamports",
+            required_accounts: 3,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                initialized_account(DESTINATION, LAMPORTS, STAKER, WITHDRAWER),
+                signer(STAKER),
+            ],
+            data: amount_data(StakeInstruction::MoveLamports, 1_000),
+        },
+        Baseline {
+            name: "Merge",
+            required_accounts: 4,
+            accounts: vec![
+                initialized_account(DESTINATION, LAMPORTS, STAKER, WITHDRAWER),
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                clock_account(&clock()),
+                // native no longer reads the stake history account, so it
+                // may be anything
+                TestAccount::new(NOT_STAKE_HISTORY, Pubkey::default(), 0, vec![]),
+                signer(STAKER),
+            ],
+            data: client::merge(&DESTINATION, &SOURCE, &STAKER).data,
+        },
+        Baseline {
+            name: "AuthorizeWithSeed",
+            required_accounts: 3,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, seed_authority, WITHDRAWER),
+                signer(BASE),
+                clock_account(&clock()),
+            ],
+            data: client::authorize_with_seed(
+                &SOURCE,
+                &BASE,
+                SEED,
+                &SEED_OWNER,
+                &[9; 32],
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+        },
+        Baseline {
+            name: "AuthorizeCheckedWithSeed",
+            required_accounts: 4,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, seed_authority, WITHDRAWER),
+                signer(BASE),
+                clock_account(&clock()),
+                signer([9; 32]),
+            ],
+            data: client::authorize_checked_with_seed(
+                &SOURCE,
+                &BASE,
+                SEED,
+                &SEED_OWNER,
+                &[9; 32],
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+        },
+    ]
+}
+
+/// Every single mutation of `baseline` in the corpus categories.
+fn cases(baseline: &Baseline) -> Vec<Case> {
+    let mut cases = Vec::new();
+    let mutated = |index: usize, mutate: &dyn Fn(&mut TestAccount)| {
+        let mut accounts = baseline.accounts.clone();
+        mutate(&mut accounts[index]);
+        accounts
+    };
+
+    for (index, account) in baseline.accounts.iter().enumerate() {
+        if account.is_signer {
+            cases.push(Case {
+                name: format!("account {index} not signed"),
+                accounts: mutated(index, &|account| account.is_signer = false),
+                expected: ProgramError::MissingRequiredSignature,
+            });
+        }
+
+        if account.owner == solana_pinocchio_starter::ID {
+            // native takes a split destination or merge source of another
+            // program for a spoofed program id
+            let expected = match (baseline.name, index) {
+                ("Split", 1) | ("Merge", 1) => ProgramError::IncorrectProgramId,
+                _ => ProgramError::InvalidAccountOwner,
+            };
+            cases.push(Case {
+                name: format!("account {index} owned by another program"),
+                accounts: mutated(index, &|account| account.owner = OTHER_PROGRAM),
+                expected: expected.clone(),
+            });
+            // the owner is checked before the size
+            cases.push(Case {
+                name: format!("account {index} owned by another program and empty"),
+                accounts: mutated(index, &|account| {
+                    account.owner = OTHER_PROGRAM;
+                    account.data.clear();
+                }),
+                expected,
+            });
+            for len in [0, StakeStateV2::size_of() - 1, StakeStateV2::size_of() + 1] {
+                cases.push(Case {
+                    name: format!("account {index} of {len} bytes"),
+                    accounts: mutated(index, &|account| account.data.resize(len, 0)),
+                    expected: ProgramError::InvalidAccountData,
+                });
+            }
+        }
+
+        if account.key == CLOCK_ID {
+            cases.push(Case {
+                name: format!("account {index} not the clock"),
+                accounts: mutated(index, &|account| account.key = NOT_CLOCK),
+                expected: ProgramError::InvalidArgument,
+            });
+            cases.push(Case {
+                name: format!("account {index} a truncated clock"),
+                accounts: mutated(index, &|account| account.data.truncate(8)),
+                expected: ProgramError::InvalidArgument,
+            });
+        }
+
+        // only passed for legacy clients, but still have to be the sysvars
+        if account.key == RENT_ID {
+            cases.push(Case {
+                name: format!("account {index} not the rent"),
+                accounts: mutated(index, &|account| account.key = NOT_RENT),
+                expected: ProgramError::InvalidArgument,
+            });
+        }
+        if account.key == stake_history::ID {
+            cases.push(Case {
+                name: format!("account {index} not the stake history"),
+                accounts: mutated(index, &|account| account.key = NOT_STAKE_HISTORY),
+                expected: ProgramError::InvalidArgument,
+            });
+        }
+    }
+
+    cases.push(Case {
+        name: "no accounts".to_string(),
+        accounts: vec![],
+        expected: ProgramError::NotEnoughAccountKeys,
+    });
+    cases.push(Case {
+        name: "last required account missing".to_string(),
+        accounts: baseline.accounts[..baseline.required_accounts - 1].to_vec(),
+        expected: ProgramError::NotEnoughAccountKeys,
+    });
+    cases
+}
+
+fn inputs(accounts: &[TestAccount]) -> Vec<Input> {
+    accounts.iter().cloned().map(Input::from).collect()
+}
+
+#[test]
+fn test_negative_corpus() {
+    set_clock(&clock());
+    set_default_rent();
+
+    let mut count = 0;
+    for baseline in baselines() {
+        // the baseline must succeed, so each case fails only by its mutation
+        with_accounts(&inputs(&baseline.accounts), |accounts| {
+            assert_eq!(
+                process(accounts, &baseline.data),
+                Ok(()),
+                "{}",
+                baseline.name
+            );
+        });
+
+        for case in cases(&baseline) {
+            with_accounts(&inputs(&case.accounts), |accounts| {
+                let lamports: Vec<u64> =
+                    accounts.iter().map(|account| account.lamports()).collect();
+                assert_eq!(
+                    process(accounts, &baseline.data),
+                    Err(case.expected.clone()),
+                    "{}: {}",
+                    baseline.name,
+                    case.name
+                );
+                let after: Vec<u64> = accounts.iter().map(|account| account.lamports()).collect();
+                assert_eq!(lamports, after, "{}: {}", baseline.name, case.name);
+            });
+            count += 1;
+        }
+    }
+
+    assert!(count >= 40, "corpus shrank to {count} cases");
+}
+
+#[test]
+fn test_trailing_instruction_data() {
+    set_clock(&clock());
+    set_default_rent();
+
+    // native ignores bytes past the arguments, the strict build rejects them
+    let expected = if cfg!(feature = "strict-instruction-data") {
+        Err(ProgramError::InvalidInstructionData)
+    } else {
+        Ok(())
+    };
+    for baseline in baselines() {
+        for trailing in [1, 100] {
+            let mut data = baseline.data.clone();
+            data.resize(data.len() + trailing, 0xff);
+            with_accounts(&inputs(&baseline.accounts), |accounts| {
+                assert_eq!(
+                    process(accounts, &data),
+                    expected,
+                    "{} with {trailing} trailing bytes",
+                    baseline.name
+                );
+            });
+        }
+    }
+}
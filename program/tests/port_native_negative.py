@@ -0,0 +1,435 @@
+#!/usr/bin/env python3
+"""Ports the native stake program's negative unit tests to `native_negative.rs`.
+
+The native tests live in `src/stake_instruction.rs` of the
+`solana-stake-program` crate, e.g. for 1.18.0:
+
+    curl -L https://crates.io/api/v1/crates/solana-stake-program/1.18.0/download | tar xz
+    python3 tests/port_native_negative.py solana-stake-program-1.18.0/src/stake_instruction.rs
+
+Only tests that spell out their accounts in the call are ported: those passing
+`process_instruction` explicit account lists, and those passing
+`process_instruction_as_one_arg` an instruction built from pubkeys whose
+accounts native derives from the key alone. Every call expecting an error
+becomes a case, with the comment above it as its note. Cases of instructions
+this program doesn't dispatch yet are listed as skipped in the output, so they
+can be picked up by rerunning the script once they are. The few where it
+deliberately fails differently are in `DIVERGENCES`, and assert its error
+with native's in a comment.
+
+The output is written next to this script and formatted with rustfmt.
+"""
+
+import json
+import re
+import subprocess
+import sys
+import textwrap
+from pathlib import Path
+
+PORTED_TESTS = [
+    "test_stake_process_instruction",
+    "test_spoofed_stake_accounts",
+    "test_stake_process_instruction_decode_bail",
+]
+
+# instructions whose handler is still `todo!()` or deprecated
+UNDISPATCHED = {
+    "Deactivate": "Deactivate isn't dispatched yet",
+    "DeactivateDelinquent": "DeactivateDelinquent isn't dispatched yet",
+    "Redelegate": "Redelegate is deprecated",
+}
+
+# `process_instruction` cases: native variables and the accounts they hold,
+# sysvars under their own keys
+KEYS = {
+    "stake_address": "[1; 32]",
+    "vote_address": "[2; 32]",
+}
+ACCOUNTS = {
+    "stake_account": "empty({key}, solana_pinocchio_starter::ID)",
+    "vote_account": "empty({key}, VOTE_PROGRAM_ID)",
+    "rent_account": "rent_account()",
+    "rewards_account": "rewards_account()",
+    "stake_history_account": "stake_history_account()",
+    "clock_account": "clock_account(&Clock::default())",
+    "config_account": "config_account()",
+}
+AMOUNTS = {
+    "withdrawal_amount": "STAKE_RENT_EXEMPT_RESERVE + FeatureSet::COMPILED.minimum_delegation()",
+}
+
+# `process_instruction_as_one_arg` cases: accounts native creates for a key
+KEY_ACCOUNTS = {
+    "Pubkey::new_unique()": "empty({key}, solana_pinocchio_starter::ID)",
+    "invalid_stake_state_pubkey()": "empty(BAD_STAKE, solana_pinocchio_starter::ID)",
+    "invalid_vote_state_pubkey()": "empty(BAD_VOTE, VOTE_PROGRAM_ID)",
+    "spoofed_stake_state_pubkey()": "empty(SPOOFED_STAKE, SPOOFED_PROGRAM)",
+}
+SYSVAR_ACCOUNTS = {
+    "clock": "clock_account(&Clock::default())",
+    "rent": "rent_account()",
+    "stake_history": "stake_history_account()",
+    "config": "config_account()",
+}
+
+# native instruction builders: the instruction, its data from the builder
+# arguments, and its accounts as (argument index or sysvar, signer, writable)
+BUILDERS = {
+    "initialize": (
+        "Initialize",
+        "client::initialize(&Pubkey::default(), &Authorized::default(), &Lockup::default()).data",
+        [(0, False, True), ("rent", False, False)],
+    ),
+    "authorize": (
+        "Authorize",
+        "client::authorize(&Pubkey::default(), &Pubkey::default(), &{key2}, StakeAuthorize::Staker, None).data",
+        [(0, False, True), ("clock", False, False), (1, True, False)],
+    ),
+    "split": (
+        "Split",
+        "amount_data(StakeInstruction::Split, {arg2})",
+        [(0, False, True), (3, False, True), (1, True, False)],
+    ),
+    "split_with_seed": (
+        "Split",
+        "amount_data(StakeInstruction::Split, {arg2})",
+        [(0, False, True), (3, False, True), (1, True, False)],
+    ),
+    "merge": (
+        "Merge",
+        "vec![StakeInstruction::Merge as u8]",
+        [
+            (0, False, True),
+            (1, False, True),
+            ("clock", False, False),
+            ("stake_history", False, False),
+            (2, True, False),
+        ],
+    ),
+    "delegate_stake": (
+        "DelegateStake",
+        "vec![StakeInstruction::DelegateStake as u8]",
+        [
+            (0, False, True),
+            (2, False, False),
+            ("clock", False, False),
+            ("stake_history", False, False),
+            ("config", False, False),
+            (1, True, False),
+        ],
+    ),
+    "withdraw": (
+        "Withdraw",
+        "amount_data(StakeInstruction::Withdraw, {arg3})",
+        [
+            (0, False, True),
+            (2, False, True),
+            ("clock", False, False),
+            ("stake_history", False, False),
+            (1, True, False),
+        ],
+    ),
+    "set_lockup": (
+        "SetLockup",
+        "vec![StakeInstruction::SetLockup as u8, 0, 0, 0]",
+        [(0, False, True), (2, True, False)],
+    ),
+    "deactivate_stake": ("Deactivate", None, None),
+    "deactivate_delinquent_stake": ("DeactivateDelinquent", None, None),
+    "redelegate": ("Redelegate", None, None),
+}
+
+# `serialize(&StakeInstruction::..)` data of `process_instruction` cases
+VARIANT_DATA = {
+    "Initialize": "client::initialize(&Pubkey::default(), &Authorized::default(), &Lockup::default()).data",
+    "DelegateStake": "vec![StakeInstruction::DelegateStake as u8]",
+    "Withdraw": "amount_data(StakeInstruction::Withdraw, {amount})",
+}
+
+# cases where this program deliberately gives another error, by note:
+# the error it gives and why
+DIVERGENCES = {
+    "Tests 3rd keyed account is of correct type (Clock instead of rewards) in withdraw": (
+        "NotEnoughAccountKeys",
+        "but accounts are counted before any is read",
+    ),
+}
+
+ERRORS = {
+    "NotEnoughAccountKeys",
+    "InvalidAccountData",
+    "InvalidArgument",
+    "InvalidAccountOwner",
+    "IncorrectProgramId",
+    "MissingRequiredSignature",
+    "InvalidInstructionData",
+}
+
+PRELUDE = '''\
+//! Negative unit tests of the native stake program, ported from
+//! solana-stake-program {version}'s `stake_instruction.rs` by
+//! `tests/port_native_negative.py`. Generated, rerun the script rather than
+//! editing by hand.
+
+mod common;
+
+use common::*;
+use pinocchio::{{
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvars::clock::Clock,
+}};
+use pinocchio_stake_interface::{{client, consts::STAKE_CONFIG_ID}};
+use solana_pinocchio_starter::{{
+    consts::VOTE_PROGRAM_ID,
+    instruction::StakeInstruction,
+    state::{{Authorized, FeatureSet, Lockup, StakeAuthorize}},
+}};
+
+// native derives these from strings, any distinct keys will do
+const BAD_STAKE: Pubkey = [0xb5; 32];
+const BAD_VOTE: Pubkey = [0xb7; 32];
+const SPOOFED_STAKE: Pubkey = [0x5f; 32];
+const SPOOFED_PROGRAM: Pubkey = [0x5e; 32];
+const REWARDS_ID: Pubkey = solana_sdk::sysvar::rewards::ID.to_bytes();
+
+struct NativeCase {{
+    test: &'static str,
+    note: &'static str,
+    data: Vec<u8>,
+    accounts: Vec<TestAccount>,
+    expected: ProgramError,
+}}
+
+fn empty(key: Pubkey, owner: Pubkey) -> TestAccount {{
+    TestAccount::new(key, owner, 0, vec![])
+}}
+
+fn rewards_account() -> TestAccount {{
+    TestAccount::new(REWARDS_ID, Pubkey::default(), 1, vec![0; 16])
+}}
+
+fn config_account() -> TestAccount {{
+    TestAccount::new(STAKE_CONFIG_ID, Pubkey::default(), 0, vec![0; 10])
+}}
+
+'''
+
+RUNNER = r'''
+#[test]
+fn test_native_negative_cases() {
+    set_clock(&Clock::default());
+    set_default_rent();
+
+    let mut mismatches = Vec::new();
+    for case in cases() {
+        let inputs: Vec<Input> = case.accounts.into_iter().map(Input::from).collect();
+        let result = with_accounts(&inputs, |accounts| process(accounts, &case.data));
+        if result != Err(case.expected.clone()) {
+            mismatches.push(format!(
+                "{}: {}\n  expected: {:?}\n  actual:   {:?}",
+                case.test,
+                case.note,
+                Err::<(), _>(case.expected),
+                result
+            ));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+'''
+
+
+def balanced(text, start):
+    """Index just past the bracket closing the one at `start`."""
+    pairs = {"(": ")", "[": "]", "{": "}"}
+    stack = []
+    i = start
+    while i < len(text):
+        c = text[i]
+        if c in pairs:
+            stack.append(pairs[c])
+        elif stack and c == stack[-1]:
+            stack.pop()
+            if not stack:
+                return i + 1
+        i += 1
+    raise ValueError(f"unbalanced bracket at {start}")
+
+
+def split_args(text):
+    """Top-level comma separated arguments of `text`."""
+    args, depth, current = [], 0, ""
+    for c in text:
+        if c in "([{":
+            depth += 1
+        elif c in ")]}":
+            depth -= 1
+        if c == "," and depth == 0:
+            args.append(current.strip())
+            current = ""
+        else:
+            current += c
+    if current.strip():
+        args.append(current.strip())
+    return args
+
+
+def test_bodies(source):
+    for name in PORTED_TESTS:
+        match = re.search(rf"fn {name}\(", source)
+        if not match:
+            sys.exit(f"{name} not found")
+        open_brace = source.index("{", match.end())
+        yield name, source[open_brace : balanced(source, open_brace)]
+
+
+def calls(body):
+    """(function, arguments, note) of each call expecting an error."""
+    for match in re.finditer(r"\b(process_instruction(?:_as_one_arg)?)\(", body):
+        end = balanced(body, match.end() - 1)
+        args = split_args(body[match.end() : end - 1])
+        if not args[-1].startswith("Err("):
+            continue
+        # the comment lines just above the call, or else the call itself
+        lines = body[: match.start()].rstrip().split("\n")
+        note = []
+        while lines and lines[-1].strip().startswith("//"):
+            note.insert(0, lines.pop().strip().lstrip("/").strip())
+        if not note and match.group(1) == "process_instruction_as_one_arg":
+            call = re.sub(r"\s+", " ", args[1]).replace("( ", "(").replace(", )", ")")
+            note = [re.sub(r"&|Pubkey::|_pubkey\(\)|\(\)|instruction::", "", call)]
+        yield match.group(1), args, " ".join(note)
+
+
+def rust_str(text):
+    """`text` as a string literal, continued over lines rustfmt can fit."""
+    # a trailing `\` skips the line break and the indentation after it
+    lines = textwrap.wrap(text, 60)
+    indent = " \\\n" + " " * 16
+    return '"' + indent.join(json.dumps(line)[1:-1] for line in lines) + '"'
+
+
+def expected(arg):
+    error = re.fullmatch(r"Err\(InstructionError::(\w+)\)", arg).group(1)
+    if error not in ERRORS:
+        sys.exit(f"unmapped error {error}")
+    return f"ProgramError::{error}"
+
+
+def flags(account, is_signer, is_writable):
+    if is_signer:
+        account += ".signer()"
+    if is_writable:
+        account += ".writable()"
+    return account
+
+
+def port_explicit(args):
+    """A `process_instruction` case, or the reason it is skipped."""
+    variant = re.search(r"StakeInstruction::(\w+)", args[1]).group(1)
+    if variant in UNDISPATCHED:
+        return None, UNDISPATCHED[variant]
+    amount = re.search(r"StakeInstruction::\w+\((\w+)\)", args[1])
+    data = VARIANT_DATA[variant].format(amount=AMOUNTS[amount.group(1)] if amount else "")
+
+    held = dict(re.findall(r"\((\w+), (\w+)(?:\.clone\(\))?\)", args[2]))
+    metas = re.findall(
+        r"pubkey: (\w+),\s*is_signer: (true|false),\s*is_writable: (true|false)", args[3]
+    )
+    accounts = []
+    for key, is_signer, is_writable in metas:
+        account = ACCOUNTS[held[key]].format(key=KEYS.get(key))
+        accounts.append(flags(account, is_signer == "true", is_writable == "true"))
+    return (data, accounts), None
+
+
+def port_one_arg(args, unique):
+    """A `process_instruction_as_one_arg` case, or the reason it is skipped."""
+    match = re.fullmatch(r"&instruction::(\w+)\((.*)\)(?:\[\d+\])?", args[1], re.S)
+    builder, builder_args = match.group(1), split_args(match.group(2))
+    variant, data, layout = BUILDERS[builder]
+    if variant in UNDISPATCHED:
+        return None, UNDISPATCHED[variant]
+
+    keys, accounts, seen = {}, [], set()
+    for source, is_signer, is_writable in layout:
+        if isinstance(source, str):
+            account, key = SYSVAR_ACCOUNTS[source], source
+        else:
+            pubkey = builder_args[source].lstrip("&")
+            if pubkey == "Pubkey::new_unique()":
+                key = f"[{next(unique)}; 32]"
+                keys[source] = key
+            else:
+                key = pubkey
+            account = KEY_ACCOUNTS[pubkey].format(key=key)
+        # native creates one account per key, none of the ported cases repeat one
+        if key in seen:
+            sys.exit(f"{builder} repeats {key}")
+        seen.add(key)
+        accounts.append(flags(account, is_signer, is_writable))
+
+    fields = {f"arg{i}": arg for i, arg in enumerate(builder_args)}
+    fields.update({f"key{i}": keys.get(i, "Pubkey::default()") for i in range(len(builder_args))})
+    return (data.format(**fields), accounts), None
+
+
+def main():
+    if len(sys.argv) != 2:
+        sys.exit(__doc__)
+    native = Path(sys.argv[1])
+    source = native.read_text()
+    version = re.search(r"solana-stake-program-([\d.]+)", str(native.resolve()))
+    version = version.group(1) if version else "(unknown version)"
+
+    cases, skipped = [], []
+    for test, body in test_bodies(source):
+        # keys of `Pubkey::new_unique()`, clear of the fixed ones above
+        unique = iter(range(16, 256))
+        for function, args, note in calls(body):
+            if function == "process_instruction":
+                ported, reason = port_explicit(args)
+            else:
+                ported, reason = port_one_arg(args, unique)
+            if ported is None:
+                skipped.append(f"// {test}: {note or 'unnamed case'} ({reason})")
+                continue
+            data, accounts = ported
+            error = expected(args[-1])
+            comment = ""
+            if note in DIVERGENCES:
+                ours, reason = DIVERGENCES[note]
+                comment = f"// native: {error.split('::')[1]}, {reason}\n"
+                error = f"ProgramError::{ours}"
+            note = note or f"case {len(cases) + 1}"
+            cases.append(
+                comment
+                + "NativeCase {\n"
+                f"    test: {json.dumps(test)},\n"
+                + f"    note: {rust_str(note)},\n"
+                + f"    data: {data},\n"
+                + f"    accounts: vec![{', '.join(accounts)}],\n"
+                + f"    expected: {error},\n"
+                + "}"
+            )
+
+    out = PRELUDE.format(version=version)
+    if skipped:
+        out += "// skipped, rerun the script once they are dispatched:\n"
+        out += "\n".join(skipped) + "\n"
+    # an array rather than `vec!`, which rustfmt leaves alone once a note
+    # overflows the line
+    out += "fn cases() -> Vec<NativeCase> {\n    Vec::from([\n"
+    out += ",\n".join(cases) + ",\n    ])\n}\n"
+    out += RUNNER
+
+    target = Path(__file__).with_name("native_negative.rs")
+    target.write_text(out)
+    subprocess.run(["rustfmt", "--edition", "2021", str(target)], check=True)
+    print(f"ported {len(cases)} cases, skipped {len(skipped)} to {target}")
+
+
+if __name__ == "__main__":
+    main()
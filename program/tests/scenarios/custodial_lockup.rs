@@ -0,0 +1,118 @@
+//! Stake held for a beneficiary under a vesting lockup, with a custodian who
+//! may extend it and co-sign early access. The withdraw authority starts with
+//! an address the beneficiary derived from a seed, as a wallet or a program
+//! holding many accounts would.
+
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    error::StakeError,
+    helpers::create_with_seed,
+    state::{Authorized, Lockup, StakeAuthorize, StakeStateV2},
+};
+
+use crate::{common::STAKE_RENT_EXEMPT_RESERVE, ledger::*};
+
+const STAKE: Pubkey = [1; 32];
+const STAKER: Pubkey = [2; 32];
+const BASE: Pubkey = [3; 32];
+const SEED_OWNER: Pubkey = [4; 32];
+const CUSTODIAN: Pubkey = [5; 32];
+const NEW_WITHDRAWER: Pubkey = [6; 32];
+const RECIPIENT: Pubkey = [7; 32];
+
+const SEED: &str = "vesting";
+const GRANT: u64 = 1_000 * LAMPORTS_PER_SOL;
+const EARLY_RELEASE: u64 = 100 * LAMPORTS_PER_SOL;
+
+fn lockup_epoch(ledger: &Ledger) -> u64 {
+    ledger.state(&STAKE).lockup().unwrap().epoch()
+}
+
+#[test]
+fn test_custodial_lockup() {
+    let mut ledger = Ledger::new(100);
+    let withdrawer = create_with_seed(&BASE, SEED.as_bytes(), &SEED_OWNER).unwrap();
+
+    // initialized locked up until epoch 200
+    let authorized = Authorized {
+        staker: STAKER,
+        withdrawer,
+    };
+    let mut lockup = Lockup {
+        custodian: CUSTODIAN,
+        ..Lockup::default()
+    };
+    lockup.set_epoch(200);
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + GRANT;
+    ledger.create_stake_account(STAKE, lamports, StakeStateV2::Uninitialized);
+    assert_eq!(
+        ledger.process(client::initialize(&STAKE, &authorized, &lockup)),
+        Ok(())
+    );
+    assert_eq!(lockup_epoch(&ledger), 200);
+
+    // the beneficiary can neither withdraw nor shorten the lockup alone
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &withdrawer, &RECIPIENT, 1, None)),
+        Err(StakeError::LockupInForce.into())
+    );
+    assert_eq!(
+        ledger.process(set_lockup_epoch(&STAKE, &withdrawer, 101)),
+        Err(ProgramError::MissingRequiredSignature)
+    );
+
+    // the custodian extends the vesting period
+    ledger.warp_to_epoch(150);
+    assert_eq!(
+        ledger.process(set_lockup_epoch(&STAKE, &CUSTODIAN, 250)),
+        Ok(())
+    );
+    assert_eq!(lockup_epoch(&ledger), 250);
+
+    // the withdraw authority moves to a new wallet, which the lockup only
+    // allows with the custodian's signature
+    let handover = |custodian| {
+        client::authorize_with_seed(
+            &STAKE,
+            &BASE,
+            SEED,
+            &SEED_OWNER,
+            &NEW_WITHDRAWER,
+            StakeAuthorize::Withdrawer,
+            custodian,
+        )
+    };
+    assert_eq!(
+        ledger.process(handover(None)),
+        Err(StakeError::CustodianMissing.into())
+    );
+    assert_eq!(ledger.process(handover(Some(&CUSTODIAN))), Ok(()));
+    assert_eq!(
+        ledger.state(&STAKE).authorized().unwrap().withdrawer,
+        NEW_WITHDRAWER
+    );
+
+    // the custodian co-signs an early release
+    assert_eq!(
+        ledger.process(withdraw(
+            &STAKE,
+            &NEW_WITHDRAWER,
+            &RECIPIENT,
+            EARLY_RELEASE,
+            Some(&CUSTODIAN)
+        )),
+        Ok(())
+    );
+    assert_eq!(ledger.lamports(&RECIPIENT), EARLY_RELEASE);
+
+    // once vested, the rest is the beneficiary's alone
+    ledger.warp_to_epoch(250);
+    let rest = lamports - EARLY_RELEASE;
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &NEW_WITHDRAWER, &RECIPIENT, rest, None)),
+        Ok(())
+    );
+    assert_eq!(ledger.lamports(&RECIPIENT), lamports);
+    assert_eq!(ledger.state(&STAKE), StakeStateV2::Uninitialized);
+}
@@ -0,0 +1,76 @@
+//! A validator stops voting and its delegations are deactivated by whoever
+//! notices, leaving the stakers to recover their stake while it cools down.
+
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::state::StakeStateV2;
+
+use crate::{common::STAKE_RENT_EXEMPT_RESERVE, ledger::*};
+
+const STAKE: Pubkey = [1; 32];
+const SPLIT_STAKE: Pubkey = [2; 32];
+const OWNER: Pubkey = [3; 32];
+const DELINQUENT_VOTER: Pubkey = [4; 32];
+
+const DELEGATED: u64 = 50 * LAMPORTS_PER_SOL;
+const HALF: u64 = DELEGATED / 2;
+
+#[test]
+fn test_delinquent_validator_cleanup() {
+    let mut ledger = Ledger::new(10);
+
+    // delegated and forgotten about
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + DELEGATED;
+    ledger.create_vote_account(DELINQUENT_VOTER);
+    ledger.create_stake_account(STAKE, lamports, StakeStateV2::Uninitialized);
+    assert_eq!(
+        ledger.process(client::initialize_checked(&STAKE, &OWNER, &OWNER)),
+        Ok(())
+    );
+    assert_eq!(
+        ledger.process(client::delegate_stake(&STAKE, &OWNER, &DELINQUENT_VOTER)),
+        Ok(())
+    );
+
+    // the validator has missed the last five epochs by epoch 20, and anyone
+    // may deactivate its delegations; DeactivateDelinquent isn't processed
+    // yet
+    ledger.warp_to_epoch(20);
+    ledger.deactivate_natively(&STAKE);
+
+    // the stake cools down through the epoch, so none of it can be taken out
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &OWNER, &OWNER, HALF, None)),
+        Err(ProgramError::InsufficientFunds)
+    );
+
+    // but the cooling stake can still be split, as the owner gets ready to
+    // spread it over other validators
+    ledger.create_stake_account(
+        SPLIT_STAKE,
+        STAKE_RENT_EXEMPT_RESERVE,
+        StakeStateV2::Uninitialized,
+    );
+    assert_eq!(
+        ledger.process(client::split(&STAKE, &OWNER, HALF, &SPLIT_STAKE)),
+        Ok(())
+    );
+    let deactivation_epoch =
+        |key| u64::from_le_bytes(ledger.state(key).delegation().unwrap().deactivation_epoch);
+    assert_eq!(deactivation_epoch(&SPLIT_STAKE), 20);
+
+    // fully inactive the next epoch, when both accounts can be emptied
+    ledger.warp_to_epoch(21);
+    for key in [STAKE, SPLIT_STAKE] {
+        let lamports = ledger.lamports(&key);
+        assert_eq!(
+            ledger.process(withdraw(&key, &OWNER, &OWNER, lamports, None)),
+            Ok(())
+        );
+        assert_eq!(ledger.state(&key), StakeStateV2::Uninitialized);
+    }
+    assert_eq!(
+        ledger.lamports(&OWNER),
+        lamports + STAKE_RENT_EXEMPT_RESERVE
+    );
+}
@@ -0,0 +1,228 @@
+//! A tiny bank for the scenarios: accounts persist between instructions, a
+//! failed instruction leaves them as they were, and the clock only moves when
+//! a scenario warps it.
+
+use pinocchio::{pubkey::Pubkey, sysvars::clock::Clock, ProgramResult};
+use pinocchio_stake_interface::client::{AccountMeta, Instruction};
+use solana_pinocchio_starter::{
+    consts::CLOCK_ID,
+    instruction::StakeInstruction,
+    state::{stake_history, StakeStateV2},
+};
+
+use crate::common::*;
+
+pub const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+pub struct Ledger {
+    accounts: Vec<TestAccount>,
+    clock: Clock,
+}
+
+impl Ledger {
+    pub fn new(epoch: u64) -> Self {
+        Self {
+            accounts: Vec::new(),
+            clock: warp_to_epoch(&Clock::default(), epoch),
+        }
+    }
+
+    /// Moves to the start of `epoch`. There is no stake history to hold
+    /// activation or cooldown back, so either completes an epoch after it
+    /// starts.
+    pub fn warp_to_epoch(&mut self, epoch: u64) {
+        self.clock = warp_to_epoch(&self.clock, epoch);
+    }
+
+    /// Creates a stake account, as the system program would have allocated
+    /// and funded it, in `state`.
+    pub fn create_stake_account(&mut self, key: Pubkey, lamports: u64, state: StakeStateV2) {
+        self.accounts.push(stake_account(key, lamports, state));
+    }
+
+    /// Creates a vote account that stake can be delegated to.
+    pub fn create_vote_account(&mut self, key: Pubkey) {
+        self.accounts.push(vote_account(key, &[]));
+    }
+
+    /// Lamports that arrive by transfer, outside of the stake program.
+    pub fn transfer(&mut self, key: &Pubkey, lamports: u64) {
+        self.account_mut(key).lamports += lamports;
+    }
+
+    /// Deactivates the delegated `key` in the current epoch, as native
+    /// `Deactivate` and `DeactivateDelinquent` leave it. The entrypoint doesn't
+    /// process either yet.
+    pub fn deactivate_natively(&mut self, key: &Pubkey) {
+        let StakeStateV2::Stake(meta, mut stake, flags) = self.state(key) else {
+            panic!("only delegated stake can be deactivated");
+        };
+        stake.deactivate(self.clock.epoch.to_le_bytes()).unwrap();
+        self.account_mut(key).data = stake_state_data(StakeStateV2::Stake(meta, stake, flags));
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts
+            .iter()
+            .find(|account| account.key == *key)
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub fn state(&self, key: &Pubkey) -> StakeStateV2 {
+        let account = self.accounts.iter().find(|account| account.key == *key);
+        StakeStateV2::read_from(&account.unwrap().data).unwrap()
+    }
+
+    /// Runs `instruction` through the entrypoint, keeping what it changed
+    /// only if it succeeds.
+    pub fn process(&mut self, instruction: Instruction) -> ProgramResult {
+        set_clock(&self.clock);
+        set_stake_history(self.clock.epoch, &[]);
+        set_default_rent();
+
+        let inputs = self.inputs(&instruction.accounts);
+        let (result, after) = with_accounts(&inputs, |accounts| {
+            let result = process(accounts, &instruction.data);
+            let after: Vec<(Pubkey, u64, Vec<u8>)> = accounts
+                .iter()
+                .map(|account| {
+                    (
+                        *account.key(),
+                        account.lamports(),
+                        account.try_borrow_data().unwrap().to_vec(),
+                    )
+                })
+                .collect();
+            (result, after)
+        });
+
+        if result.is_ok() {
+            for (key, lamports, data) in after {
+                if key == CLOCK_ID || key == stake_history::ID {
+                    continue;
+                }
+                match self.accounts.iter_mut().find(|account| account.key == key) {
+                    Some(account) => {
+                        account.lamports = lamports;
+                        account.data = data;
+                    }
+                    // a wallet seen for the first time
+                    None => {
+                        self.accounts
+                            .push(TestAccount::new(key, Pubkey::default(), lamports, data))
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn inputs(&self, metas: &[AccountMeta]) -> Vec<Input> {
+        metas
+            .iter()
+            .enumerate()
+            .map(|(index, meta)| {
+                if let Some(first) = metas[..index]
+                    .iter()
+                    .position(|earlier| earlier.pubkey == meta.pubkey)
+                {
+                    return Input::Duplicate(first as u8);
+                }
+                let mut account = match meta.pubkey {
+                    CLOCK_ID => clock_account(&self.clock),
+                    stake_history::ID => stake_history_account(),
+                    key => self
+                        .accounts
+                        .iter()
+                        .find(|account| account.key == key)
+                        .cloned()
+                        // a wallet that only signs
+                        .unwrap_or_else(|| TestAccount::new(key, Pubkey::default(), 0, vec![])),
+                };
+                // as in a transaction, an account repeated in the instruction
+                // signs or is writable if any of its entries is
+                let entries = metas.iter().filter(|other| other.pubkey == meta.pubkey);
+                account.is_signer = entries.clone().any(|entry| entry.is_signer);
+                account.is_writable = entries.clone().any(|entry| entry.is_writable);
+                account.into()
+            })
+            .collect()
+    }
+
+    fn account_mut(&mut self, key: &Pubkey) -> &mut TestAccount {
+        self.accounts
+            .iter_mut()
+            .find(|account| account.key == *key)
+            .unwrap()
+    }
+}
+
+fn instruction(
+    instruction: StakeInstruction,
+    args: &[u8],
+    accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let mut data = vec![instruction as u8];
+    data.extend_from_slice(args);
+    Instruction {
+        program_id: solana_pinocchio_starter::ID,
+        accounts,
+        data,
+    }
+}
+
+pub fn withdraw(
+    stake: &Pubkey,
+    withdrawer: &Pubkey,
+    recipient: &Pubkey,
+    lamports: u64,
+    custodian: Option<&Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::writable(*stake),
+        AccountMeta::writable(*recipient),
+        AccountMeta::readonly(CLOCK_ID),
+        AccountMeta::readonly(stake_history::ID),
+        AccountMeta::readonly_signer(*withdrawer),
+    ];
+    if let Some(custodian) = custodian {
+        accounts.push(AccountMeta::readonly_signer(*custodian));
+    }
+    instruction(
+        StakeInstruction::Withdraw,
+        &lamports.to_le_bytes(),
+        accounts,
+    )
+}
+
+/// Sets the lockup epoch, leaving the timestamp and custodian alone.
+pub fn set_lockup_epoch(stake: &Pubkey, authority: &Pubkey, epoch: u64) -> Instruction {
+    let mut args = vec![0, 1];
+    args.extend_from_slice(&epoch.to_le_bytes());
+    args.push(0);
+    instruction(
+        StakeInstruction::SetLockup,
+        &args,
+        vec![
+            AccountMeta::writable(*stake),
+            AccountMeta::readonly_signer(*authority),
+        ],
+    )
+}
+
+pub fn move_lamports(
+    source: &Pubkey,
+    destination: &Pubkey,
+    staker: &Pubkey,
+    lamports: u64,
+) -> Instruction {
+    instruction(
+        StakeInstruction::MoveLamports,
+        &lamports.to_le_bytes(),
+        vec![
+            AccountMeta::writable(*source),
+            AccountMeta::writable(*destination),
+            AccountMeta::readonly_signer(*staker),
+        ],
+    )
+}
@@ -0,0 +1,16 @@
+//! Named, linear flows through the program, each read top to bottom as a
+//! user would live it. They are regression tests, and documentation by
+//! example of what the program supports.
+//!
+//! Every step goes through the entrypoint, except deactivation, which it
+//! doesn't process yet. That is applied to the account state as native would
+//! leave it, and says so, so each flow stays complete.
+
+#[path = "../common/mod.rs"]
+mod common;
+mod ledger;
+
+mod custodial_lockup;
+mod delinquent_validator_cleanup;
+mod pool_split_merge_churn;
+mod solo_staker_lifecycle;
@@ -0,0 +1,98 @@
+//! A stake pool rebalancing between validators: stake is split off its
+//! reserve into a validator account, tips are swept along with it, and the
+//! account is merged back when the pool leaves the validator.
+
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::state::{Authorized, Lockup, StakeStateV2};
+
+use crate::{common::STAKE_RENT_EXEMPT_RESERVE, ledger::*};
+
+const RESERVE: Pubkey = [1; 32];
+const VALIDATOR_STAKE: Pubkey = [2; 32];
+const POOL: Pubkey = [3; 32];
+const VOTER: Pubkey = [4; 32];
+
+const POOL_STAKE: u64 = 1_000 * LAMPORTS_PER_SOL;
+const REBALANCE: u64 = 300 * LAMPORTS_PER_SOL;
+const TIPS: u64 = LAMPORTS_PER_SOL / 2;
+
+fn delegated_stake(ledger: &Ledger, key: &Pubkey) -> u64 {
+    u64::from_le_bytes(ledger.state(key).delegation().unwrap().stake)
+}
+
+#[test]
+fn test_pool_split_merge_churn() {
+    let mut ledger = Ledger::new(100);
+
+    // the pool's reserve, delegated with the pool holding both authorities
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + POOL_STAKE;
+    ledger.create_vote_account(VOTER);
+    ledger.create_stake_account(RESERVE, lamports, StakeStateV2::Uninitialized);
+    let authorized = Authorized {
+        staker: POOL,
+        withdrawer: POOL,
+    };
+    assert_eq!(
+        ledger.process(client::initialize(
+            &RESERVE,
+            &authorized,
+            &Lockup::default()
+        )),
+        Ok(())
+    );
+    assert_eq!(
+        ledger.process(client::delegate_stake(&RESERVE, &POOL, &VOTER)),
+        Ok(())
+    );
+    assert_eq!(delegated_stake(&ledger, &RESERVE), POOL_STAKE);
+    ledger.warp_to_epoch(101);
+
+    // active stake is split into an account the pool funded for rent
+    ledger.create_stake_account(
+        VALIDATOR_STAKE,
+        STAKE_RENT_EXEMPT_RESERVE,
+        StakeStateV2::Uninitialized,
+    );
+    assert_eq!(
+        ledger.process(client::split(&RESERVE, &POOL, REBALANCE, &VALIDATOR_STAKE)),
+        Ok(())
+    );
+    assert_eq!(delegated_stake(&ledger, &RESERVE), POOL_STAKE - REBALANCE);
+    assert_eq!(delegated_stake(&ledger, &VALIDATOR_STAKE), REBALANCE);
+    assert_eq!(
+        ledger.lamports(&VALIDATOR_STAKE),
+        STAKE_RENT_EXEMPT_RESERVE + REBALANCE
+    );
+
+    // tips land in the reserve and are swept across, but staked lamports
+    // can't be moved this way
+    ledger.transfer(&RESERVE, TIPS);
+    assert_eq!(
+        ledger.process(move_lamports(&RESERVE, &VALIDATOR_STAKE, &POOL, TIPS + 1)),
+        Err(ProgramError::InvalidArgument)
+    );
+    assert_eq!(
+        ledger.process(move_lamports(&RESERVE, &VALIDATOR_STAKE, &POOL, TIPS)),
+        Ok(())
+    );
+    assert_eq!(
+        ledger.lamports(&VALIDATOR_STAKE),
+        STAKE_RENT_EXEMPT_RESERVE + REBALANCE + TIPS
+    );
+
+    // leaving the validator, the account is merged back into the reserve
+    ledger.warp_to_epoch(120);
+    assert_eq!(
+        ledger.process(client::merge(&RESERVE, &VALIDATOR_STAKE, &POOL)),
+        Ok(())
+    );
+    assert_eq!(
+        ledger.lamports(&RESERVE),
+        lamports + TIPS + STAKE_RENT_EXEMPT_RESERVE
+    );
+    assert_eq!(ledger.lamports(&VALIDATOR_STAKE), 0);
+    assert_eq!(ledger.state(&VALIDATOR_STAKE), StakeStateV2::Uninitialized);
+    // the tips and the split account's reserve stay undelegated
+    assert_eq!(delegated_stake(&ledger, &RESERVE), POOL_STAKE);
+}
@@ -0,0 +1,70 @@
+//! One person staking their own SOL with one validator: delegate, take out
+//! what isn't staked along the way, then deactivate and close the account.
+
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::state::StakeStateV2;
+
+use crate::{common::STAKE_RENT_EXEMPT_RESERVE, ledger::*};
+
+const STAKE: Pubkey = [1; 32];
+const OWNER: Pubkey = [2; 32];
+const VOTER: Pubkey = [3; 32];
+
+const DELEGATED: u64 = 100 * LAMPORTS_PER_SOL;
+const TIP: u64 = LAMPORTS_PER_SOL / 10;
+
+#[test]
+fn test_solo_staker_lifecycle() {
+    let mut ledger = Ledger::new(100);
+
+    // funded, initialized and delegated in one transaction, the owner holding
+    // both authorities
+    let lamports = STAKE_RENT_EXEMPT_RESERVE + DELEGATED;
+    ledger.create_vote_account(VOTER);
+    ledger.create_stake_account(STAKE, lamports, StakeStateV2::Uninitialized);
+    assert_eq!(
+        ledger.process(client::initialize_checked(&STAKE, &OWNER, &OWNER)),
+        Ok(())
+    );
+    assert_eq!(
+        ledger.process(client::delegate_stake(&STAKE, &OWNER, &VOTER)),
+        Ok(())
+    );
+
+    // fully active the next epoch, when someone sends the account a tip
+    ledger.warp_to_epoch(101);
+    ledger.transfer(&STAKE, TIP);
+
+    // only the lamports that aren't staked can be taken out of active stake
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &OWNER, &OWNER, TIP + 1, None)),
+        Err(ProgramError::InsufficientFunds)
+    );
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &OWNER, &OWNER, TIP, None)),
+        Ok(())
+    );
+    assert_eq!(ledger.lamports(&OWNER), TIP);
+    assert_eq!(ledger.lamports(&STAKE), lamports);
+
+    // done staking; Deactivate isn't processed yet
+    ledger.warp_to_epoch(150);
+    ledger.deactivate_natively(&STAKE);
+
+    // the stake stays effective through the epoch it was deactivated in
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &OWNER, &OWNER, lamports, None)),
+        Err(ProgramError::InsufficientFunds)
+    );
+
+    // and is free once it has cooled down, closing the account
+    ledger.warp_to_epoch(151);
+    assert_eq!(
+        ledger.process(withdraw(&STAKE, &OWNER, &OWNER, lamports, None)),
+        Ok(())
+    );
+    assert_eq!(ledger.lamports(&STAKE), 0);
+    assert_eq!(ledger.lamports(&OWNER), TIP + lamports);
+    assert_eq!(ledger.state(&STAKE), StakeStateV2::Uninitialized);
+}
@@ -0,0 +1,306 @@
+mod common;
+
+use common::*;
+use pinocchio::{
+    program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock, ProgramResult,
+};
+use solana_pinocchio_starter::{
+    error::StakeError,
+    instruction::{LockupArgs, StakeInstruction},
+    state::{ClusterLockup, Meta, SetLockupSignerArgs, StakeStateV2},
+};
+
+const STAKE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const CUSTODIAN: Pubkey = [5; 32];
+
+// past 255 so that byte-wise comparisons would be caught
+const STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + 1_000_000_000;
+
+/// Sets a lockup on an unlocked account, signed by the withdraw authority.
+fn meta_with_lockup(epoch: u64, unix_timestamp: i64) -> Meta {
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.set_lockup::<ClusterLockup>(
+        &LockupArgs {
+            unix_timestamp: Some(unix_timestamp.to_le_bytes()),
+            epoch: Some(epoch.to_le_bytes()),
+            custodian: Some(CUSTODIAN),
+        },
+        SetLockupSignerArgs {
+            has_custodian_signer: false,
+            has_withdrawer_signer: true,
+        },
+        &clock(),
+    )
+    .unwrap();
+    meta
+}
+
+/// Withdraws a single lamport signed only by the withdraw authority.
+fn withdraw_without_custodian(meta: Meta) -> ProgramResult {
+    withdraw_without_custodian_at(meta, &clock())
+}
+
+fn withdraw_without_custodian_at(meta: Meta, clock: &Clock) -> ProgramResult {
+    withdraw_signed_by(meta, clock, &[])
+}
+
+/// Withdraws a single lamport signed by the withdraw authority and, as the
+/// lockup custodian, by `custodian`.
+fn withdraw_signed_by(meta: Meta, clock: &Clock, custodian: &[Pubkey]) -> ProgramResult {
+    let mut inputs: Vec<Input> = vec![
+        stake_account(STAKE, STAKE_LAMPORTS, StakeStateV2::Initialized(meta)).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(clock).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    inputs.extend(custodian.iter().map(|custodian| {
+        TestAccount::new(*custodian, Pubkey::default(), 0, vec![])
+            .signer()
+            .into()
+    }));
+    let mut data = vec![StakeInstruction::Withdraw as u8];
+    data.extend_from_slice(&1u64.to_le_bytes());
+    with_accounts(&inputs, |accounts| process(accounts, &data))
+}
+
+#[test]
+fn test_lockup_in_the_past_is_expired() {
+    let meta = meta_with_lockup(CURRENT_EPOCH - 1, CURRENT_TIMESTAMP - 1);
+    assert!(!meta.lockup.is_in_force(&clock(), None));
+    assert_eq!(withdraw_without_custodian(meta), Ok(()));
+
+    // far in the past, including before the unix epoch
+    let meta = meta_with_lockup(0, -1);
+    assert!(!meta.lockup.is_in_force(&clock(), None));
+    assert_eq!(withdraw_without_custodian(meta), Ok(()));
+}
+
+#[test]
+fn test_lockup_ending_now_is_expired() {
+    let meta = meta_with_lockup(CURRENT_EPOCH, CURRENT_TIMESTAMP);
+    assert!(!meta.lockup.is_in_force(&clock(), None));
+    assert_eq!(withdraw_without_custodian(meta), Ok(()));
+}
+
+#[test]
+fn test_lockup_in_the_future_is_in_force() {
+    // either bound still ahead keeps the lockup in force
+    for (epoch, unix_timestamp) in [
+        (CURRENT_EPOCH + 1, CURRENT_TIMESTAMP - 1),
+        (CURRENT_EPOCH - 1, CURRENT_TIMESTAMP + 1),
+    ] {
+        let meta = meta_with_lockup(epoch, unix_timestamp);
+        assert!(meta.lockup.is_in_force(&clock(), None));
+        assert!(!meta.lockup.is_in_force(&clock(), Some(&CUSTODIAN)));
+        assert_eq!(
+            withdraw_without_custodian(meta),
+            Err(StakeError::LockupInForce.into())
+        );
+    }
+}
+
+#[test]
+fn test_lockup_held_by_the_constraint_not_yet_expired() {
+    // the same lockup as time passes, with epochs and the wall clock moving
+    // at different rates
+    let lockup_epoch = CURRENT_EPOCH + 10;
+    let lockup_timestamp = CURRENT_TIMESTAMP + 30 * 86_400;
+    let meta = meta_with_lockup(lockup_epoch, lockup_timestamp);
+
+    let epoch_expired = warp_to_epoch(&clock(), lockup_epoch);
+    let timestamp_expired = warp_to_timestamp(&clock(), lockup_timestamp);
+    let both_expired = warp_to_timestamp(&epoch_expired, lockup_timestamp);
+    for (clock, in_force) in [
+        (clock(), true),
+        // epochs ran fast, the wall clock is still short
+        (epoch_expired, true),
+        (
+            warp_to_timestamp(&epoch_expired, lockup_timestamp - 1),
+            true,
+        ),
+        // slots slowed down, the wall clock passed but the epoch did not
+        (timestamp_expired, true),
+        (warp_to_epoch(&timestamp_expired, lockup_epoch - 1), true),
+        (both_expired, false),
+        (warp_to_epoch(&both_expired, lockup_epoch + 1), false),
+    ] {
+        let expected = if in_force {
+            Err(StakeError::LockupInForce.into())
+        } else {
+            Ok(())
+        };
+        assert_eq!(meta.lockup.is_in_force(&clock, None), in_force);
+        assert_eq!(
+            withdraw_without_custodian_at(meta, &clock),
+            expected,
+            "epoch {}, timestamp {}",
+            clock.epoch,
+            clock.unix_timestamp
+        );
+    }
+}
+
+#[test]
+fn test_expired_lockup_can_be_reset_by_withdrawer() {
+    // once expired, the custodian is no longer needed to replace the lockup
+    let mut meta = meta_with_lockup(CURRENT_EPOCH - 1, CURRENT_TIMESTAMP - 1);
+    meta.set_lockup::<ClusterLockup>(
+        &LockupArgs {
+            unix_timestamp: None,
+            epoch: Some((CURRENT_EPOCH + 1).to_le_bytes()),
+            custodian: None,
+        },
+        SetLockupSignerArgs {
+            has_custodian_signer: false,
+            has_withdrawer_signer: true,
+        },
+        &clock(),
+    )
+    .unwrap();
+    assert!(meta.lockup.is_in_force(&clock(), None));
+    assert_eq!(
+        withdraw_without_custodian(meta),
+        Err(StakeError::LockupInForce.into())
+    );
+}
+
+/// Runs a legacy SetLockup over an account in `meta`, signed by `signer`, and
+/// returns the result along with the account's meta afterwards.
+fn set_lockup(meta: Meta, signer: Pubkey, instruction_data: &[u8]) -> (ProgramResult, Meta) {
+    set_clock(&clock());
+
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, STAKE_LAMPORTS, StakeStateV2::Initialized(meta)).into(),
+        TestAccount::new(signer, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, instruction_data);
+        let data = accounts[0].try_borrow_data().unwrap();
+        match unsafe { StakeStateV2::from_bytes(&data) } {
+            StakeStateV2::Initialized(meta) => (result, *meta),
+            _ => panic!("stake account is no longer initialized"),
+        }
+    })
+}
+
+fn set_lockup_data(epoch: Option<u64>, custodian: Option<Pubkey>) -> Vec<u8> {
+    let mut data = vec![StakeInstruction::SetLockup as u8, 0];
+    match epoch {
+        Some(epoch) => {
+            data.push(1);
+            data.extend_from_slice(&epoch.to_le_bytes());
+        }
+        None => data.push(0),
+    }
+    match custodian {
+        Some(custodian) => {
+            data.push(1);
+            data.extend_from_slice(&custodian);
+        }
+        None => data.push(0),
+    }
+    data
+}
+
+#[test]
+fn test_set_lockup_custodian_from_instruction_data() {
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+    let data = set_lockup_data(Some(CURRENT_EPOCH + 1), Some(CUSTODIAN));
+    let (result, meta) = set_lockup(meta, WITHDRAWER, &data);
+    assert_eq!(result, Ok(()));
+    assert_eq!(meta.lockup.custodian, CUSTODIAN);
+    assert_eq!(u64::from_le_bytes(meta.lockup.epoch), CURRENT_EPOCH + 1);
+
+    // while in force only the custodian named in the data may change it, and
+    // it need not be one of the instruction accounts beforehand
+    let data = set_lockup_data(Some(CURRENT_EPOCH + 2), None);
+    let (result, unchanged) = set_lockup(meta, WITHDRAWER, &data);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_eq!(unchanged, meta);
+
+    let (result, meta) = set_lockup(meta, CUSTODIAN, &data);
+    assert_eq!(result, Ok(()));
+    assert_eq!(meta.lockup.custodian, CUSTODIAN);
+    assert_eq!(u64::from_le_bytes(meta.lockup.epoch), CURRENT_EPOCH + 2);
+}
+
+#[test]
+fn test_set_lockup_malformed_custodian() {
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+
+    // a custodian cut short, and an Option tag that is neither None nor Some
+    let mut data = set_lockup_data(None, Some(CUSTODIAN));
+    data.pop();
+    let (result, unchanged) = set_lockup(meta, WITHDRAWER, &data);
+    assert_eq!(result, Err(ProgramError::InvalidInstructionData));
+    assert_eq!(unchanged, meta);
+
+    let mut data = set_lockup_data(None, Some(CUSTODIAN));
+    data[3] = 2;
+    let (result, _) = set_lockup(meta, WITHDRAWER, &data);
+    assert_eq!(result, Err(ProgramError::InvalidInstructionData));
+}
+
+#[test]
+fn test_set_lockup_removes_custodian() {
+    // once the lockup has expired, the withdraw authority can remove the
+    // custodian by setting it to the default pubkey
+    let meta = meta_with_lockup(CURRENT_EPOCH - 1, CURRENT_TIMESTAMP - 1);
+    let data = set_lockup_data(Some(CURRENT_EPOCH + 1), Some(Pubkey::default()));
+    let (result, meta) = set_lockup(meta, WITHDRAWER, &data);
+    assert_eq!(result, Ok(()));
+    assert_eq!(meta.lockup.custodian, Pubkey::default());
+    assert!(!meta.lockup.has_custodian());
+
+    // with the new lockup in force, no one can stand in for the custodian,
+    // not even an account signing as the default pubkey
+    let data = set_lockup_data(Some(CURRENT_EPOCH), None);
+    for signer in [WITHDRAWER, CUSTODIAN, Pubkey::default()] {
+        let (result, unchanged) = set_lockup(meta, signer, &data);
+        assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+        assert_eq!(unchanged, meta);
+    }
+    assert_eq!(
+        withdraw_signed_by(meta, &clock(), &[Pubkey::default()]),
+        Err(StakeError::LockupInForce.into())
+    );
+    assert_eq!(
+        withdraw_signed_by(meta, &clock(), &[CUSTODIAN]),
+        Err(StakeError::LockupInForce.into())
+    );
+
+    // it expires like any other lockup
+    let later = Clock {
+        epoch: CURRENT_EPOCH + 1,
+        ..clock()
+    };
+    assert_eq!(withdraw_signed_by(meta, &later, &[]), Ok(()));
+}
+
+#[test]
+fn test_set_lockup_custodian_removal_needs_expiry() {
+    // while the lockup is in force, only the custodian can remove itself
+    let meta = meta_with_lockup(CURRENT_EPOCH + 1, CURRENT_TIMESTAMP);
+    let data = set_lockup_data(None, Some(Pubkey::default()));
+    let (result, unchanged) = set_lockup(meta, WITHDRAWER, &data);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_eq!(unchanged, meta);
+
+    let (result, meta) = set_lockup(meta, CUSTODIAN, &data);
+    assert_eq!(result, Ok(()));
+    assert!(!meta.lockup.has_custodian());
+    assert_eq!(
+        withdraw_signed_by(meta, &clock(), &[CUSTODIAN]),
+        Err(StakeError::LockupInForce.into())
+    );
+}
@@ -0,0 +1,355 @@
+mod common;
+
+use common::*;
+use pinocchio::{
+    program_error::ProgramError, pubkey::Pubkey, sysvars::clock::Clock, ProgramResult,
+};
+use solana_pinocchio_starter::{
+    error::StakeError,
+    instruction::StakeInstruction,
+    state::{get_stake_state, Delegation, FeatureSet, Stake, StakeFlags, StakeStateV2},
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const VOTER: Pubkey = [5; 32];
+
+const DELEGATED_STAKE: u64 = 10_000_000_000;
+const SOURCE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + DELEGATED_STAKE;
+
+// lamports on the source beyond its reserve and delegation, e.g. rewards
+// that haven't been delegated
+const UNDELEGATED_LAMPORTS: u64 = 1_000_000_000;
+
+/// Splits `split_lamports` off a fully active stake holding `extra_lamports`
+/// on top of its reserve and delegation, into a destination that is
+/// prefunded with its rent exempt reserve. Returns the result along with the
+/// delegated stake of both accounts afterwards.
+fn split(extra_lamports: u64, split_lamports: u64) -> (ProgramResult, u64, u64) {
+    split_into(StakeStateV2::size_of(), extra_lamports, split_lamports)
+}
+
+/// As `split`, with a destination of `destination_len` bytes.
+fn split_into(
+    destination_len: usize,
+    extra_lamports: u64,
+    split_lamports: u64,
+) -> (ProgramResult, u64, u64) {
+    // activated long before any stake history, so fully active
+    split_with(
+        0,
+        destination_len,
+        STAKE_RENT_EXEMPT_RESERVE,
+        extra_lamports,
+        split_lamports,
+    )
+}
+
+/// As `split_into`, from a stake activated in `activation_epoch`, into a
+/// destination holding `destination_lamports`.
+fn split_with(
+    activation_epoch: u64,
+    destination_len: usize,
+    destination_lamports: u64,
+    extra_lamports: u64,
+    split_lamports: u64,
+) -> (ProgramResult, u64, u64) {
+    set_clock(&Clock {
+        epoch: CURRENT_EPOCH,
+        ..Clock::default()
+    });
+    set_stake_history(CURRENT_EPOCH, &[]);
+    set_default_rent();
+
+    let stake = Stake {
+        delegation: Delegation::new(&VOTER, DELEGATED_STAKE, activation_epoch.to_le_bytes()),
+        ..Stake::default()
+    };
+    let source_state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        stake,
+        StakeFlags::empty(),
+    );
+    let mut destination = stake_account(
+        DESTINATION,
+        destination_lamports,
+        StakeStateV2::Uninitialized,
+    );
+    destination.data.resize(destination_len, 0);
+    let inputs: Vec<Input> = vec![
+        stake_account(SOURCE, SOURCE_LAMPORTS + extra_lamports, source_state).into(),
+        destination.into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    let mut data = vec![StakeInstruction::Split as u8];
+    data.extend_from_slice(&split_lamports.to_le_bytes());
+
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &data);
+        let delegated = |index: usize| {
+            let data = accounts[index].try_borrow_data().unwrap();
+            if data.len() != StakeStateV2::size_of() {
+                return 0;
+            }
+            match unsafe { StakeStateV2::from_bytes(&data) } {
+                StakeStateV2::Stake(_, stake, _) => u64::from_le_bytes(stake.delegation.stake),
+                _ => 0,
+            }
+        };
+        (result, delegated(0), delegated(1))
+    })
+}
+
+#[test]
+fn test_split_leaves_minimum_delegation() {
+    let minimum_delegation = FeatureSet::COMPILED.minimum_delegation();
+    let split_lamports = DELEGATED_STAKE - minimum_delegation;
+
+    let (result, source_stake, destination_stake) = split(UNDELEGATED_LAMPORTS, split_lamports);
+    assert_eq!(result, Ok(()));
+    assert_eq!(source_stake, minimum_delegation);
+    assert_eq!(destination_stake, split_lamports);
+}
+
+#[test]
+fn test_split_below_minimum_delegation() {
+    let minimum_delegation = FeatureSet::COMPILED.minimum_delegation();
+
+    // the undelegated lamports keep the source balance above its minimum, so
+    // only the delegation falls short: one lamport under, down to nothing
+    for remaining in [minimum_delegation - 1, 0] {
+        let (result, source_stake, destination_stake) =
+            split(UNDELEGATED_LAMPORTS, DELEGATED_STAKE - remaining);
+        assert_eq!(
+            result,
+            Err(StakeError::InsufficientDelegation.into()),
+            "{remaining}"
+        );
+        assert_eq!(source_stake, DELEGATED_STAKE);
+        assert_eq!(destination_stake, 0);
+    }
+}
+
+#[test]
+fn test_split_below_minimum_balance() {
+    // without spare lamports the balance check already catches it
+    let minimum_delegation = FeatureSet::COMPILED.minimum_delegation();
+    let (result, source_stake, _) = split(0, DELEGATED_STAKE - minimum_delegation + 1);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    assert_eq!(source_stake, DELEGATED_STAKE);
+}
+
+#[test]
+fn test_split_whole_account() {
+    // taking the rent exempt reserve along empties the source, which is fine
+    let (result, source_stake, destination_stake) = split(0, SOURCE_LAMPORTS);
+    assert_eq!(result, Ok(()));
+    assert_eq!(source_stake, 0);
+    assert_eq!(destination_stake, DELEGATED_STAKE);
+}
+
+#[test]
+fn test_split_into_destination_of_wrong_size() {
+    // native requires the exact size, whatever the runtime allowed the
+    // destination to be allocated with; a bigger one would otherwise keep
+    // trailing bytes no stake state accounts for
+    let split_lamports = DELEGATED_STAKE / 2;
+    for destination_len in [StakeStateV2::size_of() - 1, StakeStateV2::size_of() + 1] {
+        let (result, source_stake, destination_stake) =
+            split_into(destination_len, UNDELEGATED_LAMPORTS, split_lamports);
+        assert_eq!(
+            result,
+            Err(ProgramError::InvalidAccountData),
+            "{destination_len} bytes"
+        );
+        assert_eq!(source_stake, DELEGATED_STAKE);
+        assert_eq!(destination_stake, 0);
+    }
+
+    let (result, _, destination_stake) = split_into(
+        StakeStateV2::size_of(),
+        UNDELEGATED_LAMPORTS,
+        split_lamports,
+    );
+    assert_eq!(result, Ok(()));
+    assert_eq!(destination_stake, split_lamports);
+}
+
+#[test]
+fn test_split_active_stake_needs_prefunded_destination() {
+    // active stake can't pay the destination's reserve out of the split,
+    // however much is split, unless it takes the whole account
+    let split_lamports = DELEGATED_STAKE / 2;
+    for destination_lamports in [0, STAKE_RENT_EXEMPT_RESERVE - 1] {
+        let (result, source_stake, destination_stake) = split_with(
+            0,
+            StakeStateV2::size_of(),
+            destination_lamports,
+            0,
+            split_lamports,
+        );
+        assert_eq!(
+            result,
+            Err(ProgramError::InsufficientFunds),
+            "{destination_lamports}"
+        );
+        assert_eq!(source_stake, DELEGATED_STAKE);
+        assert_eq!(destination_stake, 0);
+    }
+
+    let (result, source_stake, destination_stake) =
+        split_with(0, StakeStateV2::size_of(), 0, 0, SOURCE_LAMPORTS);
+    assert_eq!(result, Ok(()));
+    assert_eq!(source_stake, 0);
+    assert_eq!(destination_stake, DELEGATED_STAKE);
+}
+
+#[test]
+fn test_split_activating_stake_pays_destination_reserve() {
+    // stake activating this epoch has nothing effective yet, so the split
+    // covers whatever the destination lacks of its reserve
+    let split_lamports = DELEGATED_STAKE / 2;
+    for (destination_lamports, expected_stake) in [
+        (0, split_lamports - STAKE_RENT_EXEMPT_RESERVE),
+        (
+            STAKE_RENT_EXEMPT_RESERVE / 2,
+            split_lamports - (STAKE_RENT_EXEMPT_RESERVE - STAKE_RENT_EXEMPT_RESERVE / 2),
+        ),
+        (STAKE_RENT_EXEMPT_RESERVE, split_lamports),
+        (2 * STAKE_RENT_EXEMPT_RESERVE, split_lamports),
+    ] {
+        let (result, source_stake, destination_stake) = split_with(
+            CURRENT_EPOCH,
+            StakeStateV2::size_of(),
+            destination_lamports,
+            0,
+            split_lamports,
+        );
+        assert_eq!(result, Ok(()), "{destination_lamports}");
+        // the source gives up the full split either way
+        assert_eq!(source_stake, DELEGATED_STAKE - split_lamports);
+        assert_eq!(destination_stake, expected_stake, "{destination_lamports}");
+    }
+}
+
+#[test]
+fn test_split_whole_account_ignores_prefunding() {
+    // taking the whole source moves its delegation as it is, so prefunding
+    // the destination can't add to it
+    for destination_lamports in [0, STAKE_RENT_EXEMPT_RESERVE, 2 * STAKE_RENT_EXEMPT_RESERVE] {
+        let (result, source_stake, destination_stake) = split_with(
+            CURRENT_EPOCH,
+            StakeStateV2::size_of(),
+            destination_lamports,
+            0,
+            SOURCE_LAMPORTS,
+        );
+        assert_eq!(result, Ok(()), "{destination_lamports}");
+        assert_eq!(source_stake, 0);
+        assert_eq!(destination_stake, DELEGATED_STAKE, "{destination_lamports}");
+    }
+}
+
+#[test]
+fn test_split_carries_stake_into_destination() {
+    set_clock(&Clock {
+        epoch: CURRENT_EPOCH,
+        ..Clock::default()
+    });
+    set_stake_history(CURRENT_EPOCH, &[]);
+    set_default_rent();
+
+    // a source initialized under cheaper rent, with a lockup, credits and a
+    // flag for the destination to inherit
+    let stale_reserve = STAKE_RENT_EXEMPT_RESERVE / 2;
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.rent_exempt_reserve = stale_reserve.to_le_bytes();
+    meta.lockup.epoch = (CURRENT_EPOCH + 10).to_le_bytes();
+    meta.lockup.custodian = [6; 32];
+    let stake = Stake {
+        delegation: Delegation::new(&VOTER, DELEGATED_STAKE, 0u64.to_le_bytes()),
+        credits_observed: 1_250u64.to_le_bytes(),
+    };
+    let flags = StakeFlags::from_bits(1);
+    let split_lamports = DELEGATED_STAKE / 2;
+
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            SOURCE,
+            stale_reserve + DELEGATED_STAKE,
+            StakeStateV2::Stake(meta, stake, flags),
+        )
+        .into(),
+        stake_account(
+            DESTINATION,
+            STAKE_RENT_EXEMPT_RESERVE,
+            StakeStateV2::Uninitialized,
+        )
+        .into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    let mut data = vec![StakeInstruction::Split as u8];
+    data.extend_from_slice(&split_lamports.to_le_bytes());
+
+    with_accounts(&inputs, |accounts| {
+        assert_eq!(process(accounts, &data), Ok(()));
+
+        let mut remaining = stake;
+        remaining.delegation.stake = (DELEGATED_STAKE - split_lamports).to_le_bytes();
+        assert_stake_state_eq(
+            &get_stake_state(&accounts[0]).unwrap(),
+            &StakeStateV2::Stake(meta, remaining, flags),
+        );
+
+        // everything but the reserve, which is the destination's own
+        let mut destination_meta = meta;
+        destination_meta.rent_exempt_reserve = STAKE_RENT_EXEMPT_RESERVE.to_le_bytes();
+        let mut split_off = stake;
+        split_off.delegation.stake = split_lamports.to_le_bytes();
+        assert_stake_state_eq(
+            &get_stake_state(&accounts[1]).unwrap(),
+            &StakeStateV2::Stake(destination_meta, split_off, flags),
+        );
+    });
+}
+
+#[test]
+fn test_split_by_default_staker_needs_signature() {
+    set_stake_history(CURRENT_EPOCH, &[]);
+
+    // the signers are checked against only the accounts that signed, so a
+    // staker of all zeroes doesn't match the rest of the signer array
+    let meta = initialized_meta(Pubkey::default(), WITHDRAWER);
+    let stake = Stake {
+        delegation: Delegation::new(&VOTER, DELEGATED_STAKE, 0u64.to_le_bytes()),
+        ..Stake::default()
+    };
+    for source_state in [
+        StakeStateV2::Initialized(meta),
+        StakeStateV2::Stake(meta, stake, StakeFlags::empty()),
+    ] {
+        let inputs: Vec<Input> = vec![
+            stake_account(SOURCE, SOURCE_LAMPORTS, source_state).into(),
+            stake_account(
+                DESTINATION,
+                STAKE_RENT_EXEMPT_RESERVE,
+                StakeStateV2::Uninitialized,
+            )
+            .into(),
+            TestAccount::new(Pubkey::default(), Pubkey::default(), 0, vec![]).into(),
+        ];
+        assert_rejected(
+            &inputs,
+            &amount_data(StakeInstruction::Split, DELEGATED_STAKE / 2),
+            ProgramError::MissingRequiredSignature,
+            &format!("{source_state:?}"),
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Flags are set and cleared on the account's write view in place: the one
+//! flags byte changes, and every byte around it stays as it was.
+
+mod common;
+
+use common::*;
+use pinocchio::program_error::ProgramError;
+use pinocchio_stake_interface::state::layout::STAKE_FLAGS_OFFSET;
+use solana_pinocchio_starter::state::{
+    try_get_stake_state_mut, Delegation, Stake, StakeFlags, StakeStateMut, StakeStateV2,
+};
+
+const KEY: [u8; 32] = [1; 32];
+const FLAG: StakeFlags = StakeFlags::from_bits(0b0000_0001);
+
+fn delegated(flags: StakeFlags) -> StakeStateV2 {
+    let mut delegation = Delegation::new(&[3; 32], 1_000_000_000, 10u64.to_le_bytes());
+    delegation.set_deactivation_epoch(20);
+    let stake = Stake {
+        delegation,
+        credits_observed: u64::MAX.to_le_bytes(),
+    };
+    StakeStateV2::Stake(initialized_meta([1; 32], [2; 32]), stake, flags)
+}
+
+/// Runs `update` on the write view of an account in `state` whose trailing
+/// bytes are all set, and returns its data before and after.
+fn update(
+    state: StakeStateV2,
+    update: impl FnOnce(&mut StakeStateMut) -> Result<(), ProgramError>,
+) -> (Result<(), ProgramError>, Vec<u8>, Vec<u8>) {
+    let mut data = stake_state_data(state);
+    data[STAKE_FLAGS_OFFSET + 1..].fill(0xff);
+    let account = TestAccount::new(KEY, solana_pinocchio_starter::ID, 1, data.clone()).writable();
+    with_accounts(&[account.into()], |accounts| {
+        let result = {
+            let mut view = try_get_stake_state_mut(&accounts[0]).unwrap();
+            update(&mut view)
+        };
+        let after = accounts[0].try_borrow_data().unwrap().to_vec();
+        (result, data, after)
+    })
+}
+
+fn assert_only_flags_changed(before: &[u8], after: &[u8]) {
+    assert_eq!(after[..STAKE_FLAGS_OFFSET], before[..STAKE_FLAGS_OFFSET]);
+    assert_eq!(
+        after[STAKE_FLAGS_OFFSET + 1..],
+        before[STAKE_FLAGS_OFFSET + 1..]
+    );
+}
+
+#[test]
+fn test_set_flag() {
+    let (result, before, after) = update(delegated(StakeFlags::empty()), |view| {
+        assert_eq!(view.flags(), Some(StakeFlags::empty()));
+        view.set_flag(FLAG)?;
+        assert_eq!(view.flags(), Some(FLAG));
+        // the copied state agrees with the byte
+        assert!(matches!(**view, StakeStateV2::Stake(_, _, flags) if flags == FLAG));
+        Ok(())
+    });
+    assert_eq!(result, Ok(()));
+    assert_eq!(after[STAKE_FLAGS_OFFSET], FLAG.bits());
+    assert_only_flags_changed(&before, &after);
+}
+
+#[test]
+fn test_clear_flag_keeps_unknown_bits() {
+    let flags = StakeFlags::from_bits(0b1010_0001);
+    let (result, before, after) = update(delegated(flags), |view| {
+        view.clear_flag(FLAG)?;
+        // clearing a flag that isn't set changes nothing
+        view.clear_flag(FLAG)?;
+        assert_eq!(view.flags(), Some(StakeFlags::from_bits(0b1010_0000)));
+        Ok(())
+    });
+    assert_eq!(result, Ok(()));
+    assert_eq!(after[STAKE_FLAGS_OFFSET], 0b1010_0000);
+    assert_only_flags_changed(&before, &after);
+}
+
+#[test]
+fn test_flags_with_other_changes() {
+    // a flag set alongside a change to the state is kept when the state is
+    // written back
+    let (result, _, after) = update(delegated(StakeFlags::empty()), |view| {
+        view.set_flag(FLAG)?;
+        let StakeStateV2::Stake(_, stake, _) = &mut **view else {
+            unreachable!()
+        };
+        stake.delegation.set_deactivation_epoch(30);
+        Ok(())
+    });
+    assert_eq!(result, Ok(()));
+    let state = StakeStateV2::read_from(&after[..StakeStateV2::size_of()]).unwrap();
+    assert_eq!(state.delegation().unwrap().deactivation_epoch(), 30);
+    assert_eq!(after[STAKE_FLAGS_OFFSET], FLAG.bits());
+}
+
+#[test]
+fn test_flags_need_delegation() {
+    let state = StakeStateV2::Initialized(initialized_meta([1; 32], [2; 32]));
+    let (result, before, after) = update(state, |view| {
+        assert_eq!(view.flags(), None);
+        assert_eq!(view.clear_flag(FLAG), Err(ProgramError::InvalidAccountData));
+        view.set_flag(FLAG)
+    });
+    assert_eq!(result, Err(ProgramError::InvalidAccountData));
+    assert_eq!(after, before);
+}
@@ -0,0 +1,163 @@
+//! Full-size stake history sysvars, read from the gzip'd fixtures in
+//! `tests/fixtures`.
+
+mod common;
+
+use common::*;
+use pinocchio::pubkey::Pubkey;
+use solana_pinocchio_starter::{
+    consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    state::{
+        stake_history, Delegation, StakeHistory, StakeHistoryEntry, StakeHistoryGetEntry,
+        StakeHistorySysvar, MAX_ENTRIES,
+    },
+};
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+/// The epoch every fixture was captured at; its newest entry is the epoch
+/// before.
+const CURRENT_EPOCH: u64 = 700;
+
+const VOTER: Pubkey = [7; 32];
+
+fn entry(effective: u64, activating: u64, deactivating: u64) -> StakeHistoryEntry {
+    StakeHistoryEntry {
+        effective: effective.to_le_bytes(),
+        activating: activating.to_le_bytes(),
+        deactivating: deactivating.to_le_bytes(),
+    }
+}
+
+/// A cluster drifting around 400M SOL, with a few percent of it warming up
+/// or cooling down each epoch.
+fn steady_entries() -> Vec<(u64, StakeHistoryEntry)> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (CURRENT_EPOCH - MAX_ENTRIES as u64..CURRENT_EPOCH)
+        .map(|epoch| {
+            let effective = (390_000_000 + next() % 20_000_000) * LAMPORTS_PER_SOL;
+            let activating = (next() % 8_000_000) * LAMPORTS_PER_SOL;
+            let deactivating = (next() % 8_000_000) * LAMPORTS_PER_SOL;
+            (epoch, entry(effective, activating, deactivating))
+        })
+        .collect()
+}
+
+/// A small cluster that a large deposit at `SURGE_EPOCH` keeps warming up
+/// for many epochs.
+const SURGE_EPOCH: u64 = CURRENT_EPOCH - 20;
+const SURGE_STAKE: u64 = 500_000_000 * LAMPORTS_PER_SOL;
+const SURGE_CLUSTER_STAKE: u64 = 10_000_000 * LAMPORTS_PER_SOL;
+
+fn surge_entries() -> Vec<(u64, StakeHistoryEntry)> {
+    let delegation = Delegation::new(&VOTER, SURGE_STAKE, SURGE_EPOCH.to_le_bytes());
+    let mut history = StakeHistory::default();
+    for epoch in CURRENT_EPOCH - MAX_ENTRIES as u64..CURRENT_EPOCH {
+        let status = delegation.stake_activating_and_deactivating(
+            epoch.to_le_bytes(),
+            &history,
+            PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+        );
+        let effective = SURGE_CLUSTER_STAKE + u64::from_le_bytes(status.effective);
+        history.add(
+            epoch,
+            entry(effective, u64::from_le_bytes(status.activating), 0),
+        );
+    }
+    history.iter().cloned().collect()
+}
+
+fn install(data: Vec<u8>) -> StakeHistorySysvar {
+    // a full sysvar: the length prefix, then 32 bytes per entry
+    assert_eq!(data.len(), 8 + MAX_ENTRIES * 32);
+    set_sysvar(stake_history::ID, data);
+    StakeHistorySysvar::for_epoch(CURRENT_EPOCH)
+}
+
+// the stubs are process-wide, so the fixtures are checked one after the
+// other rather than in parallel tests
+#[test]
+fn test_full_size_fixtures() {
+    for (name, data, entries) in [
+        ("steady", fixture!("steady_stake_history"), steady_entries()),
+        ("surge", fixture!("surge_stake_history"), surge_entries()),
+    ] {
+        assert_eq!(data, stake_history_data(&entries), "{name} fixture is stale");
+        assert_no_current_epoch_entry(CURRENT_EPOCH, &entries);
+
+        let sysvar = install(data);
+        let mut in_memory = StakeHistory::default();
+        for (epoch, entry) in entries {
+            in_memory.add(epoch, entry);
+        }
+
+        // every epoch the sysvar holds, and the ones on either side of it
+        for epoch in CURRENT_EPOCH - MAX_ENTRIES as u64 - 1..=CURRENT_EPOCH {
+            assert_eq!(
+                sysvar.get_entry(epoch),
+                in_memory.get_entry(epoch),
+                "{name} epoch {epoch}"
+            );
+        }
+        assert!(sysvar.get_entry(CURRENT_EPOCH - MAX_ENTRIES as u64).is_some());
+        assert!(sysvar.get_entry(CURRENT_EPOCH - MAX_ENTRIES as u64 - 1).is_none());
+        assert!(assert_stake_history_reads_before(CURRENT_EPOCH) > 0);
+
+        // the current epoch and later have no record, so are never fetched
+        for epoch in CURRENT_EPOCH..CURRENT_EPOCH + 3 {
+            assert_eq!(sysvar.get_entry(epoch), None, "{name} epoch {epoch}");
+        }
+        assert_eq!(take_stake_history_reads(), vec![]);
+
+        if name == "surge" {
+            // twenty epochs in, the deposit is still warming up
+            let delegation = Delegation::new(&VOTER, SURGE_STAKE, SURGE_EPOCH.to_le_bytes());
+            let status = delegation.stake_activating_and_deactivating(
+                CURRENT_EPOCH.to_le_bytes(),
+                &sysvar,
+                PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+            );
+            assert_eq!(
+                status,
+                delegation.stake_activating_and_deactivating(
+                    CURRENT_EPOCH.to_le_bytes(),
+                    &in_memory,
+                    PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+                )
+            );
+            assert!(u64::from_le_bytes(status.activating) > 0);
+        }
+    }
+}
+
+/// Rewrites the fixtures from the generators above:
+///
+/// ```text
+/// cargo test --test stake_history_fixtures -- --ignored regenerate
+/// ```
+#[test]
+#[ignore = "writes tests/fixtures"]
+fn regenerate_fixtures() {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    for (name, entries) in [
+        ("steady_stake_history", steady_entries()),
+        ("surge_stake_history", surge_entries()),
+    ] {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&stake_history_data(&entries)).unwrap();
+        std::fs::write(
+            format!("{FIXTURES_DIR}/{name}.bin.gz"),
+            encoder.finish().unwrap(),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+//! Stake accounts whose `u32` state tag names no `StakeStateV2` variant must
+//! be rejected with `InvalidAccountData` by every processor, rather than
+//! having their bytes read as some other variant.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use solana_pinocchio_starter::{instruction::StakeInstruction, state::StakeStateV2};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+
+const LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + 1_000_000_000;
+
+/// Every unknown tag with a zero upper half, plus a few that only differ
+/// from a valid tag in their upper bytes.
+fn unknown_tags() -> impl Iterator<Item = u32> {
+    (4..=255).chain([0x0000_0100, 0x0100_0001, 0x8000_0002, u32::MAX])
+}
+
+/// An initialized stake account, with its tag replaced by `tag`.
+fn tagged_account(key: Pubkey, tag: u32) -> TestAccount {
+    let mut data = stake_state_data(StakeStateV2::Initialized(initialized_meta(
+        STAKER, WITHDRAWER,
+    )));
+    data[..4].copy_from_slice(&tag.to_le_bytes());
+    TestAccount::new(key, solana_pinocchio_starter::ID, LAMPORTS, data).writable()
+}
+
+#[test]
+fn test_withdraw_rejects_unknown_tag() {
+    for tag in unknown_tags() {
+        let inputs = vec![
+            tagged_account(SOURCE, tag).into(),
+            TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+                .writable()
+                .into(),
+            clock_account(&clock()).into(),
+            stake_history_account().into(),
+            signer(WITHDRAWER).into(),
+        ];
+        let data = amount_data(StakeInstruction::Withdraw, 1);
+        assert_rejected(
+            &inputs,
+            &data,
+            ProgramError::InvalidAccountData,
+            &format!("tag {tag:#x}"),
+        );
+    }
+}
+
+#[test]
+fn test_set_lockup_rejects_unknown_tag() {
+    for tag in unknown_tags() {
+        let inputs = vec![
+            tagged_account(SOURCE, tag).into(),
+            signer(WITHDRAWER).into(),
+        ];
+        let data = [StakeInstruction::SetLockup as u8, 0, 0, 0];
+        assert_rejected(
+            &inputs,
+            &data,
+            ProgramError::InvalidAccountData,
+            &format!("tag {tag:#x}"),
+        );
+    }
+}
+
+#[test]
+fn test_split_rejects_unknown_tag() {
+    for tag in unknown_tags() {
+        let data = amount_data(StakeInstruction::Split, 1_000);
+
+        let inputs = vec![
+            tagged_account(SOURCE, tag).into(),
+            stake_account(DESTINATION, 0, StakeStateV2::Uninitialized).into(),
+            signer(STAKER).into(),
+        ];
+        assert_rejected(
+            &inputs,
+            &data,
+            ProgramError::InvalidAccountData,
+            &format!("source tag {tag:#x}"),
+        );
+
+        let inputs = vec![
+            initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER).into(),
+            tagged_account(DESTINATION, tag).into(),
+            signer(STAKER).into(),
+        ];
+        assert_rejected(
+            &inputs,
+            &data,
+            ProgramError::InvalidAccountData,
+            &format!("destination tag {tag:#x}"),
+        );
+    }
+}
+
+#[test]
+fn test_move_lamports_rejects_unknown_tag() {
+    for tag in unknown_tags() {
+        let data = amount_data(StakeInstruction::MoveLamports, 1_000);
+
+        let inputs = vec![
+            tagged_account(SOURCE, tag).into(),
+            initialized_account(DESTINATION, LAMPORTS, STAKER, WITHDRAWER).into(),
+            signer(STAKER).into(),
+        ];
+        assert_rejected(
+            &inputs,
+            &data,
+            ProgramError::InvalidAccountData,
+            &format!("source tag {tag:#x}"),
+        );
+
+        let inputs = vec![
+            initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER).into(),
+            tagged_account(DESTINATION, tag).into(),
+            signer(STAKER).into(),
+        ];
+        assert_rejected(
+            &inputs,
+            &data,
+            ProgramError::InvalidAccountData,
+            &format!("destination tag {tag:#x}"),
+        );
+    }
+}
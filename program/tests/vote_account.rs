@@ -0,0 +1,189 @@
+//! Vote accounts must be validated before stake is delegated to them.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey};
+use solana_pinocchio_starter::{
+    consts::VOTE_PROGRAM_ID,
+    state::{get_vote_credits, validate_vote_account, VoteState},
+};
+
+const VOTE: Pubkey = [1; 32];
+const VOTE_LAMPORTS: u64 = 27_074_400;
+const V1_14_11_SIZE: usize = 3731;
+
+fn vote_account(owner: Pubkey, lamports: u64, data_len: usize) -> TestAccount {
+    TestAccount::new(VOTE, owner, lamports, vec![0; data_len])
+}
+
+fn validate(account: TestAccount) -> Result<(), ProgramError> {
+    with_accounts(&[account.into()], |accounts| {
+        validate_vote_account(&accounts[0])
+    })
+}
+
+fn credits(account: TestAccount) -> Result<u64, ProgramError> {
+    with_accounts(&[account.into()], |accounts| get_vote_credits(&accounts[0]))
+}
+
+#[test]
+fn test_funded_vote_account() {
+    assert_eq!(
+        validate(vote_account(
+            VOTE_PROGRAM_ID,
+            VOTE_LAMPORTS,
+            VoteState::size_of()
+        )),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_drained_vote_account() {
+    // withdrawn from earlier in the transaction, the data is still there
+    assert_eq!(
+        validate(vote_account(VOTE_PROGRAM_ID, 0, VoteState::size_of())),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_closed_vote_account() {
+    // as RPC may still return it: no lamports, no data, still owned by the
+    // vote program
+    assert_eq!(
+        validate(vote_account(VOTE_PROGRAM_ID, 0, 0)),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
+
+#[test]
+fn test_vote_account_of_any_size() {
+    // the length is for the vote state reader to judge, which rejects data
+    // too short to hold even the version
+    for data_len in [0, 1, VoteState::size_of() - 1, VoteState::size_of() + 1] {
+        let account = vote_account(VOTE_PROGRAM_ID, VOTE_LAMPORTS, data_len);
+        assert_eq!(validate(account.clone()), Ok(()), "{data_len} bytes");
+        if data_len < 4 {
+            assert_eq!(
+                credits(account),
+                Err(ProgramError::InvalidAccountData),
+                "{data_len} bytes"
+            );
+        }
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_unpadded_v1_14_11_vote_account() {
+    use solana_sdk::vote::state::{
+        VoteState as NativeVoteState, VoteState1_14_11, VoteStateVersions,
+    };
+
+    // accounts created before the current vote state are still sized for
+    // the 1.14.11 one, 31 bytes shorter
+    let native = NativeVoteState {
+        epoch_credits: vec![(7, 150, 100), (8, 180, 150)],
+        ..NativeVoteState::default()
+    };
+    let mut data = vec![0; V1_14_11_SIZE];
+    bincode::serialize_into(
+        &mut data[..],
+        &VoteStateVersions::V1_14_11(Box::new(VoteState1_14_11::from(native))),
+    )
+    .unwrap();
+    assert_eq!(
+        VoteState::size_of() - V1_14_11_SIZE,
+        31,
+        "one latency byte for each of the 31 lockouts"
+    );
+
+    let account = TestAccount::new(VOTE, VOTE_PROGRAM_ID, VOTE_LAMPORTS, data);
+    assert_eq!(validate(account.clone()), Ok(()));
+    assert_eq!(credits(account), Ok(180));
+}
+
+#[test]
+fn test_vote_account_not_owned_by_vote_program() {
+    assert_eq!(
+        validate(vote_account(
+            Pubkey::default(),
+            VOTE_LAMPORTS,
+            VoteState::size_of()
+        )),
+        Err(ProgramError::IncorrectProgramId)
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_vote_credits_of_every_version() {
+    use solana_sdk::vote::state::{
+        LandedVote, Lockout, VoteState as NativeVoteState, VoteState1_14_11, VoteStateVersions,
+    };
+
+    let mut native = NativeVoteState {
+        root_slot: Some(5),
+        epoch_credits: vec![(7, 150, 100), (8, 180, 150)],
+        ..NativeVoteState::default()
+    };
+    native.votes.push_back(LandedVote {
+        latency: 2,
+        lockout: Lockout::new(9),
+    });
+
+    // `VoteState0_23_5` isn't public, so its layout is built by hand and
+    // checked by deserializing it natively
+    let mut v0_23_5 = vec![0; VoteState::size_of()];
+    let mut offset = 4 + 32 + 32 + 8 + 32 * 56 + 8 + 32 + 1;
+    for field in [
+        &1u64.to_le_bytes()[..],
+        &9u64.to_le_bytes(),
+        &1u32.to_le_bytes(),
+        &[1],
+    ] {
+        v0_23_5[offset..offset + field.len()].copy_from_slice(field);
+        offset += field.len();
+    }
+    offset += 8; // root slot
+    for value in [2, 7, 150, 100, 8, 180, 150] {
+        v0_23_5[offset..offset + 8].copy_from_slice(&(value as u64).to_le_bytes());
+        offset += 8;
+    }
+
+    for data in [
+        vote_account_data(&VoteStateVersions::new_current(native.clone())),
+        vote_account_data(&VoteStateVersions::V1_14_11(Box::new(
+            VoteState1_14_11::from(native.clone()),
+        ))),
+        v0_23_5,
+    ] {
+        let versions: VoteStateVersions = bincode::deserialize(&data).unwrap();
+        assert_eq!(versions.convert_to_current().credits(), 180);
+        assert_eq!(VoteState::credits_from_bytes(&data), Ok(180));
+    }
+
+    // and none earned yet
+    let data = vote_account_data(&VoteStateVersions::new_current(NativeVoteState::default()));
+    assert_eq!(VoteState::credits_from_bytes(&data), Ok(0));
+
+    // an unknown version, or lengths running past the account
+    let mut unknown = data.clone();
+    unknown[..4].copy_from_slice(&3u32.to_le_bytes());
+    assert_eq!(
+        VoteState::credits_from_bytes(&unknown),
+        Err(ProgramError::InvalidAccountData)
+    );
+    let mut votes_overrun = data.clone();
+    votes_overrun[4 + 32 + 32 + 1..][..8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert_eq!(
+        VoteState::credits_from_bytes(&votes_overrun),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert_eq!(
+        VoteState::credits_from_bytes(&data[..100]),
+        Err(ProgramError::InvalidAccountData)
+    );
+}
@@ -0,0 +1,255 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    consts::NEW_WARMUP_COOLDOWN_RATE,
+    error::StakeError,
+    instruction::StakeInstruction,
+    state::{get_stake_state, Delegation, Stake, StakeFlags, StakeHistoryEntry, StakeStateV2},
+};
+
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+
+// effective stake of the rest of the cluster, which stays constant
+const CLUSTER_STAKE: u64 = 10_000_000 * LAMPORTS_PER_SOL;
+// large relative to the cluster so warmup takes many epochs
+const DELEGATED_STAKE: u64 = 4_000_000 * LAMPORTS_PER_SOL;
+// past 255 so that byte-wise epoch comparisons would be caught
+const ACTIVATION_EPOCH: u64 = 300;
+const DEACTIVATION_EPOCH: u64 = 300;
+const RENT_EXEMPT_RESERVE: u64 = STAKE_RENT_EXEMPT_RESERVE;
+const STAKE_LAMPORTS: u64 = RENT_EXEMPT_RESERVE + DELEGATED_STAKE;
+
+const STAKE: Pubkey = [1; 32];
+const PROBE: Pubkey = [2; 32];
+const VOTE: Pubkey = [3; 32];
+const STAKER: Pubkey = [4; 32];
+const WITHDRAWER: Pubkey = [5; 32];
+const STAKE_CONFIG: Pubkey = [6; 32];
+const DESTINATION: Pubkey = [7; 32];
+
+fn entry(effective: u64, activating: u64, deactivating: u64) -> StakeHistoryEntry {
+    StakeHistoryEntry {
+        effective: effective.to_le_bytes(),
+        activating: activating.to_le_bytes(),
+        deactivating: deactivating.to_le_bytes(),
+    }
+}
+
+/// Delegates an Initialized account of `DELEGATED_STAKE` through
+/// `DelegateStake` at `ACTIVATION_EPOCH`, returning the state it leaves.
+fn delegate() -> StakeStateV2 {
+    let clock = clock_at(ACTIVATION_EPOCH);
+    set_clock(&clock);
+    set_default_rent();
+
+    let instruction = client::delegate_stake(&STAKE, &STAKER, &VOTE);
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            STAKE,
+            STAKE_LAMPORTS,
+            StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+        )
+        .into(),
+        vote_account(VOTE, &[]).into(),
+        clock_account(&clock).into(),
+        stake_history_account().into(),
+        TestAccount::new(STAKE_CONFIG, Pubkey::default(), 0, vec![]).into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        assert_eq!(process(accounts, &instruction.data), Ok(()));
+        get_stake_state(&accounts[0]).unwrap()
+    })
+}
+
+/// Merges an Initialized account into `stake_state` at `epoch`. Merge tells
+/// apart the three phases of a warmup: an account with nothing effective
+/// takes the lamports, one with some stake still activating is transient,
+/// and a fully active one can't take an inactive source.
+fn merge_inactive_into(stake_state: StakeStateV2, epoch: u64) -> ProgramResult {
+    let clock = clock_at(epoch);
+    set_clock(&clock);
+
+    let instruction = client::merge(&STAKE, &PROBE, &STAKER);
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, STAKE_LAMPORTS, stake_state).into(),
+        stake_account(
+            PROBE,
+            RENT_EXEMPT_RESERVE,
+            StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+        )
+        .into(),
+        clock_account(&clock).into(),
+        stake_history_account().into(),
+        TestAccount::new(STAKER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| process(accounts, &instruction.data))
+}
+
+#[test]
+fn test_multi_epoch_warmup() {
+    let mut stake_history: Vec<(u64, StakeHistoryEntry)> = (ACTIVATION_EPOCH - 10
+        ..ACTIVATION_EPOCH)
+        .map(|epoch| (epoch, entry(CLUSTER_STAKE, 0, 0)))
+        .collect();
+    set_stake_history(ACTIVATION_EPOCH, &stake_history);
+
+    let stake_state = delegate();
+    let StakeStateV2::Stake(_, stake, _) = stake_state else {
+        panic!("not delegated: {stake_state:?}");
+    };
+    assert_eq!(
+        stake.delegation,
+        Delegation::new(&VOTE, DELEGATED_STAKE, ACTIVATION_EPOCH.to_le_bytes())
+    );
+
+    // the delegation is the only activating stake in the cluster, so it is
+    // entitled to all newly effective cluster stake each epoch
+    let mut expected_effective = 0;
+    let mut epoch = ACTIVATION_EPOCH;
+    loop {
+        set_stake_history(epoch, &stake_history);
+        let expected = match expected_effective {
+            0 => Ok(()),
+            DELEGATED_STAKE => Err(StakeError::MergeMismatch.into()),
+            _ => Err(StakeError::MergeTransientStake.into()),
+        };
+        assert_eq!(
+            merge_inactive_into(stake_state, epoch),
+            expected,
+            "epoch {epoch}, {expected_effective} effective"
+        );
+
+        if expected_effective == DELEGATED_STAKE {
+            break;
+        }
+
+        // close out the epoch with what the cluster saw during it
+        let cluster_effective = CLUSTER_STAKE + expected_effective;
+        stake_history.push((
+            epoch,
+            entry(cluster_effective, DELEGATED_STAKE - expected_effective, 0),
+        ));
+
+        let newly_effective = (cluster_effective as f64 * NEW_WARMUP_COOLDOWN_RATE) as u64;
+        expected_effective = (expected_effective + newly_effective).min(DELEGATED_STAKE);
+        epoch += 1;
+    }
+
+    // ~40% of the cluster at 9% growth per epoch
+    assert_eq!(epoch - ACTIVATION_EPOCH, 4);
+
+    // once fully active it stays that way
+    stake_history.push((epoch, entry(CLUSTER_STAKE + DELEGATED_STAKE, 0, 0)));
+    set_stake_history(epoch + 5, &stake_history);
+    assert_eq!(
+        merge_inactive_into(stake_state, epoch + 5),
+        Err(StakeError::MergeMismatch.into())
+    );
+}
+
+/// Withdraws `lamports` from an account holding `stake_state` and
+/// `STAKE_LAMPORTS` at `epoch`, returning the result and the state and
+/// lamports the account is left with.
+fn withdraw(
+    stake_state: StakeStateV2,
+    epoch: u64,
+    lamports: u64,
+) -> (ProgramResult, StakeStateV2, u64) {
+    let clock = clock_at(epoch);
+    set_clock(&clock);
+
+    let mut data = vec![StakeInstruction::Withdraw as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, STAKE_LAMPORTS, stake_state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &data);
+        (
+            result,
+            get_stake_state(&accounts[0]).unwrap(),
+            accounts[0].lamports(),
+        )
+    })
+}
+
+#[test]
+fn test_multi_epoch_cooldown_and_withdraw() {
+    let meta = initialized_meta(STAKER, WITHDRAWER);
+    let mut stake = Stake {
+        // no history for the activation epoch, so the stake is fully active
+        delegation: Delegation::new(&VOTE, DELEGATED_STAKE, 1u64.to_le_bytes()),
+        ..Stake::default()
+    };
+
+    let mut stake_history: Vec<(u64, StakeHistoryEntry)> = (DEACTIVATION_EPOCH - 10
+        ..DEACTIVATION_EPOCH)
+        .map(|epoch| (epoch, entry(CLUSTER_STAKE + DELEGATED_STAKE, 0, 0)))
+        .collect();
+    set_stake_history(DEACTIVATION_EPOCH, &stake_history);
+
+    // nothing is withdrawable while the stake is active
+    let active = StakeStateV2::Stake(meta, stake, StakeFlags::empty());
+    let (result, _, _) = withdraw(active, DEACTIVATION_EPOCH - 1, 1);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+
+    // Deactivate isn't dispatched yet, so the account is deactivated as the
+    // instruction would leave it
+    stake.deactivate(DEACTIVATION_EPOCH.to_le_bytes()).unwrap();
+    let deactivating = StakeStateV2::Stake(meta, stake, StakeFlags::empty());
+
+    // the delegation is the only deactivating stake in the cluster
+    let mut expected_effective = DELEGATED_STAKE;
+    let mut epoch = DEACTIVATION_EPOCH;
+    loop {
+        set_stake_history(epoch, &stake_history);
+
+        // the cooled portion can be withdrawn, the still effective remainder can't
+        let cooled = DELEGATED_STAKE - expected_effective;
+        let (result, state, lamports) = withdraw(deactivating, epoch, cooled);
+        assert_eq!(result, Ok(()), "epoch {epoch}");
+        assert_stake_state_eq(&state, &deactivating);
+        assert_eq!(lamports, STAKE_LAMPORTS - cooled);
+        let (result, _, _) = withdraw(deactivating, epoch, cooled + 1);
+        assert_eq!(
+            result,
+            Err(ProgramError::InsufficientFunds),
+            "epoch {epoch}, {expected_effective} effective"
+        );
+
+        let (result, state, lamports) = withdraw(deactivating, epoch, STAKE_LAMPORTS);
+        if expected_effective == 0 {
+            // nothing left staked, the account can be closed
+            assert_eq!(result, Ok(()));
+            assert_stake_state_eq(&state, &StakeStateV2::Uninitialized);
+            assert_eq!(lamports, 0);
+            break;
+        }
+        assert_eq!(result, Err(ProgramError::InsufficientFunds));
+
+        let cluster_effective = CLUSTER_STAKE + expected_effective;
+        stake_history.push((epoch, entry(cluster_effective, 0, expected_effective)));
+
+        let newly_not_effective = (cluster_effective as f64 * NEW_WARMUP_COOLDOWN_RATE) as u64;
+        expected_effective = expected_effective.saturating_sub(newly_not_effective);
+        epoch += 1;
+    }
+
+    assert_eq!(epoch - DEACTIVATION_EPOCH, 4);
+}
@@ -0,0 +1,574 @@
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use solana_pinocchio_starter::{
+    consts::PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    error::StakeError,
+    instruction::{process_withdraw_with, StakeInstruction},
+    state::{
+        Delegation, Lockup, Stake, StakeFlags, StakeHistory, StakeHistoryData, StakeHistoryEntry,
+        StakeStateV2,
+    },
+};
+
+const STAKE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const CUSTODIAN: Pubkey = [5; 32];
+
+const STAKE_LAMPORTS: u64 = STAKE_RENT_EXEMPT_RESERVE + 1_000_000_000;
+
+const VOTER: Pubkey = [6; 32];
+const LAMPORTS_PER_SOL: u64 = 1_000_000_000;
+// effective stake of the rest of the cluster, which stays constant
+const CLUSTER_STAKE: u64 = 10_000_000 * LAMPORTS_PER_SOL;
+// large relative to the cluster so cooldown takes many epochs
+const DELEGATED_STAKE: u64 = 4_000_000 * LAMPORTS_PER_SOL;
+const DEACTIVATION_EPOCH: u64 = CURRENT_EPOCH - 3;
+// rewards paid out after deactivation, never delegated
+const UNDELEGATED_LAMPORTS: u64 = 1_000_000_000;
+const COOLING_STAKE_LAMPORTS: u64 =
+    STAKE_RENT_EXEMPT_RESERVE + DELEGATED_STAKE + UNDELEGATED_LAMPORTS;
+
+/// An initialized stake account whose lockup is in force for another epoch.
+fn locked_stake_account(custodian: Pubkey) -> TestAccount {
+    let mut meta = initialized_meta(STAKER, WITHDRAWER);
+    meta.lockup = Lockup {
+        unix_timestamp: 0i64.to_le_bytes(),
+        epoch: (CURRENT_EPOCH + 1).to_le_bytes(),
+        custodian,
+    };
+    stake_account(STAKE, STAKE_LAMPORTS, StakeStateV2::Initialized(meta))
+}
+
+fn withdraw(custodian: Pubkey, extra: Vec<Input>, lamports: u64) -> (ProgramResult, u64, u64) {
+    let mut inputs: Vec<Input> = vec![
+        locked_stake_account(custodian).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    inputs.extend(extra);
+
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &withdraw_data(lamports));
+        (result, accounts[0].lamports(), accounts[1].lamports())
+    })
+}
+
+fn withdraw_data(lamports: u64) -> Vec<u8> {
+    let mut data = vec![StakeInstruction::Withdraw as u8];
+    data.extend_from_slice(&lamports.to_le_bytes());
+    data
+}
+
+#[test]
+fn test_withdraw_locked_without_custodian() {
+    let (result, _, _) = withdraw(CUSTODIAN, vec![], 1);
+    assert_eq!(result, Err(StakeError::LockupInForce.into()));
+}
+
+#[test]
+fn test_withdraw_locked_with_distinct_custodian() {
+    let custodian = TestAccount::new(CUSTODIAN, Pubkey::default(), 0, vec![]).signer();
+    let (result, stake_lamports, destination_lamports) =
+        withdraw(CUSTODIAN, vec![custodian.into()], 1);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, STAKE_LAMPORTS - 1);
+    assert_eq!(destination_lamports, 1);
+}
+
+#[test]
+fn test_withdraw_locked_with_unsigned_custodian() {
+    let custodian = TestAccount::new(CUSTODIAN, Pubkey::default(), 0, vec![]);
+    let (result, _, _) = withdraw(CUSTODIAN, vec![custodian.into()], 1);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+
+    // native asks any account passed as custodian to sign, before it looks
+    // at the lockup, so a stranger's fails the same way rather than with
+    // `LockupInForce` as no custodian does
+    let stranger = TestAccount::new([9; 32], Pubkey::default(), 0, vec![]);
+    let (result, stake_lamports, _) = withdraw(CUSTODIAN, vec![stranger.into()], 1);
+    assert_eq!(result, Err(ProgramError::MissingRequiredSignature));
+    assert_eq!(stake_lamports, STAKE_LAMPORTS);
+}
+
+#[test]
+fn test_withdraw_locked_with_withdrawer_as_custodian() {
+    // the withdrawer is also the custodian, passed again in the custodian slot
+    let (result, stake_lamports, destination_lamports) =
+        withdraw(WITHDRAWER, vec![Input::Duplicate(4)], 1);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, STAKE_LAMPORTS - 1);
+    assert_eq!(destination_lamports, 1);
+
+    // a full withdrawal closes the account in the same way
+    let (result, stake_lamports, destination_lamports) =
+        withdraw(WITHDRAWER, vec![Input::Duplicate(4)], STAKE_LAMPORTS);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, 0);
+    assert_eq!(destination_lamports, STAKE_LAMPORTS);
+}
+
+#[test]
+fn test_withdraw_locked_with_withdrawer_as_custodian_not_passed() {
+    // signing as withdrawer alone does not lift the lockup, the custodian
+    // account has to be supplied even when it is the same key
+    let (result, _, _) = withdraw(WITHDRAWER, vec![], 1);
+    assert_eq!(result, Err(StakeError::LockupInForce.into()));
+}
+
+fn cooling_delegation() -> Delegation {
+    let mut delegation = Delegation::new(&VOTER, DELEGATED_STAKE, 0u64.to_le_bytes());
+    delegation.set_deactivation_epoch(DEACTIVATION_EPOCH);
+    delegation
+}
+
+/// History for every epoch before the current one, in which the delegation
+/// is the only stake cooling down. Returns it along with the delegation's
+/// effective stake in the current epoch.
+fn cooldown_history() -> (Vec<(u64, StakeHistoryEntry)>, u64) {
+    let delegation = cooling_delegation();
+    let mut stake_history = StakeHistory::default();
+    let mut entries = vec![];
+    let effective = |stake_history: &StakeHistory, epoch: u64| {
+        delegation.stake(
+            epoch.to_le_bytes(),
+            stake_history,
+            PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+        )
+    };
+
+    for epoch in CURRENT_EPOCH - 20..CURRENT_EPOCH {
+        let (effective, deactivating) = if epoch < DEACTIVATION_EPOCH {
+            (DELEGATED_STAKE, 0)
+        } else {
+            let effective = effective(&stake_history, epoch);
+            (effective, effective)
+        };
+        let entry = StakeHistoryEntry {
+            effective: (CLUSTER_STAKE + effective).to_le_bytes(),
+            activating: 0u64.to_le_bytes(),
+            deactivating: deactivating.to_le_bytes(),
+        };
+        stake_history.add(epoch, entry.clone());
+        entries.push((epoch, entry));
+    }
+
+    let effective = effective(&stake_history, CURRENT_EPOCH);
+    (entries, effective)
+}
+
+/// Lamports of the cooling stake that are neither reserve nor effective stake.
+fn free_lamports() -> u64 {
+    let (_, effective) = cooldown_history();
+    assert!(0 < effective && effective < DELEGATED_STAKE);
+    COOLING_STAKE_LAMPORTS - STAKE_RENT_EXEMPT_RESERVE - effective
+}
+
+/// Withdraws from a stake that is partway through cooldown, installing the
+/// stake history the processor reads. Returns the result along with the
+/// lamports of the stake and destination accounts afterwards.
+fn withdraw_cooling(lamports: u64) -> (ProgramResult, u64, u64) {
+    let (entries, _) = cooldown_history();
+    set_stake_history(CURRENT_EPOCH, &entries);
+    take_stake_history_reads();
+
+    let stake = Stake {
+        delegation: cooling_delegation(),
+        ..Stake::default()
+    };
+    let state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        stake,
+        StakeFlags::empty(),
+    );
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, COOLING_STAKE_LAMPORTS, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    let (result, stake_lamports, destination_lamports) = with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &withdraw_data(lamports));
+        (result, accounts[0].lamports(), accounts[1].lamports())
+    });
+    // the cooldown walks the history up to, but never into, the current epoch
+    assert!(assert_stake_history_reads_before(CURRENT_EPOCH) > 0);
+    (result, stake_lamports, destination_lamports)
+}
+
+#[test]
+#[should_panic(expected = "at or after the current epoch")]
+fn test_stake_history_with_current_epoch_entry() {
+    let (mut entries, _) = cooldown_history();
+    entries.push((CURRENT_EPOCH, entries.last().unwrap().1.clone()));
+    assert_no_current_epoch_entry(CURRENT_EPOCH, &entries);
+}
+
+#[test]
+fn test_withdraw_exactly_free_lamports() {
+    let free_lamports = free_lamports();
+
+    let (result, stake_lamports, destination_lamports) = withdraw_cooling(free_lamports);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, COOLING_STAKE_LAMPORTS - free_lamports);
+    assert_eq!(destination_lamports, free_lamports);
+}
+
+#[test]
+fn test_withdraw_one_less_than_free_lamports() {
+    let free_lamports = free_lamports();
+
+    let (result, stake_lamports, destination_lamports) = withdraw_cooling(free_lamports - 1);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, COOLING_STAKE_LAMPORTS - free_lamports + 1);
+    assert_eq!(destination_lamports, free_lamports - 1);
+}
+
+#[test]
+fn test_withdraw_one_more_than_free_lamports() {
+    let free_lamports = free_lamports();
+
+    // would dip into the rent exempt reserve or the still effective stake
+    let (result, stake_lamports, destination_lamports) = withdraw_cooling(free_lamports + 1);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    assert_eq!(stake_lamports, COOLING_STAKE_LAMPORTS);
+    assert_eq!(destination_lamports, 0);
+
+    // nor can the account be closed while stake is still effective
+    let (result, _, _) = withdraw_cooling(COOLING_STAKE_LAMPORTS);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+}
+
+#[test]
+fn test_withdraw_with_injected_stake_history() {
+    let (entries, _) = cooldown_history();
+    let mut stake_history = StakeHistory::default();
+    for (epoch, entry) in entries {
+        stake_history.add(epoch, entry);
+    }
+    let free_lamports = free_lamports();
+    set_clock(&clock());
+
+    let stake = Stake {
+        delegation: cooling_delegation(),
+        ..Stake::default()
+    };
+    let state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        stake,
+        StakeFlags::empty(),
+    );
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, COOLING_STAKE_LAMPORTS, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    take_stake_history_reads();
+    with_accounts(&inputs, |accounts| {
+        // the in-memory history holds stake back just as the sysvar does
+        assert_eq!(
+            process_withdraw_with(accounts, free_lamports + 1, &stake_history),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(
+            process_withdraw_with(accounts, free_lamports, &stake_history),
+            Ok(())
+        );
+        // and without history to walk, the cooldown is over
+        assert_eq!(
+            process_withdraw_with(
+                accounts,
+                COOLING_STAKE_LAMPORTS - free_lamports,
+                &StakeHistory::default()
+            ),
+            Ok(())
+        );
+    });
+    assert_eq!(take_stake_history_reads(), vec![]);
+}
+
+#[test]
+fn test_withdraw_with_stake_history_account_data() {
+    let (entries, _) = cooldown_history();
+    let data = stake_history_data(&entries);
+    let stake_history = StakeHistoryData::new(&data).unwrap();
+    let free_lamports = free_lamports();
+    set_clock(&clock());
+
+    let stake = Stake {
+        delegation: cooling_delegation(),
+        ..Stake::default()
+    };
+    let state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        stake,
+        StakeFlags::empty(),
+    );
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, COOLING_STAKE_LAMPORTS, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    take_stake_history_reads();
+    with_accounts(&inputs, |accounts| {
+        // the account bytes hold stake back just as the sysvar does
+        assert_eq!(
+            process_withdraw_with(accounts, free_lamports + 1, &stake_history),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(
+            process_withdraw_with(accounts, free_lamports, &stake_history),
+            Ok(())
+        );
+    });
+    assert_eq!(take_stake_history_reads(), vec![]);
+}
+
+#[test]
+fn test_withdraw_reads_stake_history_account() {
+    // an old client's stake history account, with the sysvar's bytes in it
+    let (entries, _) = cooldown_history();
+    let mut stake_history_account = stake_history_account();
+    stake_history_account.data = stake_history_data(&entries);
+    let free_lamports = free_lamports();
+    set_clock(&clock());
+
+    let state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        Stake {
+            delegation: cooling_delegation(),
+            ..Stake::default()
+        },
+        StakeFlags::empty(),
+    );
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, COOLING_STAKE_LAMPORTS, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account.into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    take_stake_history_reads();
+    with_accounts(&inputs, |accounts| {
+        assert_eq!(
+            process(accounts, &withdraw_data(free_lamports + 1)),
+            Err(ProgramError::InsufficientFunds)
+        );
+        assert_eq!(process(accounts, &withdraw_data(free_lamports)), Ok(()));
+    });
+    // served from the account, without a syscall
+    assert_eq!(take_stake_history_reads(), vec![]);
+}
+
+const OTHER_PROGRAM: Pubkey = [7; 32];
+// well short of rent exemption for any data length
+const DUST: u64 = 1;
+
+/// Withdraws `lamports` from an unlocked initialized stake account into
+/// `destination`, returning both balances and the destination's data.
+fn withdraw_to(destination: Input, lamports: u64) -> (ProgramResult, u64, u64, Vec<u8>) {
+    let inputs: Vec<Input> = vec![
+        stake_account(
+            STAKE,
+            STAKE_LAMPORTS,
+            StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+        )
+        .into(),
+        destination,
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+
+    with_accounts(&inputs, |accounts| {
+        let result = process(accounts, &withdraw_data(lamports));
+        let data = accounts[1].try_borrow_data().unwrap().to_vec();
+        (result, accounts[0].lamports(), accounts[1].lamports(), data)
+    })
+}
+
+#[test]
+fn test_partial_withdraw_regardless_of_destination_balance() {
+    let lamports = STAKE_LAMPORTS - STAKE_RENT_EXEMPT_RESERVE;
+    for balance in [0, DUST, STAKE_RENT_EXEMPT_RESERVE, u64::MAX - lamports] {
+        let destination = TestAccount::new(DESTINATION, Pubkey::default(), balance, vec![]);
+        let (result, stake_lamports, destination_lamports, _) =
+            withdraw_to(destination.writable().into(), lamports);
+        assert_eq!(result, Ok(()), "destination balance {balance}");
+        // the stake account keeps exactly its reserve
+        assert_eq!(stake_lamports, STAKE_RENT_EXEMPT_RESERVE);
+        assert_eq!(destination_lamports, balance + lamports);
+    }
+
+    // one more would leave the stake account below its reserve, whoever
+    // receives it
+    let destination = TestAccount::new(DESTINATION, Pubkey::default(), DUST, vec![]);
+    let (result, stake_lamports, destination_lamports, _) =
+        withdraw_to(destination.writable().into(), lamports + 1);
+    assert_eq!(result, Err(ProgramError::InsufficientFunds));
+    assert_eq!(
+        (stake_lamports, destination_lamports),
+        (STAKE_LAMPORTS, DUST)
+    );
+}
+
+#[test]
+fn test_withdraw_destination_overflow() {
+    let destination = TestAccount::new(DESTINATION, Pubkey::default(), u64::MAX, vec![]);
+    let (result, stake_lamports, destination_lamports, _) =
+        withdraw_to(destination.writable().into(), 1);
+    assert_eq!(result, Err(ProgramError::ArithmeticOverflow));
+    // nothing is debited for a credit that can't be made
+    assert_eq!(stake_lamports, STAKE_LAMPORTS);
+    assert_eq!(destination_lamports, u64::MAX);
+}
+
+#[test]
+fn test_withdraw_to_program_owned_destination() {
+    // native doesn't look at the destination's owner or data, and neither are
+    // touched
+    let data = vec![0xab; 64];
+    let destination = TestAccount::new(DESTINATION, OTHER_PROGRAM, DUST, data.clone());
+    let (result, stake_lamports, destination_lamports, destination_data) =
+        withdraw_to(destination.writable().into(), 1_000);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, STAKE_LAMPORTS - 1_000);
+    assert_eq!(destination_lamports, DUST + 1_000);
+    assert_eq!(destination_data, data);
+
+    // another stake account, whatever its state
+    let destination = stake_account(
+        DESTINATION,
+        STAKE_RENT_EXEMPT_RESERVE,
+        StakeStateV2::Initialized(initialized_meta(STAKER, WITHDRAWER)),
+    );
+    let data = destination.data.clone();
+    let (result, _, destination_lamports, destination_data) =
+        withdraw_to(destination.into(), 1_000);
+    assert_eq!(result, Ok(()));
+    assert_eq!(destination_lamports, STAKE_RENT_EXEMPT_RESERVE + 1_000);
+    assert_eq!(destination_data, data);
+}
+
+#[test]
+fn test_withdraw_to_source() {
+    // the lamports leave and come straight back
+    let (result, stake_lamports, destination_lamports, _) = withdraw_to(Input::Duplicate(0), 1_000);
+    assert_eq!(result, Ok(()));
+    assert_eq!(
+        (stake_lamports, destination_lamports),
+        (STAKE_LAMPORTS, STAKE_LAMPORTS)
+    );
+
+    // a full withdrawal still deinitializes the account, as native does, only
+    // the tag is written
+    let (result, stake_lamports, _, data) = withdraw_to(Input::Duplicate(0), STAKE_LAMPORTS);
+    assert_eq!(result, Ok(()));
+    assert_eq!(stake_lamports, STAKE_LAMPORTS);
+    assert_eq!(data[..4], [0; 4]);
+}
+
+/// Withdraws `lamports` from a stake delegated in `activation_epoch`, with
+/// the cluster warming up only part of it in its first epoch. The history is
+/// passed in, not installed, as the cooldown tests share the sysvar.
+fn withdraw_activating(activation_epoch: u64, lamports: u64) -> ProgramResult {
+    let mut stake_history = StakeHistory::default();
+    stake_history.add(
+        activation_epoch,
+        StakeHistoryEntry {
+            effective: CLUSTER_STAKE.to_le_bytes(),
+            activating: DELEGATED_STAKE.to_le_bytes(),
+            ..StakeHistoryEntry::default()
+        },
+    );
+    let delegation = Delegation::new(&VOTER, DELEGATED_STAKE, activation_epoch.to_le_bytes());
+    let status = delegation.stake_activating_and_deactivating(
+        CURRENT_EPOCH.to_le_bytes(),
+        &stake_history,
+        PERPETUAL_NEW_WARMUP_COOLDOWN_RATE_EPOCH,
+    );
+    assert!(u64::from_le_bytes(status.activating) > 0);
+    assert!(u64::from_le_bytes(status.effective) < DELEGATED_STAKE);
+
+    let state = StakeStateV2::Stake(
+        initialized_meta(STAKER, WITHDRAWER),
+        Stake {
+            delegation,
+            ..Stake::default()
+        },
+        StakeFlags::empty(),
+    );
+    let inputs: Vec<Input> = vec![
+        stake_account(STAKE, COOLING_STAKE_LAMPORTS, state).into(),
+        TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![])
+            .writable()
+            .into(),
+        clock_account(&clock()).into(),
+        stake_history_account().into(),
+        TestAccount::new(WITHDRAWER, Pubkey::default(), 0, vec![])
+            .signer()
+            .into(),
+    ];
+    with_accounts(&inputs, |accounts| {
+        process_withdraw_with(accounts, lamports, &stake_history)
+    })
+}
+
+#[test]
+fn test_withdraw_activating_stake() {
+    // in its activation epoch nothing is effective yet, the epoch after only
+    // part of it is; either way all of the delegation is held back
+    for activation_epoch in [CURRENT_EPOCH, CURRENT_EPOCH - 1] {
+        assert_eq!(
+            withdraw_activating(activation_epoch, UNDELEGATED_LAMPORTS),
+            Ok(()),
+            "{activation_epoch}"
+        );
+        assert_eq!(
+            withdraw_activating(activation_epoch, UNDELEGATED_LAMPORTS + 1),
+            Err(ProgramError::InsufficientFunds),
+            "{activation_epoch}"
+        );
+        assert_eq!(
+            withdraw_activating(activation_epoch, COOLING_STAKE_LAMPORTS),
+            Err(ProgramError::InsufficientFunds),
+            "{activation_epoch}"
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! Every instruction that reads a stake account, run with that account in
+//! each `StakeStateV2` variant, must succeed or fail exactly as native does.
+//! Variants an instruction doesn't take are rejected with the error native
+//! returns, `InvalidAccountData` but for a source native lets its own key
+//! withdraw or split while Uninitialized, and no lamports move.
+
+mod common;
+
+use common::*;
+use pinocchio::{program_error::ProgramError, pubkey::Pubkey, ProgramResult};
+use pinocchio_stake_interface::client;
+use solana_pinocchio_starter::{
+    consts::LAMPORTS_PER_SOL,
+    error::StakeError,
+    helpers::create_with_seed,
+    instruction::StakeInstruction,
+    state::{Authorized, Delegation, Lockup, Stake, StakeAuthorize, StakeFlags, StakeStateV2},
+};
+
+const SOURCE: Pubkey = [1; 32];
+const DESTINATION: Pubkey = [2; 32];
+const STAKER: Pubkey = [3; 32];
+const WITHDRAWER: Pubkey = [4; 32];
+const BASE: Pubkey = [5; 32];
+const SEED_OWNER: Pubkey = [6; 32];
+const VOTER: Pubkey = [7; 32];
+const NEW_AUTHORITY: Pubkey = [9; 32];
+const STAKE_CONFIG: Pubkey = [10; 32];
+
+const SEED: &str = "wrong-state";
+const LAMPORTS: u64 = 2 * STAKE_RENT_EXEMPT_RESERVE + LAMPORTS_PER_SOL;
+
+const OK: ProgramResult = Ok(());
+const MISSING_SIGNATURE: ProgramResult = Err(ProgramError::MissingRequiredSignature);
+const WRONG_STATE: ProgramResult = Err(ProgramError::InvalidAccountData);
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    Uninitialized,
+    Initialized,
+    Stake,
+    RewardsPool,
+}
+
+const STATES: [State; 4] = [
+    State::Uninitialized,
+    State::Initialized,
+    State::Stake,
+    State::RewardsPool,
+];
+
+impl State {
+    /// The variant, authorized to `staker` and `WITHDRAWER` where it has a
+    /// meta, and delegating half a SOL since epoch 0 where it has a stake.
+    fn stake_state(self, staker: Pubkey) -> StakeStateV2 {
+        let meta = initialized_meta(staker, WITHDRAWER);
+        match self {
+            State::Uninitialized => StakeStateV2::Uninitialized,
+            State::Initialized => StakeStateV2::Initialized(meta),
+            State::Stake => StakeStateV2::Stake(
+                meta,
+                Stake {
+                    delegation: Delegation::new(&VOTER, LAMPORTS_PER_SOL / 2, 0u64.to_le_bytes()),
+                    ..Stake::default()
+                },
+                StakeFlags::empty(),
+            ),
+            State::RewardsPool => StakeStateV2::RewardsPool,
+        }
+    }
+}
+
+/// One stake account of one instruction, with what native returns for each
+/// of `STATES` there.
+struct Row {
+    instruction: &'static str,
+    index: usize,
+    staker: Pubkey,
+    accounts: Vec<TestAccount>,
+    data: Vec<u8>,
+    expected: [ProgramResult; 4],
+}
+
+fn rows() -> Vec<Row> {
+    let seed_authority = create_with_seed(&BASE, SEED.as_bytes(), &SEED_OWNER).unwrap();
+    let split = || {
+        vec![
+            initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+            // prefunded, as splitting active stake asks
+            stake_account(
+                DESTINATION,
+                STAKE_RENT_EXEMPT_RESERVE,
+                StakeStateV2::Uninitialized,
+            ),
+            signer(STAKER),
+        ]
+    };
+    let move_lamports = || {
+        vec![
+            initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+            initialized_account(DESTINATION, LAMPORTS, STAKER, WITHDRAWER),
+            signer(STAKER),
+        ]
+    };
+    let merge = || {
+        vec![
+            initialized_account(DESTINATION, LAMPORTS, STAKER, WITHDRAWER),
+            initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+            clock_account(&clock()),
+            stake_history_account(),
+            signer(STAKER),
+        ]
+    };
+    let split_data = amount_data(StakeInstruction::Split, STAKE_RENT_EXEMPT_RESERVE + 1_000);
+    let move_data = amount_data(StakeInstruction::MoveLamports, 1_000);
+    let merge_data = client::merge(&DESTINATION, &SOURCE, &STAKER).data;
+
+    // states: Uninitialized, Initialized, Stake, RewardsPool
+    vec![
+        Row {
+            instruction: "Split source",
+            index: 0,
+            staker: STAKER,
+            accounts: split(),
+            data: split_data.clone(),
+            expected: [MISSING_SIGNATURE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "Split destination",
+            index: 1,
+            staker: STAKER,
+            accounts: split(),
+            data: split_data,
+            expected: [OK, WRONG_STATE, WRONG_STATE, WRONG_STATE],
+        },
+        Row {
+            instruction: "Initialize",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                stake_account(SOURCE, LAMPORTS, StakeStateV2::Uninitialized),
+                rent_account(),
+            ],
+            data: client::initialize(
+                &SOURCE,
+                &Authorized {
+                    staker: STAKER,
+                    withdrawer: WITHDRAWER,
+                },
+                &Lockup::default(),
+            )
+            .data,
+            expected: [OK, WRONG_STATE, WRONG_STATE, WRONG_STATE],
+        },
+        Row {
+            instruction: "InitializeChecked",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                stake_account(SOURCE, LAMPORTS, StakeStateV2::Uninitialized),
+                rent_account(),
+                TestAccount::new(STAKER, Pubkey::default(), 0, vec![]),
+                signer(WITHDRAWER),
+            ],
+            data: client::initialize_checked(&SOURCE, &STAKER, &WITHDRAWER).data,
+            expected: [OK, WRONG_STATE, WRONG_STATE, WRONG_STATE],
+        },
+        Row {
+            instruction: "DelegateStake",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                vote_account(VOTER, &[]),
+                clock_account(&clock()),
+                stake_history_account(),
+                TestAccount::new(STAKE_CONFIG, Pubkey::default(), 0, vec![]),
+                signer(STAKER),
+            ],
+            data: vec![StakeInstruction::DelegateStake as u8],
+            // the stake is still active
+            expected: [
+                WRONG_STATE,
+                OK,
+                Err(StakeError::TooSoonToRedelegate.into()),
+                WRONG_STATE,
+            ],
+        },
+        Row {
+            instruction: "Withdraw",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                TestAccount::new(DESTINATION, Pubkey::default(), 0, vec![]).writable(),
+                clock_account(&clock()),
+                stake_history_account(),
+                signer(WITHDRAWER),
+            ],
+            data: amount_data(StakeInstruction::Withdraw, 1),
+            expected: [MISSING_SIGNATURE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "SetLockup",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                signer(WITHDRAWER),
+            ],
+            data: vec![StakeInstruction::SetLockup as u8, 0, 0, 0],
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "Merge destination",
+            index: 0,
+            staker: STAKER,
+            accounts: merge(),
+            data: merge_data.clone(),
+            // active stake can't take in an inactive source
+            expected: [
+                WRONG_STATE,
+                OK,
+                Err(StakeError::MergeMismatch.into()),
+                WRONG_STATE,
+            ],
+        },
+        Row {
+            instruction: "Merge source",
+            index: 1,
+            staker: STAKER,
+            accounts: merge(),
+            data: merge_data,
+            // nor inactive stake an active one
+            expected: [
+                WRONG_STATE,
+                OK,
+                Err(StakeError::MergeMismatch.into()),
+                WRONG_STATE,
+            ],
+        },
+        Row {
+            instruction: "MoveLamports source",
+            index: 0,
+            staker: STAKER,
+            accounts: move_lamports(),
+            data: move_data.clone(),
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "MoveLamports destination",
+            index: 1,
+            staker: STAKER,
+            accounts: move_lamports(),
+            data: move_data,
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "Authorize",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                clock_account(&clock()),
+                signer(STAKER),
+            ],
+            data: client::authorize(
+                &SOURCE,
+                &STAKER,
+                &NEW_AUTHORITY,
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "AuthorizeWithSeed",
+            index: 0,
+            staker: seed_authority,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, seed_authority, WITHDRAWER),
+                signer(BASE),
+                clock_account(&clock()),
+            ],
+            data: client::authorize_with_seed(
+                &SOURCE,
+                &BASE,
+                SEED,
+                &SEED_OWNER,
+                &NEW_AUTHORITY,
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "AuthorizeCheckedWithSeed",
+            index: 0,
+            staker: seed_authority,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, seed_authority, WITHDRAWER),
+                signer(BASE),
+                clock_account(&clock()),
+                signer(NEW_AUTHORITY),
+            ],
+            data: client::authorize_checked_with_seed(
+                &SOURCE,
+                &BASE,
+                SEED,
+                &SEED_OWNER,
+                &NEW_AUTHORITY,
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+        Row {
+            instruction: "AuthorizeChecked",
+            index: 0,
+            staker: STAKER,
+            accounts: vec![
+                initialized_account(SOURCE, LAMPORTS, STAKER, WITHDRAWER),
+                clock_account(&clock()),
+                signer(STAKER),
+                signer(NEW_AUTHORITY),
+            ],
+            data: client::authorize_checked(
+                &SOURCE,
+                &STAKER,
+                &NEW_AUTHORITY,
+                StakeAuthorize::Staker,
+                None,
+            )
+            .data,
+            expected: [WRONG_STATE, OK, OK, WRONG_STATE],
+        },
+    ]
+}
+
+fn inputs(accounts: &[TestAccount]) -> Vec<Input> {
+    accounts.iter().cloned().map(Input::from).collect()
+}
+
+#[test]
+fn test_instruction_state_matrix() {
+    set_clock(&clock());
+    set_stake_history(clock().epoch, &[]);
+    set_default_rent();
+
+    for row in rows() {
+        for (state, expected) in STATES.into_iter().zip(row.expected) {
+            let mut accounts = row.accounts.clone();
+            let account = &mut accounts[row.index];
+            *account = stake_account(account.key, account.lamports, state.stake_state(row.staker));
+
+            with_accounts(&inputs(&accounts), |accounts| {
+                let before: Vec<u64> = accounts.iter().map(|account| account.lamports()).collect();
+                assert_eq!(
+                    process(accounts, &row.data),
+                    expected,
+                    "{} {state:?}",
+                    row.instruction
+                );
+                if expected.is_err() {
+                    let after: Vec<u64> =
+                        accounts.iter().map(|account| account.lamports()).collect();
+                    assert_eq!(before, after, "{} {state:?}", row.instruction);
+                }
+            });
+        }
+    }
+}
+
+#[test]
+fn test_state_rejected_before_authority_check() {
+    set_clock(&clock());
+    set_default_rent();
+
+    // native matches on the state before looking at who signed, so an
+    // account nobody may authorize still reads as the wrong state
+    for row in rows() {
+        if !matches!(
+            row.instruction,
+            "SetLockup" | "Authorize" | "AuthorizeWithSeed" | "Merge destination"
+        ) {
+            continue;
+        }
+        for state in [State::Uninitialized, State::RewardsPool] {
+            let mut accounts = row.accounts.clone();
+            let account = &mut accounts[row.index];
+            *account = stake_account(account.key, LAMPORTS, state.stake_state(row.staker));
+            for account in &mut accounts {
+                account.is_signer = false;
+            }
+            with_accounts(&inputs(&accounts), |accounts| {
+                assert_eq!(
+                    process(accounts, &row.data),
+                    WRONG_STATE,
+                    "{} {state:?}",
+                    row.instruction
+                );
+            });
+        }
+    }
+}